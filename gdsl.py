@@ -184,6 +184,14 @@ def parse():
 
 un_ops, bin_ops, blocks, reporters = parse()
 
+
+def field_pattern(fields: dict[str, str]):
+    assert len(fields) <= 1, "only one field is supported"
+    if not fields:
+        return "None"
+    return f"Some({json.dumps(next(iter(fields.values())))})"
+
+
 f = open("src/blocks.rs", "w")
 f.write("""
 pub struct Menu {
@@ -229,6 +237,17 @@ for variant, op in un_ops.items():
         )
 f.write("_ => unreachable!()")
 f.write("}")
+f.write("}\n\n")
+f.write(
+    "pub fn from_opcode(opcode: &str, field: Option<&str>) -> Option<Self> {"
+)
+f.write("match (opcode, field) {")
+for variant, op in un_ops.items():
+    if not op:
+        continue
+    f.write(f"({json.dumps(op.opcode)}, {field_pattern(op.fields)}) => Some(Self::{variant}),")
+f.write("_ => None")
+f.write("}")
 f.write("}")
 f.write("}\n\n")
 f.write("#[derive(Debug, Copy, Clone)]\npub enum BinOp {")
@@ -262,6 +281,15 @@ for variant, op in bin_ops.items():
     f.write(f'Self::{variant} => "{op.rhs}",')
 f.write("_ => unreachable!()")
 f.write("}")
+f.write("}\n\n")
+f.write("pub fn from_opcode(opcode: &str) -> Option<Self> {")
+f.write("match opcode {")
+for variant, op in bin_ops.items():
+    if not op:
+        continue
+    f.write(f'"{op.opcode}" => Some(Self::{variant}),')
+f.write("_ => None")
+f.write("}")
 f.write("}")
 f.write("}")
 
@@ -300,6 +328,18 @@ def write_blocks(typename: str, blocks: dict[str, Block | list[Block]]):
             f.write(f'"{block[0].name}" => &[{variants}],')
     f.write("_ => &[] }")
     f.write("}\n\n")
+    f.write("pub fn variants(name: &str) -> &'static [Self] {")
+    f.write("match name {")
+    for variant, block in blocks.items():
+        if not block:
+            continue
+        if isinstance(block, list):
+            variants = ",".join(f"Self::{variant}{len(b.args)}" for b in block)
+            f.write(f'"{block[0].name}" => &[{variants}],')
+        else:
+            f.write(f'"{block.name}" => &[Self::{variant}],')
+    f.write("_ => &[] }")
+    f.write("}\n\n")
     f.write("pub fn from_shape(name: &str, args: usize) -> Option<Self> {")
     f.write("match (name, args) {")
     for variant, block in blocks.items():
@@ -389,6 +429,26 @@ def write_blocks(typename: str, blocks: dict[str, Block | list[Block]]):
             )
     f.write("}")
     f.write("}\n\n")
+    f.write(
+        "pub fn from_opcode(opcode: &str, field: Option<&str>) -> &'static [Self] {"
+    )
+    f.write("match (opcode, field) {")
+    shapes: dict[tuple[str, str], list[str]] = {}
+    for variant, block in blocks.items():
+        if isinstance(block, list):
+            for block in block:
+                shapes.setdefault((block.opcode, field_pattern(block.fields)), []).append(
+                    f"Self::{variant}{len(block.args)}"
+                )
+        else:
+            shapes.setdefault((block.opcode, field_pattern(block.fields)), []).append(
+                f"Self::{variant}"
+            )
+    for (opcode, field), variants in shapes.items():
+        f.write(f"({json.dumps(opcode)}, {field}) => &[{','.join(variants)}],")
+    f.write("_ => &[]")
+    f.write("}")
+    f.write("}\n\n")
     f.write("}")
 
 
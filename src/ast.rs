@@ -1,4 +1,5 @@
 mod arg;
+mod const_;
 mod const_expr;
 mod costume;
 mod enum_;
@@ -8,10 +9,14 @@ mod event_kind;
 mod expr;
 mod func;
 mod list;
+mod map;
+mod match_arm;
 mod name;
 mod proc;
 mod project;
+mod property;
 mod references;
+mod sound;
 mod sprite;
 mod stmt;
 mod struct_;
@@ -22,6 +27,7 @@ mod value;
 mod var;
 
 pub use arg::*;
+pub use const_::*;
 pub use const_expr::*;
 pub use costume::*;
 pub use enum_::*;
@@ -31,10 +37,14 @@ pub use event_kind::*;
 pub use expr::*;
 pub use func::*;
 pub use list::*;
+pub use map::*;
+pub use match_arm::*;
 pub use name::*;
 pub use proc::*;
 pub use project::*;
+pub use property::*;
 pub use references::*;
+pub use sound::*;
 pub use sprite::*;
 pub use stmt::*;
 pub use struct_::*;
@@ -0,0 +1,28 @@
+use logos::Span;
+
+use super::{
+    ConstExpr,
+    Type,
+};
+use crate::misc::SmolStr;
+
+#[derive(Debug)]
+pub struct Const {
+    pub name: SmolStr,
+    pub span: Span,
+    pub type_: Type,
+    pub value: ConstExpr,
+    pub is_used: bool,
+}
+
+impl Const {
+    pub fn new(name: SmolStr, span: Span, type_: Type, value: ConstExpr) -> Self {
+        Self {
+            name,
+            span,
+            type_,
+            value,
+            is_used: false,
+        }
+    }
+}
@@ -5,6 +5,7 @@ use super::{
     stmt::Stmt,
     References,
 };
+use crate::misc::SmolStr;
 
 #[derive(Debug)]
 pub struct Event {
@@ -12,4 +13,5 @@ pub struct Event {
     pub span: Span,
     pub body: Vec<Stmt>,
     pub references: References,
+    pub doc: Option<SmolStr>,
 }
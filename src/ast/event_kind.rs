@@ -18,6 +18,7 @@ pub enum EventKind {
     OnTimerGt { value: Box<Expr> },
     OnClone,
     On { event: SmolStr },
+    Test { name: SmolStr },
 }
 
 impl EventKind {
@@ -29,7 +30,8 @@ impl EventKind {
             EventKind::OnBackdrop { .. } => "event_whenbackdropswitchesto",
             EventKind::OnLoudnessGt { .. } | EventKind::OnTimerGt { .. } => "event_whengreaterthan",
             EventKind::OnClone => "control_start_as_clone",
-            EventKind::On { .. } => "event_whenbroadcastreceived",
+            // Tests are started by a hidden broadcast.
+            EventKind::On { .. } | EventKind::Test { .. } => "event_whenbroadcastreceived",
         }
     }
 
@@ -40,6 +42,7 @@ impl EventKind {
             body,
             span,
             references: Default::default(),
+            doc: None,
         }
     }
 }
@@ -53,6 +53,12 @@ pub enum Expr {
         span: Span,
         fields: Vec<StructLiteralField>,
     },
+    Ternary {
+        span: Span,
+        cond: Box<Expr>,
+        then: Box<Expr>,
+        else_: Box<Expr>,
+    },
 }
 
 impl Expr {
@@ -67,6 +73,7 @@ impl Expr {
             Self::UnOp { span, .. } => span.clone(),
             Self::BinOp { span, .. } => span.clone(),
             Self::StructLiteral { span, .. } => span.clone(),
+            Self::Ternary { span, .. } => span.clone(),
         }
     }
 }
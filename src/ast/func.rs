@@ -1,8 +1,10 @@
-use fxhash::FxHashMap;
 use logos::Span;
 
 use super::*;
-use crate::misc::SmolStr;
+use crate::misc::{
+    IndexMap,
+    SmolStr,
+};
 
 #[derive(Debug)]
 pub struct Func {
@@ -10,7 +12,8 @@ pub struct Func {
     pub span: Span,
     pub type_: Type,
     pub args: Vec<Arg>,
-    pub locals: FxHashMap<SmolStr, Var>,
+    pub locals: IndexMap<SmolStr, Var>,
+    pub doc: Option<SmolStr>,
 }
 
 impl Func {
@@ -20,7 +23,18 @@ impl Func {
             span,
             type_,
             args,
-            locals: FxHashMap::default(),
+            locals: IndexMap::default(),
+            doc: None,
         }
     }
 }
+
+/// The hidden variable which holds the value returned by `func` in the given slot of a
+/// tuple. The first slot is also the return value of functions which return one value.
+pub fn return_var(func: &str, slot: usize) -> SmolStr {
+    if slot == 0 {
+        format!("{func}:return").into()
+    } else {
+        format!("{func}:return:{slot}").into()
+    }
+}
@@ -0,0 +1,30 @@
+use logos::Span;
+
+use crate::misc::SmolStr;
+
+/// A map is stored as two hidden lists, which hold its keys and the value for each key
+/// at the same index.
+#[derive(Debug)]
+pub struct Map {
+    pub name: SmolStr,
+    pub span: Span,
+    pub is_used: bool,
+}
+
+impl Map {
+    pub fn new(name: SmolStr, span: Span) -> Self {
+        Self {
+            name,
+            span,
+            is_used: false,
+        }
+    }
+
+    pub fn keys(&self) -> SmolStr {
+        format!("{}:keys", self.name).into()
+    }
+
+    pub fn values(&self) -> SmolStr {
+        format!("{}:values", self.name).into()
+    }
+}
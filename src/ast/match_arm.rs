@@ -0,0 +1,20 @@
+use logos::Span;
+
+use super::{
+    Expr,
+    Stmt,
+};
+
+/// An arm of a `match` statement. The wildcard arm `_` has no patterns.
+#[derive(Debug)]
+pub struct MatchArm {
+    pub patterns: Vec<Expr>,
+    pub span: Span,
+    pub body: Vec<Stmt>,
+}
+
+impl MatchArm {
+    pub fn is_wildcard(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
@@ -1,16 +1,19 @@
-use fxhash::FxHashMap;
 use logos::Span;
 
 use super::*;
-use crate::misc::SmolStr;
+use crate::misc::{
+    IndexMap,
+    SmolStr,
+};
 
 #[derive(Debug)]
 pub struct Proc {
     pub name: SmolStr,
     pub span: Span,
     pub args: Vec<Arg>,
-    pub locals: FxHashMap<SmolStr, Var>,
+    pub locals: IndexMap<SmolStr, Var>,
     pub warp: bool,
+    pub doc: Option<SmolStr>,
 }
 
 impl Proc {
@@ -19,8 +22,9 @@ impl Proc {
             name,
             span,
             args,
-            locals: FxHashMap::default(),
+            locals: IndexMap::default(),
             warp,
+            doc: None,
         }
     }
 }
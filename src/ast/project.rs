@@ -1,10 +1,11 @@
-use fxhash::FxHashMap;
-
 use super::sprite::Sprite;
-use crate::misc::SmolStr;
+use crate::misc::{
+    IndexMap,
+    SmolStr,
+};
 
 #[derive(Debug)]
 pub struct Project {
     pub stage: Sprite,
-    pub sprites: FxHashMap<SmolStr, Sprite>,
+    pub sprites: IndexMap<SmolStr, Sprite>,
 }
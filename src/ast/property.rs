@@ -0,0 +1,11 @@
+use logos::Span;
+
+use super::ConstExpr;
+use crate::misc::SmolStr;
+
+#[derive(Debug)]
+pub struct Property {
+    pub name: SmolStr,
+    pub span: Span,
+    pub value: ConstExpr,
+}
@@ -1,26 +1,30 @@
-use fxhash::{
-    FxHashMap,
-    FxHashSet,
-};
+use fxhash::FxHashSet;
 
 use super::*;
-use crate::misc::SmolStr;
+use crate::misc::{
+    IndexMap,
+    SmolStr,
+};
 
 #[derive(Debug, Default)]
 pub struct Sprite {
     pub costumes: Vec<Costume>,
-    pub procs: FxHashMap<SmolStr, Proc>,
-    pub proc_definitions: FxHashMap<SmolStr, Vec<Stmt>>,
-    pub proc_references: FxHashMap<SmolStr, References>,
-    pub proc_used_args: FxHashMap<SmolStr, FxHashSet<SmolStr>>,
-    pub funcs: FxHashMap<SmolStr, Func>,
-    pub func_definitions: FxHashMap<SmolStr, Vec<Stmt>>,
-    pub func_references: FxHashMap<SmolStr, References>,
-    pub func_used_args: FxHashMap<SmolStr, FxHashSet<SmolStr>>,
-    pub enums: FxHashMap<SmolStr, Enum>,
-    pub structs: FxHashMap<SmolStr, Struct>,
-    pub vars: FxHashMap<SmolStr, Var>,
-    pub lists: FxHashMap<SmolStr, List>,
+    pub sounds: Vec<Sound>,
+    pub properties: Vec<Property>,
+    pub procs: IndexMap<SmolStr, Proc>,
+    pub proc_definitions: IndexMap<SmolStr, Vec<Stmt>>,
+    pub proc_references: IndexMap<SmolStr, References>,
+    pub proc_used_args: IndexMap<SmolStr, FxHashSet<SmolStr>>,
+    pub funcs: IndexMap<SmolStr, Func>,
+    pub func_definitions: IndexMap<SmolStr, Vec<Stmt>>,
+    pub func_references: IndexMap<SmolStr, References>,
+    pub func_used_args: IndexMap<SmolStr, FxHashSet<SmolStr>>,
+    pub enums: IndexMap<SmolStr, Enum>,
+    pub consts: IndexMap<SmolStr, Const>,
+    pub structs: IndexMap<SmolStr, Struct>,
+    pub vars: IndexMap<SmolStr, Var>,
+    pub lists: IndexMap<SmolStr, List>,
+    pub maps: IndexMap<SmolStr, Map>,
     pub events: Vec<Event>,
     pub used_procs: FxHashSet<SmolStr>,
     pub used_funcs: FxHashSet<SmolStr>,
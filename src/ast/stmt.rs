@@ -3,6 +3,7 @@ use logos::Span;
 use super::{
     expr::Expr,
     type_::Type,
+    MatchArm,
     Name,
     Value,
 };
@@ -33,6 +34,27 @@ pub enum Stmt {
         cond: Box<Expr>,
         body: Vec<Stmt>,
     },
+    ForRange {
+        name: Name,
+        is_local: bool,
+        start: Box<Expr>,
+        end: Box<Expr>,
+        step: Option<Box<Expr>>,
+        body: Vec<Stmt>,
+    },
+    ForEach {
+        name: Name,
+        is_local: bool,
+        list: Name,
+        body: Vec<Stmt>,
+    },
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+        span: Span,
+    },
+    Break(Span),
+    Continue(Span),
     SetVar {
         name: Name,
         value: Box<Expr>,
@@ -80,8 +102,13 @@ pub enum Stmt {
         span: Span,
         args: Vec<(Option<(SmolStr, Span)>, Expr)>,
     },
-    Return {
+    Destructure {
+        names: Vec<Name>,
         value: Box<Expr>,
+        is_local: bool,
+    },
+    Return {
+        values: Vec<Expr>,
         visited: bool,
     },
 }
@@ -91,6 +118,19 @@ impl Stmt {
         todo!()
     }
 
+    /// Returns the span of the statement if no statement after it can run.
+    pub fn terminator_span(&self) -> Option<&Span> {
+        match self {
+            Stmt::Forever { span, .. } => Some(span),
+            Stmt::Block {
+                block: Block::DeleteThisClone | Block::StopAll | Block::StopThisScript,
+                span,
+                ..
+            } => Some(span),
+            _ => None,
+        }
+    }
+
     pub fn increment(name: Name) -> Self {
         let span = name.span();
         Self::ChangeVar {
@@ -1,6 +1,9 @@
 use logos::Span;
 
-use super::struct_field::StructField;
+use super::{
+    struct_field::StructField,
+    Type,
+};
 use crate::misc::SmolStr;
 
 #[derive(Debug)]
@@ -12,19 +15,61 @@ pub struct Struct {
 }
 
 impl Struct {
-    pub fn new(name: SmolStr, span: Span, fields: Vec<(SmolStr, Span)>) -> Self {
+    pub fn new(name: SmolStr, span: Span, fields: Vec<(Type, SmolStr, Span)>) -> Self {
         Self {
             name,
             span,
             fields: fields
                 .into_iter()
-                .map(|(name, span)| StructField {
+                .map(|(type_, name, span)| StructField {
                     name,
                     span,
+                    type_,
                     is_used: false,
                 })
                 .collect(),
             is_used: false,
         }
     }
+
+    /// Finds a field by its path, such as `pos.x` for the field `x` of the struct-typed
+    /// field `pos`.
+    pub fn field<'a, F>(&'a self, path: &str, get_struct: &F) -> Option<&'a StructField>
+    where F: Fn(&str) -> Option<&'a Struct> {
+        let (name, rest) = match path.split_once('.') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let field = self.fields.iter().find(|field| field.name == name)?;
+        match rest {
+            None => Some(field),
+            Some(rest) => {
+                let (type_name, _) = field.type_.struct_()?;
+                get_struct(type_name)?.field(rest, get_struct)
+            }
+        }
+    }
+
+    /// Returns the paths of the fields which hold values, with the fields of nested
+    /// structs flattened into paths such as `pos.x`. Recursive structs are reported in
+    /// pass0, which replaces the offending field types with values.
+    pub fn leaf_fields<'a, F>(&self, get_struct: &F) -> Vec<(SmolStr, Span)>
+    where F: Fn(&str) -> Option<&'a Struct> {
+        let mut leaf_fields = vec![];
+        for field in &self.fields {
+            match field
+                .type_
+                .struct_()
+                .and_then(|(type_name, _)| get_struct(type_name))
+            {
+                Some(struct_) => {
+                    for (path, span) in struct_.leaf_fields(get_struct) {
+                        leaf_fields.push((format!("{}.{}", field.name, path).into(), span));
+                    }
+                }
+                None => leaf_fields.push((field.name.clone(), field.span.clone())),
+            }
+        }
+        leaf_fields
+    }
 }
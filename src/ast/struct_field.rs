@@ -1,10 +1,12 @@
 use logos::Span;
 
+use super::Type;
 use crate::misc::SmolStr;
 
 #[derive(Debug)]
 pub struct StructField {
     pub name: SmolStr,
     pub span: Span,
+    pub type_: Type,
     pub is_used: bool,
 }
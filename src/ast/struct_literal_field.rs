@@ -9,3 +9,30 @@ pub struct StructLiteralField {
     pub span: Span,
     pub value: Box<Expr>,
 }
+
+/// Replaces fields whose values are nested struct literals with the fields of those
+/// literals, named by their path such as `pos.x`. If `prefix` is given, it is prepended
+/// to every path.
+pub fn flatten_struct_literal_fields(
+    fields: &[StructLiteralField],
+    prefix: Option<&str>,
+) -> Vec<StructLiteralField> {
+    let mut flattened = vec![];
+    for field in fields {
+        let path: SmolStr = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, field.name).into(),
+            None => field.name.clone(),
+        };
+        match field.value.as_ref() {
+            Expr::StructLiteral { fields, .. } => {
+                flattened.extend(flatten_struct_literal_fields(fields, Some(&path)));
+            }
+            _ => flattened.push(StructLiteralField {
+                name: path,
+                span: field.span.clone(),
+                value: field.value.clone(),
+            }),
+        }
+    }
+    flattened
+}
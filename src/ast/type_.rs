@@ -8,12 +8,21 @@ use crate::misc::SmolStr;
 #[derive(Debug, Clone)]
 pub enum Type {
     Value,
-    Struct { name: SmolStr, span: Span },
+    Struct {
+        name: SmolStr,
+        span: Span,
+    },
+    /// A value which holds a variant of an enum. The parser produces [`Type::Struct`]
+    /// for every named type, pass0 resolves the names of enums to this.
+    Enum {
+        name: SmolStr,
+        span: Span,
+    },
 }
 
 impl Type {
     pub fn is_value(&self) -> bool {
-        matches!(self, Self::Value)
+        matches!(self, Self::Value | Self::Enum { .. })
     }
 
     pub fn is_struct(&self) -> bool {
@@ -33,6 +42,7 @@ impl Display for Type {
         match self {
             Type::Value => write!(f, "value"),
             Type::Struct { name, span: _ } => write!(f, "{}", name),
+            Type::Enum { name, span: _ } => write!(f, "{}", name),
         }
     }
 }
@@ -169,7 +169,9 @@ impl Value {
 
     fn join(&self, rhs: &Value) -> Option<Value> {
         match (self, rhs) {
-            (Value::String(a), Value::String(b)) => Some(format!("{a}{b}").into()),
+            (Value::String(_) | Value::Int(_), Value::String(_) | Value::Int(_)) => {
+                Some(format!("{self}{rhs}").into())
+            }
             _ => None,
         }
     }
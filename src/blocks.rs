@@ -95,6 +95,29 @@ impl UnOp {
             _ => unreachable!(),
         }
     }
+
+    pub fn from_opcode(opcode: &str, field: Option<&str>) -> Option<Self> {
+        match (opcode, field) {
+            ("operator_not", None) => Some(Self::Not),
+            ("operator_length", None) => Some(Self::Length),
+            ("operator_round", None) => Some(Self::Round),
+            ("operator_mathop", Some("abs")) => Some(Self::Abs),
+            ("operator_mathop", Some("floor")) => Some(Self::Floor),
+            ("operator_mathop", Some("ceiling")) => Some(Self::Ceil),
+            ("operator_mathop", Some("sqrt")) => Some(Self::Sqrt),
+            ("operator_mathop", Some("sin")) => Some(Self::Sin),
+            ("operator_mathop", Some("cos")) => Some(Self::Cos),
+            ("operator_mathop", Some("tan")) => Some(Self::Tan),
+            ("operator_mathop", Some("asin")) => Some(Self::Asin),
+            ("operator_mathop", Some("acos")) => Some(Self::Acos),
+            ("operator_mathop", Some("atan")) => Some(Self::Atan),
+            ("operator_mathop", Some("ln")) => Some(Self::Ln),
+            ("operator_mathop", Some("log")) => Some(Self::Log),
+            ("operator_mathop", Some("e ^")) => Some(Self::AntiLn),
+            ("operator_mathop", Some("10 ^")) => Some(Self::AntiLog),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -175,6 +198,25 @@ impl BinOp {
             _ => unreachable!(),
         }
     }
+
+    pub fn from_opcode(opcode: &str) -> Option<Self> {
+        match opcode {
+            "operator_add" => Some(Self::Add),
+            "operator_subtract" => Some(Self::Sub),
+            "operator_multiply" => Some(Self::Mul),
+            "operator_divide" => Some(Self::Div),
+            "operator_mod" => Some(Self::Mod),
+            "operator_lt" => Some(Self::Lt),
+            "operator_gt" => Some(Self::Gt),
+            "operator_equals" => Some(Self::Eq),
+            "operator_and" => Some(Self::And),
+            "operator_or" => Some(Self::Or),
+            "operator_join" => Some(Self::Join),
+            "operator_contains" => Some(Self::In),
+            "operator_letter_of" => Some(Self::Of),
+            _ => None,
+        }
+    }
 }
 #[derive(Debug, Copy, Clone)]
 pub enum Block {
@@ -446,6 +488,105 @@ impl Block {
         }
     }
 
+    pub fn variants(name: &str) -> &'static [Self] {
+        match name {
+            "move" => &[Self::Move],
+            "turn_left" => &[Self::TurnLeft],
+            "turn_right" => &[Self::TurnRight],
+            "goto_random_position" => &[Self::GotoRandomPosition],
+            "goto_mouse_pointer" => &[Self::GotoMousePointer],
+            "goto" => &[Self::Goto1, Self::Goto2],
+            "glide" => &[Self::Glide3, Self::Glide2],
+            "glide_to_random_position" => &[Self::GlideToRandomPosition],
+            "glide_to_mouse_pointer" => &[Self::GlideToMousePointer],
+            "point_in_direction" => &[Self::PointInDirection],
+            "point_towards_mouse_pointer" => &[Self::PointTowardsMousePointer],
+            "point_towards_random_direction" => &[Self::PointTowardsRandomDirection],
+            "point_towards" => &[Self::PointTowards],
+            "change_x" => &[Self::ChangeX],
+            "set_x" => &[Self::SetX],
+            "change_y" => &[Self::ChangeY],
+            "set_y" => &[Self::SetY],
+            "if_on_edge_bounce" => &[Self::IfOnEdgeBounce],
+            "set_rotation_style_left_right" => &[Self::SetRotationStyleLeftRight],
+            "set_rotation_style_do_not_rotate" => &[Self::SetRotationStyleDoNotRotate],
+            "set_rotation_style_all_around" => &[Self::SetRotationStyleAllAround],
+            "say" => &[Self::Say2, Self::Say1],
+            "think" => &[Self::Think2, Self::Think1],
+            "switch_costume" => &[Self::SwitchCostume],
+            "next_costume" => &[Self::NextCostume],
+            "switch_backdrop" => &[Self::SwitchBackdrop],
+            "previous_backdrop" => &[Self::PreviousBackdrop],
+            "random_backdrop" => &[Self::RandomBackdrop],
+            "next_backdrop" => &[Self::NextBackdrop],
+            "set_size" => &[Self::SetSize],
+            "change_size" => &[Self::ChangeSize],
+            "change_color_effect" => &[Self::ChangeColorEffect],
+            "change_fisheye_effect" => &[Self::ChangeFisheyeEffect],
+            "change_whirl_effect" => &[Self::ChangeWhirlEffect],
+            "change_pixelate_effect" => &[Self::ChangePixelateEffect],
+            "change_mosaic_effect" => &[Self::ChangeMosaicEffect],
+            "change_brightness_effect" => &[Self::ChangeBrightnessEffect],
+            "change_ghost_effect" => &[Self::ChangeGhostEffect],
+            "set_color_effect" => &[Self::SetColorEffect],
+            "set_fisheye_effect" => &[Self::SetFisheyeEffect],
+            "set_whirl_effect" => &[Self::SetWhirlEffect],
+            "set_pixelate_effect" => &[Self::SetPixelateEffect],
+            "set_mosaic_effect" => &[Self::SetMosaicEffect],
+            "set_brightness_effect" => &[Self::SetBrightnessEffect],
+            "set_ghost_effect" => &[Self::SetGhostEffect],
+            "clear_graphic_effects" => &[Self::ClearGraphicEffects],
+            "show" => &[Self::Show],
+            "hide" => &[Self::Hide],
+            "goto_front" => &[Self::GotoFront],
+            "goto_back" => &[Self::GotoBack],
+            "go_forward" => &[Self::GoForward],
+            "go_backward" => &[Self::GoBackward],
+            "play_sound_until_done" => &[Self::PlaySoundUntilDone],
+            "start_sound" => &[Self::StartSound],
+            "stop_all_sounds" => &[Self::StopAllSounds],
+            "change_pitch_effect" => &[Self::ChangePitchEffect],
+            "change_pan_effect" => &[Self::ChangePanEffect],
+            "set_pitch_effect" => &[Self::SetPitchEffect],
+            "set_pan_effect" => &[Self::SetPanEffect],
+            "change_volume" => &[Self::ChangeVolume],
+            "set_volume" => &[Self::SetVolume],
+            "clear_sound_effects" => &[Self::ClearSoundEffects],
+            "broadcast" => &[Self::Broadcast],
+            "broadcast_and_wait" => &[Self::BroadcastAndWait],
+            "wait" => &[Self::Wait],
+            "wait_until" => &[Self::WaitUntil],
+            "stop_all" => &[Self::StopAll],
+            "stop_this_script" => &[Self::StopThisScript],
+            "stop_other_scripts" => &[Self::StopOtherScripts],
+            "delete_this_clone" => &[Self::DeleteThisClone],
+            "clone" => &[Self::Clone0, Self::Clone1],
+            "ask" => &[Self::Ask],
+            "set_drag_mode_draggable" => &[Self::SetDragModeDraggable],
+            "set_drag_mode_not_draggable" => &[Self::SetDragModeNotDraggable],
+            "reset_timer" => &[Self::ResetTimer],
+            "erase_all" => &[Self::EraseAll],
+            "stamp" => &[Self::Stamp],
+            "pen_down" => &[Self::PenDown],
+            "pen_up" => &[Self::PenUp],
+            "set_pen_color" => &[Self::SetPenColor],
+            "change_pen_size" => &[Self::ChangePenSize],
+            "set_pen_size" => &[Self::SetPenSize],
+            "set_pen_hue" => &[Self::SetPenHue],
+            "set_pen_saturation" => &[Self::SetPenSaturation],
+            "set_pen_brightness" => &[Self::SetPenBrightness],
+            "set_pen_transparency" => &[Self::SetPenTransparency],
+            "change_pen_hue" => &[Self::ChangePenHue],
+            "change_pen_saturation" => &[Self::ChangePenSaturation],
+            "change_pen_brightness" => &[Self::ChangePenBrightness],
+            "change_pen_transparency" => &[Self::ChangePenTransparency],
+            "rest" => &[Self::Rest],
+            "set_tempo" => &[Self::SetTempo],
+            "change_tempo" => &[Self::ChangeTempo],
+            _ => &[],
+        }
+    }
+
     pub fn from_shape(name: &str, args: usize) -> Option<Self> {
         match (name, args) {
             ("move", _) => Some(Self::Move),
@@ -1066,6 +1207,123 @@ impl Block {
             Self::ChangeTempo => None,
         }
     }
+
+    pub fn from_opcode(opcode: &str, field: Option<&str>) -> &'static [Self] {
+        match (opcode, field) {
+            ("motion_movesteps", None) => &[Self::Move],
+            ("motion_turnleft", None) => &[Self::TurnLeft],
+            ("motion_turnright", None) => &[Self::TurnRight],
+            ("motion_goto", None) => &[
+                Self::GotoRandomPosition,
+                Self::GotoMousePointer,
+                Self::Goto1,
+            ],
+            ("motion_gotoxy", None) => &[Self::Goto2],
+            ("motion_glidesecstoxy", None) => &[Self::Glide3],
+            ("motion_glideto", None) => &[
+                Self::Glide2,
+                Self::GlideToRandomPosition,
+                Self::GlideToMousePointer,
+            ],
+            ("motion_pointindirection", None) => &[Self::PointInDirection],
+            ("motion_pointtowards", None) => &[
+                Self::PointTowardsMousePointer,
+                Self::PointTowardsRandomDirection,
+                Self::PointTowards,
+            ],
+            ("motion_changexby", None) => &[Self::ChangeX],
+            ("motion_setx", None) => &[Self::SetX],
+            ("motion_changeyby", None) => &[Self::ChangeY],
+            ("motion_sety", None) => &[Self::SetY],
+            ("motion_ifonedgebounce", None) => &[Self::IfOnEdgeBounce],
+            ("motion_setrotationstyle", Some("left-right")) => &[Self::SetRotationStyleLeftRight],
+            ("motion_setrotationstyle", Some("don't rotate")) => {
+                &[Self::SetRotationStyleDoNotRotate]
+            }
+            ("motion_setrotationstyle", Some("all around")) => &[Self::SetRotationStyleAllAround],
+            ("looks_sayforsecs", None) => &[Self::Say2],
+            ("looks_say", None) => &[Self::Say1],
+            ("looks_thinkforsecs", None) => &[Self::Think2],
+            ("looks_think", None) => &[Self::Think1],
+            ("looks_switchcostumeto", None) => &[Self::SwitchCostume],
+            ("looks_nextcostume", None) => &[Self::NextCostume],
+            ("looks_switchbackdropto", None) => &[
+                Self::SwitchBackdrop,
+                Self::PreviousBackdrop,
+                Self::RandomBackdrop,
+            ],
+            ("looks_nextbackdrop", None) => &[Self::NextBackdrop],
+            ("looks_setsizeto", None) => &[Self::SetSize],
+            ("looks_changesizeby", None) => &[Self::ChangeSize],
+            ("looks_changeeffectby", Some("COLOR")) => &[Self::ChangeColorEffect],
+            ("looks_changeeffectby", Some("FISHEYE")) => &[Self::ChangeFisheyeEffect],
+            ("looks_changeeffectby", Some("WHIRL")) => &[Self::ChangeWhirlEffect],
+            ("looks_changeeffectby", Some("PIXELATE")) => &[Self::ChangePixelateEffect],
+            ("looks_changeeffectby", Some("MOSAIC")) => &[Self::ChangeMosaicEffect],
+            ("looks_changeeffectby", Some("BRIGHTNESS")) => &[Self::ChangeBrightnessEffect],
+            ("looks_changeeffectby", Some("GHOST")) => &[Self::ChangeGhostEffect],
+            ("looks_seteffectto", Some("COLOR")) => &[Self::SetColorEffect],
+            ("looks_seteffectto", Some("FISHEYE")) => &[Self::SetFisheyeEffect],
+            ("looks_seteffectto", Some("WHIRL")) => &[Self::SetWhirlEffect],
+            ("looks_seteffectto", Some("PIXELATE")) => &[Self::SetPixelateEffect],
+            ("looks_seteffectto", Some("MOSAIC")) => &[Self::SetMosaicEffect],
+            ("looks_seteffectto", Some("BRIGHTNESS")) => &[Self::SetBrightnessEffect],
+            ("looks_seteffectto", Some("GHOST")) => &[Self::SetGhostEffect],
+            ("looks_cleargraphiceffects", None) => &[Self::ClearGraphicEffects],
+            ("looks_show", None) => &[Self::Show],
+            ("looks_hide", None) => &[Self::Hide],
+            ("looks_gotofrontback", Some("front")) => &[Self::GotoFront],
+            ("looks_gotofrontback", Some("back")) => &[Self::GotoBack],
+            ("looks_goforwardbackwardlayers", Some("forward")) => &[Self::GoForward],
+            ("looks_goforwardbackwardlayers", Some("backward")) => &[Self::GoBackward],
+            ("sound_playuntildone", None) => &[Self::PlaySoundUntilDone],
+            ("sound_play", None) => &[Self::StartSound],
+            ("sound_stopallsounds", None) => &[Self::StopAllSounds],
+            ("sound_changeeffectby", Some("PITCH")) => &[Self::ChangePitchEffect],
+            ("sound_changeeffectby", Some("PAN")) => &[Self::ChangePanEffect],
+            ("sound_seteffectto", Some("PITCH")) => &[Self::SetPitchEffect],
+            ("sound_seteffectto", Some("PAN")) => &[Self::SetPanEffect],
+            ("sound_changevolumeby", None) => &[Self::ChangeVolume],
+            ("sound_setvolumeto", None) => &[Self::SetVolume],
+            ("sound_cleareffects", None) => &[Self::ClearSoundEffects],
+            ("event_broadcast", None) => &[Self::Broadcast],
+            ("event_broadcastandwait", None) => &[Self::BroadcastAndWait],
+            ("control_wait", None) => &[Self::Wait],
+            ("control_wait_until", None) => &[Self::WaitUntil],
+            ("control_stop", Some("all")) => &[Self::StopAll],
+            ("control_stop", Some("this script")) => &[Self::StopThisScript],
+            ("control_stop", Some("other scripts in sprite")) => &[Self::StopOtherScripts],
+            ("control_delete_this_clone", None) => &[Self::DeleteThisClone],
+            ("control_create_clone_of", None) => &[Self::Clone0, Self::Clone1],
+            ("sensing_askandwait", None) => &[Self::Ask],
+            ("sensing_setdragmode", Some("draggable")) => &[Self::SetDragModeDraggable],
+            ("sensing_setdragmode", Some("not draggable")) => &[Self::SetDragModeNotDraggable],
+            ("sensing_resettimer", None) => &[Self::ResetTimer],
+            ("pen_clear", None) => &[Self::EraseAll],
+            ("pen_stamp", None) => &[Self::Stamp],
+            ("pen_penDown", None) => &[Self::PenDown],
+            ("pen_penUp", None) => &[Self::PenUp],
+            ("pen_setPenColorToColor", None) => &[Self::SetPenColor],
+            ("pen_changePenSizeBy", None) => &[Self::ChangePenSize],
+            ("pen_setPenSizeTo", None) => &[Self::SetPenSize],
+            ("pen_setPenColorParamTo", None) => &[
+                Self::SetPenHue,
+                Self::SetPenSaturation,
+                Self::SetPenBrightness,
+                Self::SetPenTransparency,
+            ],
+            ("pen_changePenColorParamBy", None) => &[
+                Self::ChangePenHue,
+                Self::ChangePenSaturation,
+                Self::ChangePenBrightness,
+                Self::ChangePenTransparency,
+            ],
+            ("music_restForBeats", None) => &[Self::Rest],
+            ("music_setTempo", None) => &[Self::SetTempo],
+            ("music_changeTempo", None) => &[Self::ChangeTempo],
+            _ => &[],
+        }
+    }
 }
 #[derive(Debug, Copy, Clone)]
 pub enum Repr {
@@ -1154,6 +1412,46 @@ impl Repr {
         }
     }
 
+    pub fn variants(name: &str) -> &'static [Self] {
+        match name {
+            "x_position" => &[Self::XPosition],
+            "y_position" => &[Self::YPosition],
+            "direction" => &[Self::Direction],
+            "size" => &[Self::Size],
+            "costume_number" => &[Self::CostumeNumber],
+            "costume_name" => &[Self::CostumeName],
+            "backdrop_number" => &[Self::BackdropNumber],
+            "backdrop_name" => &[Self::BackdropName],
+            "volume" => &[Self::Volume],
+            "distance_to_mouse_pointer" => &[Self::DistanceToMousePointer],
+            "distance_to" => &[Self::DistanceTo],
+            "touching_mouse_pointer" => &[Self::TouchingMousePointer],
+            "touching_edge" => &[Self::TouchingEdge],
+            "touching" => &[Self::Touching],
+            "key_pressed" => &[Self::KeyPressed],
+            "mouse_down" => &[Self::MouseDown],
+            "mouse_x" => &[Self::MouseX],
+            "mouse_y" => &[Self::MouseY],
+            "loudness" => &[Self::Loudness],
+            "timer" => &[Self::Timer],
+            "current_year" => &[Self::CurrentYear],
+            "current_month" => &[Self::CurrentMonth],
+            "current_date" => &[Self::CurrentDate],
+            "current_day_of_week" => &[Self::CurrentDayOfWeek],
+            "current_hour" => &[Self::CurrentHour],
+            "current_minute" => &[Self::CurrentMinute],
+            "current_second" => &[Self::CurrentSecond],
+            "days_since_2000" => &[Self::DaysSince2000],
+            "username" => &[Self::Username],
+            "touching_color" => &[Self::TouchingColor],
+            "color_is_touching_color" => &[Self::ColorIsTouchingColor],
+            "answer" => &[Self::Answer],
+            "random" => &[Self::Random],
+            "contains" => &[Self::Contains],
+            _ => &[],
+        }
+    }
+
     pub fn from_shape(name: &str, args: usize) -> Option<Self> {
         match (name, args) {
             ("x_position", _) => Some(Self::XPosition),
@@ -1388,4 +1686,45 @@ impl Repr {
             Self::Contains => None,
         }
     }
+
+    pub fn from_opcode(opcode: &str, field: Option<&str>) -> &'static [Self] {
+        match (opcode, field) {
+            ("motion_xposition", None) => &[Self::XPosition],
+            ("motion_yposition", None) => &[Self::YPosition],
+            ("motion_direction", None) => &[Self::Direction],
+            ("looks_size", None) => &[Self::Size],
+            ("looks_costumenumbername", Some("number")) => &[Self::CostumeNumber],
+            ("looks_costumenumbername", Some("name")) => &[Self::CostumeName],
+            ("looks_backdropnumbername", Some("number")) => &[Self::BackdropNumber],
+            ("looks_backdropnumbername", Some("name")) => &[Self::BackdropName],
+            ("sound_volume", None) => &[Self::Volume],
+            ("sensing_distanceto", None) => &[Self::DistanceToMousePointer, Self::DistanceTo],
+            ("sensing_touchingobject", None) => &[
+                Self::TouchingMousePointer,
+                Self::TouchingEdge,
+                Self::Touching,
+            ],
+            ("sensing_keypressed", None) => &[Self::KeyPressed],
+            ("sensing_mousedown", None) => &[Self::MouseDown],
+            ("sensing_mousex", None) => &[Self::MouseX],
+            ("sensing_mousey", None) => &[Self::MouseY],
+            ("sensing_loudness", None) => &[Self::Loudness],
+            ("sensing_timer", None) => &[Self::Timer],
+            ("sensing_current", Some("YEAR")) => &[Self::CurrentYear],
+            ("sensing_current", Some("MONTH")) => &[Self::CurrentMonth],
+            ("sensing_current", Some("DATE")) => &[Self::CurrentDate],
+            ("sensing_current", Some("DAYOFWEEK")) => &[Self::CurrentDayOfWeek],
+            ("sensing_current", Some("HOUR")) => &[Self::CurrentHour],
+            ("sensing_current", Some("MINUTE")) => &[Self::CurrentMinute],
+            ("sensing_current", Some("SECOND")) => &[Self::CurrentSecond],
+            ("sensing_dayssince2000", None) => &[Self::DaysSince2000],
+            ("sensing_username", None) => &[Self::Username],
+            ("sensing_touchingcolor", None) => &[Self::TouchingColor],
+            ("sensing_coloristouchingcolor", None) => &[Self::ColorIsTouchingColor],
+            ("sensing_answer", None) => &[Self::Answer],
+            ("operator_random", None) => &[Self::Random],
+            ("operator_contains", None) => &[Self::Contains],
+            _ => &[],
+        }
+    }
 }
@@ -1,11 +1,14 @@
+mod audio;
 mod cmd;
 mod event;
 mod expr;
 mod input;
+mod layout;
 mod mutation;
 mod node;
 mod node_id;
 mod node_id_factory;
 pub mod sb3;
+pub mod sprite_state;
 mod stmt;
 pub mod turbowarp_config;
@@ -0,0 +1,253 @@
+use std::io;
+
+/// Metadata that Scratch stores alongside every sound asset.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct AudioMetadata {
+    pub rate: u32,
+    pub sample_count: u64,
+}
+
+pub fn audio_metadata(data: &[u8], extension: &str) -> io::Result<AudioMetadata> {
+    match extension {
+        "wav" | "wave" => wav_metadata(data),
+        "mp3" => mp3_metadata(data),
+        _ => Err(invalid_data(format!(
+            "unsupported sound format `{extension}`, expected `wav` or `mp3`"
+        ))),
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn u16_le(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_le(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn wav_metadata(data: &[u8]) -> io::Result<AudioMetadata> {
+    if data.get(0..4) != Some(b"RIFF") || data.get(8..12) != Some(b"WAVE") {
+        return Err(invalid_data("not a RIFF WAVE file".to_string()));
+    }
+    let mut rate = None;
+    let mut block_align = None;
+    let mut fact_sample_count = None;
+    let mut data_size = None;
+    let mut i = 12;
+    while let (Some(chunk_id), Some(chunk_size)) = (data.get(i..i + 4), u32_le(data, i + 4)) {
+        let body = i + 8;
+        // Streaming encoders may write a placeholder size, so clamp to what is there.
+        let chunk_size = (chunk_size as usize).min(data.len() - body);
+        match chunk_id {
+            b"fmt " => {
+                rate = u32_le(data, body + 4);
+                block_align = u16_le(data, body + 12);
+            }
+            b"fact" => fact_sample_count = u32_le(data, body),
+            b"data" => data_size = Some(chunk_size),
+            _ => {}
+        }
+        i = body + chunk_size + (chunk_size & 1);
+    }
+    let rate = rate.ok_or_else(|| invalid_data("WAVE file has no `fmt ` chunk".to_string()))?;
+    let data_size =
+        data_size.ok_or_else(|| invalid_data("WAVE file has no `data` chunk".to_string()))?;
+    // Compressed formats such as ADPCM store the real sample count in a `fact` chunk.
+    let sample_count = match (fact_sample_count, block_align) {
+        (Some(sample_count), _) => sample_count as u64,
+        (None, Some(block_align)) if block_align > 0 => (data_size / block_align as usize) as u64,
+        _ => {
+            return Err(invalid_data(
+                "WAVE file has invalid block alignment".to_string(),
+            ))
+        }
+    };
+    Ok(AudioMetadata { rate, sample_count })
+}
+
+const MP3_BITRATES_V1: [[u32; 16]; 3] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448, 0,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 0,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0,
+    ],
+];
+
+const MP3_BITRATES_V2: [[u32; 16]; 3] = [
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256, 0,
+    ],
+    [
+        0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0,
+    ],
+    [
+        0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0,
+    ],
+];
+
+#[derive(Debug)]
+struct Mp3Frame {
+    rate: u32,
+    samples: u32,
+    length: usize,
+}
+
+fn mp3_frame(header: u32) -> Option<Mp3Frame> {
+    if header >> 21 != 0x7FF {
+        return None;
+    }
+    let version = (header >> 19) & 3;
+    let layer = (header >> 17) & 3;
+    let bitrate_index = ((header >> 12) & 0xF) as usize;
+    let rate_index = ((header >> 10) & 3) as usize;
+    let padding = (header >> 9) & 1;
+    if version == 1 || layer == 0 || rate_index == 3 {
+        return None;
+    }
+    // layer bits are 3 for layer I, 2 for layer II and 1 for layer III.
+    let layer = (3 - layer) as usize;
+    let bitrate = match version {
+        3 => MP3_BITRATES_V1[layer][bitrate_index],
+        _ => MP3_BITRATES_V2[layer][bitrate_index],
+    } * 1000;
+    if bitrate == 0 {
+        return None;
+    }
+    let rate = match version {
+        3 => [44100, 48000, 32000][rate_index],
+        2 => [22050, 24000, 16000][rate_index],
+        _ => [11025, 12000, 8000][rate_index],
+    };
+    let samples = match (layer, version) {
+        (0, _) => 384,
+        (1, _) | (2, 3) => 1152,
+        _ => 576,
+    };
+    let length = if layer == 0 {
+        ((12 * bitrate / rate + padding) * 4) as usize
+    } else {
+        (samples / 8 * bitrate / rate + padding) as usize
+    };
+    Some(Mp3Frame {
+        rate,
+        samples,
+        length,
+    })
+}
+
+fn mp3_metadata(data: &[u8]) -> io::Result<AudioMetadata> {
+    let mut i = 0;
+    if data.starts_with(b"ID3") && data.len() >= 10 {
+        let size = data[6..10]
+            .iter()
+            .fold(0, |size, &byte| (size << 7) | (byte & 0x7F) as usize);
+        let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+        i = 10 + size + footer;
+    }
+    let mut rate = None;
+    let mut sample_count = 0;
+    while let Some(header) = data.get(i..i + 4) {
+        if header.starts_with(b"TAG") {
+            break;
+        }
+        let Some(frame) = mp3_frame(u32::from_be_bytes(header.try_into().unwrap())) else {
+            i += 1;
+            continue;
+        };
+        let body = &data[i + 4..(i + frame.length).min(data.len())];
+        let is_info_frame = rate.is_none()
+            && body
+                .windows(4)
+                .take(40)
+                .any(|tag| tag == b"Xing" || tag == b"Info");
+        if !is_info_frame {
+            sample_count += frame.samples as u64;
+        }
+        rate.get_or_insert(frame.rate);
+        i += frame.length.max(1);
+    }
+    let rate = rate.ok_or_else(|| invalid_data("MP3 file has no audio frames".to_string()))?;
+    Ok(AudioMetadata { rate, sample_count })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(rate: u32, channels: u16, bits: u16, frames: u32) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+        let data_size = frames * block_align as u32;
+        let mut wav = vec![];
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&channels.to_le_bytes());
+        wav.extend_from_slice(&rate.to_le_bytes());
+        wav.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&bits.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        wav.resize(wav.len() + data_size as usize, 0);
+        wav
+    }
+
+    #[test]
+    fn test_wav() {
+        assert_eq!(
+            audio_metadata(&wav(48000, 1, 16, 1124), "wav").unwrap(),
+            AudioMetadata {
+                rate: 48000,
+                sample_count: 1124
+            }
+        );
+        assert_eq!(
+            audio_metadata(&wav(22050, 2, 16, 100), "wav").unwrap(),
+            AudioMetadata {
+                rate: 22050,
+                sample_count: 100
+            }
+        );
+        assert!(audio_metadata(b"RIFF\0\0\0\0WAVE", "wav").is_err());
+        assert!(audio_metadata(b"not a wav file", "wav").is_err());
+    }
+
+    #[test]
+    fn test_mp3() {
+        // MPEG-1 layer III, 128 kbps, 44100 Hz, no padding: 417 bytes per frame.
+        let mut mp3 = vec![];
+        for _ in 0..3 {
+            mp3.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+            mp3.resize(mp3.len() + 413, 0);
+        }
+        assert_eq!(
+            audio_metadata(&mp3, "mp3").unwrap(),
+            AudioMetadata {
+                rate: 44100,
+                sample_count: 3 * 1152
+            }
+        );
+        let mut tagged = b"ID3\x04\x00\x00\x00\x00\x00\x05hello".to_vec();
+        tagged.extend_from_slice(&mp3);
+        assert_eq!(
+            audio_metadata(&tagged, "mp3").unwrap(),
+            audio_metadata(&mp3, "mp3").unwrap()
+        );
+        assert!(audio_metadata(&[0; 64], "mp3").is_err());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(audio_metadata(&[], "ogg").is_err());
+    }
+}
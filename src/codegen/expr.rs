@@ -25,7 +25,6 @@ use crate::{
         Repr,
         UnOp,
     },
-    diagnostic::DiagnosticKind,
     misc::{
         write_comma_io,
         SmolStr,
@@ -35,14 +34,7 @@ use crate::{
 impl<T> Sb3<T>
 where T: Write + Seek
 {
-    pub fn arg(
-        &mut self,
-        s: S,
-        d: D,
-        this_id: NodeID,
-        parent_id: NodeID,
-        name: &Name,
-    ) -> io::Result<()> {
+    pub fn arg(&mut self, s: S, this_id: NodeID, parent_id: NodeID, name: &Name) -> io::Result<()> {
         let basename = name.basename();
 
         if !(s
@@ -60,10 +52,6 @@ where T: Write + Seek
             if basename == "tw_is_forkphorus" {
                 return self.arg_impl(this_id, parent_id, "is forkphorus?", true);
             }
-            d.report(
-                DiagnosticKind::UnrecognizedArgument(basename.clone()),
-                &name.span(),
-            );
             return Ok(());
         }
 
@@ -104,20 +92,12 @@ where T: Write + Seek
         this_id: NodeID,
         parent_id: NodeID,
         repr: &Repr,
-        span: &Span,
         args: &[(Option<(SmolStr, Span)>, Expr)],
     ) -> io::Result<()> {
         if args.iter().any(|(keyword, _)| keyword.is_some()) {
             panic!("repr's do not support keyword args yet.")
         }
         if args.len() != repr.args().len() {
-            d.report(
-                DiagnosticKind::ReprArgsCountMismatch {
-                    repr: *repr,
-                    given: args.len(),
-                },
-                span,
-            );
             return Ok(());
         }
         self.begin_node(Node::new(repr.opcode(), this_id).parent_id(parent_id))?;
@@ -133,10 +113,10 @@ where T: Write + Seek
                     continue;
                 } else {
                     menu_is_default = false;
-                    self.input_with_shadow(s, d, arg_name, arg_value, arg_id, menu_id.unwrap())?;
+                    self.input_with_shadow(s, arg_name, arg_value, arg_id, menu_id.unwrap())?;
                 }
             } else {
-                self.input(s, d, arg_name, arg_value, arg_id)?;
+                self.input(s, arg_name, arg_value, arg_id)?;
             }
         }
         if menu_is_default {
@@ -194,7 +174,7 @@ where T: Write + Seek
         let opr_id = self.id.new_id();
         self.begin_node(Node::new(op.opcode(), this_id).parent_id(parent_id))?;
         self.begin_inputs()?;
-        self.input(s, d, op.input(), opr, opr_id)?;
+        self.input(s, op.input(), opr, opr_id)?;
         self.end_obj()?; // inputs
         if let Some(fields) = op.fields() {
             write!(self, r#","fields":{fields}"#)?;
@@ -216,14 +196,14 @@ where T: Write + Seek
     ) -> io::Result<()> {
         if let BinOp::Of = op {
             if let Expr::Name(name) = lhs {
-                if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(d, name) {
+                if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(name) {
                     return self.list_index(s, d, this_id, parent_id, &qualified_name, rhs);
                 }
             }
         }
         if let BinOp::In = op {
             if let Expr::Name(name) = rhs {
-                if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(d, name) {
+                if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(name) {
                     return self.list_contains(s, d, this_id, parent_id, &qualified_name, lhs);
                 }
             }
@@ -232,8 +212,8 @@ where T: Write + Seek
         let rhs_id = self.id.new_id();
         self.begin_node(Node::new(op.opcode(), this_id).parent_id(parent_id))?;
         self.begin_inputs()?;
-        self.input(s, d, op.lhs(), lhs, lhs_id)?;
-        self.input(s, d, op.rhs(), rhs, rhs_id)?;
+        self.input(s, op.lhs(), lhs, lhs_id)?;
+        self.input(s, op.rhs(), rhs, rhs_id)?;
         self.end_obj()?; // inputs
         self.end_obj()?; // node
         self.expr(s, d, lhs, lhs_id, this_id)?;
@@ -252,7 +232,7 @@ where T: Write + Seek
         let index_id = self.id.new_id();
         self.begin_node(Node::new("data_itemoflist", this_id).parent_id(parent_id))?;
         self.begin_inputs()?;
-        self.input(s, d, "INDEX", index, index_id)?;
+        self.input(s, "INDEX", index, index_id)?;
         self.end_obj()?; // inputs
         self.single_field_id("LIST", name)?;
         self.end_obj()?; // node
@@ -271,7 +251,7 @@ where T: Write + Seek
         let index_id = self.id.new_id();
         self.begin_node(Node::new("data_itemnumoflist", this_id).parent_id(parent_id))?;
         self.begin_inputs()?;
-        self.input(s, d, "ITEM", item, index_id)?;
+        self.input(s, "ITEM", item, index_id)?;
         self.end_obj()?; // inputs
         self.single_field_id("LIST", name)?;
         self.end_obj()?; // node
@@ -289,7 +269,8 @@ where T: Write + Seek
         let list = s.get_list(name).unwrap();
         if let Some((type_name, _type_span)) = list.type_.struct_() {
             let struct_ = s.get_struct(type_name).unwrap();
-            let qualified_name = qualify_struct_var_name(&struct_.fields[0].name, name);
+            let (path, _) = &struct_.leaf_fields(&|name| s.get_struct(name))[0];
+            let qualified_name = qualify_struct_var_name(path, name);
             self.single_field_id("LIST", &qualified_name)?;
         } else {
             self.single_field_id("LIST", name)?;
@@ -303,39 +284,27 @@ where T: Write + Seek
         d: D,
         this_id: NodeID,
         name: &SmolStr,
-        span: &Span,
         args: &[(Option<(SmolStr, Span)>, Expr)],
     ) -> io::Result<()> {
         let Some(func) = s.sprite.funcs.get(name) else {
-            d.report(DiagnosticKind::UnrecognizedFunction(name.clone()), span);
             return Ok(());
         };
         if args.iter().any(|(keyword, _)| keyword.is_some()) {
             panic!("func's do not support keyword args yet.")
         }
-        if func.args.len() != args.len() {
-            d.report(
-                DiagnosticKind::FuncArgsCountMismatch {
-                    func: name.clone(),
-                    given: args.len(),
-                },
-                span,
-            )
-        }
         let mut qualified_args: Vec<(SmolStr, NodeID)> = vec![];
         let mut qualified_arg_values: Vec<Expr> = vec![];
         self.begin_inputs()?;
         for (arg, (_, kwarg)) in func.args.iter().zip(args) {
             match &arg.type_ {
-                Type::Value => {
+                Type::Value | Type::Enum { .. } => {
                     let arg_id = self.id.new_id();
-                    self.input(s, d, &arg.name, kwarg, arg_id)?;
+                    self.input(s, &arg.name, kwarg, arg_id)?;
                     qualified_args.push((arg.name.clone(), arg_id));
                     qualified_arg_values.push(kwarg.clone());
                 }
                 Type::Struct {
-                    name: type_name,
-                    span: type_span,
+                    name: type_name, ..
                 } => {
                     let Some(struct_) = s.sprite.structs.get(type_name) else {
                         continue;
@@ -344,50 +313,31 @@ where T: Write + Seek
                     let struct_literal_fields = match arg_value {
                         Expr::StructLiteral {
                             name: struct_literal_name,
-                            span: struct_literal_span,
                             fields: struct_literal_fields,
+                            ..
                         } => {
                             if struct_literal_name != &struct_.name {
-                                d.report(
-                                    DiagnosticKind::TypeMismatch {
-                                        expected: arg.type_.clone(),
-                                        given: Type::Struct {
-                                            name: struct_literal_name.clone(),
-                                            span: struct_literal_span.clone(),
-                                        },
-                                    },
-                                    type_span,
-                                );
                                 continue;
                             }
-                            if struct_literal_fields.len() != struct_.fields.len() {
-                                panic!()
-                            }
-                            for (struct_field, struct_literal_field) in
-                                struct_.fields.iter().zip(struct_literal_fields)
-                            {
-                                if struct_field.name != struct_literal_field.name {
-                                    panic!()
-                                }
-                            }
-                            struct_literal_fields
+                            flatten_struct_literal_fields(struct_literal_fields, None)
                         }
                         _ => {
                             continue;
                         }
                     };
-                    for (field, struct_literal_field) in
-                        struct_.fields.iter().zip(struct_literal_fields)
+                    let leaf_fields = struct_.leaf_fields(&|name| s.sprite.structs.get(name));
+                    if struct_literal_fields.len() != leaf_fields.len() {
+                        panic!()
+                    }
+                    for ((path, _), struct_literal_field) in
+                        leaf_fields.iter().zip(&struct_literal_fields)
                     {
-                        let qualified_arg_name = qualify_struct_var_name(&field.name, &arg.name);
+                        if path != &struct_literal_field.name {
+                            panic!()
+                        }
+                        let qualified_arg_name = qualify_struct_var_name(path, &arg.name);
                         let arg_id = self.id.new_id();
-                        self.input(
-                            s,
-                            d,
-                            &qualified_arg_name,
-                            &struct_literal_field.value,
-                            arg_id,
-                        )?;
+                        self.input(s, &qualified_arg_name, &struct_literal_field.value, arg_id)?;
                         qualified_args.push((qualified_arg_name, arg_id));
                         qualified_arg_values.push(struct_literal_field.value.as_ref().clone());
                     }
@@ -11,7 +11,6 @@ use super::{
     sb3::{
         QualifiedName,
         Sb3,
-        D,
         S,
     },
 };
@@ -21,40 +20,30 @@ use crate::{
         Name,
         Value,
     },
-    diagnostic::DiagnosticKind,
     misc::write_comma_io,
 };
 
 impl<T> Sb3<T>
 where T: Write + Seek
 {
-    pub fn input(
-        &mut self,
-        s: S,
-        d: D,
-        name: &str,
-        expr: &Expr,
-        this_id: NodeID,
-    ) -> io::Result<()> {
-        self._input(s, d, name, expr, this_id, None)
+    pub fn input(&mut self, s: S, name: &str, expr: &Expr, this_id: NodeID) -> io::Result<()> {
+        self._input(s, name, expr, this_id, None)
     }
 
     pub fn input_with_shadow(
         &mut self,
         s: S,
-        d: D,
         name: &str,
         expr: &Expr,
         this_id: NodeID,
         shadow_id: NodeID,
     ) -> io::Result<()> {
-        self._input(s, d, name, expr, this_id, Some(shadow_id))
+        self._input(s, name, expr, this_id, Some(shadow_id))
     }
 
     fn _input(
         &mut self,
         s: S,
-        d: D,
         input_name: &str,
         expr: &Expr,
         this_id: NodeID,
@@ -64,8 +53,8 @@ where T: Write + Seek
         write!(self, r#""{input_name}":"#)?;
         match expr {
             Expr::Value { value, span: _ } => return self.value_input(input_name, value),
-            Expr::Name(name) => return self.name_input(s, d, input_name, name, shadow_id),
-            Expr::Dot { lhs, rhs, rhs_span } => {
+            Expr::Name(name) => return self.name_input(s, input_name, name, shadow_id),
+            Expr::Dot { lhs, rhs, .. } => {
                 if let Expr::Name(lhs_name) = &**lhs {
                     if let Some(enum_) = s.get_enum(lhs_name.basename()) {
                         if let Some(variant) =
@@ -73,13 +62,6 @@ where T: Write + Seek
                         {
                             return self
                                 .value_input(input_name, &variant.value.as_ref().unwrap().0);
-                        } else {
-                            d.report(
-                                DiagnosticKind::UnrecognizedEnumVariant(
-                                    lhs_name.basename().clone(),
-                                ),
-                                rhs_span,
-                            );
                         }
                     }
                 }
@@ -128,12 +110,11 @@ where T: Write + Seek
     fn name_input(
         &mut self,
         s: S,
-        d: D,
         input_name: &str,
         name: &Name,
         shadow_id: Option<NodeID>,
     ) -> io::Result<()> {
-        match s.qualify_name(d, name) {
+        match s.qualify_name(name) {
             Some(QualifiedName::Var(name, _)) => {
                 write!(self, "[3,[12,{},{}],", json!(*name), json!(*name))?;
             }
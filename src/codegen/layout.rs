@@ -0,0 +1,147 @@
+use crate::ast::Stmt;
+
+/// Height of a stack block, in Scratch workspace units.
+const BLOCK_HEIGHT: i64 = 48;
+/// Height of a hat block.
+const HAT_HEIGHT: i64 = 88;
+/// Height of the parts of a C block around its substacks.
+const C_BLOCK_HEIGHT: i64 = 80;
+/// Height of the `else` row of an if else block.
+const ELSE_HEIGHT: i64 = 48;
+/// Height of an empty substack.
+const EMPTY_SUBSTACK_HEIGHT: i64 = 24;
+const LINE_HEIGHT: i64 = 20;
+const GAP: i64 = 48;
+const COLUMN_WIDTH: i64 = 720;
+
+/// Arranges the top-level scripts of a sprite in columns. Each script is placed below
+/// the previous one in its column, using the height of the blocks emitted for it.
+#[derive(Debug, Default)]
+pub struct Layout {
+    column: i64,
+    y: i64,
+    height: i64,
+}
+
+impl Layout {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Starts a new column, unless the current column is empty.
+    pub fn next_column(&mut self) {
+        if self.y > 0 {
+            self.column += 1;
+            self.y = 0;
+        }
+    }
+
+    /// Reserves space with the given height above the next script, and returns its
+    /// position.
+    pub fn reserve(&mut self, height: i64) -> (i64, i64) {
+        let position = (self.x(), self.y);
+        self.y += height + LINE_HEIGHT;
+        position
+    }
+
+    /// Reserves space for a comment with the given number of lines above the next
+    /// script, and returns its position and height.
+    pub fn comment(&mut self, lines: usize) -> (i64, i64, i64) {
+        let height = 2 * LINE_HEIGHT + LINE_HEIGHT * lines as i64;
+        let (x, y) = self.reserve(height);
+        (x, y, height)
+    }
+
+    /// Returns the position of the next script, whose height is measured until
+    /// [`Layout::end_script`] is called.
+    pub fn begin_script(&mut self) -> (i64, i64) {
+        self.height = HAT_HEIGHT;
+        (self.x(), self.y)
+    }
+
+    pub fn end_script(&mut self) {
+        self.y += self.height + GAP;
+    }
+
+    /// Adds the height of the block emitted for `stmt`, not including the blocks
+    /// inside its substacks.
+    pub fn stmt(&mut self, stmt: &Stmt) {
+        self.height += match stmt {
+            Stmt::Repeat { body, .. } | Stmt::Forever { body, .. } | Stmt::Until { body, .. } => {
+                C_BLOCK_HEIGHT + substack(body)
+            }
+            Stmt::Branch {
+                if_body, else_body, ..
+            } if else_body.is_empty() => C_BLOCK_HEIGHT + substack(if_body),
+            Stmt::Branch {
+                if_body, else_body, ..
+            } => C_BLOCK_HEIGHT + ELSE_HEIGHT + substack(if_body) + substack(else_body),
+            _ => BLOCK_HEIGHT,
+        }
+    }
+
+    /// Adds the height of a stack block which was not emitted for a statement.
+    pub fn block(&mut self) {
+        self.height += BLOCK_HEIGHT;
+    }
+
+    fn x(&self) -> i64 {
+        self.column * COLUMN_WIDTH
+    }
+}
+
+fn substack(body: &[Stmt]) -> i64 {
+    if body.is_empty() {
+        EMPTY_SUBSTACK_HEIGHT
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Expr;
+
+    fn stmt() -> Stmt {
+        Stmt::Show(crate::ast::Name::Name {
+            name: "x".into(),
+            span: 0..0,
+        })
+    }
+
+    #[test]
+    fn test_scripts_do_not_overlap() {
+        let mut layout = Layout::default();
+        let (x, y) = layout.begin_script();
+        assert_eq!((x, y), (0, 0));
+        layout.stmt(&Stmt::Until {
+            cond: Box::new(Expr::Value {
+                value: 1.into(),
+                span: 0..0,
+            }),
+            body: vec![stmt(), stmt()],
+        });
+        layout.stmt(&stmt());
+        layout.stmt(&stmt());
+        layout.end_script();
+        let (x, y) = layout.begin_script();
+        assert_eq!(x, 0);
+        assert_eq!(y, HAT_HEIGHT + C_BLOCK_HEIGHT + 2 * BLOCK_HEIGHT + GAP);
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut layout = Layout::default();
+        layout.next_column();
+        assert_eq!(layout.begin_script(), (0, 0));
+        layout.end_script();
+        layout.next_column();
+        let (x, y, _) = layout.comment(2);
+        assert_eq!((x, y), (COLUMN_WIDTH, 0));
+        assert_eq!(
+            layout.begin_script(),
+            (COLUMN_WIDTH, 4 * LINE_HEIGHT + LINE_HEIGHT)
+        );
+    }
+}
@@ -13,6 +13,8 @@ pub struct Node {
     parent_id: Option<NodeID>,
     top_level: bool,
     shadow: bool,
+    comment_id: Option<NodeID>,
+    position: Option<(i64, i64)>,
 }
 
 impl Node {
@@ -24,6 +26,8 @@ impl Node {
             parent_id: None,
             top_level: false,
             shadow: false,
+            comment_id: None,
+            position: None,
         }
     }
 
@@ -49,6 +53,21 @@ impl Node {
     pub fn some_parent_id(self, parent_id: Option<NodeID>) -> Self {
         Self { parent_id, ..self }
     }
+
+    pub fn position(self, position: (i64, i64)) -> Self {
+        Self {
+            position: Some(position),
+            ..self
+        }
+    }
+
+    pub fn some_comment_id(self, comment_id: Option<NodeID>) -> Self {
+        Self { comment_id, ..self }
+    }
+
+    pub fn opcode(&self) -> &'static str {
+        self.opcode
+    }
 }
 
 impl Display for Node {
@@ -75,6 +94,12 @@ impl Display for Node {
         } else {
             write!(f, ",\"shadow\":false")?;
         }
+        if let Some((x, y)) = self.position {
+            write!(f, ",\"x\":{x},\"y\":{y}")?;
+        }
+        if let Some(comment_id) = self.comment_id {
+            write!(f, ",\"comment\":{comment_id}")?;
+        }
         Ok(())
     }
 }
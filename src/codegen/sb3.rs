@@ -1,19 +1,16 @@
 use core::str;
 use std::{
+    collections::BTreeSet,
     fs::File,
     io::{
         self,
         Seek,
         Write,
     },
+    iter,
     path::Path,
 };
 
-use fxhash::{
-    FxHashMap,
-    FxHashSet,
-};
-use logos::Span;
 use md5::{
     Digest,
     Md5,
@@ -25,15 +22,20 @@ use zip::{
 };
 
 use super::{
+    audio::{
+        audio_metadata,
+        AudioMetadata,
+    },
     cmd::cmd_to_list,
+    layout::Layout,
     node::Node,
     node_id::NodeID,
     node_id_factory::NodeIDFactory,
+    sprite_state::SpriteState,
     turbowarp_config::TurbowarpConfig,
 };
 use crate::{
     ast::*,
-    blocks::Block,
     codegen::mutation::Mutation,
     config::Config,
     diagnostic::{
@@ -42,12 +44,63 @@ use crate::{
     },
     misc::{
         write_comma_io,
+        IndexMap,
+        IndexSet,
         SmolStr,
     },
 };
 
 const STAGE_NAME: &str = "Stage";
 
+/// Opcode prefixes that belong to Scratch itself, and not to an extension.
+const CORE_CATEGORIES: [&str; 12] = [
+    "argument",
+    "colour",
+    "control",
+    "data",
+    "event",
+    "looks",
+    "math",
+    "motion",
+    "operator",
+    "procedures",
+    "sensing",
+    "sound",
+];
+
+/// ID of the TurboWarp debugger, which provides the `log`, `breakpoint` and `error`
+/// blocks. They are emitted as `procedures_call`, so `proc_call` adds it explicitly.
+pub const DEBUGGER_EXTENSION: &str = "twdebugger";
+
+/// Returns the ID of the extension that provides `opcode`, if any.
+fn extension_of(opcode: &'static str) -> Option<&'static str> {
+    let (prefix, _) = opcode.split_once('_')?;
+    (!CORE_CATEGORIES.contains(&prefix)).then_some(prefix)
+}
+
+/// Prefix of the hidden broadcasts which start `test` blocks. Like the debugger blocks,
+/// the name is surrounded by zero-width spaces so that it can't clash with a broadcast
+/// in the project.
+pub const TEST_BROADCAST_PREFIX: &str = "\u{200b}\u{200b}test\u{200b}\u{200b} ";
+
+/// Prefix of the hidden broadcasts which start the scripts of the test harness.
+pub const TEST_HARNESS_BROADCAST_PREFIX: &str = "\u{200b}\u{200b}run test\u{200b}\u{200b} ";
+
+/// Name of the hidden sprite which `test` blocks are collected into. It has a script for
+/// each test, which broadcasts the test's hidden broadcast and waits for it.
+pub const TEST_HARNESS_NAME: &str = "\u{200b}\u{200b}test harness\u{200b}\u{200b}";
+
+/// Broadcast which starts the `test` block `name` of the sprite `sprite_name`.
+pub fn test_broadcast(sprite_name: &str, name: &str) -> SmolStr {
+    format!("{TEST_BROADCAST_PREFIX}{sprite_name}::{name}").into()
+}
+
+/// Broadcast which starts the script of the test harness for the `test` block `name` of
+/// the sprite `sprite_name`.
+pub fn test_harness_broadcast(sprite_name: &str, name: &str) -> SmolStr {
+    format!("{TEST_HARNESS_BROADCAST_PREFIX}{sprite_name}::{name}").into()
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct S<'a> {
     pub stage: Option<&'a Sprite>,
@@ -115,8 +168,6 @@ impl S<'_> {
 
     fn qualify_field<T>(
         &self,
-        d: D,
-        span: &Span,
         qualified_var_name: SmolStr,
         field_name: Option<SmolStr>,
         type_: &Type,
@@ -126,16 +177,12 @@ impl S<'_> {
         T: FnOnce(SmolStr, Type) -> QualifiedName,
     {
         match type_ {
-            Type::Value => match field_name {
+            Type::Value | Type::Enum { .. } => match field_name {
                 None => Some(variant(qualified_var_name, type_.clone())),
-                Some(_) => {
-                    d.report(DiagnosticKind::NotStruct, span);
-                    None
-                }
+                Some(_) => None,
             },
             Type::Struct {
-                name: type_name,
-                span: type_span,
+                name: type_name, ..
             } => match field_name {
                 None => {
                     eprintln!("attempted to qualify field without field name: {qualified_var_name} with type: {type_}");
@@ -143,14 +190,10 @@ impl S<'_> {
                 }
                 Some(field_name) => {
                     let struct_ = self.get_struct(type_name)?;
-                    if !struct_.fields.iter().any(|field| field.name == field_name) {
-                        d.report(
-                            DiagnosticKind::StructDoesNotHaveField {
-                                type_name: type_name.clone(),
-                                field_name: field_name.clone(),
-                            },
-                            type_span,
-                        );
+                    if !struct_
+                        .field(&field_name, &|name| self.get_struct(name))
+                        .is_some_and(|field| field.type_.is_value())
+                    {
                         None
                     } else {
                         Some(variant(
@@ -163,13 +206,11 @@ impl S<'_> {
         }
     }
 
-    pub fn qualify_name(&self, d: D, name: &Name) -> Option<QualifiedName> {
+    pub fn qualify_name(&self, name: &Name) -> Option<QualifiedName> {
         let basename = name.basename();
         let fieldname = name.fieldname().cloned();
         if let Some(list) = self.get_list(basename) {
             return self.qualify_field(
-                d,
-                &name.span(),
                 list.name.clone(),
                 fieldname,
                 &list.type_,
@@ -184,8 +225,6 @@ impl S<'_> {
                 &var.name,
             );
             return self.qualify_field(
-                d,
-                &name.span(),
                 qualified_var_name,
                 fieldname,
                 &var.type_,
@@ -193,34 +232,13 @@ impl S<'_> {
             );
         }
         if let Some(var) = self.get_var(basename) {
-            return self.qualify_field(
-                d,
-                &name.span(),
-                var.name.clone(),
-                fieldname,
-                &var.type_,
-                QualifiedName::Var,
-            );
+            return self.qualify_field(var.name.clone(), fieldname, &var.type_, QualifiedName::Var);
         }
-        d.report(
-            DiagnosticKind::UnrecognizedVariable(basename.clone()),
-            &name.span(),
-        );
         None
     }
 }
 
 impl Stmt {
-    fn is_terminator(&self) -> bool {
-        matches!(
-            self,
-            Stmt::Forever { .. }
-                | Stmt::Block {
-                    block: Block::DeleteThisClone | Block::StopAll | Block::StopThisScript,
-                    ..
-                }
-        )
-    }
     fn opcode(&self, s: S) -> &'static str {
         match self {
             Stmt::Repeat { .. } => "control_repeat",
@@ -233,6 +251,11 @@ impl Stmt {
                 }
             }
             Stmt::Until { .. } => "control_repeat_until",
+            Stmt::ForRange { .. } | Stmt::ForEach { .. } | Stmt::Match { .. } => {
+                unreachable!("lowered in pass0")
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => unreachable!("lowered in loops"),
+            Stmt::Destructure { .. } => unreachable!("lowered in pass1"),
             Stmt::SetVar { .. } => "data_setvariableto",
             Stmt::ChangeVar { .. } => "data_changevariableby",
             Stmt::Show(name) => {
@@ -270,9 +293,28 @@ where T: Write + Seek
     pub id: NodeIDFactory,
     pub node_comma: bool,
     pub inputs_comma: bool,
-    pub costumes: FxHashMap<SmolStr, SmolStr>,
+    pub extensions: BTreeSet<&'static str>,
+    pub costumes: IndexMap<SmolStr, SmolStr>,
+    pub sounds: IndexMap<SmolStr, (SmolStr, AudioMetadata)>,
     pub srcpkg_hash: Option<String>,
     pub srcpkg: Option<Vec<u8>>,
+    pub file_options: SimpleFileOptions,
+    pub doc_comments: bool,
+    /// Whether `test` blocks are compiled, they are left out of normal builds.
+    pub tests: bool,
+    pub comments: Vec<Comment>,
+    pub layout: Layout,
+}
+
+/// A comment attached to a block.
+#[derive(Debug)]
+pub struct Comment {
+    pub id: NodeID,
+    pub block_id: NodeID,
+    pub text: SmolStr,
+    pub x: i64,
+    pub y: i64,
+    pub height: i64,
 }
 
 impl<T> Write for Sb3<T>
@@ -290,42 +332,59 @@ where T: Write + Seek
 impl<T> Sb3<T>
 where T: Write + Seek
 {
-    pub fn new(file: T) -> Self {
+    pub fn new(file: T, file_options: SimpleFileOptions) -> Self {
         Self {
             zip: ZipWriter::new(file),
             id: NodeIDFactory::new(),
             node_comma: false,
             inputs_comma: false,
-            costumes: FxHashMap::default(),
+            extensions: BTreeSet::new(),
+            costumes: IndexMap::default(),
+            sounds: IndexMap::default(),
             srcpkg_hash: None,
             srcpkg: None,
+            file_options,
+            doc_comments: true,
+            tests: false,
+            comments: Vec::new(),
+            layout: Layout::default(),
         }
     }
 
+    /// Returns the name of each asset file in the archive, with the path of the file
+    /// it is read from.
+    pub fn asset_files(&self) -> IndexMap<String, SmolStr> {
+        let costumes = self.costumes.iter();
+        let sounds = self.sounds.iter().map(|(path, (hash, _))| (path, hash));
+        costumes
+            .chain(sounds)
+            .map(|(path, hash)| {
+                let (_, extension) = path.rsplit_once('.').unwrap();
+                (format!("{hash}.{extension}"), path.clone())
+            })
+            .collect()
+    }
+
     fn assets(&mut self, input: &Path) -> io::Result<()> {
-        let mut added = FxHashSet::default();
-        for (path, hash) in &self.costumes {
-            if added.contains(hash) {
-                continue;
-            }
-            added.insert(hash);
-            let (_, extension) = path.rsplit_once('.').unwrap();
-            self.zip
-                .start_file(format!("{hash}.{extension}"), SimpleFileOptions::default())?;
-            let file = File::open(input.join(&**path));
+        for (name, path) in self.asset_files() {
+            self.zip.start_file(name, self.file_options)?;
+            let file = File::open(input.join(&*path));
             io::copy(&mut file?, &mut self.zip)?;
         }
         if self.srcpkg_hash.is_some() {
             let hash = self.srcpkg_hash.take().unwrap();
             let data = self.srcpkg.take().unwrap();
             self.zip
-                .start_file(format!("{hash}.svg"), SimpleFileOptions::default())?;
+                .start_file(format!("{hash}.svg"), self.file_options)?;
             self.zip.write_all(&data)?;
         }
         Ok(())
     }
 
     pub fn begin_node(&mut self, node: Node) -> io::Result<()> {
+        if let Some(extension) = extension_of(node.opcode()) {
+            self.extensions.insert(extension);
+        }
         write_comma_io(&mut self.zip, &mut self.node_comma)?;
         write!(self, "{node}")
     }
@@ -352,6 +411,23 @@ where T: Write + Seek
         )
     }
 
+    /// Attaches a doc comment to the block with the given id, if doc comments are
+    /// enabled.
+    pub fn doc_comment(&mut self, block_id: NodeID, doc: Option<&SmolStr>) -> Option<NodeID> {
+        let doc = doc.filter(|_| self.doc_comments)?;
+        let comment_id = self.id.new_id();
+        let (x, y, height) = self.layout.comment(doc.lines().count());
+        self.comments.push(Comment {
+            id: comment_id,
+            block_id,
+            text: doc.clone(),
+            x,
+            y,
+            height,
+        });
+        Some(comment_id)
+    }
+
     pub fn substack(&mut self, name: &str, this_id: Option<NodeID>) -> io::Result<()> {
         let Some(this_id) = this_id else {
             return Ok(());
@@ -366,26 +442,67 @@ where T: Write + Seek
         project: &Project,
         config: &Config,
         stage_diagnostics: D,
-        sprites_diagnostics: &mut FxHashMap<SmolStr, SpriteDiagnostics>,
+        sprites_diagnostics: &mut IndexMap<SmolStr, SpriteDiagnostics>,
     ) -> io::Result<()> {
-        let broadcasts: FxHashSet<_> = project
-            .stage
-            .events
+        self.doc_comments = !config.no_doc_comments.unwrap_or(false);
+        let targets = iter::once((STAGE_NAME, &project.stage)).chain(
+            project
+                .sprites
+                .iter()
+                .map(|(sprite_name, sprite)| (sprite_name.as_str(), sprite)),
+        );
+        let tests: Vec<(&str, &SmolStr)> = targets
+            .clone()
+            .filter(|_| self.tests)
+            .flat_map(|(sprite_name, sprite)| {
+                sprite
+                    .events
+                    .iter()
+                    .filter_map(move |event| match &event.kind {
+                        EventKind::Test { name } => Some((sprite_name, name)),
+                        _ => None,
+                    })
+            })
+            .collect();
+        let broadcasts: IndexSet<_> = targets
+            .flat_map(|(_, sprite)| &sprite.events)
+            .filter_map(|event| match &event.kind {
+                EventKind::On { event } => Some(event.clone()),
+                _ => None,
+            })
+            .chain(tests.iter().flat_map(|(sprite_name, name)| {
+                [
+                    test_broadcast(sprite_name, name),
+                    test_harness_broadcast(sprite_name, name),
+                ]
+            }))
+            .collect();
+        let stage_state =
+            SpriteState::new("stage", &project.stage, true, config, stage_diagnostics);
+        let mut sprite_states: IndexMap<&SmolStr, SpriteState> = project
+            .sprites
             .iter()
-            .chain(project.sprites.values().flat_map(|sprite| &sprite.events))
-            .filter_map(|event| {
-                if let EventKind::On { event } = &event.kind {
-                    Some(event.clone())
-                } else {
-                    None
-                }
+            .map(|(sprite_name, sprite)| {
+                let d = sprites_diagnostics.get_mut(sprite_name).unwrap();
+                let state = SpriteState::new(sprite_name, sprite, false, config, d);
+                (sprite_name, state)
             })
             .collect();
-        // TODO: switch to deflate compression
-        // this should be configurable, use store in debug (because it would be
-        // faster?), use deflate in release (because it would be smaller?)
-        self.zip
-            .start_file("project.json", SimpleFileOptions::default())?;
+        // Sprites with an explicit layer go first, in order of their layer. The stage
+        // is always at layer 0.
+        let mut layers: Vec<(Option<f64>, &SmolStr)> = sprite_states
+            .iter()
+            .map(|(sprite_name, state)| (state.layer, *sprite_name))
+            .collect();
+        layers.sort_by(|(a_layer, a_name), (b_layer, b_name)| {
+            let a_layer = a_layer.unwrap_or(f64::INFINITY);
+            let b_layer = b_layer.unwrap_or(f64::INFINITY);
+            a_layer.total_cmp(&b_layer).then_with(|| a_name.cmp(b_name))
+        });
+        for (layer_order, (_, sprite_name)) in layers.into_iter().enumerate() {
+            sprite_states.get_mut(sprite_name).unwrap().layer_order = layer_order + 1;
+        }
+        self.zip.start_file("project.json", self.file_options)?;
         write!(self, "{{")?;
         write!(self, r#""targets":["#)?;
         self.sprite(
@@ -394,6 +511,7 @@ where T: Write + Seek
             &project.stage,
             None,
             config,
+            &stage_state,
             stage_diagnostics,
             Some(broadcasts),
         )?;
@@ -405,13 +523,24 @@ where T: Write + Seek
                 sprite,
                 Some(&project.stage),
                 config,
+                &sprite_states[sprite_name],
                 sprites_diagnostics.get_mut(sprite_name).unwrap(),
                 None,
             )?;
         }
+        if !tests.is_empty() {
+            write!(self, r#","#)?;
+            self.test_harness(project, &tests, project.sprites.len() + 1)?;
+        }
         write!(self, "]")?; // targets
         write!(self, r#","monitors":[]"#)?;
-        write!(self, r#","extensions":[]"#)?;
+        write!(self, r#","extensions":["#)?;
+        let mut comma = false;
+        for extension in std::mem::take(&mut self.extensions) {
+            write_comma_io(&mut self.zip, &mut comma)?;
+            write!(self, "{}", json!(extension))?;
+        }
+        write!(self, "]")?; // extensions
         write!(self, r#","meta":{{"#)?;
         write!(self, r#""semver":"3.0.0""#)?;
         write!(self, r#","vm":"0.2.0""#)?;
@@ -433,75 +562,19 @@ where T: Write + Seek
         sprite: &Sprite,
         stage: Option<&Sprite>,
         config: &Config,
+        state: &SpriteState,
         d: D,
-        broadcasts: Option<FxHashSet<SmolStr>>,
+        broadcasts: Option<IndexSet<SmolStr>>,
     ) -> io::Result<()> {
-        for proc in sprite.procs.values() {
-            if !sprite.used_procs.contains(&proc.name) {
-                d.report(DiagnosticKind::UnusedProc(proc.name.clone()), &proc.span);
-            } else {
-                for arg in &proc.args {
-                    if !sprite
-                        .proc_used_args
-                        .get(&proc.name)
-                        .unwrap()
-                        .contains(&arg.name)
-                    {
-                        d.report(DiagnosticKind::UnusedArg(arg.name.clone()), &arg.span);
-                    }
-                }
-            }
-        }
-        for func in sprite.funcs.values() {
-            if !sprite.used_funcs.contains(&func.name) {
-                d.report(DiagnosticKind::UnusedFunc(func.name.clone()), &func.span);
-            } else {
-                for arg in &func.args {
-                    if !sprite
-                        .func_used_args
-                        .get(&func.name)
-                        .unwrap()
-                        .contains(&arg.name)
-                    {
-                        d.report(DiagnosticKind::UnusedArg(arg.name.clone()), &arg.span);
-                    }
-                }
-            }
-        }
-        for struct_ in sprite.structs.values() {
-            if !struct_.is_used {
-                d.report(
-                    DiagnosticKind::UnusedStruct(struct_.name.clone()),
-                    &struct_.span,
-                );
-            }
-        }
-        for enum_ in sprite.enums.values() {
-            if !enum_.is_used {
-                d.report(DiagnosticKind::UnusedEnum(enum_.name.clone()), &enum_.span);
-            }
-        }
         self.id.reset();
+        self.layout.reset();
+        if name == STAGE_NAME {
+            // Space for the TurboWarp configuration comment.
+            self.layout.reserve(170);
+        }
         write!(self, "{{")?;
         write!(self, r#""isStage":{}"#, name == STAGE_NAME)?;
         write!(self, r#","name":{}"#, json!(name))?;
-        if name == STAGE_NAME {
-            write!(self, r#","comments":{{"#)?;
-            write!(self, r#""twconfig":{{"#)?;
-            write!(self, r#""blockId":null"#)?;
-            write!(self, r#","x":0"#)?;
-            write!(self, r#","y":0"#)?;
-            write!(self, r#","width":350"#)?;
-            write!(self, r#","height":170"#)?;
-            write!(self, r#","minimized":false"#)?;
-            write!(
-                self,
-                r#","text":{}"#,
-                json!(TurbowarpConfig::from(config).to_string())
-            )?;
-            write!(self, "}}")?; // twconfig
-            write!(self, "}}")?; // comments
-        }
         write!(self, r#","broadcasts":{{"#)?;
         let mut comma = false;
         for broadcast in broadcasts.unwrap_or_default() {
@@ -517,7 +590,7 @@ where T: Write + Seek
             .filter(|proc| sprite.used_procs.contains(&proc.name))
         {
             for var in proc.locals.values() {
-                self.local_var_declaration(sprite, &proc.name, var, &mut comma, d)?;
+                self.local_var_declaration(sprite, &proc.name, var, &mut comma)?;
             }
         }
         for func in sprite
@@ -526,11 +599,11 @@ where T: Write + Seek
             .filter(|func| sprite.used_funcs.contains(&func.name))
         {
             for var in func.locals.values() {
-                self.local_var_declaration(sprite, &func.name, var, &mut comma, d)?;
+                self.local_var_declaration(sprite, &func.name, var, &mut comma)?;
             }
         }
         for var in sprite.vars.values().filter(|var| var.is_used) {
-            self.var_declaration(sprite, var, &mut comma, d)?;
+            self.var_declaration(sprite, var, &mut comma)?;
         }
         write!(self, "}}")?; // variables
         write!(self, r#","lists":{{"#)?;
@@ -559,6 +632,7 @@ where T: Write + Seek
                 proc_definition,
             )?;
         }
+        self.layout.next_column();
         for func in sprite
             .funcs
             .values()
@@ -577,7 +651,13 @@ where T: Write + Seek
                 func_definition,
             )?;
         }
-        for event in &sprite.events {
+        self.layout.next_column();
+        let tests = self.tests;
+        for event in sprite
+            .events
+            .iter()
+            .filter(|event| tests || !matches!(event.kind, EventKind::Test { .. }))
+        {
             self.event(
                 S {
                     stage,
@@ -586,13 +666,42 @@ where T: Write + Seek
                     func: None,
                 },
                 d,
+                name,
                 event,
             )?;
         }
         write!(self, "}}")?; // blocks
-        if sprite.costumes.is_empty() {
-            d.report(DiagnosticKind::NoCostumes, &(0..0));
+        write!(self, r#","comments":{{"#)?;
+        let mut comma = false;
+        if name == STAGE_NAME {
+            write_comma_io(&mut self.zip, &mut comma)?;
+            write!(self, r#""twconfig":{{"#)?;
+            write!(self, r#""blockId":null"#)?;
+            write!(self, r#","x":0"#)?;
+            write!(self, r#","y":0"#)?;
+            write!(self, r#","width":350"#)?;
+            write!(self, r#","height":170"#)?;
+            write!(self, r#","minimized":false"#)?;
+            write!(
+                self,
+                r#","text":{}"#,
+                json!(TurbowarpConfig::from(config).to_string())
+            )?;
+            write!(self, "}}")?; // twconfig
         }
+        for comment in std::mem::take(&mut self.comments) {
+            write_comma_io(&mut self.zip, &mut comma)?;
+            write!(self, r#"{}:{{"#, comment.id)?;
+            write!(self, r#""blockId":{}"#, comment.block_id)?;
+            write!(self, r#","x":{}"#, comment.x)?;
+            write!(self, r#","y":{}"#, comment.y)?;
+            write!(self, r#","width":350"#)?;
+            write!(self, r#","height":{}"#, comment.height)?;
+            write!(self, r#","minimized":false"#)?;
+            write!(self, r#","text":{}"#, json!(*comment.text))?;
+            write!(self, "}}")?; // comment
+        }
+        write!(self, "}}")?; // comments
         write!(self, r#","costumes":["#)?;
         let mut comma = false;
         for costume in &sprite.costumes {
@@ -601,11 +710,92 @@ where T: Write + Seek
         }
         write!(self, "]")?; // costumes
         write!(self, r#","sounds":["#)?;
+        let mut comma = false;
+        for sound in &sprite.sounds {
+            write_comma_io(&mut self.zip, &mut comma)?;
+            self.sound(input, sound, d)?;
+        }
         write!(self, "]")?; // sounds
+        write!(self, r#","currentCostume":{}"#, state.current_costume)?;
+        write!(self, r#","volume":{}"#, state.volume)?;
+        write!(self, r#","layerOrder":{}"#, state.layer_order)?;
+        if name != STAGE_NAME {
+            write!(self, r#","x":{}"#, state.x)?;
+            write!(self, r#","y":{}"#, state.y)?;
+            write!(self, r#","size":{}"#, state.size)?;
+            write!(self, r#","direction":{}"#, state.direction)?;
+            write!(self, r#","visible":{}"#, state.visible)?;
+            write!(self, r#","draggable":{}"#, state.draggable)?;
+            write!(self, r#","rotationStyle":{}"#, json!(state.rotation_style))?;
+        }
         write!(self, "}}")?; // sprite
         Ok(())
     }
 
+    /// Writes the hidden sprite which `goboscript test` runs each test from, it shows the
+    /// first costume of the stage.
+    fn test_harness(
+        &mut self,
+        project: &Project,
+        tests: &[(&str, &SmolStr)],
+        layer_order: usize,
+    ) -> io::Result<()> {
+        self.id.reset();
+        self.layout.reset();
+        write!(self, "{{")?;
+        write!(self, r#""isStage":false"#)?;
+        write!(self, r#","name":{}"#, json!(TEST_HARNESS_NAME))?;
+        write!(self, r#","variables":{{}}"#)?;
+        write!(self, r#","lists":{{}}"#)?;
+        write!(self, r#","blocks":{{"#)?;
+        self.node_comma = false;
+        for (sprite_name, name) in tests {
+            let this_id = self.id.new_id();
+            let next_id = self.id.new_id();
+            let position = self.layout.begin_script();
+            self.begin_node(
+                Node::new("event_whenbroadcastreceived", this_id)
+                    .some_next_id(Some(next_id))
+                    .top_level(true)
+                    .position(position),
+            )?;
+            self.on(&test_harness_broadcast(sprite_name, name))?;
+            self.begin_node(Node::new("event_broadcastandwait", next_id).parent_id(this_id))?;
+            let broadcast = test_broadcast(sprite_name, name);
+            write!(
+                self,
+                r#","inputs":{{"BROADCAST_INPUT":[1,[11,{},{}]]}}"#,
+                json!(*broadcast),
+                json!(*broadcast)
+            )?;
+            self.end_obj()?; // node
+            self.layout.block();
+            self.layout.end_script();
+        }
+        write!(self, "}}")?; // blocks
+        write!(self, r#","comments":{{}}"#)?;
+        write!(self, r#","costumes":["#)?;
+        if let Some(costume) = project.stage.costumes.first() {
+            if let Some(hash) = self.costumes.get(&costume.path).cloned() {
+                let (_, extension) = costume.path.rsplit_once('.').unwrap_or_default();
+                self.costume_entry(&costume.name, &hash, extension)?;
+            }
+        }
+        write!(self, "]")?; // costumes
+        write!(self, r#","sounds":[]"#)?;
+        write!(self, r#","currentCostume":0"#)?;
+        write!(self, r#","volume":100"#)?;
+        write!(self, r#","layerOrder":{layer_order}"#)?;
+        write!(self, r#","x":0"#)?;
+        write!(self, r#","y":0"#)?;
+        write!(self, r#","size":100"#)?;
+        write!(self, r#","direction":90"#)?;
+        write!(self, r#","visible":false"#)?;
+        write!(self, r#","draggable":false"#)?;
+        write!(self, r#","rotationStyle":"all around""#)?;
+        write!(self, "}}") // sprite
+    }
+
     pub fn json_var_declaration(
         &mut self,
         var_name: &str,
@@ -625,25 +815,19 @@ where T: Write + Seek
         sprite: &Sprite,
         var: &Var,
         comma: &mut bool,
-        d: D,
     ) -> io::Result<()> {
         match &var.type_ {
-            Type::Value => {
+            Type::Value | Type::Enum { .. } => {
                 self.json_var_declaration(&var.name, var.is_cloud, comma)?;
             }
             Type::Struct {
-                name: type_name,
-                span: type_span,
+                name: type_name, ..
             } => {
                 let Some(struct_) = sprite.structs.get(type_name) else {
-                    d.report(
-                        DiagnosticKind::UnrecognizedStruct(type_name.clone()),
-                        type_span,
-                    );
                     return Ok(());
                 };
-                for field in &struct_.fields {
-                    let qualified_var_name = qualify_struct_var_name(&field.name, &var.name);
+                for (path, _) in struct_.leaf_fields(&|name| sprite.structs.get(name)) {
+                    let qualified_var_name = qualify_struct_var_name(&path, &var.name);
                     self.json_var_declaration(&qualified_var_name, false, comma)?;
                 }
             }
@@ -657,28 +841,22 @@ where T: Write + Seek
         proc_name: &str,
         var: &Var,
         comma: &mut bool,
-        d: D,
     ) -> io::Result<()> {
         match &var.type_ {
-            Type::Value => {
+            Type::Value | Type::Enum { .. } => {
                 let qualified_var_name = qualify_local_var_name(proc_name, &var.name);
                 self.json_var_declaration(&qualified_var_name, false, comma)?;
             }
             Type::Struct {
-                name: type_name,
-                span: type_span,
+                name: type_name, ..
             } => {
                 let Some(struct_) = sprite.structs.get(type_name) else {
-                    d.report(
-                        DiagnosticKind::UnrecognizedStruct(type_name.clone()),
-                        type_span,
-                    );
                     return Ok(());
                 };
-                for field in &struct_.fields {
+                for (path, _) in struct_.leaf_fields(&|name| sprite.structs.get(name)) {
                     let qualified_var_name = qualify_local_var_name(
                         proc_name,
-                        &qualify_struct_var_name(&field.name, &var.name),
+                        &qualify_struct_var_name(&path, &var.name),
                     );
                     self.json_var_declaration(&qualified_var_name, false, comma)?;
                 }
@@ -711,7 +889,7 @@ where T: Write + Seek
                 })
             });
         match &list.type_ {
-            Type::Value => {
+            Type::Value | Type::Enum { .. } => {
                 write_comma_io(&mut self.zip, comma)?;
                 if let Some(cmd) = data {
                     write!(self, r#""{}":["{}",{}]"#, list.name, list.name, json!(cmd))?;
@@ -720,22 +898,18 @@ where T: Write + Seek
                 }
             }
             Type::Struct {
-                name: type_name,
-                span: type_span,
+                name: type_name, ..
             } => {
                 let Some(struct_) = sprite.structs.get(type_name) else {
-                    d.report(
-                        DiagnosticKind::UnrecognizedStruct(type_name.clone()),
-                        type_span,
-                    );
                     return Ok(());
                 };
-                for (i, field) in struct_.fields.iter().enumerate() {
-                    let qualified_list_name = qualify_struct_var_name(&field.name, &list.name);
+                let leaf_fields = struct_.leaf_fields(&|name| sprite.structs.get(name));
+                for (i, (path, _)) in leaf_fields.iter().enumerate() {
+                    let qualified_list_name = qualify_struct_var_name(path, &list.name);
                     write_comma_io(&mut self.zip, comma)?;
                     if let Some(cmd) = &data {
-                        let column = (0..(cmd.len() / struct_.fields.len()))
-                            .map(|j| &cmd[j * struct_.fields.len() + i])
+                        let column = (0..(cmd.len() / leaf_fields.len()))
+                            .map(|j| &cmd[j * leaf_fields.len() + i])
                             .collect::<Vec<_>>();
                         write!(
                             self,
@@ -794,14 +968,59 @@ where T: Write + Seek
         write!(self, "}}") // costume
     }
 
+    pub fn sound(&mut self, input: &Path, sound: &Sound, d: D) -> io::Result<()> {
+        let (_, extension) = sound.path.rsplit_once('.').unwrap_or_default();
+        let (hash, metadata) = match self.sounds.get(&sound.path) {
+            Some(asset) => asset.clone(),
+            None => match std::fs::read(input.join(&*sound.path)).and_then(|data| {
+                let metadata = audio_metadata(&data, extension)?;
+                Ok((data, metadata))
+            }) {
+                Ok((data, metadata)) => {
+                    let hash: SmolStr = format!("{:x}", Md5::digest(&data)).into();
+                    self.sounds
+                        .insert(sound.path.clone(), (hash.clone(), metadata));
+                    (hash, metadata)
+                }
+                Err(error) => {
+                    d.report(DiagnosticKind::IOError(error), &sound.span);
+                    Default::default()
+                }
+            },
+        };
+        self.sound_entry(&sound.name, &hash, extension, metadata)
+    }
+
+    pub fn sound_entry(
+        &mut self,
+        name: &str,
+        hash: &str,
+        extension: &str,
+        metadata: AudioMetadata,
+    ) -> io::Result<()> {
+        write!(self, "{{")?;
+        write!(self, r#""name":{}"#, json!(name))?;
+        write!(self, r#","assetId":"{hash}""#)?;
+        write!(self, r#","dataFormat":"{extension}""#)?;
+        write!(self, r#","format":"""#)?;
+        write!(self, r#","rate":{}"#, metadata.rate)?;
+        write!(self, r#","sampleCount":{}"#, metadata.sample_count)?;
+        write!(self, r#","md5ext":"{hash}.{extension}""#)?;
+        write!(self, "}}") // sound
+    }
+
     pub fn proc(&mut self, s: S, d: D, proc: &Proc, definition: &[Stmt]) -> io::Result<()> {
         let this_id = self.id.new_id();
         let prototype_id = self.id.new_id();
         let next_id = self.id.new_id();
+        let comment_id = self.doc_comment(this_id, proc.doc.as_ref());
+        let position = self.layout.begin_script();
         self.begin_node(
             Node::new("procedures_definition", this_id)
                 .some_next_id((!definition.is_empty()).then_some(next_id))
-                .top_level(true),
+                .some_comment_id(comment_id)
+                .top_level(true)
+                .position(position),
         )?;
         self.begin_inputs()?;
         write!(self, r#""custom_block":[1,{prototype_id}]"#)?;
@@ -810,7 +1029,7 @@ where T: Write + Seek
         let mut qualified_args: Vec<(SmolStr, NodeID)> = Vec::new();
         for arg in &proc.args {
             match &arg.type_ {
-                Type::Value => {
+                Type::Value | Type::Enum { .. } => {
                     let arg_id = self.id.new_id();
                     self.begin_node(
                         Node::new("argument_reporter_string_number", arg_id)
@@ -822,18 +1041,13 @@ where T: Write + Seek
                     qualified_args.push((arg.name.clone(), arg_id));
                 }
                 Type::Struct {
-                    name: type_name,
-                    span: type_span,
+                    name: type_name, ..
                 } => {
                     let Some(struct_) = s.sprite.structs.get(type_name) else {
-                        d.report(
-                            DiagnosticKind::UnrecognizedStruct(type_name.clone()),
-                            type_span,
-                        );
                         continue;
                     };
-                    for field in &struct_.fields {
-                        let qualified_arg_name = qualify_struct_var_name(&field.name, &arg.name);
+                    for (path, _) in struct_.leaf_fields(&|name| s.sprite.structs.get(name)) {
+                        let qualified_arg_name = qualify_struct_var_name(&path, &arg.name);
                         let arg_id = self.id.new_id();
                         self.begin_node(
                             Node::new("argument_reporter_string_number", arg_id)
@@ -865,17 +1079,23 @@ where T: Write + Seek
             Mutation::prototype(proc.name.clone(), &qualified_args, proc.warp, false)
         )?;
         self.end_obj()?; // node
-        self.stmts(s, d, definition, next_id, Some(this_id))
+        self.stmts(s, d, definition, next_id, Some(this_id))?;
+        self.layout.end_script();
+        Ok(())
     }
 
     pub fn func(&mut self, s: S, d: D, func: &Func, definition: &[Stmt]) -> io::Result<()> {
         let this_id = self.id.new_id();
         let prototype_id = self.id.new_id();
         let next_id = self.id.new_id();
+        let comment_id = self.doc_comment(this_id, func.doc.as_ref());
+        let position = self.layout.begin_script();
         self.begin_node(
             Node::new("procedures_definition", this_id)
                 .some_next_id((!definition.is_empty()).then_some(next_id))
-                .top_level(true),
+                .some_comment_id(comment_id)
+                .top_level(true)
+                .position(position),
         )?;
         self.begin_inputs()?;
         write!(self, r#""custom_block":[1,{prototype_id}]"#)?;
@@ -884,7 +1104,7 @@ where T: Write + Seek
         let mut qualified_args: Vec<(SmolStr, NodeID)> = Vec::new();
         for arg in &func.args {
             match &arg.type_ {
-                Type::Value => {
+                Type::Value | Type::Enum { .. } => {
                     let arg_id = self.id.new_id();
                     self.begin_node(
                         Node::new("argument_reporter_string_number", arg_id)
@@ -896,18 +1116,13 @@ where T: Write + Seek
                     qualified_args.push((arg.name.clone(), arg_id));
                 }
                 Type::Struct {
-                    name: type_name,
-                    span: type_span,
+                    name: type_name, ..
                 } => {
                     let Some(struct_) = s.sprite.structs.get(type_name) else {
-                        d.report(
-                            DiagnosticKind::UnrecognizedStruct(type_name.clone()),
-                            type_span,
-                        );
                         continue;
                     };
-                    for field in &struct_.fields {
-                        let qualified_arg_name = qualify_struct_var_name(&field.name, &arg.name);
+                    for (path, _) in struct_.leaf_fields(&|name| s.sprite.structs.get(name)) {
+                        let qualified_arg_name = qualify_struct_var_name(&path, &arg.name);
                         let arg_id = self.id.new_id();
                         self.begin_node(
                             Node::new("argument_reporter_string_number", arg_id)
@@ -939,19 +1154,26 @@ where T: Write + Seek
             Mutation::prototype(func.name.clone(), &qualified_args, true, false)
         )?;
         self.end_obj()?; // node
-        self.stmts(s, d, definition, next_id, Some(this_id))
+        self.stmts(s, d, definition, next_id, Some(this_id))?;
+        self.layout.end_script();
+        Ok(())
     }
 
-    pub fn event(&mut self, s: S, d: D, event: &Event) -> io::Result<()> {
+    pub fn event(&mut self, s: S, d: D, sprite_name: &str, event: &Event) -> io::Result<()> {
         let this_id = self.id.new_id();
         let next_id = self.id.new_id();
+        let comment_id = self.doc_comment(this_id, event.doc.as_ref());
+        let position = self.layout.begin_script();
         self.begin_node(
             Node::new(event.kind.opcode(), this_id)
                 .some_next_id((!event.body.is_empty()).then_some(next_id))
-                .top_level(true),
+                .some_comment_id(comment_id)
+                .top_level(true)
+                .position(position),
         )?;
         match &event.kind {
             EventKind::On { event } => self.on(event),
+            EventKind::Test { name } => self.on(&test_broadcast(sprite_name, name)),
             EventKind::OnFlag => self.on_flag(),
             EventKind::OnKey { key, span } => self.on_key(s, d, this_id, key, span),
             EventKind::OnClick => self.on_click(s, d, this_id),
@@ -962,7 +1184,9 @@ where T: Write + Seek
             EventKind::OnTimerGt { value } => self.on_timer_gt(s, d, this_id, value),
             EventKind::OnClone => self.on_clone(s, d, this_id),
         }?;
-        self.stmts(s, d, &event.body, next_id, Some(this_id))
+        self.stmts(s, d, &event.body, next_id, Some(this_id))?;
+        self.layout.end_script();
+        Ok(())
     }
 
     pub fn stmts(
@@ -975,11 +1199,8 @@ where T: Write + Seek
     ) -> io::Result<()> {
         for (i, stmt) in stmts.iter().enumerate() {
             let is_last = i == stmts.len() - 1;
-            if is_last || stmt.is_terminator() {
+            if is_last || stmt.terminator_span().is_some() {
                 self.stmt(s, d, stmt, this_id, None, parent_id)?;
-                if !is_last {
-                    d.report(DiagnosticKind::FollowedByUnreachableCode, stmt.span());
-                }
                 break;
             }
             let next_id = self.id.new_id();
@@ -999,6 +1220,7 @@ where T: Write + Seek
         next_id: Option<NodeID>,
         parent_id: Option<NodeID>,
     ) -> io::Result<()> {
+        self.layout.stmt(stmt);
         self.begin_node(
             Node::new(stmt.opcode(s), this_id)
                 .some_next_id(next_id)
@@ -1013,6 +1235,11 @@ where T: Write + Seek
                 else_body,
             } => self.branch(s, d, this_id, cond, if_body, else_body),
             Stmt::Until { cond, body } => self.until(s, d, this_id, cond, body),
+            Stmt::ForRange { .. } | Stmt::ForEach { .. } | Stmt::Match { .. } => {
+                unreachable!("lowered in pass0")
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => unreachable!("lowered in loops"),
+            Stmt::Destructure { .. } => unreachable!("lowered in pass1"),
             Stmt::SetVar {
                 name,
                 value,
@@ -1021,22 +1248,22 @@ where T: Write + Seek
                 is_cloud,
             } => self.set_var(s, d, this_id, name, value, type_, is_local, is_cloud),
             Stmt::ChangeVar { name, value } => self.change_var(s, d, this_id, name, value),
-            Stmt::Show(name) => self.show(s, d, name),
-            Stmt::Hide(name) => self.hide(s, d, name),
+            Stmt::Show(name) => self.show(s, name),
+            Stmt::Hide(name) => self.hide(s, name),
             Stmt::AddToList { name, value } => self.add_to_list(s, d, this_id, name, value),
             Stmt::DeleteListIndex { name, index } => {
                 self.delete_list_index(s, d, this_id, name, index)
             }
-            Stmt::DeleteList(name) => self.delete_list(s, d, name),
+            Stmt::DeleteList(name) => self.delete_list(s, name),
             Stmt::InsertAtList { name, index, value } => {
                 self.list_insert(s, d, this_id, name, index, value)
             }
             Stmt::SetListIndex { name, index, value } => {
                 self.set_list_index(s, d, this_id, name, index, value)
             }
-            Stmt::Block { block, span, args } => self.block(s, d, this_id, block, span, args),
+            Stmt::Block { block, args, .. } => self.block(s, d, this_id, block, args),
             Stmt::ProcCall { name, span, args } => self.proc_call(s, d, this_id, name, span, args),
-            Stmt::FuncCall { name, span, args } => self.func_call(s, d, this_id, name, span, args),
+            Stmt::FuncCall { name, args, .. } => self.func_call(s, d, this_id, name, args),
             Stmt::Return { .. } => panic!(),
         }
     }
@@ -1052,34 +1279,17 @@ where T: Write + Seek
         match expr {
             Expr::Value { .. } => Ok(()),
             Expr::Name { .. } => Ok(()),
-            Expr::Arg(name) => self.arg(s, d, this_id, parent_id, name),
-            Expr::Repr { repr, span, args } => {
-                self.repr(s, d, this_id, parent_id, repr, span, args)
-            }
-            Expr::FuncCall { name, span, .. } => {
-                d.report(DiagnosticKind::UnrecognizedFunction(name.clone()), span);
-                Ok(())
-            }
+            Expr::Arg(name) => self.arg(s, this_id, parent_id, name),
+            Expr::Repr { repr, args, .. } => self.repr(s, d, this_id, parent_id, repr, args),
+            Expr::FuncCall { .. } | Expr::StructLiteral { .. } => Ok(()),
             Expr::UnOp { op, span, opr } => self.un_op(s, d, this_id, parent_id, op, span, opr),
             Expr::BinOp { op, span, lhs, rhs } => {
                 self.bin_op(s, d, this_id, parent_id, op, span, lhs, rhs)
             }
-            Expr::StructLiteral { name, span, .. } => {
-                d.report(
-                    DiagnosticKind::TypeMismatch {
-                        expected: Type::Value,
-                        given: Type::Struct {
-                            name: name.clone(),
-                            span: span.clone(),
-                        },
-                    },
-                    &expr.span(),
-                );
-                Ok(())
-            }
             Expr::Dot { lhs, rhs, rhs_span } => {
                 self.expr_dot(s, d, this_id, parent_id, lhs, rhs, rhs_span.clone())
             }
+            Expr::Ternary { .. } => unreachable!("lowered in pass1"),
         }
     }
 }
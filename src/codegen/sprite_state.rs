@@ -0,0 +1,235 @@
+use crate::{
+    ast::*,
+    config::Config,
+    diagnostic::{
+        DiagnosticKind,
+        SpriteDiagnostics,
+    },
+};
+
+const ROTATION_STYLES: [&str; 3] = ["all around", "left-right", "don't rotate"];
+
+/// The initial state of a sprite or the stage, as written into project.json.
+#[derive(Debug)]
+pub struct SpriteState {
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+    pub direction: f64,
+    pub visible: bool,
+    pub draggable: bool,
+    pub rotation_style: &'static str,
+    pub current_costume: usize,
+    pub volume: f64,
+    pub layer: Option<f64>,
+    pub layer_order: usize,
+}
+
+impl Default for SpriteState {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            size: 100.0,
+            direction: 90.0,
+            visible: true,
+            draggable: false,
+            rotation_style: ROTATION_STYLES[0],
+            current_costume: 0,
+            volume: 100.0,
+            layer: None,
+            layer_order: 0,
+        }
+    }
+}
+
+impl SpriteState {
+    /// Properties from the `[sprites.<name>]` table in goboscript.toml are applied
+    /// first, so that a `sprite { ... }` declaration can override them.
+    pub fn new(
+        name: &str,
+        sprite: &Sprite,
+        is_stage: bool,
+        config: &Config,
+        d: &mut SpriteDiagnostics,
+    ) -> Self {
+        // Errors in goboscript.toml are reported before codegen, by `analyze`.
+        let mut state = Self::from_config(name, sprite, is_stage, config).unwrap_or_default();
+        for property in &sprite.properties {
+            let value = property.value.evaluate();
+            if let Err(kind) = state.set(&property.name, &property.name, value, sprite, is_stage) {
+                d.report(kind, &property.span);
+            }
+        }
+        state
+    }
+
+    /// Applies the `[sprites.<name>]` table in goboscript.toml, and fails with every
+    /// property which is not valid.
+    pub fn from_config<'a>(
+        name: &str,
+        sprite: &Sprite,
+        is_stage: bool,
+        config: &'a Config,
+    ) -> Result<Self, Vec<(&'a str, DiagnosticKind)>> {
+        let mut state = Self::default();
+        let mut errors = vec![];
+        for (property, value) in config.sprites.get(name).into_iter().flatten() {
+            let value = match value {
+                toml::Value::String(string) => Value::from(string.as_str()),
+                toml::Value::Integer(int) => Value::from(*int),
+                toml::Value::Float(float) => Value::from(*float),
+                toml::Value::Boolean(bool) => Value::from(*bool),
+                _ => Value::from(""),
+            };
+            let label = format!("sprites.{name}.{property}");
+            if let Err(kind) = state.set(property, &label, value, sprite, is_stage) {
+                errors.push((property.as_str(), kind));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(state)
+    }
+
+    fn set(
+        &mut self,
+        property: &str,
+        label: &str,
+        value: Value,
+        sprite: &Sprite,
+        is_stage: bool,
+    ) -> Result<(), DiagnosticKind> {
+        let (is_valid, expected) = match property {
+            "x" => (number(&value).map(|x| self.x = x), "a number"),
+            "y" => (number(&value).map(|y| self.y = y), "a number"),
+            "size" => (
+                number(&value)
+                    .filter(|size| *size >= 0.0)
+                    .map(|size| self.size = size),
+                "a non-negative number",
+            ),
+            "direction" => (
+                number(&value)
+                    .filter(|direction| (-180.0..=180.0).contains(direction))
+                    .map(|direction| self.direction = direction),
+                "a number between -180 and 180",
+            ),
+            "visible" => (
+                boolean(&value).map(|visible| self.visible = visible),
+                "`true` or `false`",
+            ),
+            "draggable" => (
+                boolean(&value).map(|draggable| self.draggable = draggable),
+                "`true` or `false`",
+            ),
+            "rotation_style" => (
+                ROTATION_STYLES
+                    .into_iter()
+                    .find(|style| matches!(&value, Value::String(s) if s == style))
+                    .map(|style| self.rotation_style = style),
+                "`\"all around\"`, `\"left-right\"` or `\"don't rotate\"`",
+            ),
+            "costume" => (
+                costume(&value, sprite).map(|costume| self.current_costume = costume),
+                "the name or number of one of this sprite's costumes",
+            ),
+            "volume" => (
+                number(&value)
+                    .filter(|volume| (0.0..=100.0).contains(volume))
+                    .map(|volume| self.volume = volume),
+                "a number between 0 and 100",
+            ),
+            "layer" => (
+                number(&value)
+                    .filter(|layer| *layer >= 1.0 && layer.fract() == 0.0)
+                    .map(|layer| self.layer = Some(layer)),
+                "a positive integer",
+            ),
+            _ => return Err(DiagnosticKind::UnrecognizedProperty(label.into())),
+        };
+        if is_stage && !matches!(property, "costume" | "volume") {
+            return Err(DiagnosticKind::PropertyNotOnStage(label.into()));
+        }
+        if is_valid.is_none() {
+            return Err(DiagnosticKind::InvalidPropertyValue {
+                property: label.into(),
+                expected,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(int) => Some(*int as f64),
+        Value::Float(float) if float.is_finite() => Some(*float),
+        _ => None,
+    }
+}
+
+fn boolean(value: &Value) -> Option<bool> {
+    match value {
+        Value::Int(0) => Some(false),
+        Value::Int(1) => Some(true),
+        Value::Bool(bool) => Some(*bool),
+        _ => None,
+    }
+}
+
+fn costume(value: &Value, sprite: &Sprite) -> Option<usize> {
+    match value {
+        Value::String(name) => sprite
+            .costumes
+            .iter()
+            .position(|costume| &costume.name == name),
+        Value::Int(number) if (1..=sprite.costumes.len() as i64).contains(number) => {
+            Some(*number as usize - 1)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_errors() {
+        let sprite = Sprite::default();
+        let mut config = Config::default();
+        let table = [
+            ("x".to_string(), toml::Value::String("left".into())),
+            ("y".to_string(), toml::Value::Integer(10)),
+            ("z".to_string(), toml::Value::Integer(1)),
+        ]
+        .into();
+        config.sprites.insert("main".into(), table);
+        let Err(errors) = SpriteState::from_config("main", &sprite, false, &config) else {
+            panic!("x = \"left\" and z = 1 should be invalid");
+        };
+        let errors: Vec<(&str, String)> = errors
+            .into_iter()
+            .map(|(property, kind)| (property, kind.to_string(&sprite)))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (
+                    "x",
+                    "invalid value for property sprites.main.x, expected a number".to_string()
+                ),
+                ("z", "unrecognized property sprites.main.z".to_string()),
+            ]
+        );
+        let Err(errors) = SpriteState::from_config("main", &sprite, true, &config) else {
+            panic!("x should not be settable on the stage");
+        };
+        assert_eq!(
+            errors[0].1.to_string(&sprite),
+            "property sprites.main.x cannot be set on the stage"
+        );
+    }
+}
@@ -14,7 +14,7 @@ impl<T> Sb3<T>
 where T: io::Write + io::Seek
 {
     pub fn srcpkg(&mut self, input: &Path, output: &Path) -> io::Result<()> {
-        let zipped = create_zipped_srcpkg(input, output)?;
+        let zipped = create_zipped_srcpkg(input, output, self.file_options)?;
         let header = include_bytes!("srcpkg.svg");
         let mut file: Vec<u8> = Vec::with_capacity(header.len());
         file.write_all(b"<!--")?;
@@ -41,7 +41,11 @@ where T: io::Write + io::Seek
     }
 }
 
-fn create_zipped_srcpkg(input: &Path, output: &Path) -> io::Result<Vec<u8>> {
+fn create_zipped_srcpkg(
+    input: &Path,
+    output: &Path,
+    options: SimpleFileOptions,
+) -> io::Result<Vec<u8>> {
     let output = output
         .parent()
         .unwrap()
@@ -49,14 +53,14 @@ fn create_zipped_srcpkg(input: &Path, output: &Path) -> io::Result<Vec<u8>> {
         .unwrap()
         .join(output.file_name().unwrap());
     let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
-    for entry in WalkDir::new(input).into_iter().flatten() {
+    for entry in WalkDir::new(input).sort_by_file_name().into_iter().flatten() {
         if !entry.metadata().is_ok_and(|metadata| metadata.is_file()) {
             continue;
         }
         if entry.path().canonicalize().unwrap() == output {
             continue;
         }
-        zip.start_file(entry.path().to_string_lossy(), SimpleFileOptions::default())?;
+        zip.start_file(entry.path().to_string_lossy(), options)?;
         let mut file = File::open(entry.path())?;
         std::io::copy(&mut file, &mut zip)?;
     }
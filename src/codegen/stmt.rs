@@ -14,11 +14,13 @@ use super::{
         QualifiedName,
         Sb3,
         D,
+        DEBUGGER_EXTENSION,
         S,
     },
 };
 use crate::{
     ast::{
+        flatten_struct_literal_fields,
         Arg,
         Expr,
         Name,
@@ -28,7 +30,6 @@ use crate::{
     },
     blocks::Block,
     codegen::mutation::Mutation,
-    diagnostic::DiagnosticKind,
     misc::{
         write_comma_io,
         SmolStr,
@@ -49,7 +50,7 @@ where T: Write + Seek
         let times_id = self.id.new_id();
         let body_id = self.id.new_id();
         self.begin_inputs()?;
-        self.input(s, d, "TIMES", times, times_id)?;
+        self.input(s, "TIMES", times, times_id)?;
         self.substack("SUBSTACK", (!body.is_empty()).then_some(body_id))?;
         self.end_obj()?; // inputs
         self.end_obj()?; // node
@@ -86,7 +87,7 @@ where T: Write + Seek
         let if_body_id = self.id.new_id();
         let else_body_id = self.id.new_id();
         self.begin_inputs()?;
-        self.input(s, d, "CONDITION", cond, cond_id)?;
+        self.input(s, "CONDITION", cond, cond_id)?;
         self.substack("SUBSTACK", (!if_body.is_empty()).then_some(if_body_id))?;
         self.substack("SUBSTACK2", (!else_body.is_empty()).then_some(else_body_id))?;
         self.end_obj()?; // inputs
@@ -107,7 +108,7 @@ where T: Write + Seek
         let cond_id = self.id.new_id();
         let body_id = self.id.new_id();
         self.begin_inputs()?;
-        self.input(s, d, "CONDITION", cond, cond_id)?;
+        self.input(s, "CONDITION", cond, cond_id)?;
         self.substack("SUBSTACK", (!body.is_empty()).then_some(body_id))?;
         self.end_obj()?; // inputs
         self.end_obj()?; // node
@@ -128,19 +129,10 @@ where T: Write + Seek
     ) -> io::Result<()> {
         let value_id = self.id.new_id();
         self.begin_inputs()?;
-        self.input(s, d, "VALUE", value, value_id)?;
+        self.input(s, "VALUE", value, value_id)?;
         self.end_obj()?; // inputs
-        match s.qualify_name(d, name) {
-            Some(QualifiedName::Var(qualified_name, _)) => {
-                self.single_field_id("VARIABLE", &qualified_name)?
-            }
-            Some(QualifiedName::List(..)) => {
-                d.report(
-                    DiagnosticKind::UnrecognizedVariable(name.basename().clone()),
-                    &name.span(),
-                );
-            }
-            None => {}
+        if let Some(QualifiedName::Var(qualified_name, _)) = s.qualify_name(name) {
+            self.single_field_id("VARIABLE", &qualified_name)?
         }
         self.end_obj()?; // node
         self.expr(s, d, value, value_id, this_id)
@@ -156,28 +148,19 @@ where T: Write + Seek
     ) -> io::Result<()> {
         let value_id = self.id.new_id();
         self.begin_inputs()?;
-        self.input(s, d, "VALUE", value, value_id)?;
+        self.input(s, "VALUE", value, value_id)?;
         self.end_obj()?; // inputs
-        match s.qualify_name(d, name) {
-            Some(QualifiedName::Var(qualified_name, _)) => {
-                self.single_field_id("VARIABLE", &qualified_name)?
-            }
-            Some(QualifiedName::List(..)) => {
-                d.report(
-                    DiagnosticKind::UnrecognizedVariable(name.basename().clone()),
-                    &name.span(),
-                );
-            }
-            None => {}
+        if let Some(QualifiedName::Var(qualified_name, _)) = s.qualify_name(name) {
+            self.single_field_id("VARIABLE", &qualified_name)?
         }
         self.end_obj()?; // node
         self.expr(s, d, value, value_id, this_id)
     }
 
-    pub fn show(&mut self, s: S, d: D, name: &Name) -> io::Result<()> {
+    pub fn show(&mut self, s: S, name: &Name) -> io::Result<()> {
         self.begin_inputs()?;
         self.end_obj()?; // inputs
-        match s.qualify_name(d, name) {
+        match s.qualify_name(name) {
             Some(QualifiedName::Var(qualified_name, _)) => {
                 self.single_field_id("VARIABLE", &qualified_name)?
             }
@@ -189,8 +172,8 @@ where T: Write + Seek
         self.end_obj() // node
     }
 
-    pub fn hide(&mut self, s: S, d: D, name: &Name) -> io::Result<()> {
-        self.show(s, d, name)
+    pub fn hide(&mut self, s: S, name: &Name) -> io::Result<()> {
+        self.show(s, name)
     }
 
     pub fn add_to_list(
@@ -203,19 +186,10 @@ where T: Write + Seek
     ) -> io::Result<()> {
         let value_id = self.id.new_id();
         self.begin_inputs()?;
-        self.input(s, d, "ITEM", value, value_id)?;
+        self.input(s, "ITEM", value, value_id)?;
         self.end_obj()?; // inputs
-        match s.qualify_name(d, name) {
-            Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
-            }
-            Some(QualifiedName::Var(..)) => {
-                d.report(
-                    DiagnosticKind::UnrecognizedList(name.basename().clone()),
-                    &name.span(),
-                );
-            }
-            None => {}
+        if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(name) {
+            self.single_field_id("LIST", &qualified_name)?
         }
         self.end_obj()?; // node
         self.expr(s, d, value, value_id, this_id)
@@ -231,38 +205,20 @@ where T: Write + Seek
     ) -> io::Result<()> {
         let index_id = self.id.new_id();
         self.begin_inputs()?;
-        self.input(s, d, "INDEX", index, index_id)?;
+        self.input(s, "INDEX", index, index_id)?;
         self.end_obj()?; // inputs
-        match s.qualify_name(d, name) {
-            Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
-            }
-            Some(QualifiedName::Var(..)) => {
-                d.report(
-                    DiagnosticKind::UnrecognizedList(name.basename().clone()),
-                    &name.span(),
-                );
-            }
-            None => {}
+        if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(name) {
+            self.single_field_id("LIST", &qualified_name)?
         }
         self.end_obj()?; // node
         self.expr(s, d, index, index_id, this_id)
     }
 
-    pub fn delete_list(&mut self, s: S, d: D, name: &Name) -> io::Result<()> {
+    pub fn delete_list(&mut self, s: S, name: &Name) -> io::Result<()> {
         self.begin_inputs()?;
         self.end_obj()?; // inputs
-        match s.qualify_name(d, name) {
-            Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
-            }
-            Some(QualifiedName::Var(..)) => {
-                d.report(
-                    DiagnosticKind::UnrecognizedList(name.basename().clone()),
-                    &name.span(),
-                );
-            }
-            None => {}
+        if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(name) {
+            self.single_field_id("LIST", &qualified_name)?
         }
         self.end_obj() // node
     }
@@ -279,20 +235,11 @@ where T: Write + Seek
         let index_id = self.id.new_id();
         let value_id = self.id.new_id();
         self.begin_inputs()?;
-        self.input(s, d, "INDEX", index, index_id)?;
-        self.input(s, d, "ITEM", value, value_id)?;
+        self.input(s, "INDEX", index, index_id)?;
+        self.input(s, "ITEM", value, value_id)?;
         self.end_obj()?; // inputs
-        match s.qualify_name(d, name) {
-            Some(QualifiedName::List(qualified_name, _)) => {
-                self.single_field_id("LIST", &qualified_name)?
-            }
-            Some(QualifiedName::Var(..)) => {
-                d.report(
-                    DiagnosticKind::UnrecognizedList(name.basename().clone()),
-                    &name.span(),
-                );
-            }
-            None => {}
+        if let Some(QualifiedName::List(qualified_name, _)) = s.qualify_name(name) {
+            self.single_field_id("LIST", &qualified_name)?
         }
         self.end_obj()?; // node
         self.expr(s, d, index, index_id, this_id)?;
@@ -317,21 +264,11 @@ where T: Write + Seek
         d: D,
         this_id: NodeID,
         block: &Block,
-        span: &Span,
         args: &[(Option<(SmolStr, Span)>, Expr)],
     ) -> io::Result<()> {
         if args.iter().any(|(keyword, _)| keyword.is_some()) {
             panic!("block's do not support keyword args yet.")
         }
-        if block.args().len() != args.len() {
-            d.report(
-                DiagnosticKind::BlockArgsCountMismatch {
-                    block: *block,
-                    given: args.len(),
-                },
-                span,
-            )
-        }
         self.begin_inputs()?;
         let arg_ids: Vec<NodeID> = (&mut self.id).take(args.len()).collect();
         let menu_id = block.menu().map(|_| self.id.new_id());
@@ -344,10 +281,10 @@ where T: Write + Seek
                     continue;
                 } else {
                     menu_is_default = false;
-                    self.input_with_shadow(s, d, arg_name, arg_value, arg_id, menu_id.unwrap())?;
+                    self.input_with_shadow(s, arg_name, arg_value, arg_id, menu_id.unwrap())?;
                 }
             } else {
-                self.input(s, d, arg_name, arg_value, arg_id)?;
+                self.input(s, arg_name, arg_value, arg_id)?;
             }
         }
         if menu_is_default {
@@ -408,8 +345,6 @@ where T: Write + Seek
                 s,
                 d,
                 this_id,
-                name,
-                span,
                 args,
                 true,
             );
@@ -426,8 +361,6 @@ where T: Write + Seek
                     s,
                     d,
                     this_id,
-                    name,
-                    span,
                     args,
                     true,
                 );
@@ -443,16 +376,13 @@ where T: Write + Seek
                     s,
                     d,
                     this_id,
-                    name,
-                    span,
                     args,
                     true,
                 );
             }
-            d.report(DiagnosticKind::UnrecognizedProcedure(name.clone()), span);
             return Ok(());
         };
-        self.proc_call_impl(proc, s, d, this_id, name, span, args, false)
+        self.proc_call_impl(proc, s, d, this_id, args, false)
     }
 
     fn proc_call_impl(
@@ -461,37 +391,29 @@ where T: Write + Seek
         s: S,
         d: D,
         this_id: NodeID,
-        name: &SmolStr,
-        span: &Span,
         args: &[(Option<(SmolStr, Span)>, Expr)],
         compact: bool,
     ) -> io::Result<()> {
         if args.iter().any(|(keyword, _)| keyword.is_some()) {
             panic!("keyword args not transformed.")
         }
-        if proc.args.len() != args.len() {
-            d.report(
-                DiagnosticKind::ProcArgsCountMismatch {
-                    proc: name.clone(),
-                    given: args.len(),
-                },
-                span,
-            )
+        // Only the debugger blocks are compact.
+        if compact {
+            self.extensions.insert(DEBUGGER_EXTENSION);
         }
         let mut qualified_args: Vec<(SmolStr, NodeID)> = Vec::new();
-        let mut qualified_arg_values: Vec<&Expr> = Vec::new();
+        let mut qualified_arg_values: Vec<Expr> = Vec::new();
         self.begin_inputs()?;
         for (arg, (_, arg_value)) in proc.args.iter().zip(args) {
             match &arg.type_ {
-                Type::Value => {
+                Type::Value | Type::Enum { .. } => {
                     let arg_id = self.id.new_id();
-                    self.input(s, d, &arg.name, arg_value, arg_id)?;
+                    self.input(s, &arg.name, arg_value, arg_id)?;
                     qualified_args.push((arg.name.clone(), arg_id));
-                    qualified_arg_values.push(arg_value);
+                    qualified_arg_values.push(arg_value.clone());
                 }
                 Type::Struct {
-                    name: type_name,
-                    span: type_span,
+                    name: type_name, ..
                 } => {
                     let Some(struct_) = s.sprite.structs.get(type_name) else {
                         continue;
@@ -499,52 +421,33 @@ where T: Write + Seek
                     let struct_literal_fields = match arg_value {
                         Expr::StructLiteral {
                             name: struct_literal_name,
-                            span: struct_literal_span,
                             fields: struct_literal_fields,
+                            ..
                         } => {
                             if struct_literal_name != &struct_.name {
-                                d.report(
-                                    DiagnosticKind::TypeMismatch {
-                                        expected: arg.type_.clone(),
-                                        given: Type::Struct {
-                                            name: struct_literal_name.clone(),
-                                            span: struct_literal_span.clone(),
-                                        },
-                                    },
-                                    type_span,
-                                );
                                 continue;
                             }
-                            if struct_literal_fields.len() != struct_.fields.len() {
-                                panic!()
-                            }
-                            for (struct_field, struct_literal_field) in
-                                struct_.fields.iter().zip(struct_literal_fields)
-                            {
-                                if struct_field.name != struct_literal_field.name {
-                                    panic!()
-                                }
-                            }
-                            struct_literal_fields
+                            flatten_struct_literal_fields(struct_literal_fields, None)
                         }
                         _ => {
                             continue;
                         }
                     };
-                    for (field, struct_literal_field) in
-                        struct_.fields.iter().zip(struct_literal_fields)
+                    let leaf_fields = struct_.leaf_fields(&|name| s.sprite.structs.get(name));
+                    if struct_literal_fields.len() != leaf_fields.len() {
+                        panic!()
+                    }
+                    for ((path, _), struct_literal_field) in
+                        leaf_fields.iter().zip(&struct_literal_fields)
                     {
-                        let qualified_arg_name = qualify_struct_var_name(&field.name, &arg.name);
+                        if path != &struct_literal_field.name {
+                            panic!()
+                        }
+                        let qualified_arg_name = qualify_struct_var_name(path, &arg.name);
                         let arg_id = self.id.new_id();
-                        self.input(
-                            s,
-                            d,
-                            &qualified_arg_name,
-                            &struct_literal_field.value,
-                            arg_id,
-                        )?;
+                        self.input(s, &qualified_arg_name, &struct_literal_field.value, arg_id)?;
                         qualified_args.push((qualified_arg_name, arg_id));
-                        qualified_arg_values.push(&struct_literal_field.value);
+                        qualified_arg_values.push(struct_literal_field.value.as_ref().clone());
                     }
                 }
             }
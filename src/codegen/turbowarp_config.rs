@@ -64,6 +64,42 @@ impl Display for TurbowarpConfig {
     }
 }
 
+impl TurbowarpConfig {
+    /// Reads the settings from the text of a `_twconfig_` comment, such as the one
+    /// written by [`TurbowarpConfig`]'s [`Display`]. Settings missing from the comment
+    /// keep their default values.
+    pub fn from_comment(text: &str) -> Option<Self> {
+        let json = text[..text.rfind("// _twconfig_")?].trim_end();
+        let json = &json[json.find('{')?..];
+        // TurboWarp writes infinite clones as `Infinity`, which is not valid JSON.
+        let json: serde_json::Value =
+            serde_json::from_str(&json.replace(":Infinity", ":null")).ok()?;
+        let runtime_options = &json["runtimeOptions"];
+        let default = Self::default();
+        Some(Self {
+            frame_rate: json["framerate"].as_u64().unwrap_or(default.frame_rate),
+            max_clones: match &runtime_options["maxClones"] {
+                serde_json::Value::Null if runtime_options.get("maxClones").is_some() => {
+                    f64::INFINITY
+                }
+                max_clones => max_clones.as_f64().unwrap_or(default.max_clones),
+            },
+            no_miscellaneous_limits: runtime_options["miscLimits"]
+                .as_bool()
+                .map_or(default.no_miscellaneous_limits, |limits| !limits),
+            no_sprite_fencing: runtime_options["fencing"]
+                .as_bool()
+                .map_or(default.no_sprite_fencing, |fencing| !fencing),
+            frame_interpolation: json["interpolation"]
+                .as_bool()
+                .unwrap_or(default.frame_interpolation),
+            high_quality_pen: json["hq"].as_bool().unwrap_or(default.high_quality_pen),
+            stage_width: json["width"].as_u64().unwrap_or(default.stage_width),
+            stage_height: json["height"].as_u64().unwrap_or(default.stage_height),
+        })
+    }
+}
+
 impl From<&Config> for TurbowarpConfig {
     fn from(config: &Config) -> Self {
         let default = Self::default();
@@ -85,3 +121,59 @@ impl From<&Config> for TurbowarpConfig {
         }
     }
 }
+
+/// Only the settings which differ from the defaults are set, so that the config stays
+/// minimal.
+impl From<&TurbowarpConfig> for Config {
+    fn from(turbowarp_config: &TurbowarpConfig) -> Self {
+        let default = TurbowarpConfig::default();
+        Self {
+            frame_rate: (turbowarp_config.frame_rate != default.frame_rate)
+                .then_some(turbowarp_config.frame_rate),
+            max_clones: (turbowarp_config.max_clones != default.max_clones)
+                .then_some(turbowarp_config.max_clones),
+            no_miscellaneous_limits: turbowarp_config.no_miscellaneous_limits.then_some(true),
+            no_sprite_fencing: turbowarp_config.no_sprite_fencing.then_some(true),
+            frame_interpolation: turbowarp_config.frame_interpolation.then_some(true),
+            high_quality_pen: turbowarp_config.high_quality_pen.then_some(true),
+            stage_width: (turbowarp_config.stage_width != default.stage_width)
+                .then_some(turbowarp_config.stage_width),
+            stage_height: (turbowarp_config.stage_height != default.stage_height)
+                .then_some(turbowarp_config.stage_height),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_comment() {
+        let turbowarp_config = TurbowarpConfig {
+            frame_rate: 60,
+            max_clones: f64::INFINITY,
+            no_sprite_fencing: true,
+            stage_width: 640,
+            ..Default::default()
+        };
+        let config: Config =
+            (&TurbowarpConfig::from_comment(&turbowarp_config.to_string()).unwrap()).into();
+        assert_eq!(
+            config,
+            Config {
+                frame_rate: Some(60),
+                max_clones: Some(f64::INFINITY),
+                no_sprite_fencing: Some(true),
+                stage_width: Some(640),
+                ..Default::default()
+            }
+        );
+        // Settings missing from the comment are left as their defaults.
+        let config: Config =
+            (&TurbowarpConfig::from_comment(r#"{"framerate":30} // _twconfig_"#).unwrap()).into();
+        assert_eq!(config, Config::default());
+        assert!(TurbowarpConfig::from_comment("not a config").is_none());
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{
     Deserialize,
     Serialize,
@@ -23,4 +25,23 @@ pub struct Config {
     pub stage_width: Option<u64>,
     #[serde(default)]
     pub stage_height: Option<u64>,
+    #[serde(default)]
+    pub no_doc_comments: Option<bool>,
+    #[serde(default)]
+    pub compression: Option<Compression>,
+    #[serde(default)]
+    pub compression_level: Option<i64>,
+    /// Initial state of each sprite, keyed by sprite name. Uses the same property
+    /// names as the `sprite { ... }` declaration.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sprites: BTreeMap<String, BTreeMap<String, toml::Value>>,
+}
+
+/// How the files inside the `.sb3` are compressed.
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    Store,
+    #[default]
+    Deflate,
 }
@@ -0,0 +1,129 @@
+mod expr;
+mod names;
+pub mod project;
+mod sprite;
+mod stmt;
+
+use fxhash::{
+    FxHashMap,
+    FxHashSet,
+};
+pub use names::file_name;
+pub use project::Project;
+use sprite::Sprite;
+
+use crate::{
+    codegen::turbowarp_config::TurbowarpConfig,
+    config::Config,
+    misc::IndexSet,
+};
+
+/// Paths that the costumes and sounds of a project are extracted to, relative to the
+/// project directory and keyed by their md5ext.
+#[derive(Debug, Default)]
+pub struct Assets {
+    pub paths: FxHashMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct Decompiled {
+    pub config: Config,
+    /// Source of each sprite, keyed by file stem. The stage is `stage`.
+    pub sprites: Vec<(String, String)>,
+    pub assets: Assets,
+    /// Opcodes of blocks which could not be decompiled, and were left out.
+    pub unsupported: IndexSet<String>,
+}
+
+pub fn decompile(project: &Project) -> Decompiled {
+    let stage = project.targets.iter().find(|target| target.is_stage);
+    let assets = extract_assets(project);
+    let mut config = Config::default();
+    let mut unsupported = IndexSet::default();
+    let mut used_file_stems = FxHashSet::default();
+    let file_stems: Vec<String> = project
+        .targets
+        .iter()
+        .map(|target| {
+            if target.is_stage {
+                "stage".to_string()
+            } else {
+                unique(file_name(&target.name), &mut used_file_stems, |stem| {
+                    stem.eq_ignore_ascii_case("stage")
+                })
+            }
+        })
+        .collect();
+    // Sprites without a layer are layered in order of their names, so layers are only
+    // written if the sprites are layered in some other order.
+    let mut layers: Vec<(&str, usize)> = project
+        .targets
+        .iter()
+        .zip(&file_stems)
+        .filter(|(target, _)| !target.is_stage)
+        .map(|(target, file_stem)| (file_stem.as_str(), target.layer_order))
+        .collect();
+    layers.sort();
+    let explicit_layers = !layers.is_sorted_by_key(|(_, layer_order)| *layer_order);
+    let mut sprites = vec![];
+    for (target, file_stem) in project.targets.iter().zip(file_stems) {
+        let mut comments = FxHashMap::default();
+        for comment in target.comments.values() {
+            match &comment.block_id {
+                Some(block_id) => {
+                    comments.insert(block_id.as_str(), comment.text.as_str());
+                }
+                None if target.is_stage => {
+                    if let Some(turbowarp_config) = TurbowarpConfig::from_comment(&comment.text) {
+                        config = (&turbowarp_config).into();
+                    }
+                }
+                None => {}
+            }
+        }
+        let globals = stage.filter(|_| !target.is_stage);
+        let sprite = Sprite::new(target, globals, explicit_layers, &mut unsupported);
+        sprites.push((file_stem, sprite.decompile(&assets, &comments)));
+    }
+    Decompiled {
+        config,
+        sprites,
+        assets,
+        unsupported,
+    }
+}
+
+/// Gives each asset a path in the `assets` directory, named after the first costume or
+/// sound which uses it.
+fn extract_assets(project: &Project) -> Assets {
+    let mut assets = Assets::default();
+    let mut used = FxHashSet::default();
+    for target in &project.targets {
+        for asset in target.costumes.iter().chain(&target.sounds) {
+            let md5ext = asset.md5ext();
+            if assets.paths.contains_key(&md5ext) {
+                continue;
+            }
+            let stem = unique(file_name(&asset.name), &mut used, |_| false);
+            let path = format!("assets/{stem}.{}", asset.data_format);
+            assets.paths.insert(md5ext, path);
+        }
+    }
+    assets
+}
+
+/// Appends `_2`, `_3`, ... to `name` until it is not used or reserved.
+fn unique(
+    name: String,
+    used: &mut FxHashSet<String>,
+    is_reserved: impl Fn(&str) -> bool,
+) -> String {
+    let mut unique = name.clone();
+    let mut suffix = 1;
+    while is_reserved(&unique) || used.contains(&unique.to_lowercase()) {
+        suffix += 1;
+        unique = format!("{name}_{suffix}");
+    }
+    used.insert(unique.to_lowercase());
+    unique
+}
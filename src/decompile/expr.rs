@@ -0,0 +1,344 @@
+use std::fmt::{
+    self,
+    Display,
+};
+
+use serde_json::Value;
+
+use super::{
+    names::Names,
+    project::Block,
+    sprite::{
+        number,
+        string,
+        Sprite,
+    },
+};
+use crate::{
+    blocks::{
+        self,
+        BinOp,
+        Menu,
+        Repr,
+        UnOp,
+    },
+    misc::SmolStr,
+};
+
+/// Source of an expression, with the precedence level of its outermost operator as in
+/// the `Expr` rule of the grammar.
+pub struct Code {
+    pub text: String,
+    pub level: u8,
+}
+
+pub const TERM: u8 = 1;
+pub const UNARY: u8 = 2;
+const IN: u8 = 8;
+
+impl Code {
+    fn term(text: String) -> Self {
+        Self { text, level: TERM }
+    }
+
+    /// Wraps the code in parentheses if its level is above `level`.
+    pub fn at(self, level: u8) -> String {
+        if self.level > level {
+            format!("({})", self.text)
+        } else {
+            self.text
+        }
+    }
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl<'a> Sprite<'a> {
+    /// Returns the expression given to the input `name` of a block, or an empty string
+    /// if the input is empty.
+    pub fn input(&mut self, block: &'a Block, name: &str) -> Code {
+        self.input_or_none(block, name)
+            .unwrap_or_else(|| Code::term("\"\"".to_string()))
+    }
+
+    /// Returns the condition given to the boolean input `name` of a block, or `false` if
+    /// the input is empty.
+    pub fn condition(&mut self, block: &'a Block, name: &str) -> Code {
+        self.input_or_none(block, name)
+            .unwrap_or_else(|| Code::term("false".to_string()))
+    }
+
+    fn input_or_none(&mut self, block: &'a Block, name: &str) -> Option<Code> {
+        let input = block.inputs.get(name)?;
+        // The value of an input is either a block, or a primitive such as a literal or a
+        // variable. If it is null, the input's shadow is used instead.
+        let value = match input.get(1)? {
+            Value::Null => input.get(2)?,
+            value => value,
+        };
+        match value {
+            Value::String(id) => self.reporter(id),
+            Value::Array(primitive) => Some(self.primitive(primitive)),
+            _ => None,
+        }
+    }
+
+    /// Returns the id of the first block of a substack input.
+    pub fn substack(&self, block: &'a Block, name: &str) -> Option<&'a str> {
+        block.inputs.get(name)?.get(1)?.as_str()
+    }
+
+    fn primitive(&mut self, primitive: &[Value]) -> Code {
+        let value = primitive.get(1).unwrap_or(&Value::Null);
+        let id = primitive.get(2).and_then(Value::as_str);
+        match primitive.first().and_then(Value::as_u64) {
+            Some(4..=8) => literal_code(value, true),
+            Some(12) => Code::term(self.var(id, value.as_str()).to_string()),
+            Some(13) => Code::term(self.list(id, value.as_str()).to_string()),
+            _ => literal_code(value, false),
+        }
+    }
+
+    /// Returns the name of a variable, looked up by its id and then by its name.
+    pub fn var(&self, id: Option<&str>, name: Option<&str>) -> SmolStr {
+        lookup(&self.vars, id, name)
+    }
+
+    pub fn list(&self, id: Option<&str>, name: Option<&str>) -> SmolStr {
+        lookup(&self.lists, id, name)
+    }
+
+    pub fn var_field(&self, block: &Block, field: &str) -> SmolStr {
+        let field = block.fields.get(field);
+        let name = field.and_then(|field| field.get(0)).and_then(Value::as_str);
+        let id = field.and_then(|field| field.get(1)).and_then(Value::as_str);
+        self.var(id, name)
+    }
+
+    pub fn list_field(&self, block: &Block, field: &str) -> SmolStr {
+        let field = block.fields.get(field);
+        let name = field.and_then(|field| field.get(0)).and_then(Value::as_str);
+        let id = field.and_then(|field| field.get(1)).and_then(Value::as_str);
+        self.list(id, name)
+    }
+
+    fn reporter(&mut self, id: &str) -> Option<Code> {
+        let block: &'a Block = self.blocks.get(id)?;
+        // Menus are shadow blocks with a single field.
+        if block.shadow && block.inputs.is_empty() {
+            let value = Value::String(block.field()?.to_string());
+            return Some(literal_code(&value, true));
+        }
+        let code = match block.opcode.as_str() {
+            "argument_reporter_string_number" | "argument_reporter_boolean" => {
+                let name = block.field_named("VALUE").unwrap_or_default();
+                let text = match self.args.get(name) {
+                    Some(arg) => format!("${arg}"),
+                    None => match name {
+                        "is compiled?" => "$tw_is_compiled".to_string(),
+                        "is TurboWarp?" => "$tw_is_turbowarp".to_string(),
+                        "is forkphorus?" => "$tw_is_forkphorus".to_string(),
+                        // Arguments used outside of their custom block report a default.
+                        _ if block.opcode == "argument_reporter_boolean" => "false".to_string(),
+                        _ => "0".to_string(),
+                    },
+                };
+                Code::term(text)
+            }
+            "data_variable" => Code::term(self.var_field(block, "VARIABLE").to_string()),
+            "data_listcontents" => Code::term(self.list_field(block, "LIST").to_string()),
+            "data_itemoflist" => {
+                let index = self.input(block, "INDEX");
+                Code::term(format!("{}[{index}]", self.list_field(block, "LIST")))
+            }
+            "data_itemnumoflist" | "data_listcontainsitem" => {
+                let item = self.input(block, "ITEM").at(IN);
+                Code {
+                    text: format!("{item} in {}", self.list_field(block, "LIST")),
+                    level: IN,
+                }
+            }
+            "data_lengthoflist" => Code {
+                text: format!("length {}", self.list_field(block, "LIST")),
+                level: UNARY,
+            },
+            opcode => {
+                if let Some(op) = BinOp::from_opcode(opcode) {
+                    self.bin_op(block, op)
+                } else if let Some(op) = UnOp::from_opcode(opcode, block.field()) {
+                    self.un_op(block, op)
+                } else {
+                    let candidates = Repr::from_opcode(opcode, block.field());
+                    let Some(repr) = self.overload(block, candidates, Repr::menu, Repr::args)
+                    else {
+                        self.unsupported.insert(opcode.to_string());
+                        return None;
+                    };
+                    let args = self.args(block, repr.args());
+                    Code::term(format!("{}({})", repr.name(), args.join(", ")))
+                }
+            }
+        };
+        Some(code)
+    }
+
+    fn bin_op(&mut self, block: &'a Block, op: BinOp) -> Code {
+        let (lhs, rhs) = if matches!(op, BinOp::And | BinOp::Or) {
+            (
+                self.condition(block, op.lhs()),
+                self.condition(block, op.rhs()),
+            )
+        } else {
+            (self.input(block, op.lhs()), self.input(block, op.rhs()))
+        };
+        let (symbol, level) = match op {
+            BinOp::Of => return Code::term(format!("{}[{rhs}]", lhs.at(TERM))),
+            BinOp::Mul => ("*", 3),
+            BinOp::Div => ("/", 3),
+            BinOp::Mod => ("%", 3),
+            BinOp::Add => ("+", 4),
+            BinOp::Sub => ("-", 4),
+            BinOp::Lt => ("<", 5),
+            BinOp::Gt => (">", 5),
+            BinOp::Join => ("&", 6),
+            BinOp::In => ("in", IN),
+            BinOp::Eq => ("==", IN),
+            BinOp::And => ("and", 9),
+            BinOp::Or => ("or", 10),
+            BinOp::Le | BinOp::Ge | BinOp::Ne | BinOp::FloorDiv => unreachable!(),
+        };
+        // Operators are left-associative.
+        Code {
+            text: format!("{} {symbol} {}", lhs.at(level), rhs.at(level - 1)),
+            level,
+        }
+    }
+
+    fn un_op(&mut self, block: &'a Block, op: UnOp) -> Code {
+        let opr = if matches!(op, UnOp::Not) {
+            self.condition(block, op.input())
+        } else {
+            self.input(block, op.input())
+        };
+        let keyword = match op {
+            UnOp::Not => "not",
+            UnOp::Length => "length",
+            UnOp::Round => "round",
+            UnOp::Abs => "abs",
+            UnOp::Floor => "floor",
+            UnOp::Ceil => "ceil",
+            UnOp::Sqrt => "sqrt",
+            UnOp::Sin => "sin",
+            UnOp::Cos => "cos",
+            UnOp::Tan => "tan",
+            UnOp::Asin => "asin",
+            UnOp::Acos => "acos",
+            UnOp::Atan => "atan",
+            UnOp::Ln => "ln",
+            UnOp::Log => "log",
+            UnOp::AntiLn => "antiln",
+            UnOp::AntiLog => "antilog",
+            UnOp::Minus => unreachable!(),
+        };
+        Code {
+            text: format!("{keyword} {}", opr.at(UNARY)),
+            level: UNARY,
+        }
+    }
+
+    /// Chooses which of the blocks or reporters that compile to the same opcode and
+    /// field a block was compiled from. Overloads without an argument for their menu are
+    /// chosen if the menu has their default value.
+    pub fn overload<T: Copy>(
+        &self,
+        block: &'a Block,
+        candidates: &[T],
+        menu: fn(&T) -> Option<Menu>,
+        args: fn(&T) -> &'static [&'static str],
+    ) -> Option<T> {
+        let has_menu_arg = |candidate: &T| {
+            menu(candidate).is_none_or(|menu| args(candidate).contains(&menu.input))
+        };
+        candidates
+            .iter()
+            .find(|candidate| {
+                !has_menu_arg(candidate)
+                    && menu(candidate)
+                        .is_some_and(|menu| self.menu_value(block, &menu) == Some(menu.default))
+            })
+            .or_else(|| candidates.iter().find(|candidate| has_menu_arg(candidate)))
+            .copied()
+    }
+
+    /// Returns the value of a menu input, if it was not replaced by a reporter.
+    fn menu_value(&self, block: &'a Block, menu: &blocks::Menu) -> Option<&'a str> {
+        let input = block.inputs.get(menu.input)?;
+        if input.get(0)?.as_u64()? != 1 {
+            return None;
+        }
+        self.blocks.get(input.get(1)?.as_str()?)?.field()
+    }
+
+    pub fn args(&mut self, block: &'a Block, args: &[&str]) -> Vec<String> {
+        args.iter()
+            .map(|&arg| {
+                if arg == "CONDITION" {
+                    self.condition(block, arg).text
+                } else {
+                    self.input(block, arg).text
+                }
+            })
+            .collect()
+    }
+}
+
+fn lookup(
+    names: &fxhash::FxHashMap<&str, SmolStr>,
+    id: Option<&str>,
+    name: Option<&str>,
+) -> SmolStr {
+    id.and_then(|id| names.get(id))
+        .or_else(|| name.and_then(|name| names.get(name)))
+        .cloned()
+        .unwrap_or_else(|| Names::default().insert(name.unwrap_or_default()))
+}
+
+/// Returns the literal for a value. If `is_number` is set, strings which are numbers
+/// are written as numbers.
+pub fn literal(value: &Value, is_number: bool) -> String {
+    literal_code(value, is_number).text
+}
+
+fn literal_code(value: &Value, is_number: bool) -> Code {
+    let text = match value {
+        Value::Number(value) => match value.as_i64() {
+            Some(int) => int.to_string(),
+            None => number_literal(value.to_string().as_str())
+                .unwrap_or_else(|| string(&value.to_string())),
+        },
+        Value::String(value) if is_number => number_literal(value).unwrap_or_else(|| string(value)),
+        Value::String(value) => string(value),
+        Value::Bool(value) => value.to_string(),
+        Value::Null | Value::Array(_) | Value::Object(_) => "\"\"".to_string(),
+    };
+    let level = if text.starts_with('-') { UNARY } else { TERM };
+    Code { text, level }
+}
+
+/// Returns `value` as a number literal, if it is written the same way when it is
+/// compiled back.
+fn number_literal(value: &str) -> Option<String> {
+    if let Ok(int) = value.parse::<i64>() {
+        return (int.to_string() == value).then_some(value.to_string());
+    }
+    let float = value
+        .parse::<f64>()
+        .ok()
+        .filter(|float| float.is_finite())?;
+    let literal = number(float);
+    (literal == value && literal.contains('.')).then_some(literal)
+}
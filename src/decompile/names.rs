@@ -0,0 +1,100 @@
+use fxhash::FxHashSet;
+use logos::Logos;
+
+use crate::{
+    blocks::Block,
+    lexer::token::Token,
+    misc::SmolStr,
+};
+
+/// Turns Scratch names, which may contain any character, into goboscript names which
+/// are unique within a scope.
+#[derive(Debug, Default, Clone)]
+pub struct Names {
+    used: FxHashSet<SmolStr>,
+}
+
+impl Names {
+    pub fn insert(&mut self, name: &str) -> SmolStr {
+        self.insert_unless(name, |_| false)
+    }
+
+    /// Procedure names must also not be the name of a block or of a hidden procedure,
+    /// or calls to the procedure would call those instead.
+    pub fn insert_proc(&mut self, name: &str) -> SmolStr {
+        self.insert_unless(name, |name| {
+            matches!(name, "breakpoint" | "error") || Block::from_shape(name, 0).is_some()
+        })
+    }
+
+    fn insert_unless(&mut self, name: &str, is_reserved: impl Fn(&str) -> bool) -> SmolStr {
+        let base = sanitize(name);
+        let mut name = base.clone();
+        let mut suffix = 1;
+        while !is_name(&name) || is_reserved(&name) || self.used.contains(&*name) {
+            suffix += 1;
+            name = format!("{base}_{suffix}");
+        }
+        let name = SmolStr::from(name);
+        self.used.insert(name.clone());
+        name
+    }
+}
+
+/// Replaces every run of characters which cannot appear in a name with an underscore.
+fn sanitize(name: &str) -> String {
+    let mut sanitized = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            sanitized.push(c);
+        } else if !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Returns whether `name` is lexed as a name, and not as a keyword.
+fn is_name(name: &str) -> bool {
+    let mut lexer = Token::lexer(name);
+    matches!(lexer.next(), Some(Ok(Token::Name(_)))) && lexer.next().is_none()
+}
+
+/// Replaces the characters which cannot appear in a file name on some platforms.
+pub fn file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_matches([' ', '.']);
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        let mut names = Names::default();
+        assert_eq!(names.insert("my variable"), "my_variable");
+        assert_eq!(names.insert("my-variable"), "my_variable_2");
+        assert_eq!(names.insert("greet:name"), "greet_name");
+        assert_eq!(names.insert("2d"), "_2d");
+        assert_eq!(names.insert("if"), "if_2");
+        assert_eq!(names.insert("☁ score"), "_score");
+        assert_eq!(names.insert_proc("move"), "move_2");
+        assert_eq!(file_name("a/b: c"), "a_b_ c");
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::misc::IndexMap;
+
+/// The parts of project.json which are decompiled or interpreted.
+#[derive(Debug, Deserialize)]
+pub struct Project {
+    pub targets: Vec<Target>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Target {
+    pub is_stage: bool,
+    pub name: String,
+    #[serde(default)]
+    pub variables: BTreeMap<String, Variable>,
+    #[serde(default)]
+    pub lists: BTreeMap<String, List>,
+    /// Blocks in the order they were written, which is the order scripts are started in.
+    #[serde(default)]
+    pub blocks: IndexMap<String, BlockEntry>,
+    #[serde(default)]
+    pub comments: BTreeMap<String, Comment>,
+    #[serde(default)]
+    pub costumes: Vec<Asset>,
+    #[serde(default)]
+    pub sounds: Vec<Asset>,
+    #[serde(default)]
+    pub current_costume: usize,
+    pub volume: Option<f64>,
+    #[serde(default)]
+    pub layer_order: usize,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub size: Option<f64>,
+    pub direction: Option<f64>,
+    pub visible: Option<bool>,
+    pub draggable: Option<bool>,
+    pub rotation_style: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Variable {
+    Cloud(String, Value, bool),
+    Normal(String, Value),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct List(pub String, pub Vec<Value>);
+
+/// Top-level variable and list reporters are stored as arrays instead of blocks, they
+/// do nothing and are not decompiled.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BlockEntry {
+    Block(Box<Block>),
+    Primitive(#[allow(dead_code)] Value),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Block {
+    pub opcode: String,
+    pub next: Option<String>,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub shadow: bool,
+    #[serde(default)]
+    pub top_level: bool,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    pub mutation: Option<Mutation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Mutation {
+    #[serde(default)]
+    pub proccode: String,
+    pub argumentids: Option<String>,
+    pub argumentnames: Option<String>,
+    pub warp: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comment {
+    pub block_id: Option<String>,
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Asset {
+    pub name: String,
+    pub asset_id: String,
+    pub data_format: String,
+    pub md5ext: Option<String>,
+    /// Sample rate and length of a sound.
+    pub rate: Option<f64>,
+    pub sample_count: Option<f64>,
+}
+
+impl Variable {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Cloud(name, _, _) | Self::Normal(name, _) => name,
+        }
+    }
+
+    pub fn value(&self) -> &Value {
+        match self {
+            Self::Cloud(_, value, _) | Self::Normal(_, value) => value,
+        }
+    }
+
+    pub fn is_cloud(&self) -> bool {
+        matches!(self, Self::Cloud(_, _, true))
+    }
+}
+
+impl Block {
+    /// Returns the value of the first field, blocks from the tables in `blocks.rs` have
+    /// at most one field.
+    pub fn field(&self) -> Option<&str> {
+        self.fields.values().next().and_then(field_value)
+    }
+
+    pub fn field_named(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).and_then(field_value)
+    }
+}
+
+impl Mutation {
+    pub fn argument_ids(&self) -> Vec<String> {
+        parse_string_array(self.argumentids.as_deref())
+    }
+
+    pub fn argument_names(&self) -> Vec<String> {
+        parse_string_array(self.argumentnames.as_deref())
+    }
+
+    pub fn warp(&self) -> bool {
+        matches!(&self.warp, Some(Value::Bool(true)))
+            || matches!(&self.warp, Some(Value::String(warp)) if warp == "true")
+    }
+}
+
+impl Asset {
+    pub fn md5ext(&self) -> String {
+        self.md5ext
+            .clone()
+            .unwrap_or_else(|| format!("{}.{}", self.asset_id, self.data_format))
+    }
+}
+
+fn field_value(field: &Value) -> Option<&str> {
+    field.get(0).and_then(Value::as_str)
+}
+
+/// Mutations store their argument ids and names as JSON encoded inside a string.
+fn parse_string_array(array: Option<&str>) -> Vec<String> {
+    array
+        .and_then(|array| serde_json::from_str(array).ok())
+        .unwrap_or_default()
+}
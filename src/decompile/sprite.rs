@@ -0,0 +1,477 @@
+use std::fmt::Write;
+
+use fxhash::{
+    FxHashMap,
+    FxHashSet,
+};
+
+use super::{
+    expr::literal,
+    names::Names,
+    project::{
+        Block,
+        BlockEntry,
+        Target,
+    },
+    Assets,
+};
+use crate::misc::{
+    IndexSet,
+    SmolStr,
+};
+
+/// Decompiles one target of a project to the source of a goboscript sprite.
+pub struct Sprite<'a> {
+    pub target: &'a Target,
+    pub blocks: FxHashMap<&'a str, &'a Block>,
+    /// Names of variables and lists, keyed by their Scratch id and by their Scratch
+    /// name. Includes the variables and lists of the stage.
+    pub vars: FxHashMap<&'a str, SmolStr>,
+    pub lists: FxHashMap<&'a str, SmolStr>,
+    pub cloud_vars: FxHashSet<SmolStr>,
+    /// Variables and lists which blocks refer to, but which are missing from the
+    /// project. Scratch creates these when the project is loaded.
+    pub missing_vars: Vec<SmolStr>,
+    pub missing_lists: Vec<SmolStr>,
+    /// Custom blocks defined in this target, keyed by proccode.
+    pub procs: FxHashMap<&'a str, Proc>,
+    /// Arguments of the custom block being decompiled, keyed by Scratch name.
+    pub args: FxHashMap<String, SmolStr>,
+    /// Variables which are assigned to by a script of this target.
+    pub assigned: FxHashSet<SmolStr>,
+    /// Whether the layer of the sprite is declared.
+    pub explicit_layer: bool,
+    pub unsupported: &'a mut IndexSet<String>,
+    pub src: String,
+    pub indent: usize,
+}
+
+#[derive(Debug)]
+pub struct Proc {
+    pub name: SmolStr,
+    pub warp: bool,
+    /// Argument ids, in the order of the arguments.
+    pub arg_ids: Vec<String>,
+    pub arg_names: Vec<String>,
+}
+
+impl<'a> Sprite<'a> {
+    /// The names of the stage's variables and lists are declared first, so that every
+    /// target gives them the same names and its own names do not shadow them. Names
+    /// missing from the stage are declared by the stage.
+    pub fn new(
+        target: &'a Target,
+        stage: Option<&'a Target>,
+        explicit_layer: bool,
+        unsupported: &'a mut IndexSet<String>,
+    ) -> Self {
+        let blocks = target
+            .blocks
+            .iter()
+            .filter_map(|(id, block)| match block {
+                BlockEntry::Block(block) => Some((id.as_str(), &**block)),
+                BlockEntry::Primitive(_) => None,
+            })
+            .collect();
+        let mut sprite = Self {
+            target,
+            blocks,
+            vars: Default::default(),
+            lists: Default::default(),
+            cloud_vars: Default::default(),
+            missing_vars: vec![],
+            missing_lists: vec![],
+            procs: Default::default(),
+            args: Default::default(),
+            assigned: Default::default(),
+            explicit_layer,
+            unsupported,
+            src: String::new(),
+            indent: 0,
+        };
+        let mut names = Names::default();
+        for other in stage.into_iter().chain([target]) {
+            sprite.declare_names(other, &mut names);
+            for block in other.blocks.values() {
+                if let BlockEntry::Block(block) = block {
+                    let is_own = std::ptr::eq(other, target);
+                    sprite.declare_missing_names(block, &mut names, is_own);
+                }
+            }
+        }
+        let mut names = Names::default();
+        for block in target.blocks.values() {
+            let BlockEntry::Block(block) = block else {
+                continue;
+            };
+            let Some(mutation) = block
+                .mutation
+                .as_ref()
+                .filter(|_| block.opcode == "procedures_prototype")
+            else {
+                continue;
+            };
+            let arg_names = mutation.argument_names();
+            let name = names.insert_proc(&proc_name(&mutation.proccode, &arg_names));
+            sprite.procs.insert(
+                &mutation.proccode,
+                Proc {
+                    name,
+                    warp: mutation.warp(),
+                    arg_ids: mutation.argument_ids(),
+                    arg_names,
+                },
+            );
+        }
+        sprite
+    }
+
+    fn declare_names(&mut self, target: &'a Target, names: &mut Names) {
+        for (id, var) in &target.variables {
+            let name = var.name().strip_prefix("☁ ").unwrap_or(var.name());
+            let name = names.insert(name);
+            if var.is_cloud() {
+                self.cloud_vars.insert(name.clone());
+            }
+            self.vars.insert(var.name(), name.clone());
+            self.vars.insert(id, name);
+        }
+        for (id, list) in &target.lists {
+            let name = names.insert(&list.0);
+            self.lists.insert(&list.0, name.clone());
+            self.lists.insert(id, name);
+        }
+    }
+
+    fn declare_missing_names(&mut self, block: &'a Block, names: &mut Names, is_own: bool) {
+        let mut references = vec![];
+        for (field, is_list) in [("VARIABLE", false), ("LIST", true)] {
+            if let Some(field) = block.fields.get(field) {
+                references.push((field.get(1), field.get(0), is_list));
+            }
+        }
+        for input in block.inputs.values().filter_map(|input| input.as_array()) {
+            for primitive in input.iter().filter_map(|value| value.as_array()) {
+                match primitive.first().and_then(|kind| kind.as_u64()) {
+                    Some(12) => references.push((primitive.get(2), primitive.get(1), false)),
+                    Some(13) => references.push((primitive.get(2), primitive.get(1), true)),
+                    _ => {}
+                }
+            }
+        }
+        for (id, name, is_list) in references {
+            let id = id.and_then(|id| id.as_str());
+            let Some(name) = name.and_then(|name| name.as_str()) else {
+                continue;
+            };
+            let (declared, missing) = if is_list {
+                (&mut self.lists, &mut self.missing_lists)
+            } else {
+                (&mut self.vars, &mut self.missing_vars)
+            };
+            if id.is_some_and(|id| declared.contains_key(id)) || declared.contains_key(name) {
+                continue;
+            }
+            let ident = names.insert(name);
+            declared.insert(name, ident.clone());
+            if let Some(id) = id {
+                declared.insert(id, ident.clone());
+            }
+            if is_own {
+                missing.push(ident);
+            }
+        }
+    }
+
+    /// Returns the source of the sprite. `assets` are the paths that the costumes and
+    /// sounds of the sprite are extracted to.
+    pub fn decompile(mut self, assets: &Assets, comments: &FxHashMap<&str, &str>) -> String {
+        let mut scripts: Vec<(&str, &'a Block)> = self
+            .blocks
+            .iter()
+            .filter(|(_, block)| block.top_level && !block.shadow)
+            .map(|(&id, &block)| (id, block))
+            .collect();
+        scripts.sort_by(|(a_id, a), (b_id, b)| {
+            let a = (a.x.unwrap_or(0.0), a.y.unwrap_or(0.0));
+            let b = (b.x.unwrap_or(0.0), b.y.unwrap_or(0.0));
+            a.partial_cmp(&b).unwrap().then(a_id.cmp(b_id))
+        });
+        for (id, block) in scripts {
+            let begin = self.src.len();
+            if let Some(doc) = comments.get(id) {
+                for line in doc.lines() {
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        self.src.push_str("##\n");
+                    } else {
+                        writeln!(self.src, "## {line}").unwrap();
+                    }
+                }
+            }
+            if !self.script(block) {
+                self.src.truncate(begin);
+                continue;
+            }
+            self.src.push('\n');
+        }
+        let scripts = std::mem::take(&mut self.src);
+        self.declarations(assets);
+        self.src.push_str(&scripts);
+        let src = self.src.trim_end().to_string() + "\n";
+        crate::fmt::format_source(&src).unwrap_or(src)
+    }
+
+    fn declarations(&mut self, assets: &Assets) {
+        let target = self.target;
+        for costume in &target.costumes {
+            self.asset("costumes", &assets.paths[&costume.md5ext()], &costume.name);
+        }
+        for sound in &target.sounds {
+            self.asset("sounds", &assets.paths[&sound.md5ext()], &sound.name);
+        }
+        if !(target.costumes.is_empty() && target.sounds.is_empty()) {
+            self.src.push('\n');
+        }
+        let properties = self.properties();
+        if !properties.is_empty() {
+            self.src.push_str("sprite {\n");
+            for (name, value) in properties {
+                writeln!(self.src, "    {name}: {value},").unwrap();
+            }
+            self.src.push_str("}\n\n");
+        }
+        let mut lists: Vec<(&SmolStr, &[serde_json::Value])> = target
+            .lists
+            .iter()
+            .map(|(id, list)| (&self.lists[id.as_str()], list.1.as_slice()))
+            .collect();
+        lists.extend(self.missing_lists.iter().map(|name| (name, [].as_slice())));
+        lists.sort_by_key(|(name, _)| *name);
+        for (name, items) in &lists {
+            if items.is_empty() {
+                writeln!(self.src, "list {name};").unwrap();
+            } else {
+                let items: Vec<String> = items.iter().map(|item| literal(item, false)).collect();
+                writeln!(self.src, "list {name} = [{}];", items.join(", ")).unwrap();
+            }
+        }
+        if !lists.is_empty() {
+            self.src.push('\n');
+        }
+        // Variables are declared by assigning to them, a variable which no script of
+        // its target assigns to is declared by a custom block which is never used.
+        let mut unassigned: Vec<(&SmolStr, bool, String)> = target
+            .variables
+            .iter()
+            .map(|(id, var)| {
+                let value = literal(var.value(), false);
+                (&self.vars[id.as_str()], var.is_cloud(), value)
+            })
+            .collect();
+        unassigned.extend(
+            self.missing_vars
+                .iter()
+                .map(|name| (name, false, "0".to_string())),
+        );
+        unassigned.retain(|(name, _, _)| !self.assigned.contains(*name));
+        unassigned.sort_by_key(|(name, _, _)| *name);
+        if !unassigned.is_empty() {
+            let mut names = Names::default();
+            for proc in self.procs.values() {
+                names.insert(&proc.name);
+            }
+            let mut declarations = format!("proc {} {{\n", names.insert("declare_variables"));
+            for (name, is_cloud, value) in unassigned {
+                let cloud = if is_cloud { "cloud " } else { "" };
+                writeln!(declarations, "    {cloud}{name} = {value};").unwrap();
+            }
+            declarations.push_str("}\n\n");
+            self.src.push_str(&declarations);
+        }
+    }
+
+    fn asset(&mut self, kind: &str, path: &str, name: &str) {
+        let stem = path
+            .rsplit_once('.')
+            .map_or(path, |(stem, _)| stem)
+            .rsplit('/')
+            .next()
+            .unwrap();
+        write!(self.src, "{kind} {}", serde_json::to_string(path).unwrap()).unwrap();
+        if stem != name {
+            write!(self.src, " as {}", serde_json::to_string(name).unwrap()).unwrap();
+        }
+        self.src.push_str(";\n");
+    }
+
+    /// Returns the properties of the `sprite` declaration, leaving out the ones which
+    /// have their default value.
+    fn properties(&self) -> Vec<(&'static str, String)> {
+        let target = self.target;
+        let mut properties = vec![];
+        let mut push_number = |name, value: Option<f64>, default: f64| {
+            if let Some(value) = value.filter(|value| *value != default) {
+                properties.push((name, number(value)));
+            }
+        };
+        if !target.is_stage {
+            push_number("x", target.x, 0.0);
+            push_number("y", target.y, 0.0);
+            push_number("size", target.size, 100.0);
+            push_number("direction", target.direction, 90.0);
+        }
+        push_number("volume", target.volume, 100.0);
+        if !target.is_stage {
+            if target.visible == Some(false) {
+                properties.push(("visible", "false".to_string()));
+            }
+            if target.draggable == Some(true) {
+                properties.push(("draggable", "true".to_string()));
+            }
+            if let Some(style) = &target.rotation_style {
+                if style != "all around" {
+                    properties.push(("rotation_style", serde_json::to_string(style).unwrap()));
+                }
+            }
+            if self.explicit_layer {
+                properties.push(("layer", target.layer_order.to_string()));
+            }
+        }
+        if target.current_costume != 0 {
+            if let Some(costume) = target.costumes.get(target.current_costume) {
+                let costume = serde_json::to_string(&costume.name).unwrap();
+                properties.push(("costume", costume));
+            }
+        }
+        properties
+    }
+
+    /// Writes a script, returns false if the script does not start with a hat block
+    /// that goboscript supports.
+    fn script(&mut self, hat: &'a Block) -> bool {
+        let next = hat.next.as_deref();
+        match hat.opcode.as_str() {
+            "event_whenflagclicked" => self.src.push_str("onflag"),
+            "event_whenthisspriteclicked" | "event_whenstageclicked" => {
+                self.src.push_str("onclick")
+            }
+            "control_start_as_clone" => self.src.push_str("onclone"),
+            "event_whenkeypressed" => {
+                let key = hat.field_named("KEY_OPTION").unwrap_or_default();
+                write!(self.src, "onkey {}", string(key)).unwrap();
+            }
+            "event_whenbackdropswitchesto" => {
+                let backdrop = hat.field_named("BACKDROP").unwrap_or_default();
+                write!(self.src, "onbackdrop {}", string(backdrop)).unwrap();
+            }
+            "event_whenbroadcastreceived" => {
+                let broadcast = hat.field_named("BROADCAST_OPTION").unwrap_or_default();
+                write!(self.src, "on {}", string(broadcast)).unwrap();
+            }
+            "event_whengreaterthan" => {
+                let value = self.input(hat, "VALUE");
+                match hat.field_named("WHENGREATERTHANMENU") {
+                    Some("TIMER") => write!(self.src, "ontimer > {value}").unwrap(),
+                    _ => write!(self.src, "onloudness > {value}").unwrap(),
+                }
+            }
+            "procedures_definition" => return self.proc_definition(hat),
+            opcode => {
+                if opcode.contains("_when") {
+                    self.unsupported.insert(opcode.to_string());
+                }
+                return false;
+            }
+        }
+        self.src.push(' ');
+        self.body(next);
+        true
+    }
+
+    fn proc_definition(&mut self, definition: &'a Block) -> bool {
+        let Some(prototype) = definition
+            .inputs
+            .get("custom_block")
+            .and_then(|input| input.get(1))
+            .and_then(|id| id.as_str())
+            .and_then(|id| self.blocks.get(id))
+        else {
+            return false;
+        };
+        let Some(proc) = prototype
+            .mutation
+            .as_ref()
+            .and_then(|mutation| self.procs.get(&*mutation.proccode))
+        else {
+            return false;
+        };
+        let mut names = Names::default();
+        self.args = proc
+            .arg_names
+            .iter()
+            .map(|arg_name| (arg_name.clone(), names.insert(arg_name)))
+            .collect();
+        let args: Vec<&str> = proc
+            .arg_names
+            .iter()
+            .map(|arg_name| &*self.args[arg_name])
+            .collect();
+        let nowarp = if proc.warp { "" } else { "nowarp " };
+        write!(self.src, "{nowarp}proc {}", proc.name).unwrap();
+        if !args.is_empty() {
+            write!(self.src, " {}", args.join(", ")).unwrap();
+        }
+        self.src.push(' ');
+        self.body(definition.next.as_deref());
+        self.args.clear();
+        true
+    }
+
+    /// Writes a `{ ... }` block of the stack of blocks starting at `first`.
+    pub fn body(&mut self, first: Option<&'a str>) {
+        if first.is_none() {
+            self.src.push_str("{}\n");
+            return;
+        }
+        self.src.push_str("{\n");
+        self.indent += 1;
+        self.stmts(first);
+        self.indent -= 1;
+        self.line_start();
+        self.src.push_str("}\n");
+    }
+
+    pub fn line_start(&mut self) {
+        for _ in 0..self.indent {
+            self.src.push_str("    ");
+        }
+    }
+}
+
+/// Returns the name of a custom block. Custom blocks compiled by goboscript have a
+/// proccode of their name followed by `arg: %s` for each argument.
+fn proc_name(proccode: &str, arg_names: &[String]) -> String {
+    let args: String = arg_names.iter().map(|arg| format!(" {arg}: %s")).collect();
+    if let Some(name) = proccode.strip_suffix(&args) {
+        return name.to_string();
+    }
+    let words: Vec<&str> = proccode
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "%s" | "%b" | "%n"))
+        .collect();
+    words.join("_")
+}
+
+pub fn string(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+/// Formats a number so that it is lexed as the same number.
+pub fn number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{value}")
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt::Write;
+
+use super::{
+    expr::UNARY,
+    project::Block,
+    sprite::Sprite,
+};
+use crate::blocks;
+
+impl<'a> Sprite<'a> {
+    /// Writes the stack of blocks starting at `next`, one statement per line.
+    pub fn stmts(&mut self, mut next: Option<&'a str>) {
+        while let Some(block) = next.and_then(|id| self.blocks.get(id).copied()) {
+            let begin = self.src.len();
+            self.line_start();
+            if !self.stmt(block) {
+                self.src.truncate(begin);
+            }
+            next = block.next.as_deref();
+        }
+    }
+
+    /// Writes a statement, returns false if the block is not supported.
+    fn stmt(&mut self, block: &'a Block) -> bool {
+        match block.opcode.as_str() {
+            "control_repeat" => {
+                let times = self.input(block, "TIMES");
+                write!(self.src, "repeat {times} ").unwrap();
+                self.body(self.substack(block, "SUBSTACK"));
+            }
+            "control_forever" => {
+                self.src.push_str("forever ");
+                self.body(self.substack(block, "SUBSTACK"));
+            }
+            "control_repeat_until" => {
+                let condition = self.condition(block, "CONDITION");
+                write!(self.src, "until {condition} ").unwrap();
+                self.body(self.substack(block, "SUBSTACK"));
+            }
+            "control_while" => {
+                let condition = self.condition(block, "CONDITION").at(UNARY);
+                write!(self.src, "until not {condition} ").unwrap();
+                self.body(self.substack(block, "SUBSTACK"));
+            }
+            "control_if" | "control_if_else" => {
+                let condition = self.condition(block, "CONDITION");
+                write!(self.src, "if {condition} ").unwrap();
+                self.branches(block);
+            }
+            "data_setvariableto" => {
+                let name = self.var_field(block, "VARIABLE");
+                let value = self.input(block, "VALUE");
+                let cloud = if self.cloud_vars.contains(&name) {
+                    "cloud "
+                } else {
+                    ""
+                };
+                writeln!(self.src, "{cloud}{name} = {value};").unwrap();
+                self.assigned.insert(name);
+            }
+            "data_changevariableby" => {
+                let name = self.var_field(block, "VARIABLE");
+                let value = self.input(block, "VALUE");
+                writeln!(self.src, "{name} += {value};").unwrap();
+            }
+            "data_showvariable" | "data_hidevariable" => {
+                let keyword = block.opcode.strip_prefix("data_").unwrap();
+                let keyword = keyword.strip_suffix("variable").unwrap();
+                let name = self.var_field(block, "VARIABLE");
+                writeln!(self.src, "{keyword} {name};").unwrap();
+            }
+            "data_showlist" | "data_hidelist" => {
+                let keyword = block.opcode.strip_prefix("data_").unwrap();
+                let keyword = keyword.strip_suffix("list").unwrap();
+                let name = self.list_field(block, "LIST");
+                writeln!(self.src, "{keyword} {name};").unwrap();
+            }
+            "data_addtolist" => {
+                let item = self.input(block, "ITEM");
+                let name = self.list_field(block, "LIST");
+                writeln!(self.src, "add {item} to {name};").unwrap();
+            }
+            "data_deleteoflist" => {
+                let index = self.input(block, "INDEX");
+                let name = self.list_field(block, "LIST");
+                writeln!(self.src, "delete {name}[{index}];").unwrap();
+            }
+            "data_deletealloflist" => {
+                let name = self.list_field(block, "LIST");
+                writeln!(self.src, "delete {name};").unwrap();
+            }
+            "data_insertatlist" => {
+                let item = self.input(block, "ITEM");
+                let index = self.input(block, "INDEX");
+                let name = self.list_field(block, "LIST");
+                writeln!(self.src, "insert {item} at {name}[{index}];").unwrap();
+            }
+            "data_replaceitemoflist" => {
+                let index = self.input(block, "INDEX");
+                let item = self.input(block, "ITEM");
+                let name = self.list_field(block, "LIST");
+                writeln!(self.src, "{name}[{index}] = {item};").unwrap();
+            }
+            "procedures_call" => return self.proc_call(block),
+            opcode => {
+                let candidates = blocks::Block::from_opcode(opcode, block.field());
+                let menu = blocks::Block::menu;
+                let Some(variant) = self.overload(block, candidates, menu, blocks::Block::args)
+                else {
+                    self.unsupported.insert(opcode.to_string());
+                    return false;
+                };
+                let args = self.args(block, variant.args());
+                self.src.push_str(variant.name());
+                if !args.is_empty() {
+                    write!(self.src, " {}", args.join(", ")).unwrap();
+                }
+                self.src.push_str(";\n");
+            }
+        }
+        true
+    }
+
+    /// Writes the branches of an if block, an else branch which is only an if block is
+    /// written as an `elif`.
+    fn branches(&mut self, block: &'a Block) {
+        self.body(self.substack(block, "SUBSTACK"));
+        if block.opcode != "control_if_else" {
+            return;
+        }
+        let Some(first) = self.substack(block, "SUBSTACK2") else {
+            return;
+        };
+        // Continue on the line of the closing brace.
+        self.src.pop();
+        let nested = self.blocks.get(first).copied().filter(|nested| {
+            matches!(nested.opcode.as_str(), "control_if" | "control_if_else")
+                && nested.next.is_none()
+        });
+        if let Some(nested) = nested {
+            let condition = self.condition(nested, "CONDITION");
+            write!(self.src, " elif {condition} ").unwrap();
+            self.branches(nested);
+        } else {
+            self.src.push_str(" else ");
+            self.body(Some(first));
+        }
+    }
+
+    fn proc_call(&mut self, block: &'a Block) -> bool {
+        let Some(mutation) = &block.mutation else {
+            return false;
+        };
+        // The log, breakpoint and error statements compile to calls to hidden custom
+        // blocks, with names surrounded by zero-width spaces.
+        if mutation.proccode.starts_with('\u{200b}') {
+            let name = mutation.proccode.split(' ').next().unwrap();
+            let name = name.trim_matches('\u{200b}');
+            let arg_ids = mutation.argument_ids();
+            match (name, arg_ids.first()) {
+                ("breakpoint", _) => self.src.push_str("breakpoint;\n"),
+                ("log" | "error", Some(arg_id)) => {
+                    let arg = self.input(block, arg_id);
+                    writeln!(self.src, "{name} {arg};").unwrap();
+                }
+                _ => {
+                    self.unsupported.insert(mutation.proccode.clone());
+                    return false;
+                }
+            }
+            return true;
+        }
+        let Some(proc) = self.procs.get(mutation.proccode.as_str()) else {
+            self.unsupported
+                .insert(format!("call to {}", mutation.proccode));
+            return false;
+        };
+        let name = proc.name.clone();
+        let arg_ids = proc.arg_ids.clone();
+        let args: Vec<String> = arg_ids
+            .iter()
+            .map(|arg_id| self.input(block, arg_id).text)
+            .collect();
+        self.src.push_str(&name);
+        if !args.is_empty() {
+            write!(self.src, " {}", args.join(", ")).unwrap();
+        }
+        self.src.push_str(";\n");
+        true
+    }
+}
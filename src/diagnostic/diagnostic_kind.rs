@@ -52,6 +52,13 @@ pub enum DiagnosticKind {
         func: SmolStr,
         given: usize,
     },
+    ReturnCountMismatch {
+        returns: usize,
+        given: usize,
+    },
+    AssertArgsCountMismatch {
+        given: usize,
+    },
     CommandFailed {
         stderr: Vec<u8>,
     },
@@ -64,18 +71,30 @@ pub enum DiagnosticKind {
         type_name: SmolStr,
         field_name: SmolStr,
     },
+    UnrecognizedProperty(SmolStr),
+    PropertyNotOnStage(SmolStr),
+    InvalidPropertyValue {
+        property: SmolStr,
+        expected: &'static str,
+    },
+    OutsideLoop(&'static str),
+    RecursiveStruct(SmolStr),
+    ConstReassignment(SmolStr),
     // Warnings
     FollowedByUnreachableCode,
     UnrecognizedKey(SmolStr),
     UnusedVariable(SmolStr),
     UnusedList(SmolStr),
     UnusedEnum(SmolStr),
+    UnusedConst(SmolStr),
     UnusedStruct(SmolStr),
     UnusedProc(SmolStr),
     UnusedFunc(SmolStr),
     UnusedArg(SmolStr),
     UnusedStructField(SmolStr),
     UnusedEnumVariant(SmolStr),
+    NonExhaustiveMatch(Vec<SmolStr>),
+    ConstShadowsVariable(SmolStr),
 }
 
 impl DiagnosticKind {
@@ -150,7 +169,19 @@ impl DiagnosticKind {
                     given
                 )
             }
+            DiagnosticKind::AssertArgsCountMismatch { given } => {
+                format!("assert expects 1 or 2 arguments, but {given} were given")
+            }
             DiagnosticKind::CommandFailed { .. } => "command failed".to_string(),
+            DiagnosticKind::ReturnCountMismatch { returns: 1, given } => {
+                format!("expected {given} values, but 1 was returned")
+            }
+            DiagnosticKind::ReturnCountMismatch { returns, given: 1 } => {
+                format!("expected 1 value, but {returns} were returned")
+            }
+            DiagnosticKind::ReturnCountMismatch { returns, given } => {
+                format!("expected {} values, but {} were returned", given, returns)
+            }
             DiagnosticKind::TypeMismatch { expected, given } => {
                 format!("type mismatch: expected {}, but got {}", expected, given)
             }
@@ -158,12 +189,16 @@ impl DiagnosticKind {
             DiagnosticKind::UnusedVariable(name) => format!("unused variable {name}"),
             DiagnosticKind::UnusedList(name) => format!("unused list {name}"),
             DiagnosticKind::UnusedEnum(name) => format!("unused enum {name}"),
+            DiagnosticKind::UnusedConst(name) => format!("unused constant {name}"),
             DiagnosticKind::UnusedStruct(name) => format!("unused struct {name}"),
             DiagnosticKind::UnusedProc(name) => format!("unused procedure {name}"),
             DiagnosticKind::UnusedFunc(name) => format!("unused function {name}"),
             DiagnosticKind::UnusedArg(name) => format!("unused argument {name}"),
             DiagnosticKind::UnusedStructField(name) => format!("unused struct field {name}"),
             DiagnosticKind::UnusedEnumVariant(name) => format!("unused enum variant {name}"),
+            DiagnosticKind::NonExhaustiveMatch(variants) => {
+                format!("match does not cover {}", variants.join(", "))
+            }
             DiagnosticKind::NotStruct => "not a struct".to_string(),
             DiagnosticKind::StructDoesNotHaveField {
                 type_name,
@@ -171,6 +206,19 @@ impl DiagnosticKind {
             } => {
                 format!("struct {type_name} does not have field {field_name}")
             }
+            DiagnosticKind::UnrecognizedProperty(name) => format!("unrecognized property {name}"),
+            DiagnosticKind::PropertyNotOnStage(name) => {
+                format!("property {name} cannot be set on the stage")
+            }
+            DiagnosticKind::InvalidPropertyValue { property, expected } => {
+                format!("invalid value for property {property}, expected {expected}")
+            }
+            DiagnosticKind::OutsideLoop(keyword) => format!("`{keyword}` outside of a loop"),
+            DiagnosticKind::RecursiveStruct(name) => format!("struct {name} contains itself"),
+            DiagnosticKind::ConstReassignment(name) => format!("cannot assign to constant {name}"),
+            DiagnosticKind::ConstShadowsVariable(name) => {
+                format!("constant {name} shadows the stage variable {name}")
+            }
         }
     }
 
@@ -179,6 +227,24 @@ impl DiagnosticKind {
             DiagnosticKind::NoCostumes => {
                 Some("if this is a header, move it inside a directory such as `lib/`".to_string())
             }
+            DiagnosticKind::UnrecognizedProperty(_) => Some(
+                "available properties are `x`, `y`, `size`, `direction`, `visible`, \
+                 `draggable`, `rotation_style`, `costume`, `volume` and `layer`"
+                    .to_string(),
+            ),
+            DiagnosticKind::PropertyNotOnStage(_) => {
+                Some("the stage only has the `costume` and `volume` properties".to_string())
+            }
+            DiagnosticKind::NonExhaustiveMatch(_) => {
+                Some("add arms for the missing variants, or a `_` arm".to_string())
+            }
+            DiagnosticKind::ConstShadowsVariable(_) => {
+                Some("the variable cannot be used in this sprite, rename the constant".to_string())
+            }
+            DiagnosticKind::RecursiveStruct(_) => Some(
+                "a struct cannot have a field of its own type, directly or through other structs"
+                    .to_string(),
+            ),
             _ => None,
         }
     }
@@ -209,22 +275,33 @@ impl From<&DiagnosticKind> for Level {
             | DiagnosticKind::ReprArgsCountMismatch { .. }
             | DiagnosticKind::ProcArgsCountMismatch { .. }
             | DiagnosticKind::FuncArgsCountMismatch { .. }
+            | DiagnosticKind::ReturnCountMismatch { .. }
+            | DiagnosticKind::AssertArgsCountMismatch { .. }
             | DiagnosticKind::CommandFailed { .. }
             | DiagnosticKind::TypeMismatch { .. }
             | DiagnosticKind::NotStruct
-            | DiagnosticKind::StructDoesNotHaveField { .. } => Level::Error,
+            | DiagnosticKind::StructDoesNotHaveField { .. }
+            | DiagnosticKind::UnrecognizedProperty(_)
+            | DiagnosticKind::PropertyNotOnStage(_)
+            | DiagnosticKind::InvalidPropertyValue { .. }
+            | DiagnosticKind::OutsideLoop(_)
+            | DiagnosticKind::RecursiveStruct(_)
+            | DiagnosticKind::ConstReassignment(_) => Level::Error,
 
             | DiagnosticKind::FollowedByUnreachableCode
             | DiagnosticKind::UnrecognizedKey(_)
             | DiagnosticKind::UnusedVariable(_)
             | DiagnosticKind::UnusedList(_)
             | DiagnosticKind::UnusedEnum(_)
+            | DiagnosticKind::UnusedConst(_)
             | DiagnosticKind::UnusedStruct(_)
             | DiagnosticKind::UnusedProc(_)
             | DiagnosticKind::UnusedFunc(_)
             | DiagnosticKind::UnusedArg(_)
             | DiagnosticKind::UnusedStructField(_)
-            | DiagnosticKind::UnusedEnumVariant(_) => Level::Warning,
+            | DiagnosticKind::UnusedEnumVariant(_)
+            | DiagnosticKind::NonExhaustiveMatch(_)
+            | DiagnosticKind::ConstShadowsVariable(_) => Level::Warning,
         }
     }
 }
@@ -2,18 +2,20 @@ use annotate_snippets::{
     Level,
     Renderer,
 };
-use fxhash::FxHashMap;
 
 use super::SpriteDiagnostics;
 use crate::{
     ast::Project,
-    misc::SmolStr,
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
 };
 
 pub struct ProjectDiagnostics {
     pub project: Project,
     pub stage_diagnostics: SpriteDiagnostics,
-    pub sprites_diagnostics: FxHashMap<SmolStr, SpriteDiagnostics>,
+    pub sprites_diagnostics: IndexMap<SmolStr, SpriteDiagnostics>,
 }
 
 impl ProjectDiagnostics {
@@ -25,6 +27,14 @@ impl ProjectDiagnostics {
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.stage_diagnostics.diagnostics.is_empty()
+            && self
+                .sprites_diagnostics
+                .values()
+                .all(|sprite_diagnostics| sprite_diagnostics.diagnostics.is_empty())
+    }
+
     pub fn failure(&self) -> bool {
         self.stage_diagnostics
             .diagnostics
@@ -1,51 +1,1484 @@
 use std::{
+    fmt::Display,
     fs,
-    path::PathBuf,
+    path::Path,
 };
 
-pub enum FmtError {}
+use anyhow::Context;
+use logos::Logos;
 
-pub fn format_file(path: PathBuf) -> Result<(), FmtError> {
-    let mut src = fs::read(&path).unwrap();
-    format_buffer_inplace(&mut src)?;
-    fs::write(path, src).unwrap();
-    Ok(())
+use crate::lexer::token::Token;
+
+const MAX_LINE_LENGTH: usize = 88;
+const INDENT: &str = "    ";
+
+#[derive(Debug)]
+pub enum FmtError {
+    /// The source contains text which is not a valid token.
+    InvalidToken { line: usize, column: usize },
+    /// The source is not valid goboscript, so it is left untouched.
+    UnexpectedToken { line: usize, column: usize },
+    /// The formatted source does not contain the same tokens as the original source.
+    /// This is a bug in the formatter, and the file is left untouched.
+    TokensChanged,
+}
+
+impl Display for FmtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FmtError::InvalidToken { line, column } => {
+                write!(f, "invalid token at line {line}, column {column}")
+            }
+            FmtError::UnexpectedToken { line, column } => {
+                write!(f, "unexpected token at line {line}, column {column}")
+            }
+            FmtError::TokensChanged => write!(f, "formatting would change the meaning of the code"),
+        }
+    }
+}
+
+impl std::error::Error for FmtError {}
+
+/// Formats the file at `path`, and returns whether it was already formatted. If `check`
+/// is true, the file is never written to.
+pub fn format_file(path: &Path, check: bool) -> anyhow::Result<bool> {
+    let src = fs::read_to_string(path)?;
+    let formatted =
+        format_source(&src).with_context(|| format!("failed to format {}", path.display()))?;
+    if formatted == src {
+        return Ok(true);
+    }
+    if !check {
+        fs::write(path, formatted)?;
+    }
+    Ok(false)
+}
+
+/// Formats goboscript source code. Comments are kept, and preprocessor lines are kept
+/// as written, except that the backslashes of multi-line macros are aligned.
+pub fn format_source(src: &str) -> Result<String, FmtError> {
+    let items = scan(src)?;
+    let declarations = Parser::new(src, &items).file()?;
+    let formatted = Printer::new(&items).file(&declarations);
+    if code(&items) != code(&scan(&formatted)?) {
+        return Err(FmtError::TokensChanged);
+    }
+    Ok(formatted)
+}
+
+#[derive(Debug)]
+enum ItemKind {
+    Token(Token),
+    Comment,
+    DocComment,
+    /// A preprocessor line, such as `%include` or `%define`, with its continuation lines.
+    Directive,
+}
+
+#[derive(Debug)]
+struct Item<'src> {
+    kind: ItemKind,
+    text: &'src str,
+    /// Byte offset of the item in the source.
+    offset: usize,
+    /// Number of line breaks between the previous item and this one.
+    newlines: usize,
+    /// Whether there is any whitespace between the previous item and this one.
+    space: bool,
 }
 
-fn format_buffer_inplace(src: &mut Vec<u8>) -> Result<(), FmtError> {
-    let max_line_length = 88;
+impl Item<'_> {
+    fn token(&self) -> Option<&Token> {
+        match &self.kind {
+            ItemKind::Token(token) => Some(token),
+            _ => None,
+        }
+    }
+
+    fn is(&self, f: impl Fn(&Token) -> bool) -> bool {
+        self.token().is_some_and(f)
+    }
+}
+
+fn scan(src: &str) -> Result<Vec<Item<'_>>, FmtError> {
+    let mut items = vec![];
     let mut i = 0;
-    let mut line_begin = true;
+    let mut newlines = 0;
+    let mut space = false;
     while i < src.len() {
-        if line_begin && src[i] == b'%' {
-            loop {
-                let begin = i;
-                i += src[i..].split(|c| *c == b'\n').next().unwrap().len();
-                if src[i - 1] != b'\\' {
-                    i += 1;
-                    break;
-                }
-                i -= 1;
-                let mut slash = i;
-                let diff = slash - begin;
-                if diff >= (max_line_length - 1) {
-                    for _ in 0..(diff - (max_line_length - 1)) {
-                        src.remove(slash - 1);
-                        slash -= 1;
-                        i -= 1;
+        let rest = &src[i..];
+        let c = rest.chars().next().unwrap();
+        if c.is_whitespace() {
+            newlines += (c == '\n') as usize;
+            space = true;
+            i += c.len_utf8();
+            continue;
+        }
+        let begin = i;
+        let kind = if rest.starts_with('#') {
+            i += rest.find('\n').unwrap_or(rest.len());
+            if rest.starts_with("##") {
+                ItemKind::DocComment
+            } else {
+                ItemKind::Comment
+            }
+        } else if rest.starts_with('%') && src[..i].rsplit('\n').next().unwrap().trim().is_empty() {
+            i += directive_len(rest);
+            ItemKind::Directive
+        } else {
+            let mut lexer = Token::lexer(rest);
+            match lexer.next() {
+                Some(Ok(token)) => {
+                    i += lexer.span().end;
+                    ItemKind::Token(token)
+                }
+                _ => {
+                    let (line, column) = position(src, i);
+                    return Err(FmtError::InvalidToken { line, column });
+                }
+            }
+        };
+        items.push(Item {
+            kind,
+            text: src[begin..i].trim_end(),
+            offset: begin,
+            newlines,
+            space,
+        });
+        newlines = 0;
+        space = false;
+    }
+    Ok(items)
+}
+
+/// Returns the line and column of the byte at `offset`, both starting at 1.
+fn position(src: &str, offset: usize) -> (usize, usize) {
+    let line = src[..offset].matches('\n').count() + 1;
+    let column = offset - src[..offset].rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, column)
+}
+
+/// Returns the length of the preprocessor line at the start of `src`, including the
+/// lines it is continued on with a trailing backslash, but not the final line break.
+fn directive_len(src: &str) -> usize {
+    let mut len = 0;
+    for line in src.split_inclusive('\n') {
+        if !line.trim_end().ends_with('\\') {
+            return len + line.strip_suffix('\n').unwrap_or(line).len();
+        }
+        len += line.len();
+    }
+    len
+}
+
+/// Pads the trailing backslashes of a multi-line macro so that they line up.
+fn align_backslashes(directive: &str) -> String {
+    let lines: Vec<&str> = directive.lines().collect();
+    let mut aligned = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i + 1 < lines.len() {
+            let code = line.trim_end().strip_suffix('\\').unwrap().trim_end();
+            let padding = (MAX_LINE_LENGTH - 1).saturating_sub(code.len()).max(1);
+            aligned.push_str(code);
+            aligned.push_str(&" ".repeat(padding));
+            aligned.push_str("\\\n");
+        } else {
+            aligned.push_str(line);
+        }
+    }
+    aligned
+}
+
+/// Returns the text of every token and comment, leaving out trailing commas, which the
+/// formatter adds and removes.
+fn code<'src>(items: &[Item<'src>]) -> Vec<&'src str> {
+    let tokens: Vec<&Item> = items
+        .iter()
+        .filter(|item| !matches!(item.kind, ItemKind::Directive))
+        .collect();
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(i, item)| {
+            let next = tokens[i + 1..].iter().find(|item| item.token().is_some());
+            !(item.is(|t| matches!(t, Token::Comma)) && next.is_some_and(|next| next.is(is_closer)))
+        })
+        .map(|(_, item)| item.text)
+        .collect()
+}
+
+fn is_closer(token: &Token) -> bool {
+    matches!(token, Token::RParen | Token::RBracket | Token::RBrace)
+}
+
+fn is_name(token: &Token) -> bool {
+    matches!(token, Token::Name(_))
+}
+
+fn is_str(token: &Token) -> bool {
+    matches!(token, Token::Str(_))
+}
+
+fn is_assign(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Assign
+            | Token::AssignAdd
+            | Token::AssignSubtract
+            | Token::AssignMultiply
+            | Token::AssignDivide
+            | Token::AssignFloorDiv
+            | Token::AssignModulo
+            | Token::AssignJoin
+    )
+}
+
+/// Index of a token in the scanned items.
+type Tok = usize;
+
+/// A declaration, a statement or an arm of a match statement.
+#[derive(Debug)]
+struct Stmt {
+    parts: Vec<Part>,
+    /// Semicolons, or the comma after a match arm.
+    ends: Vec<Tok>,
+}
+
+#[derive(Debug)]
+enum Part {
+    /// A keyword, name or operator.
+    Token(Tok),
+    /// A token written directly after the previous part, such as `++` or the `:` of a
+    /// keyword argument.
+    Suffix(Tok),
+    Expr(Expr),
+    /// Comma separated elements without brackets, such as the arguments of a block.
+    List(Vec<Element>),
+    /// Fields of a struct, variants of an enum or sprite properties.
+    Items(Group),
+    Block(Block),
+}
+
+impl Part {
+    fn first(&self) -> Tok {
+        match self {
+            Part::Token(tok) | Part::Suffix(tok) => *tok,
+            Part::Expr(expr) => expr.first(),
+            Part::List(elements) => elements[0].parts[0].first(),
+            Part::Items(group) => group.open,
+            Part::Block(block) => block.open,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Element {
+    parts: Vec<Part>,
+    comma: Option<Tok>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GroupKind {
+    /// Parentheses around an expression or a tuple.
+    Paren,
+    /// An index into a list.
+    Index,
+    /// Arguments of a function call, or a list literal.
+    List,
+    /// A struct literal, or the items of a declaration.
+    Braces,
+}
+
+#[derive(Debug)]
+struct Group {
+    kind: GroupKind,
+    open: Tok,
+    elements: Vec<Element>,
+    close: Tok,
+    /// Whether every element is put on its own line.
+    multiline: bool,
+}
+
+impl Group {
+    /// Whether a comma is added after the last element when the group is multiline.
+    fn trailing_comma(&self) -> bool {
+        match self.kind {
+            GroupKind::Paren => self.elements.len() > 1,
+            GroupKind::Index => false,
+            GroupKind::List | GroupKind::Braces => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Block {
+    open: Tok,
+    stmts: Vec<Stmt>,
+    close: Tok,
+}
+
+#[derive(Debug)]
+enum Expr {
+    /// A name or a literal.
+    Token(Tok),
+    Prefix {
+        op: Tok,
+        operand: Box<Expr>,
+    },
+    /// `op` is two tokens for `not in`.
+    Binary {
+        lhs: Box<Expr>,
+        op: Vec<Tok>,
+        rhs: Box<Expr>,
+    },
+    /// `if`, `then` and `else`, each followed by an expression.
+    Ternary(Vec<(Tok, Expr)>),
+    /// A function call, or an index into a list.
+    Call {
+        callee: Box<Expr>,
+        args: Group,
+    },
+    Dot {
+        lhs: Box<Expr>,
+        dot: Tok,
+        name: Tok,
+    },
+    Struct {
+        name: Tok,
+        fields: Group,
+    },
+    Group(Group),
+}
+
+impl Expr {
+    fn first(&self) -> Tok {
+        match self {
+            Expr::Token(tok) => *tok,
+            Expr::Prefix { op, .. } => *op,
+            Expr::Binary { lhs, .. } | Expr::Call { callee: lhs, .. } | Expr::Dot { lhs, .. } => {
+                lhs.first()
+            }
+            Expr::Ternary(parts) => parts[0].0,
+            Expr::Struct { name, .. } => *name,
+            Expr::Group(group) => group.open,
+        }
+    }
+}
+
+type Parse<T> = Result<T, FmtError>;
+
+/// Parses the tokens of a file into a syntax tree which keeps every token, following
+/// the productions of the grammar.
+struct Parser<'a, 'src> {
+    src: &'src str,
+    items: &'a [Item<'src>],
+    /// Indices of the tokens in `items`, without comments and preprocessor lines.
+    tokens: Vec<Tok>,
+    pos: usize,
+}
+
+impl<'a, 'src> Parser<'a, 'src> {
+    fn new(src: &'src str, items: &'a [Item<'src>]) -> Self {
+        let tokens = items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.token().is_some())
+            .map(|(i, _)| i)
+            .collect();
+        Self {
+            src,
+            items,
+            tokens,
+            pos: 0,
+        }
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<&'a Token> {
+        let items = self.items;
+        self.tokens
+            .get(self.pos + n)
+            .and_then(|&i| items[i].token())
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.peek_nth(0)
+    }
+
+    fn at(&self, f: impl Fn(&Token) -> bool) -> bool {
+        self.peek().is_some_and(f)
+    }
+
+    fn bump(&mut self) -> Tok {
+        self.pos += 1;
+        self.tokens[self.pos - 1]
+    }
+
+    fn eat(&mut self, f: impl Fn(&Token) -> bool) -> Option<Tok> {
+        self.at(f).then(|| self.bump())
+    }
+
+    fn expect(&mut self, f: impl Fn(&Token) -> bool) -> Parse<Tok> {
+        self.eat(f).ok_or_else(|| self.error())
+    }
+
+    fn error(&self) -> FmtError {
+        let offset = self
+            .tokens
+            .get(self.pos)
+            .map_or(self.src.len(), |&i| self.items[i].offset);
+        let (line, column) = position(self.src, offset);
+        FmtError::UnexpectedToken { line, column }
+    }
+
+    fn file(&mut self) -> Parse<Vec<Stmt>> {
+        let mut declarations = vec![];
+        while self.peek().is_some() {
+            declarations.push(self.declaration()?);
+        }
+        Ok(declarations)
+    }
+
+    fn declaration(&mut self) -> Parse<Stmt> {
+        let mut parts = vec![];
+        match self.peek() {
+            Some(Token::Semicolon) => {}
+            Some(Token::Costumes | Token::Sounds) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::List(
+                    self.list(Self::asset, |t| matches!(t, Token::Semicolon))?,
+                ));
+            }
+            Some(Token::Sprite) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Items(self.items()?));
+                return Ok(Stmt {
+                    parts,
+                    ends: vec![],
+                });
+            }
+            Some(Token::Test | Token::On | Token::OnKey | Token::OnBackdrop) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Token(self.expect(is_str)?));
+                parts.push(Part::Block(self.block()?));
+                return Ok(Stmt {
+                    parts,
+                    ends: vec![],
+                });
+            }
+            Some(Token::OnFlag | Token::OnClick | Token::OnClone) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Block(self.block()?));
+                return Ok(Stmt {
+                    parts,
+                    ends: vec![],
+                });
+            }
+            Some(Token::OnLoudness | Token::OnTimer) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Token(self.expect(|t| matches!(t, Token::Gt))?));
+                parts.push(Part::Expr(self.expr(true)?));
+                parts.push(Part::Block(self.block()?));
+                return Ok(Stmt {
+                    parts,
+                    ends: vec![],
+                });
+            }
+            Some(Token::NoWarp | Token::Proc) => {
+                if let Some(nowarp) = self.eat(|t| matches!(t, Token::NoWarp)) {
+                    parts.push(Part::Token(nowarp));
+                }
+                parts.push(Part::Token(self.expect(|t| matches!(t, Token::Proc))?));
+                parts.push(Part::Token(self.expect(is_name)?));
+                let args = self.list(Self::declared, |t| matches!(t, Token::LBrace))?;
+                if !args.is_empty() {
+                    parts.push(Part::List(args));
+                }
+                parts.push(Part::Block(self.block()?));
+                return Ok(Stmt {
+                    parts,
+                    ends: vec![],
+                });
+            }
+            Some(Token::Func) => {
+                parts.push(Part::Token(self.bump()));
+                let name = self.expect(is_name)?;
+                if !self.at(|t| matches!(t, Token::LParen)) {
+                    return Err(self.error());
+                }
+                let args = self.group(GroupKind::List, Self::declared)?;
+                parts.push(Part::Expr(Expr::Call {
+                    callee: Box::new(Expr::Token(name)),
+                    args,
+                }));
+                if let Some(type_) = self.eat(is_name) {
+                    parts.push(Part::Token(type_));
+                }
+                parts.push(Part::Block(self.block()?));
+                return Ok(Stmt {
+                    parts,
+                    ends: vec![],
+                });
+            }
+            Some(Token::Struct | Token::Enum) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Token(self.expect(is_name)?));
+                parts.push(Part::Items(self.items()?));
+                return Ok(Stmt {
+                    parts,
+                    ends: vec![],
+                });
+            }
+            Some(Token::Const) => {
+                parts.push(Part::Token(self.bump()));
+                while let Some(name) = self.eat(is_name) {
+                    parts.push(Part::Token(name));
+                }
+                parts.push(Part::Token(self.expect(|t| matches!(t, Token::Assign))?));
+                parts.push(Part::Expr(self.expr(false)?));
+            }
+            Some(Token::Map) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Token(self.expect(is_name)?));
+            }
+            Some(Token::List) => {
+                parts.push(Part::Token(self.bump()));
+                while let Some(name) = self.eat(is_name) {
+                    parts.push(Part::Token(name));
+                }
+                if let Some(assign) = self.eat(|t| matches!(t, Token::Assign)) {
+                    parts.push(Part::Token(assign));
+                    if self.at(|t| matches!(t, Token::LBracket)) {
+                        let default = self.group(GroupKind::List, Self::argument)?;
+                        parts.push(Part::Expr(Expr::Group(default)));
+                    } else {
+                        if let Some(program) = self.eat(is_name) {
+                            parts.push(Part::Token(program));
+                        }
+                        parts.push(Part::Token(self.expect(|t| matches!(t, Token::Cmd(_)))?));
                     }
-                } else {
-                    for _ in 0..((max_line_length - 1) - diff) {
-                        src.insert(slash, b' ');
-                        i += 1;
+                }
+            }
+            _ => return Err(self.error()),
+        }
+        let semicolon = self.expect(|t| matches!(t, Token::Semicolon))?;
+        Ok(Stmt {
+            parts,
+            ends: vec![semicolon],
+        })
+    }
+
+    fn asset(&mut self) -> Parse<Vec<Part>> {
+        let mut parts = vec![Part::Token(self.expect(is_str)?)];
+        if let Some(as_) = self.eat(|t| matches!(t, Token::As)) {
+            parts.push(Part::Token(as_));
+            parts.push(Part::Token(self.expect(is_str)?));
+        }
+        Ok(parts)
+    }
+
+    /// Parses a struct field, an enum variant, an argument of a procedure or function,
+    /// or a sprite property.
+    fn declared(&mut self) -> Parse<Vec<Part>> {
+        let mut parts = vec![Part::Token(self.expect(is_name)?)];
+        if let Some(name) = self.eat(is_name) {
+            parts.push(Part::Token(name));
+        }
+        if let Some(assign) = self.eat(|t| matches!(t, Token::Assign)) {
+            parts.push(Part::Token(assign));
+            parts.push(Part::Expr(self.expr(true)?));
+        } else if let Some(colon) = self.eat(|t| matches!(t, Token::Colon)) {
+            parts.push(Part::Suffix(colon));
+            parts.push(Part::Expr(self.expr(false)?));
+        }
+        Ok(parts)
+    }
+
+    /// Parses an expression, or a keyword argument such as `x: 1`.
+    fn argument(&mut self) -> Parse<Vec<Part>> {
+        if self.at(is_name) && matches!(self.peek_nth(1), Some(Token::Colon)) {
+            let name = self.bump();
+            let colon = self.bump();
+            return Ok(vec![
+                Part::Token(name),
+                Part::Suffix(colon),
+                Part::Expr(self.expr(false)?),
+            ]);
+        }
+        Ok(vec![Part::Expr(self.expr(false)?)])
+    }
+
+    /// Parses comma separated elements, up to a token which is not a comma or up to a
+    /// token for which `end` is true.
+    fn list(
+        &mut self,
+        element: fn(&mut Self) -> Parse<Vec<Part>>,
+        end: fn(&Token) -> bool,
+    ) -> Parse<Vec<Element>> {
+        let mut elements = vec![];
+        while !self.at(end) {
+            let parts = element(self)?;
+            let comma = self.eat(|t| matches!(t, Token::Comma));
+            let last = comma.is_none();
+            elements.push(Element { parts, comma });
+            if last {
+                break;
+            }
+        }
+        Ok(elements)
+    }
+
+    fn group(
+        &mut self,
+        kind: GroupKind,
+        element: fn(&mut Self) -> Parse<Vec<Part>>,
+    ) -> Parse<Group> {
+        let open = self.bump();
+        let elements = self.list(element, is_closer)?;
+        let closer = match self.items[open].text {
+            "(" => ")",
+            "[" => "]",
+            _ => "}",
+        };
+        let close = self.expect(is_closer)?;
+        if self.items[close].text != closer {
+            self.pos -= 1;
+            return Err(self.error());
+        }
+        let multiline = self.is_broken(open, &elements, close);
+        Ok(Group {
+            kind,
+            open,
+            elements,
+            close,
+            multiline,
+        })
+    }
+
+    /// Whether a group is written across lines, with a line break or a comment after the
+    /// opening bracket, after a comma or before the closing bracket.
+    fn is_broken(&self, open: Tok, elements: &[Element], close: Tok) -> bool {
+        let items = self.items;
+        if elements.is_empty() {
+            return items[open + 1..close]
+                .iter()
+                .any(|item| item.token().is_none());
+        }
+        let breaks_after =
+            |tok: Tok| items[tok + 1].newlines > 0 || items[tok + 1].token().is_none();
+        breaks_after(open)
+            || elements
+                .iter()
+                .filter_map(|element| element.comma)
+                .any(breaks_after)
+            || items[close].newlines > 0
+            || items[close - 1].token().is_none()
+    }
+
+    /// Parses the braces of a struct, an enum or the sprite properties, which always have
+    /// an item on each line.
+    fn items(&mut self) -> Parse<Group> {
+        if !self.at(|t| matches!(t, Token::LBrace)) {
+            return Err(self.error());
+        }
+        let mut group = self.group(GroupKind::Braces, Self::declared)?;
+        group.multiline = true;
+        Ok(group)
+    }
+
+    fn block(&mut self) -> Parse<Block> {
+        let open = self.expect(|t| matches!(t, Token::LBrace))?;
+        let mut stmts = vec![];
+        while !self.at(|t| matches!(t, Token::RBrace)) {
+            stmts.push(self.statement()?);
+        }
+        let close = self.bump();
+        Ok(Block { open, stmts, close })
+    }
+
+    fn arms(&mut self) -> Parse<Block> {
+        let open = self.expect(|t| matches!(t, Token::LBrace))?;
+        let mut stmts = vec![];
+        while !self.at(|t| matches!(t, Token::RBrace)) {
+            let mut parts = vec![Part::Expr(self.expr(true)?)];
+            while let Some(bar) = self.eat(|t| matches!(t, Token::Bar)) {
+                parts.push(Part::Token(bar));
+                parts.push(Part::Expr(self.expr(true)?));
+            }
+            parts.push(Part::Token(self.expect(|t| matches!(t, Token::FatArrow))?));
+            parts.push(Part::Block(self.block()?));
+            let ends = self
+                .eat(|t| matches!(t, Token::Comma))
+                .into_iter()
+                .collect();
+            stmts.push(Stmt { parts, ends });
+        }
+        let close = self.bump();
+        Ok(Block { open, stmts, close })
+    }
+
+    fn statement(&mut self) -> Parse<Stmt> {
+        let mut parts = vec![];
+        match self.peek() {
+            Some(Token::If) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Expr(self.expr(true)?));
+                parts.push(Part::Block(self.block()?));
+                while let Some(elif) = self.eat(|t| matches!(t, Token::Elif)) {
+                    parts.push(Part::Token(elif));
+                    parts.push(Part::Expr(self.expr(true)?));
+                    parts.push(Part::Block(self.block()?));
+                }
+                if let Some(else_) = self.eat(|t| matches!(t, Token::Else)) {
+                    parts.push(Part::Token(else_));
+                    parts.push(Part::Block(self.block()?));
+                }
+            }
+            Some(Token::Repeat | Token::Until) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Expr(self.expr(true)?));
+                parts.push(Part::Block(self.block()?));
+            }
+            Some(Token::Forever) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Block(self.block()?));
+            }
+            Some(Token::Match) => {
+                parts.push(Part::Token(self.bump()));
+                parts.push(Part::Expr(self.expr(true)?));
+                parts.push(Part::Block(self.arms()?));
+            }
+            Some(Token::For) => {
+                parts.push(Part::Token(self.bump()));
+                if let Some(local) = self.eat(|t| matches!(t, Token::Local)) {
+                    parts.push(Part::Token(local));
+                }
+                parts.push(Part::Token(self.expect(is_name)?));
+                parts.push(Part::Token(self.expect(|t| matches!(t, Token::In))?));
+                parts.push(Part::Expr(self.expr(true)?));
+                if let Some(step) = self.eat(|t| matches!(t, Token::Step)) {
+                    parts.push(Part::Token(step));
+                    parts.push(Part::Expr(self.expr(true)?));
+                }
+                parts.push(Part::Block(self.block()?));
+            }
+            _ => self.simple(&mut parts)?,
+        }
+        let mut ends = vec![];
+        while let Some(semicolon) = self.eat(|t| matches!(t, Token::Semicolon)) {
+            ends.push(semicolon);
+        }
+        Ok(Stmt { parts, ends })
+    }
+
+    /// Parses a statement which ends with a semicolon, up to the semicolon.
+    fn simple(&mut self, parts: &mut Vec<Part>) -> Parse<()> {
+        loop {
+            match self.peek() {
+                None => return Err(self.error()),
+                Some(Token::Semicolon) => return Ok(()),
+                Some(
+                    Token::Return
+                    | Token::Break
+                    | Token::Continue
+                    | Token::Local
+                    | Token::Cloud
+                    | Token::Show
+                    | Token::Hide
+                    | Token::Add
+                    | Token::To
+                    | Token::Insert
+                    | Token::At
+                    | Token::Delete
+                    | Token::Assert,
+                ) => parts.push(Part::Token(self.bump())),
+                Some(Token::Log) if parts.is_empty() => parts.push(Part::Token(self.bump())),
+                Some(token) if is_assign(token) => parts.push(Part::Token(self.bump())),
+                Some(Token::Increment | Token::Decrement) => parts.push(Part::Suffix(self.bump())),
+                Some(Token::Name(_)) if self.is_command(parts) => {
+                    parts.push(Part::Token(self.bump()));
+                }
+                Some(_) => parts.push(Part::List(self.list(Self::argument, |t| {
+                    matches!(t, Token::Semicolon) || is_assign(t)
+                })?)),
+            }
+        }
+    }
+
+    /// Whether the name at the current token is a block or procedure which is called
+    /// with arguments, or the type of a variable, rather than the start of an expression.
+    /// `say -1;` and `Point p = ...;` start with such a name, `x -= 1;` does not.
+    fn is_command(&self, parts: &[Part]) -> bool {
+        let after_keyword = parts.iter().all(|part| {
+            matches!(part, Part::Token(tok) if self.items[*tok].is(|t| matches!(t, Token::Local | Token::Cloud)))
+        });
+        let Some(&next) = self.tokens.get(self.pos + 1) else {
+            return false;
+        };
+        let next = &self.items[next];
+        after_keyword
+            && match next.token().unwrap() {
+                Token::LParen => next.space,
+                token if is_assign(token) => false,
+                Token::Increment
+                | Token::Decrement
+                | Token::LBracket
+                | Token::Dot
+                | Token::Comma
+                | Token::Semicolon => false,
+                _ => true,
+            }
+    }
+
+    /// Parses an expression. Struct literals are not allowed in the conditions of
+    /// control flow statements, where `{` starts the block.
+    fn expr(&mut self, no_struct: bool) -> Parse<Expr> {
+        self.binary(0, no_struct)
+    }
+
+    fn binary(&mut self, min: u8, no_struct: bool) -> Parse<Expr> {
+        let mut lhs = self.unary(no_struct)?;
+        while let Some(precedence) = self.infix().filter(|&p| p > min) {
+            let mut op = vec![self.bump()];
+            if self.items[op[0]].is(|t| matches!(t, Token::Not)) {
+                op.push(self.bump());
+            }
+            let rhs = if self.items[op[0]].is(|t| matches!(t, Token::Pipe)) {
+                self.call()?
+            } else {
+                self.binary(precedence, no_struct)?
+            };
+            lhs = Expr::Binary {
+                lhs: Box::new(lhs),
+                op,
+                rhs: Box::new(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    /// Returns how tightly the binary operator at the current token binds.
+    fn infix(&self) -> Option<u8> {
+        Some(match self.peek()? {
+            Token::DotDot => 1,
+            Token::Or => 2,
+            Token::And => 3,
+            Token::In | Token::Eq | Token::Ne => 4,
+            Token::Not if matches!(self.peek_nth(1), Some(Token::In)) => 4,
+            Token::Pipe => 5,
+            Token::Amp => 6,
+            Token::Lt | Token::Le | Token::Gt | Token::Ge => 7,
+            Token::Plus | Token::Minus => 8,
+            Token::Star | Token::Slash | Token::FloorDiv | Token::Percent => 9,
+            _ => return None,
+        })
+    }
+
+    fn unary(&mut self, no_struct: bool) -> Parse<Expr> {
+        match self.peek() {
+            Some(
+                Token::Minus
+                | Token::Not
+                | Token::Length
+                | Token::Round
+                | Token::Abs
+                | Token::Floor
+                | Token::Ceil
+                | Token::Sqrt
+                | Token::Sin
+                | Token::Cos
+                | Token::Tan
+                | Token::Asin
+                | Token::Acos
+                | Token::Atan
+                | Token::Ln
+                | Token::Log
+                | Token::Antiln
+                | Token::Antilog,
+            ) => {
+                let op = self.bump();
+                let operand = Box::new(self.unary(no_struct)?);
+                Ok(Expr::Prefix { op, operand })
+            }
+            Some(Token::If) => {
+                let if_ = self.bump();
+                let cond = self.expr(false)?;
+                let then = self.expect(|t| matches!(t, Token::Then))?;
+                let then_value = self.expr(false)?;
+                let else_ = self.expect(|t| matches!(t, Token::Else))?;
+                let else_value = self.expr(false)?;
+                Ok(Expr::Ternary(vec![
+                    (if_, cond),
+                    (then, then_value),
+                    (else_, else_value),
+                ]))
+            }
+            _ => self.postfix(no_struct),
+        }
+    }
+
+    fn postfix(&mut self, no_struct: bool) -> Parse<Expr> {
+        let mut expr = self.term(no_struct)?;
+        loop {
+            if self.at(|t| matches!(t, Token::LBracket)) {
+                let args = self.group(GroupKind::Index, Self::argument)?;
+                expr = Expr::Call {
+                    callee: Box::new(expr),
+                    args,
+                };
+            } else if let Some(dot) = self.eat(|t| matches!(t, Token::Dot)) {
+                let name = self.expect(is_name)?;
+                expr = Expr::Dot {
+                    lhs: Box::new(expr),
+                    dot,
+                    name,
+                };
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn term(&mut self, no_struct: bool) -> Parse<Expr> {
+        match self.peek() {
+            Some(Token::Name(_)) => match self.peek_nth(1) {
+                Some(Token::LParen) => self.call(),
+                Some(Token::LBrace) if !no_struct => {
+                    let name = self.bump();
+                    let fields = self.group(GroupKind::Braces, Self::argument)?;
+                    Ok(Expr::Struct { name, fields })
+                }
+                _ => Ok(Expr::Token(self.bump())),
+            },
+            Some(
+                Token::Arg(_)
+                | Token::Bin(_)
+                | Token::Oct(_)
+                | Token::Int(_)
+                | Token::Hex(_)
+                | Token::Float(_)
+                | Token::Str(_)
+                | Token::FStr(_)
+                | Token::True
+                | Token::False,
+            ) => Ok(Expr::Token(self.bump())),
+            Some(Token::LParen) => Ok(Expr::Group(self.group(GroupKind::Paren, Self::argument)?)),
+            Some(Token::LBracket) => Ok(Expr::Group(self.group(GroupKind::List, Self::argument)?)),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn call(&mut self) -> Parse<Expr> {
+        let name = self.expect(is_name)?;
+        if !self.at(|t| matches!(t, Token::LParen)) {
+            return Err(self.error());
+        }
+        let args = self.group(GroupKind::List, Self::argument)?;
+        Ok(Expr::Call {
+            callee: Box::new(Expr::Token(name)),
+            args,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Sep {
+    /// Directly after the previous token.
+    None,
+    Space,
+    /// A space, or a line break if the token is on a new line in the source.
+    Soft,
+    /// A line break. The first line of a block is never preceded by a blank line, and
+    /// `blank` adds one before the line and the comments directly above it.
+    Line {
+        first: bool,
+        blank: bool,
+    },
+    /// A line break before a closing bracket. The comments above it are indented like
+    /// the contents of the brackets.
+    Close,
+}
+
+struct Printer<'a, 'src> {
+    items: &'a [Item<'src>],
+    out: String,
+    indent: usize,
+    /// Index of the first item which has not been written.
+    next: usize,
+    /// Comment after the previous token, which is written at the end of the line.
+    comment: Option<&'src str>,
+}
+
+impl<'a, 'src> Printer<'a, 'src> {
+    fn new(items: &'a [Item<'src>]) -> Self {
+        Self {
+            items,
+            out: String::new(),
+            indent: 0,
+            next: 0,
+            comment: None,
+        }
+    }
+
+    fn file(mut self, declarations: &[Stmt]) -> String {
+        let mut prev_has_block = false;
+        for (i, declaration) in declarations.iter().enumerate() {
+            let has_block = declaration
+                .parts
+                .iter()
+                .any(|part| matches!(part, Part::Block(_) | Part::Items(_)));
+            let blank = i > 0 && (has_block || prev_has_block);
+            self.stmt(
+                declaration,
+                Sep::Line {
+                    first: i == 0,
+                    blank,
+                },
+            );
+            prev_has_block = has_block;
+        }
+        let first = declarations.is_empty();
+        self.trivia(
+            self.items.len(),
+            Sep::Line {
+                first,
+                blank: false,
+            },
+        );
+        if let Some(comment) = self.comment.take() {
+            self.out.push(' ');
+            self.out.push_str(comment);
+        }
+        let mut out = self.out.trim_end().to_owned();
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+
+    fn is_word(&self, tok: Tok) -> bool {
+        self.items[tok]
+            .text
+            .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+    }
+
+    /// Whether `tok` is a `(` written directly after the previous token, as in
+    /// `round(x)`.
+    fn is_tight_paren(&self, tok: Tok) -> bool {
+        let item = &self.items[tok];
+        item.is(|t| matches!(t, Token::LParen)) && !item.space
+    }
+
+    fn line(&mut self, indent: usize, blank: bool) {
+        if let Some(comment) = self.comment.take() {
+            self.out.push(' ');
+            self.out.push_str(comment);
+        }
+        self.out
+            .truncate(self.out.trim_end_matches([' ', '\t']).len());
+        if !self.out.is_empty() {
+            self.out.push('\n');
+            if blank {
+                self.out.push('\n');
+            }
+        }
+        for _ in 0..indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    /// Writes the comments and preprocessor lines before the item at `end`, and the line
+    /// break before it if there is one. Returns whether a line break was written.
+    fn trivia(&mut self, end: usize, sep: Sep) -> bool {
+        let items = self.items;
+        let trivia: Vec<usize> = (self.next..end)
+            .filter(|&i| items[i].token().is_none())
+            .collect();
+        let (line, first, force_blank, close) = match sep {
+            Sep::Line { first, blank } => (true, first, blank, false),
+            Sep::Close => (true, false, false, true),
+            _ => (false, false, false, false),
+        };
+        // Comments directly above a line stay attached to it.
+        let mut attached = trivia.len();
+        while attached > 0 {
+            let below = trivia.get(attached).copied().unwrap_or(end);
+            let newlines = items.get(below).map_or(0, |item| item.newlines);
+            let item = &items[trivia[attached - 1]];
+            if newlines != 1 || !matches!(item.kind, ItemKind::Comment | ItemKind::DocComment) {
+                break;
+            }
+            attached -= 1;
+        }
+        let lines = trivia.len() + usize::from(line);
+        for k in 0..lines {
+            let is_end = k == trivia.len();
+            let index = trivia.get(k).copied().unwrap_or(end);
+            let newlines = items.get(index).map_or(0, |item| item.newlines);
+            let blank = line
+                && (force_blank && k == attached
+                    || newlines > 1 && !(first && k == 0) && !(close && is_end));
+            let indent = if !line || close && !is_end {
+                self.indent + 1
+            } else {
+                self.indent
+            };
+            self.line(indent, blank);
+            if is_end {
+                break;
+            }
+            let item = &items[index];
+            if matches!(item.kind, ItemKind::Directive) {
+                self.out.truncate(self.out.trim_end_matches(' ').len());
+                self.out.push_str(&align_backslashes(item.text));
+            } else {
+                self.out.push_str(item.text);
+            }
+        }
+        if !line && (!trivia.is_empty() || self.comment.is_some()) {
+            self.line(self.indent + 1, false);
+            return true;
+        }
+        line
+    }
+
+    fn token(&mut self, tok: Tok, sep: Sep) {
+        let item = &self.items[tok];
+        if !self.trivia(tok, sep) {
+            match sep {
+                Sep::Space => self.out.push(' '),
+                Sep::Soft if item.newlines > 0 => self.line(self.indent + 1, false),
+                Sep::Soft => self.out.push(' '),
+                _ => {}
+            }
+        }
+        self.out.push_str(item.text);
+        self.next = tok + 1;
+        if let Some(next) = self.items.get(tok + 1) {
+            if matches!(next.kind, ItemKind::Comment | ItemKind::DocComment) && next.newlines == 0 {
+                self.comment = Some(next.text);
+                self.next = tok + 2;
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, sep: Sep) {
+        let mut sep = self.parts(&stmt.parts, sep);
+        for &end in &stmt.ends {
+            self.token(end, sep);
+            sep = Sep::None;
+        }
+    }
+
+    /// Writes parts separated by spaces, and returns the separator for what follows them.
+    fn parts(&mut self, parts: &[Part], sep: Sep) -> Sep {
+        for (i, part) in parts.iter().enumerate() {
+            let sep = match (i.checked_sub(1).map(|i| &parts[i]), part) {
+                (None, _) => sep,
+                (_, Part::Suffix(_)) => Sep::None,
+                (_, Part::Block(_) | Part::Items(_)) | (Some(Part::Block(_)), _) => Sep::Space,
+                (Some(Part::Token(word)), _)
+                    if self.is_word(*word) && self.is_tight_paren(part.first()) =>
+                {
+                    Sep::None
+                }
+                _ => Sep::Soft,
+            };
+            self.part(part, sep);
+        }
+        if parts.is_empty() {
+            sep
+        } else {
+            Sep::None
+        }
+    }
+
+    fn part(&mut self, part: &Part, sep: Sep) {
+        match part {
+            Part::Token(tok) | Part::Suffix(tok) => self.token(*tok, sep),
+            Part::Expr(expr) => self.expr(expr, sep),
+            Part::List(elements) => {
+                for (i, element) in elements.iter().enumerate() {
+                    self.parts(&element.parts, if i == 0 { sep } else { Sep::Soft });
+                    if let Some(comma) = element.comma {
+                        self.token(comma, Sep::None);
                     }
                 }
-                i += 2;
             }
+            Part::Items(group) => self.group(group, sep),
+            Part::Block(block) => self.block(block, sep),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, sep: Sep) {
+        match expr {
+            Expr::Token(tok) => self.token(*tok, sep),
+            Expr::Prefix { op, operand } => {
+                self.token(*op, sep);
+                let first = operand.first();
+                let operand_sep = if self.is_word(*op) {
+                    if self.is_tight_paren(first) {
+                        Sep::None
+                    } else {
+                        Sep::Soft
+                    }
+                } else if self.items[first].is(|t| matches!(t, Token::Minus)) {
+                    // `- -x` would become a decrement.
+                    Sep::Space
+                } else {
+                    Sep::None
+                };
+                self.expr(operand, operand_sep);
+            }
+            Expr::Binary { lhs, op, rhs } => {
+                self.expr(lhs, sep);
+                let sep = if self.items[op[0]].is(|t| matches!(t, Token::DotDot)) {
+                    Sep::None
+                } else {
+                    Sep::Soft
+                };
+                for &tok in op {
+                    self.token(tok, sep);
+                }
+                self.expr(rhs, sep);
+            }
+            Expr::Ternary(parts) => {
+                for (i, (keyword, value)) in parts.iter().enumerate() {
+                    self.token(*keyword, if i == 0 { sep } else { Sep::Soft });
+                    self.expr(value, Sep::Soft);
+                }
+            }
+            Expr::Call { callee, args } => {
+                self.expr(callee, sep);
+                self.group(args, Sep::None);
+            }
+            Expr::Dot { lhs, dot, name } => {
+                self.expr(lhs, sep);
+                self.token(*dot, Sep::None);
+                self.token(*name, Sep::None);
+            }
+            Expr::Struct { name, fields } => {
+                self.token(*name, sep);
+                self.group(fields, Sep::Space);
+            }
+            Expr::Group(group) => self.group(group, sep),
+        }
+    }
+
+    fn group(&mut self, group: &Group, sep: Sep) {
+        self.token(group.open, sep);
+        if group.multiline {
+            self.indent += 1;
+            for (i, element) in group.elements.iter().enumerate() {
+                self.parts(
+                    &element.parts,
+                    Sep::Line {
+                        first: i == 0,
+                        blank: false,
+                    },
+                );
+                match element.comma {
+                    Some(comma) => self.token(comma, Sep::None),
+                    None if group.trailing_comma() => self.out.push(','),
+                    None => {}
+                }
+            }
+            self.indent -= 1;
+            self.token(group.close, Sep::Close);
+            return;
+        }
+        // Trailing commas are left out of brackets on a single line.
+        let padding = if group.kind == GroupKind::Braces && !group.elements.is_empty() {
+            Sep::Space
         } else {
-            line_begin = src[i] == b'\n';
-            i += 1;
+            Sep::None
+        };
+        let last = group.elements.len().saturating_sub(1);
+        for (i, element) in group.elements.iter().enumerate() {
+            self.parts(&element.parts, if i == 0 { padding } else { Sep::Soft });
+            match element.comma {
+                Some(comma) if i < last => self.token(comma, Sep::None),
+                _ => {}
+            }
+        }
+        self.token(group.close, padding);
+    }
+
+    fn block(&mut self, block: &Block, sep: Sep) {
+        self.token(block.open, sep);
+        let has_comments = self.items[block.open + 1..block.close]
+            .iter()
+            .any(|item| item.token().is_none());
+        if block.stmts.is_empty() && !has_comments {
+            self.token(block.close, Sep::None);
+            return;
         }
+        self.indent += 1;
+        for (i, stmt) in block.stmts.iter().enumerate() {
+            self.stmt(
+                stmt,
+                Sep::Line {
+                    first: i == 0,
+                    blank: false,
+                },
+            );
+        }
+        self.indent -= 1;
+        self.token(block.close, Sep::Close);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_source() {
+        let src = "\
+%define ADD(A,B) A+B
+%if not FOO
+costumes \"blank.svg\";
+%endif
+# A comment.
+struct Point {x,y}
+list points = [1,2,3,];
+list names = [
+\"a\", # First.
+\"b\" # Last.
+];
+list digits = [1,2,
+3];
+proc   foo   x,y{
+say -1; # Trailing comment.
+
+
+x=ADD(1,2)- -3;
+if $x==1{say \"one\";}else{say(1+2);}
+    Point p = Point {
+x:1,y: 2
+};
+match x { 1 => { say x; }, _ => {} }
+}
+## Docs.
+onflag { foo 1, 2; }
+test \"sum\" {assert(1+1==2,\"two\");}
+";
+        let expected = "\
+%define ADD(A,B) A+B
+%if not FOO
+costumes \"blank.svg\";
+%endif
+
+# A comment.
+struct Point {
+    x,
+    y,
+}
+
+list points = [1, 2, 3];
+list names = [
+    \"a\", # First.
+    \"b\", # Last.
+];
+list digits = [
+    1,
+    2,
+    3,
+];
+
+proc foo x, y {
+    say -1; # Trailing comment.
+
+    x = ADD(1, 2) - -3;
+    if $x == 1 {
+        say \"one\";
+    } else {
+        say(1 + 2);
+    }
+    Point p = Point {
+        x: 1,
+        y: 2,
+    };
+    match x {
+        1 => {
+            say x;
+        },
+        _ => {}
+    }
+}
+
+## Docs.
+onflag {
+    foo 1, 2;
+}
+
+test \"sum\" {
+    assert(1 + 1 == 2, \"two\");
+}
+";
+        let formatted = format_source(src).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_statements() {
+        let src = "\
+nowarp proc go Point p,x=1{
+local a,b=split(\"a b\",\" \");
+x[1].y+=2; l[i]++;
+x=if a then b else c+1;
+y=a not in b and not c or -(d);
+z=x|>foo(1) ; w=round(x)+abs x;
+for local i in 1..10 step 2{break;}
+repeat 10{ # Loop.
+%if X
+say 2;
+%endif
+}
+until x>1{}
+}
+func f(Point p,y=2)Point{return Point{x:p.x,y:2};}
+onloudness>10{}
+";
+        let expected = "\
+nowarp proc go Point p, x = 1 {
+    local a, b = split(\"a b\", \" \");
+    x[1].y += 2;
+    l[i]++;
+    x = if a then b else c + 1;
+    y = a not in b and not c or -(d);
+    z = x |> foo(1);
+    w = round(x) + abs x;
+    for local i in 1..10 step 2 {
+        break;
+    }
+    repeat 10 { # Loop.
+%if X
+        say 2;
+%endif
+    }
+    until x > 1 {}
+}
+
+func f(Point p, y = 2) Point {
+    return Point { x: p.x, y: 2 };
+}
+
+onloudness > 10 {}
+";
+        let formatted = format_source(src).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+        assert!(matches!(
+            format_source("proc foo {\n    say 1\n}\n"),
+            Err(FmtError::UnexpectedToken { line: 3, column: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_align_backslashes() {
+        let aligned = align_backslashes("%define A \\\n    1");
+        assert_eq!(aligned.find('\\'), Some(MAX_LINE_LENGTH - 1));
+        assert!(aligned.ends_with("\\\n    1"));
     }
-    Ok(())
 }
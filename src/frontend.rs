@@ -1,7 +1,12 @@
 pub mod build;
+mod check;
 mod cli;
+mod decompile;
 mod fmt;
 mod new;
+mod run;
+mod test;
+mod watch;
 
 use std::process::ExitCode;
 
@@ -19,23 +24,73 @@ use new::NewError;
 
 use crate::config::Config;
 
+fn build_exit_code(result: Result<(), build::BuildError>, warnings_as_errors: bool) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(build::BuildError::AnyhowError(err)) => {
+            eprintln!("{}: {:?}", "error".red().bold(), err);
+            ExitCode::FAILURE
+        }
+        Err(build::BuildError::ProjectDiagnostics(diagnostics)) => {
+            diagnostics.eprint();
+            eprintln!();
+            if diagnostics.failure() || warnings_as_errors {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+    }
+}
+
 pub fn frontend() -> ExitCode {
     match Cli::parse().command {
-        Command::Build { input, output } => match build::build(input, output) {
+        Command::Build {
+            input,
+            output,
+            reproducible,
+            compression,
+            compression_level,
+            sizes,
+            watch,
+        } => {
+            let compression = compression.map(Into::into);
+            if watch {
+                if let Err(err) = watch::watch(
+                    input,
+                    output,
+                    reproducible,
+                    compression,
+                    compression_level,
+                    sizes,
+                ) {
+                    eprintln!("{}: {:?}", "error".red().bold(), err);
+                }
+                return ExitCode::FAILURE;
+            }
+            build_exit_code(
+                build::build(
+                    input,
+                    output,
+                    reproducible,
+                    compression,
+                    compression_level,
+                    sizes,
+                    &mut vec![],
+                ),
+                false,
+            )
+        }
+        Command::Check {
+            input,
+            warnings_as_errors,
+        } => build_exit_code(check::check(input), warnings_as_errors),
+        Command::Lsp => match crate::lsp::run() {
             Ok(()) => ExitCode::SUCCESS,
-            Err(build::BuildError::AnyhowError(err)) => {
+            Err(err) => {
                 eprintln!("{}: {:?}", "error".red().bold(), err);
                 ExitCode::FAILURE
             }
-            Err(build::BuildError::ProjectDiagnostics(diagnostics)) => {
-                diagnostics.eprint();
-                eprintln!();
-                if diagnostics.failure() {
-                    ExitCode::FAILURE
-                } else {
-                    ExitCode::SUCCESS
-                }
-            }
         },
         Command::Completions { shell } => {
             shell.generate(&mut Cli::command(), &mut std::io::stdout());
@@ -65,6 +120,10 @@ pub fn frontend() -> ExitCode {
                     high_quality_pen: Some(high_quality_pen),
                     stage_width,
                     stage_height,
+                    no_doc_comments: None,
+                    compression: None,
+                    compression_level: None,
+                    sprites: Default::default(),
                 },
             ) {
                 Err(NewError::AnyhowError(err)) => {
@@ -84,12 +143,31 @@ pub fn frontend() -> ExitCode {
                 Ok(_) => ExitCode::SUCCESS,
             }
         }
-        Command::Fmt { input } => match fmt::fmt(input) {
+        Command::Run { input, frames } => build_exit_code(run::run(input, frames), false),
+        Command::Test { input, frames } => build_exit_code(test::test(input, frames), false),
+        Command::Decompile { input, output } => match decompile::decompile(input, output) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{}: {:?}", "error".red().bold(), err);
+                ExitCode::FAILURE
+            }
+        },
+        Command::Fmt { input, check } => match fmt::fmt(input, check) {
             Ok(_) => ExitCode::SUCCESS,
             Err(FmtError::AnyhowError(err)) => {
                 eprintln!("{}: {:?}", "error".red().bold(), err);
                 ExitCode::FAILURE
             }
+            Err(FmtError::Unformatted(paths)) => {
+                for path in paths {
+                    eprintln!(
+                        "{}: {} is not formatted",
+                        "error".red().bold(),
+                        path.display()
+                    );
+                }
+                ExitCode::FAILURE
+            }
         },
     }
 }
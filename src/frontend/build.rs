@@ -4,37 +4,64 @@ use std::{
         self,
         File,
     },
-    io::BufWriter,
-    path::PathBuf,
+    io::{
+        self,
+        BufWriter,
+        Seek,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 
 use anyhow::{
     anyhow,
+    bail,
     Context,
 };
 use directories::ProjectDirs;
-use fxhash::FxHashMap;
+use zip::{
+    write::SimpleFileOptions,
+    CompressionMethod,
+    DateTime,
+    ZipArchive,
+};
 
 use crate::{
     ast::{
         Project,
         Sprite,
     },
-    codegen::sb3::Sb3,
-    config::Config,
+    codegen::{
+        sb3::Sb3,
+        sprite_state::SpriteState,
+    },
+    config::{
+        Compression,
+        Config,
+    },
     diagnostic::{
         ProjectDiagnostics,
         SpriteDiagnostics,
     },
-    misc::SmolStr,
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
     parser,
     standard_library::StandardLibrary,
+    translation_unit::{
+        Owner,
+        TranslationUnit,
+    },
     visitor,
 };
 
 pub enum BuildError {
     AnyhowError(anyhow::Error),
-    ProjectDiagnostics(ProjectDiagnostics),
+    ProjectDiagnostics(Box<ProjectDiagnostics>),
 }
 
 impl<T> From<T> for BuildError
@@ -47,16 +74,51 @@ where T: Into<anyhow::Error>
 
 impl From<ProjectDiagnostics> for BuildError {
     fn from(value: ProjectDiagnostics) -> Self {
-        Self::ProjectDiagnostics(value)
+        Self::ProjectDiagnostics(Box::new(value))
     }
 }
 
-pub fn build(input: Option<PathBuf>, output: Option<PathBuf>) -> Result<(), BuildError> {
-    let dirs = ProjectDirs::from("com", "aspizu", "goboscript").unwrap();
+pub fn build(
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    reproducible: bool,
+    compression: Option<Compression>,
+    compression_level: Option<i64>,
+    sizes: bool,
+    dependencies: &mut Vec<PathBuf>,
+) -> Result<(), BuildError> {
     let input = input.unwrap_or_else(|| env::current_dir().unwrap());
-    let canonical_input = input.canonicalize()?;
-    let project_name = canonical_input.file_name().unwrap().to_str().unwrap();
-    let output = output.unwrap_or_else(|| input.join(format!("{project_name}.sb3")));
+    let output = output_path(&input, output)?;
+    let (config, stdlib) = load(&input)?;
+    let file_options = file_options(compression, compression_level, &config, reproducible)?;
+    // The project is written to a temporary file first, so that a failed build keeps the
+    // last good `.sb3`.
+    let partial_output = partial_output_path(&output);
+    let mut sb3 = Sb3::new(BufWriter::new(File::create(&partial_output)?), file_options);
+    let result = compile(&input, &config, &stdlib, &mut sb3, dependencies);
+    let written = match &result {
+        Ok(()) => true,
+        Err(BuildError::ProjectDiagnostics(diagnostics)) => !diagnostics.failure(),
+        Err(BuildError::AnyhowError(_)) => false,
+    };
+    if !written {
+        drop(sb3);
+        fs::remove_file(&partial_output)?;
+        return result;
+    }
+    let asset_files = sb3.asset_files();
+    sb3.zip.finish()?.flush()?;
+    fs::rename(&partial_output, &output)?;
+    if sizes {
+        print_sizes(&output, &asset_files)?;
+    }
+    result
+}
+
+/// Reads the project's `goboscript.toml`, and fetches the standard library version it
+/// uses.
+pub fn load(input: &Path) -> anyhow::Result<(Config, StandardLibrary)> {
+    let dirs = ProjectDirs::from("com", "aspizu", "goboscript").unwrap();
     let config_path = input.join("goboscript.toml");
     let config_src = fs::read_to_string(&config_path).unwrap_or_default();
     let config: Config = toml::from_str(&config_src)
@@ -72,20 +134,198 @@ pub fn build(input: Option<PathBuf>, output: Option<PathBuf>) -> Result<(), Buil
         StandardLibrary::from_latest(&dirs.config_dir().join("std"))?
     };
     stdlib.fetch()?;
+    Ok((config, stdlib))
+}
+
+/// Returns the path of the `.sb3` to write, if not given, it will be the project
+/// directory's name + `.sb3`.
+pub fn output_path(input: &Path, output: Option<PathBuf>) -> io::Result<PathBuf> {
+    if let Some(output) = output {
+        return Ok(output);
+    }
+    let canonical_input = input.canonicalize()?;
+    let project_name = canonical_input.file_name().unwrap().to_str().unwrap();
+    Ok(input.join(format!("{project_name}.sb3")))
+}
+
+pub fn partial_output_path(output: &Path) -> PathBuf {
+    let mut file_name = output.file_name().unwrap_or_default().to_owned();
+    file_name.push(".tmp");
+    output.with_file_name(file_name)
+}
+
+/// Returns the files other than the standard library that a sprite is compiled from.
+fn sprite_dependencies<'a>(
+    input: &'a Path,
+    translation_unit: &'a TranslationUnit,
+    sprite: &'a Sprite,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    let includes = translation_unit
+        .get_includes()
+        .iter()
+        .filter(|include| matches!(include.owner, Owner::Local))
+        .map(|include| include.path.clone());
+    let costumes = sprite.costumes.iter().map(|costume| &costume.path);
+    let sounds = sprite.sounds.iter().map(|sound| &sound.path);
+    let files = sprite.lists.values().filter_map(|list| {
+        let cmd = list.cmd()?;
+        let program = cmd.program.as_ref()?;
+        (&*program.name == "file").then_some(&cmd.cmd)
+    });
+    includes.chain(
+        costumes
+            .chain(sounds)
+            .chain(files)
+            .map(|path| input.join(&**path)),
+    )
+}
+
+/// The compression options given on the command line override the ones in
+/// goboscript.toml. A compression level only applies to deflate, so the level from
+/// goboscript.toml is ignored when the files are stored, and it is an error to give both
+/// a level and store compression on the command line.
+///
+/// If `reproducible` is set, every file in the archive gets the same fixed modification
+/// time, so that identical input produces a byte-identical `.sb3`.
+fn file_options(
+    compression: Option<Compression>,
+    compression_level: Option<i64>,
+    config: &Config,
+    reproducible: bool,
+) -> anyhow::Result<SimpleFileOptions> {
+    if compression == Some(Compression::Store) && compression_level.is_some() {
+        bail!("compression level cannot be set with store compression");
+    }
+    let mut options = SimpleFileOptions::default();
+    let compression_level = match compression.or(config.compression).unwrap_or_default() {
+        Compression::Store => {
+            options = options.compression_method(CompressionMethod::Stored);
+            None
+        }
+        Compression::Deflate => {
+            options = options.compression_method(CompressionMethod::Deflated);
+            compression_level.or(config.compression_level)
+        }
+    };
+    if let Some(level) = compression_level {
+        if !(1..=264).contains(&level) {
+            bail!("compression level must be from 1 to 264, got {level}");
+        }
+        options = options.compression_level(Some(level));
+    }
+    if reproducible {
+        options = options.last_modified_time(DateTime::default());
+    }
+    Ok(options)
+}
+
+fn print_sizes(output: &Path, asset_files: &IndexMap<String, SmolStr>) -> anyhow::Result<()> {
+    let mut archive = ZipArchive::new(File::open(output)?)?;
+    let mut total_size = 0;
+    let mut total_compressed_size = 0;
+    println!("{:>10} {:>10}  file", "size", "compressed");
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        total_size += file.size();
+        total_compressed_size += file.compressed_size();
+        let name = match asset_files.get(file.name()) {
+            Some(path) => format!("{path} ({})", file.name()),
+            None => file.name().to_owned(),
+        };
+        println!(
+            "{:>10} {:>10}  {name}",
+            format_size(file.size()),
+            format_size(file.compressed_size())
+        );
+    }
+    println!(
+        "{:>10} {:>10}  total",
+        format_size(total_size),
+        format_size(total_compressed_size)
+    );
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{bytes} B")
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// Compiles a project into `sb3`. Fails with the diagnostics if any were reported, the
+/// project is still written if none of them are errors.
+pub fn compile<T>(
+    input: &Path,
+    config: &Config,
+    stdlib: &StandardLibrary,
+    sb3: &mut Sb3<T>,
+    dependencies: &mut Vec<PathBuf>,
+) -> Result<(), BuildError>
+where
+    T: Write + Seek,
+{
+    let ProjectDiagnostics {
+        project,
+        mut stage_diagnostics,
+        mut sprites_diagnostics,
+    } = analyze(input, config, stdlib, sb3.tests, dependencies)?;
+    sb3.project(
+        input,
+        &project,
+        config,
+        &mut stage_diagnostics,
+        &mut sprites_diagnostics,
+    )?;
+    let diagnostics = ProjectDiagnostics {
+        project,
+        stage_diagnostics,
+        sprites_diagnostics,
+    };
+    if !diagnostics.is_empty() {
+        return Err(diagnostics.into());
+    }
+    Ok(())
+}
+
+/// Parses the project and runs every pass over it, stopping before codegen. Asserts
+/// outside of `test` blocks are removed unless `tests` is set. Fails if the project could
+/// not be parsed, otherwise returns the project with the diagnostics that were reported
+/// for it.
+pub fn analyze(
+    input: &Path,
+    config: &Config,
+    stdlib: &StandardLibrary,
+    tests: bool,
+    dependencies: &mut Vec<PathBuf>,
+) -> Result<ProjectDiagnostics, BuildError> {
     let stage_path = input.join("stage.gs");
     if !stage_path.is_file() {
         return Err(anyhow!("{} not found", stage_path.display()).into());
     }
-    let mut stage_diagnostics = SpriteDiagnostics::new(stage_path, &stdlib);
+    let mut stage_diagnostics = SpriteDiagnostics::new(stage_path, stdlib);
     let stage = parser::parse(&stage_diagnostics.translation_unit)
         .map_err(|err| {
             stage_diagnostics.diagnostics.push(err);
         })
         .unwrap_or_default();
-    let mut sprites_diagnostics: FxHashMap<SmolStr, SpriteDiagnostics> = Default::default();
-    let mut sprites: FxHashMap<SmolStr, Sprite> = Default::default();
-    for sprite_path in fs::read_dir(&input)? {
-        let sprite_path = sprite_path?.path();
+    dependencies.extend(sprite_dependencies(
+        input,
+        &stage_diagnostics.translation_unit,
+        &stage,
+    ));
+    let mut sprites_diagnostics: IndexMap<SmolStr, SpriteDiagnostics> = Default::default();
+    let mut sprites: IndexMap<SmolStr, Sprite> = Default::default();
+    // Sprites are compiled in order of their file name, as the order of directory
+    // entries depends on the filesystem.
+    let mut sprite_paths = fs::read_dir(input)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    sprite_paths.sort();
+    for sprite_path in sprite_paths {
         if sprite_path.file_stem().is_some_and(|stem| stem == "stage") {
             continue;
         }
@@ -104,13 +344,29 @@ pub fn build(input: Option<PathBuf>, output: Option<PathBuf>) -> Result<(), Buil
             .to_str()
             .unwrap()
             .into();
-        let mut sprite_diagnostics = SpriteDiagnostics::new(sprite_path, &stdlib);
+        let mut sprite_diagnostics = SpriteDiagnostics::new(sprite_path, stdlib);
         let sprite = parser::parse(&sprite_diagnostics.translation_unit)
             .map_err(|err| sprite_diagnostics.diagnostics.push(err))
             .unwrap_or_default();
+        dependencies.extend(sprite_dependencies(
+            input,
+            &sprite_diagnostics.translation_unit,
+            &sprite,
+        ));
         sprites_diagnostics.insert(sprite_name.clone(), sprite_diagnostics);
         sprites.insert(sprite_name, sprite);
     }
+    if let Some(name) = config
+        .sprites
+        .keys()
+        .find(|name| *name != "stage" && !sprites.contains_key(name.as_str()))
+    {
+        return Err(anyhow!(
+            "{}: [sprites.{name}] does not match any sprite",
+            input.join("goboscript.toml").display()
+        )
+        .into());
+    }
     let mut project = Project { stage, sprites };
     if !(stage_diagnostics.diagnostics.is_empty()
         && sprites_diagnostics
@@ -124,7 +380,38 @@ pub fn build(input: Option<PathBuf>, output: Option<PathBuf>) -> Result<(), Buil
         }
         .into());
     }
-    visitor::pass0::visit_project(&mut project);
+    visitor::pass0::visit_project(
+        &mut project,
+        input,
+        tests,
+        &mut stage_diagnostics,
+        &mut sprites_diagnostics,
+    );
+    // Costume names are only known after pass0 expands globs.
+    let targets = [("stage", &project.stage, true)].into_iter().chain(
+        project
+            .sprites
+            .iter()
+            .map(|(name, sprite)| (name.as_str(), sprite, false)),
+    );
+    let mut config_errors = vec![];
+    for (name, sprite, is_stage) in targets {
+        if let Err(errors) = SpriteState::from_config(name, sprite, is_stage, config) {
+            config_errors.extend(
+                errors
+                    .into_iter()
+                    .map(|(property, kind)| (name, property, kind.to_string(sprite))),
+            );
+        }
+    }
+    if !config_errors.is_empty() {
+        return Err(invalid_config(input, &config_errors).into());
+    }
+    visitor::loops::visit_project(
+        &mut project,
+        &mut stage_diagnostics,
+        &mut sprites_diagnostics,
+    );
     visitor::pass1::visit_project(&mut project);
     visitor::pass2::visit_project(
         &mut project,
@@ -132,26 +419,346 @@ pub fn build(input: Option<PathBuf>, output: Option<PathBuf>) -> Result<(), Buil
         &mut sprites_diagnostics,
     );
     visitor::pass3::visit_project(&mut project);
+    visitor::unused::visit_project(&project, &mut stage_diagnostics, &mut sprites_diagnostics);
+    visitor::resolve::visit_project(&project, &mut stage_diagnostics, &mut sprites_diagnostics);
     log::info!("{:#?}", project);
-    let mut sb3 = Sb3::new(BufWriter::new(File::create(&output)?));
-    sb3.project(
-        &input,
-        &project,
-        &config,
-        &mut stage_diagnostics,
-        &mut sprites_diagnostics,
-    )?;
-    if !(stage_diagnostics.diagnostics.is_empty()
-        && sprites_diagnostics
-            .values()
-            .all(|sprite_diagnostics| sprite_diagnostics.diagnostics.is_empty()))
-    {
-        return Err(ProjectDiagnostics {
-            project,
-            stage_diagnostics,
-            sprites_diagnostics,
+    Ok(ProjectDiagnostics {
+        project,
+        stage_diagnostics,
+        sprites_diagnostics,
+    })
+}
+
+/// Reports each invalid sprite property in goboscript.toml, with the line and column of
+/// its key.
+fn invalid_config(input: &Path, errors: &[(&str, &str, String)]) -> anyhow::Error {
+    let config_path = input.join("goboscript.toml");
+    let config_src = fs::read_to_string(&config_path).unwrap_or_default();
+    let document = toml_edit::ImDocument::parse(config_src.as_str()).ok();
+    let messages: Vec<String> = errors
+        .iter()
+        .map(|(name, property, message)| {
+            let span = document.as_ref().and_then(|document| {
+                let sprites = document.get("sprites")?.as_table_like()?;
+                let properties = sprites.get(name)?.as_table_like()?;
+                properties.get_key_value(property)?.0.span()
+            });
+            let Some(span) = span else {
+                return format!("{}: {message}", config_path.display());
+            };
+            let before = &config_src[..span.start];
+            let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+            let line = before.matches('\n').count() + 1;
+            let column = before[line_start..].chars().count() + 1;
+            format!("{}:{line}:{column}: {message}", config_path.display())
+        })
+        .collect();
+    anyhow!("{}", messages.join("\n"))
+}
+
+/// Returns a standard library which is never fetched, in a directory of its own for each
+/// test process.
+#[cfg(test)]
+pub fn test_stdlib() -> StandardLibrary {
+    let path = env::temp_dir().join(format!("goboscript-std-{}", std::process::id()));
+    StandardLibrary::new("0.0.0".parse().unwrap(), &path)
+}
+
+/// Compiles a project with the config in its `goboscript.toml` into `sb3`, and returns
+/// the finished sb3. Panics if any errors were reported, warnings are printed.
+#[cfg(test)]
+pub fn compile_for_test<T: Write + Seek>(input: &Path, mut sb3: Sb3<T>) -> T {
+    let config_src = fs::read_to_string(input.join("goboscript.toml")).unwrap_or_default();
+    let config: Config = toml::from_str(&config_src).unwrap();
+    match compile(input, &config, &test_stdlib(), &mut sb3, &mut vec![]) {
+        Ok(()) => {}
+        Err(BuildError::ProjectDiagnostics(diagnostics)) => {
+            diagnostics.eprint();
+            if diagnostics.failure() {
+                panic!("failed to compile {}", input.display());
+            }
+        }
+        Err(BuildError::AnyhowError(err)) => {
+            panic!("failed to compile {}: {err:?}", input.display())
         }
-        .into());
     }
-    Ok(())
+    sb3.zip.finish().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::diagnostic::DiagnosticKind;
+
+    fn compile_reproducible(input: &Path) -> Vec<u8> {
+        let file_options = file_options(None, None, &Config::default(), true).unwrap();
+        compile_for_test(input, Sb3::new(Cursor::new(Vec::new()), file_options)).into_inner()
+    }
+
+    fn project_json(input: &Path) -> serde_json::Value {
+        let mut archive = ZipArchive::new(Cursor::new(compile_reproducible(input))).unwrap();
+        serde_json::from_reader(archive.by_name("project.json").unwrap()).unwrap()
+    }
+
+    /// Writes a project with a single sprite named main to a temporary directory.
+    fn temp_project(name: &str, main: &str) -> PathBuf {
+        let input = env::temp_dir().join(format!("goboscript-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&input);
+        fs::create_dir_all(&input).unwrap();
+        let blank = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/run/blank.svg");
+        fs::copy(blank, input.join("blank.svg")).unwrap();
+        fs::write(input.join("stage.gs"), "costumes \"blank.svg\";\n").unwrap();
+        fs::write(input.join("main.gs"), main).unwrap();
+        input
+    }
+
+    fn diagnose_main(name: &str, main: &str) -> Vec<DiagnosticKind> {
+        diagnose_project(name, "costumes \"blank.svg\";\n", main)
+    }
+
+    /// Returns the kinds of the diagnostics of the sprite main.
+    fn diagnose_project(name: &str, stage: &str, main: &str) -> Vec<DiagnosticKind> {
+        let input = temp_project(name, main);
+        fs::write(input.join("stage.gs"), stage).unwrap();
+        let Ok(mut diagnostics) = analyze(
+            &input,
+            &Config::default(),
+            &test_stdlib(),
+            false,
+            &mut vec![],
+        ) else {
+            panic!("failed to analyze {}", input.display());
+        };
+        let main = diagnostics.sprites_diagnostics.swap_remove("main").unwrap();
+        main.diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.kind)
+            .collect()
+    }
+
+    /// Every invalid sprite property in goboscript.toml is reported at its key.
+    #[test]
+    fn test_invalid_config() {
+        let input = temp_project("invalid-config", "costumes \"blank.svg\";\n");
+        let config = "[sprites.main]\nx = \"left\"\nsize = 50\nz = 1\n";
+        fs::write(input.join("goboscript.toml"), config).unwrap();
+        let config: Config = toml::from_str(config).unwrap();
+        let Err(BuildError::AnyhowError(err)) =
+            analyze(&input, &config, &test_stdlib(), false, &mut vec![])
+        else {
+            panic!("invalid properties should fail");
+        };
+        let path = input.join("goboscript.toml");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{path}:2:1: invalid value for property sprites.main.x, expected a number\n\
+                 {path}:4:1: unrecognized property sprites.main.z",
+                path = path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_mixed_return_counts() {
+        let kinds = diagnose_main(
+            "mixed-return-counts",
+            "costumes \"blank.svg\";\n\
+             onflag { a, b = f(1); }\n\
+             func f(x) { if $x > 0 { return ($x, 2); } return $x; }\n",
+        );
+        assert!(
+            matches!(
+                kinds[..],
+                [DiagnosticKind::ReturnCountMismatch {
+                    returns: 1,
+                    given: 2
+                }]
+            ),
+            "{kinds:?}"
+        );
+    }
+
+    #[test]
+    fn test_const_diagnostics() {
+        let kinds = diagnose_project(
+            "const-diagnostics",
+            "costumes \"blank.svg\";\nonflag { score = 1; }\n",
+            "costumes \"blank.svg\";\n\
+             struct point { x, y }\n\
+             const point ORIGIN = 0;\n\
+             const score = 2;\n\
+             onflag { say ORIGIN + score; }\n",
+        );
+        assert!(
+            matches!(
+                kinds[..],
+                [
+                    DiagnosticKind::TypeMismatch { .. },
+                    DiagnosticKind::ConstShadowsVariable(_),
+                    DiagnosticKind::UnusedStruct(_)
+                ]
+            ),
+            "{kinds:?}"
+        );
+    }
+
+    /// A function which returns more than one value can only be destructured.
+    #[test]
+    fn test_multiple_returns_in_expression() {
+        let kinds = diagnose_main(
+            "multiple-returns-in-expression",
+            "costumes \"blank.svg\";\n\
+             onflag { x = f(); say 1 + f(); a, b = f(); }\n\
+             func f() { return (1, 2); }\n",
+        );
+        assert!(
+            matches!(
+                kinds[..],
+                [
+                    DiagnosticKind::ReturnCountMismatch {
+                        returns: 2,
+                        given: 1
+                    },
+                    DiagnosticKind::ReturnCountMismatch {
+                        returns: 2,
+                        given: 1
+                    }
+                ]
+            ),
+            "{kinds:?}"
+        );
+    }
+
+    /// Names which do not refer to anything are reported before codegen.
+    #[test]
+    fn test_resolve_diagnostics() {
+        let kinds = diagnose_main(
+            "resolve-diagnostics",
+            "costumes \"blank.svg\";\nonflag { say undefined_var; nope 1, 2; }\n",
+        );
+        assert!(
+            matches!(
+                kinds[..],
+                [
+                    DiagnosticKind::UnrecognizedVariable(_),
+                    DiagnosticKind::UnrecognizedProcedure(_)
+                ]
+            ),
+            "{kinds:?}"
+        );
+    }
+
+    #[test]
+    fn test_reproducible() {
+        let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/operators");
+        let first = compile_reproducible(&input);
+        assert_eq!(first, compile_reproducible(&input));
+        let mut archive = ZipArchive::new(Cursor::new(first)).unwrap();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).unwrap();
+            assert_eq!(file.last_modified(), Some(DateTime::default()));
+        }
+    }
+
+    /// Extension blocks and the debugger blocks declare the extension they come from.
+    #[test]
+    fn test_extensions() {
+        let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/extensions");
+        let project = project_json(&input);
+        assert_eq!(
+            project["extensions"],
+            serde_json::json!(["music", "pen", "twdebugger"])
+        );
+    }
+
+    /// An f-string with a single expression is still joined, with an empty string.
+    #[test]
+    fn test_fstring_join() {
+        let input = temp_project(
+            "fstring-join",
+            "costumes \"blank.svg\";\nonflag { x = 1; say f\"{x}\"; }\n",
+        );
+        let project = project_json(&input);
+        let blocks = project["targets"][1]["blocks"].as_object().unwrap();
+        let joins: Vec<_> = blocks
+            .values()
+            .filter(|block| block["opcode"] == "operator_join")
+            .collect();
+        assert_eq!(joins.len(), 1);
+        assert_eq!(joins[0]["inputs"]["STRING1"][1][1], "");
+    }
+
+    /// A `sprite` declaration overrides goboscript.toml, and sprites are layered in order
+    /// of their `layer`.
+    #[test]
+    fn test_properties() {
+        let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/properties");
+        let project = project_json(&input);
+        let targets = project["targets"].as_array().unwrap();
+        let names: Vec<&str> = targets
+            .iter()
+            .map(|target| target["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["Stage", "background", "main"]);
+        let [stage, background, main] = &targets[..] else {
+            unreachable!();
+        };
+        assert_eq!(stage["volume"], 50.0);
+        assert_eq!(stage["layerOrder"], 0);
+        assert_eq!(main["layerOrder"], 1);
+        assert_eq!(background["layerOrder"], 2);
+        assert_eq!(main["x"], -100.0);
+        assert_eq!(main["y"], 50.5);
+        assert_eq!(main["size"], 25.0);
+        assert_eq!(main["direction"], -90.0);
+        assert_eq!(main["visible"], false);
+        assert_eq!(main["draggable"], true);
+        assert_eq!(main["rotationStyle"], "left-right");
+        assert_eq!(main["currentCostume"], 1);
+        assert_eq!(main["volume"], 80.0);
+    }
+
+    /// The compression method of a file written with `options`.
+    fn compression_method(options: anyhow::Result<SimpleFileOptions>) -> CompressionMethod {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("file", options.unwrap()).unwrap();
+        let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+        let method = archive.by_index(0).unwrap().compression();
+        method
+    }
+
+    #[test]
+    fn test_compression_options() {
+        let store = Config {
+            compression: Some(Compression::Store),
+            compression_level: Some(9),
+            ..Default::default()
+        };
+        assert_eq!(
+            compression_method(file_options(None, None, &store, false)),
+            CompressionMethod::Stored
+        );
+        assert_eq!(
+            compression_method(file_options(
+                Some(Compression::Deflate),
+                None,
+                &store,
+                false
+            )),
+            CompressionMethod::Deflated
+        );
+        assert!(file_options(None, Some(9), &store, false).is_ok());
+        assert!(file_options(Some(Compression::Store), Some(9), &store, false).is_err());
+        assert!(file_options(None, Some(0), &Config::default(), false).is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
 }
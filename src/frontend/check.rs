@@ -0,0 +1,22 @@
+use std::{
+    env,
+    path::PathBuf,
+};
+
+use super::build::{
+    analyze,
+    load,
+    BuildError,
+};
+
+/// Checks a project for errors and warnings without writing the compiled project. Fails
+/// with the diagnostics if any were reported.
+pub fn check(input: Option<PathBuf>) -> Result<(), BuildError> {
+    let input = input.unwrap_or_else(|| env::current_dir().unwrap());
+    let (config, stdlib) = load(&input)?;
+    let diagnostics = analyze(&input, &config, &stdlib, false, &mut vec![])?;
+    if !diagnostics.is_empty() {
+        return Err(diagnostics.into());
+    }
+    Ok(())
+}
@@ -3,8 +3,11 @@ use std::path::PathBuf;
 use clap_derive::{
     Parser,
     Subcommand,
+    ValueEnum,
 };
 
+use crate::config::Compression;
+
 #[derive(Debug, Parser)]
 #[command(
     version = env!("CARGO_PKG_VERSION"),
@@ -25,6 +28,26 @@ pub enum Command {
         #[arg(short, long)]
         /// Output file, if not given, it will be the project directory's name + `.sb3`
         output: Option<PathBuf>,
+        #[arg(short, long)]
+        /// Give every file in the `.sb3` a fixed modification time, so that building
+        /// the same project twice produces byte-identical output.
+        reproducible: bool,
+        #[arg(short = 'z', long, value_enum)]
+        /// Compression method for the files inside the `.sb3`, overrides `compression`
+        /// in `goboscript.toml`. Defaults to deflate.
+        compression: Option<CompressionArg>,
+        #[arg(short = 'l', long)]
+        /// Deflate compression level, from 1 (fastest) to 9 (smallest). Levels above 9
+        /// are much slower and compress further. Overrides `compression_level` in
+        /// `goboscript.toml`.
+        compression_level: Option<i64>,
+        #[arg(short, long)]
+        /// Print the size of each file inside the `.sb3`.
+        sizes: bool,
+        #[arg(short, long)]
+        /// Rebuild the project whenever one of its source files, includes or assets
+        /// changes.
+        watch: bool,
     },
 
     /// Create a new goboscript project with a blank backdrop, a main sprite with a
@@ -74,14 +97,72 @@ pub enum Command {
         stage_height: Option<u64>,
     },
 
+    /// Check a goboscript project for errors and warnings, without writing an `.sb3`.
+    #[command()]
+    Check {
+        #[arg(short, long)]
+        /// Project directory, if not given, the current directory is used.
+        input: Option<PathBuf>,
+        #[arg(short = 'W', long)]
+        /// Exit with a failure if there are any warnings.
+        warnings_as_errors: bool,
+    },
+
+    /// Compile a goboscript project and run it without opening it in a player. Motion
+    /// and looks blocks change the state of sprites without drawing anything, and `log`
+    /// statements print to stdout.
+    #[command()]
+    Run {
+        #[arg(short, long)]
+        /// Project directory, if not given, the current directory is used.
+        input: Option<PathBuf>,
+        #[arg(short, long)]
+        /// Stop after this many frames, if not given, the project runs until every
+        /// script is done.
+        frames: Option<u64>,
+    },
+
+    /// Compile a goboscript project with its `test` blocks, and run each test without
+    /// opening it in a player. Each test starts with a fresh copy of every variable and
+    /// list, and fails at its first failed `assert`.
+    #[command()]
+    Test {
+        #[arg(short, long)]
+        /// Project directory, if not given, the current directory is used.
+        input: Option<PathBuf>,
+        #[arg(short, long, default_value_t = 1800)]
+        /// Stop each test after this many frames, a test which is still running then
+        /// fails.
+        frames: u64,
+    },
+
     /// Format a goboscript project.
     #[command()]
     Fmt {
         /// Project directory or file, if not given, the current directory is used.
         #[arg(short, long)]
         input: Option<PathBuf>,
+        /// Do not write any files, exit with a failure if any file is not formatted.
+        #[arg(short, long)]
+        check: bool,
+    },
+
+    /// Decompile a `.sb3` project to a goboscript project.
+    #[command()]
+    Decompile {
+        /// `.sb3` file to decompile.
+        #[arg(short, long)]
+        input: PathBuf,
+        /// Project directory to create, if not given, it will be the `.sb3` file's name.
+        /// If this is a path to an existing directory, it must be empty.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 
+    /// Start a language server for editors, which communicates over stdin and stdout.
+    #[command()]
+    Lsp,
+
     /// Generate completions for a shell.
     #[command()]
     Completions {
@@ -90,3 +171,19 @@ pub enum Command {
         shell: clap_complete_command::Shell,
     },
 }
+
+/// Compression method for the files inside the `.sb3`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CompressionArg {
+    Store,
+    Deflate,
+}
+
+impl From<CompressionArg> for Compression {
+    fn from(value: CompressionArg) -> Self {
+        match value {
+            CompressionArg::Store => Compression::Store,
+            CompressionArg::Deflate => Compression::Deflate,
+        }
+    }
+}
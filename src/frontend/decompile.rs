@@ -0,0 +1,137 @@
+use std::{
+    fs::{
+        self,
+        File,
+    },
+    io,
+    path::PathBuf,
+};
+
+use anyhow::{
+    bail,
+    Context,
+};
+use colored::Colorize;
+use zip::ZipArchive;
+
+use crate::decompile::{
+    self,
+    Project,
+};
+
+pub fn decompile(input: PathBuf, output: Option<PathBuf>) -> anyhow::Result<()> {
+    let output = output.unwrap_or_else(|| PathBuf::from(input.file_stem().unwrap()));
+    let mut sb3 = ZipArchive::new(
+        File::open(&input).with_context(|| format!("failed to open {}", input.display()))?,
+    )?;
+    let project: Project = serde_json::from_reader(sb3.by_name("project.json")?)
+        .context("failed to parse project.json")?;
+    if output.exists() && output.read_dir()?.next().is_some() {
+        bail!("{} is not empty", output.display());
+    }
+    let decompiled = decompile::decompile(&project);
+    fs::create_dir_all(output.join("assets"))?;
+    for (md5ext, path) in &decompiled.assets.paths {
+        let mut asset = sb3
+            .by_name(md5ext)
+            .with_context(|| format!("{md5ext} is missing from {}", input.display()))?;
+        io::copy(&mut asset, &mut File::create(output.join(path))?)?;
+    }
+    for (file_stem, src) in &decompiled.sprites {
+        fs::write(output.join(format!("{file_stem}.gs")), src)?;
+    }
+    if decompiled.config != Default::default() {
+        fs::write(
+            output.join("goboscript.toml"),
+            toml::to_string(&decompiled.config)?,
+        )?;
+    }
+    for opcode in &decompiled.unsupported {
+        eprintln!(
+            "{}: {} is not supported, and was left out",
+            "warning".yellow().bold(),
+            opcode
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        path::Path,
+    };
+
+    use serde_json::Value;
+    use zip::write::SimpleFileOptions;
+
+    use super::*;
+    use crate::{
+        codegen::sb3::Sb3,
+        config::Config,
+        frontend::build::compile_for_test,
+        interpreter::Runtime,
+    };
+
+    fn build_to(input: &Path, output: &Path) {
+        let file = File::create(output).unwrap();
+        compile_for_test(input, Sb3::new(file, SimpleFileOptions::default()));
+    }
+
+    fn read_project_json(sb3: &Path) -> Value {
+        let mut sb3 = ZipArchive::new(File::open(sb3).unwrap()).unwrap();
+        serde_json::from_reader(sb3.by_name("project.json").unwrap()).unwrap()
+    }
+
+    fn read_sources(dir: &Path) -> BTreeMap<String, String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "gs"))
+            .map(|path| {
+                let name = path.file_name().unwrap().to_str().unwrap().to_string();
+                (name, fs::read_to_string(path).unwrap())
+            })
+            .collect()
+    }
+
+    /// Returns what a project logs in its first frames.
+    fn run_output(project: &Value) -> String {
+        let project: Project = serde_json::from_value(project.clone()).unwrap();
+        let mut out = vec![];
+        let mut runtime = Runtime::new(&project, &Config::default(), &mut out);
+        runtime.run(Some(30)).unwrap();
+        drop(runtime);
+        String::from_utf8(out).unwrap()
+    }
+
+    /// The compiler folds constant expressions, so decompiled source may be folded
+    /// further when it is rebuilt. After that, rebuilding and decompiling a project must
+    /// give back the same source and the same project.json. The decompiled project must
+    /// also behave like the original.
+    #[test]
+    fn test_round_trip() {
+        let dir = std::env::temp_dir().join(format!("goboscript-decompile-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for project in fs::read_dir("tests").unwrap() {
+            let mut project = project.unwrap().path();
+            let name = project.file_name().unwrap().to_str().unwrap().to_string();
+            let mut sources = vec![];
+            let mut projects = vec![];
+            for round in 0..3 {
+                let sb3 = dir.join(format!("{name}-{round}.sb3"));
+                let decompiled = dir.join(format!("{name}-{round}"));
+                build_to(&project, &sb3);
+                projects.push(read_project_json(&sb3));
+                decompile(sb3, Some(decompiled.clone())).unwrap();
+                sources.push(read_sources(&decompiled));
+                project = decompiled;
+            }
+            assert_eq!(sources[1], sources[2], "{name}");
+            assert_eq!(projects[1], projects[2], "{name}");
+            assert_eq!(run_output(&projects[0]), run_output(&projects[1]), "{name}");
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
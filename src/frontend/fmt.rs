@@ -9,6 +9,8 @@ use crate::fmt;
 
 pub enum FmtError {
     AnyhowError(anyhow::Error),
+    /// Files which are not formatted, when only checking.
+    Unformatted(Vec<PathBuf>),
 }
 
 impl<T> From<T> for FmtError
@@ -19,21 +21,21 @@ where T: Into<anyhow::Error>
     }
 }
 
-impl From<fmt::FmtError> for FmtError {
-    fn from(value: fmt::FmtError) -> Self {
-        todo!()
-    }
-}
-
-pub fn fmt(input: Option<PathBuf>) -> Result<(), FmtError> {
+pub fn fmt(input: Option<PathBuf>, check: bool) -> Result<(), FmtError> {
     let input = input.unwrap_or_else(|| env::current_dir().unwrap());
-    if input.is_file() {
-        fmt::format_file(input)?;
+    let paths = if input.is_file() {
+        vec![input]
     } else {
-        for path in glob(input.join("**/*.gs").to_str().unwrap())? {
-            let path = path?;
-            fmt::format_file(path)?;
+        glob(input.join("**/*.gs").to_str().unwrap())?.collect::<Result<_, _>>()?
+    };
+    let mut unformatted = vec![];
+    for path in paths {
+        if !fmt::format_file(&path, check)? {
+            unformatted.push(path);
         }
     }
+    if check && !unformatted.is_empty() {
+        return Err(FmtError::Unformatted(unformatted));
+    }
     Ok(())
 }
@@ -0,0 +1,143 @@
+use std::{
+    env,
+    io::{
+        self,
+        Cursor,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use colored::Colorize;
+use zip::{
+    write::SimpleFileOptions,
+    CompressionMethod,
+    ZipArchive,
+};
+
+use super::build::{
+    compile,
+    load,
+    BuildError,
+};
+use crate::{
+    codegen::sb3::Sb3,
+    config::Config,
+    decompile::project::Project,
+    interpreter::Runtime,
+    standard_library::StandardLibrary,
+};
+
+/// Compiles a project and runs it without opening it in a player, until every script is
+/// done or for at most `frames` frames. `log` statements are written to stdout.
+pub fn run(input: Option<PathBuf>, frames: Option<u64>) -> Result<(), BuildError> {
+    let input = input.unwrap_or_else(|| env::current_dir().unwrap());
+    let (config, stdlib) = load(&input)?;
+    let project = compile_project(&input, &config, &stdlib, false)?;
+    run_project(&project, &config, frames, io::stdout().lock())?;
+    Ok(())
+}
+
+/// Compiles a project in memory, and returns its project.json. Warnings are printed,
+/// and do not stop the project from running. `test` blocks are only compiled if `tests`
+/// is set.
+pub fn compile_project(
+    input: &Path,
+    config: &Config,
+    stdlib: &StandardLibrary,
+    tests: bool,
+) -> Result<Project, BuildError> {
+    let file_options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut sb3 = Sb3::new(Cursor::new(vec![]), file_options);
+    sb3.tests = tests;
+    match compile(input, config, stdlib, &mut sb3, &mut vec![]) {
+        Ok(()) => {}
+        Err(BuildError::ProjectDiagnostics(diagnostics)) if !diagnostics.failure() => {
+            diagnostics.eprint();
+            eprintln!();
+        }
+        Err(err) => return Err(err),
+    }
+    let mut archive = ZipArchive::new(sb3.zip.finish()?)?;
+    let project = serde_json::from_reader(archive.by_name("project.json")?)?;
+    Ok(project)
+}
+
+fn run_project<W: Write>(
+    project: &Project,
+    config: &Config,
+    frames: Option<u64>,
+    out: W,
+) -> anyhow::Result<()> {
+    let mut runtime = Runtime::new(project, config, out);
+    runtime.run(frames)?;
+    for opcode in &runtime.unsupported {
+        eprintln!(
+            "{}: {} is not supported, and did nothing",
+            "warning".yellow().bold(),
+            opcode
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::build::compile_for_test;
+
+    fn run_test_project(frames: Option<u64>) -> String {
+        let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/run");
+        let sb3 = Sb3::new(Cursor::new(vec![]), SimpleFileOptions::default());
+        let mut archive = ZipArchive::new(compile_for_test(&input, sb3)).unwrap();
+        let project = serde_json::from_reader(archive.by_name("project.json").unwrap()).unwrap();
+        let mut out = vec![];
+        run_project(&project, &Config::default(), frames, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Scripts yield after each iteration of a loop, unless they are in a custom block
+    /// which runs without screen refresh.
+    #[test]
+    fn test_run() {
+        let out = run_test_project(None);
+        let expected = [
+            "a",
+            "w",
+            "w",
+            "b",
+            "stage",
+            "a",
+            "b",
+            "true",
+            "10.5",
+            "Infinity",
+            "0.30000000000000004",
+            "xy",
+            "x y 3",
+            "2",
+            "3",
+            "true",
+            "false",
+            "for 1",
+            "for 3",
+            "bumps 2",
+            "down or left",
+            "walk 1",
+            "walk 1",
+            "hi 6",
+            "10 2 false",
+            "11 12 5 9",
+            "clone 10 -70",
+            "two",
+            "calls 1",
+            "fact 120",
+            "negative positive",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+        assert_eq!(run_test_project(Some(2)), "a\nw\nw\nb\nstage\na\nb\n");
+    }
+}
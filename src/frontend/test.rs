@@ -0,0 +1,215 @@
+use std::{
+    env,
+    io::{
+        self,
+        Write,
+    },
+    path::PathBuf,
+};
+
+use anyhow::anyhow;
+use colored::Colorize;
+
+use super::{
+    build::{
+        load,
+        BuildError,
+    },
+    run::compile_project,
+};
+use crate::{
+    config::Config,
+    decompile::project::Project,
+    interpreter::Runtime,
+    misc::IndexSet,
+};
+
+struct TestResult {
+    name: String,
+    /// Messages of the failed asserts, each starts with the location of the assert.
+    failures: Vec<String>,
+    /// Output of the `log` statements in the test, only shown if it failed.
+    output: Vec<u8>,
+    /// Whether the test was done within the frame limit.
+    finished: bool,
+}
+
+impl TestResult {
+    fn failed(&self) -> bool {
+        !self.failures.is_empty() || !self.finished
+    }
+}
+
+/// Compiles a project with its `test` blocks, and runs each test in a new runtime, so
+/// that every test starts with the initial state of the project. Fails if any test
+/// failed, or was still running after `frames` frames.
+pub fn test(input: Option<PathBuf>, frames: u64) -> Result<(), BuildError> {
+    let input = input.unwrap_or_else(|| env::current_dir().unwrap());
+    let (config, stdlib) = load(&input)?;
+    let project = compile_project(&input, &config, &stdlib, true)?;
+    let (results, unsupported) = run_tests(&project, &config, frames)?;
+    for opcode in &unsupported {
+        eprintln!(
+            "{}: {} is not supported, and did nothing",
+            "warning".yellow().bold(),
+            opcode
+        );
+    }
+    let failed = print_summary(&results, frames)?;
+    if failed > 0 {
+        return Err(anyhow!("{failed} of {} tests failed", results.len()).into());
+    }
+    Ok(())
+}
+
+fn run_tests(
+    project: &Project,
+    config: &Config,
+    frames: u64,
+) -> anyhow::Result<(Vec<TestResult>, IndexSet<String>)> {
+    let tests = Runtime::new(project, config, io::sink()).tests();
+    let mut results = vec![];
+    let mut unsupported = IndexSet::default();
+    for test in &tests {
+        let mut output = vec![];
+        let mut runtime = Runtime::new(project, config, &mut output);
+        let (failures, finished) = runtime.run_test(test, frames)?;
+        unsupported.extend(runtime.unsupported);
+        results.push(TestResult {
+            name: test.name.to_string(),
+            failures,
+            output,
+            finished,
+        });
+    }
+    Ok((results, unsupported))
+}
+
+/// Prints the result of each test, and then the failed asserts and output of each
+/// failed test. Returns the number of failed tests.
+fn print_summary(results: &[TestResult], frames: u64) -> io::Result<usize> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "running {} tests", results.len())?;
+    for result in results {
+        let status = if !result.failed() {
+            "ok".green()
+        } else {
+            "FAILED".red()
+        };
+        writeln!(stdout, "test {} ... {status}", result.name)?;
+    }
+    let failed: Vec<&TestResult> = results.iter().filter(|result| result.failed()).collect();
+    if !failed.is_empty() {
+        writeln!(stdout, "\nfailures:")?;
+        for result in &failed {
+            writeln!(stdout, "\n---- {} ----", result.name)?;
+            stdout.write_all(&result.output)?;
+            for failure in &result.failures {
+                writeln!(stdout, "{}: {failure}", "assert failed".red().bold())?;
+            }
+            if !result.finished {
+                writeln!(
+                    stdout,
+                    "{}: still running after {frames} frames",
+                    "timed out".red().bold()
+                )?;
+            }
+        }
+    }
+    let status = if failed.is_empty() {
+        "ok".green()
+    } else {
+        "FAILED".red()
+    };
+    writeln!(
+        stdout,
+        "\ntest result: {status}. {} passed; {} failed",
+        results.len() - failed.len(),
+        failed.len()
+    )?;
+    Ok(failed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        path::Path,
+    };
+
+    use zip::{
+        write::SimpleFileOptions,
+        ZipArchive,
+    };
+
+    use super::*;
+    use crate::{
+        codegen::sb3::{
+            Sb3,
+            TEST_HARNESS_NAME,
+        },
+        decompile::project::BlockEntry,
+        frontend::build::compile_for_test,
+    };
+
+    /// Compiles tests/test, with its `test` blocks if `tests` is set.
+    fn compile_test_project(tests: bool) -> Project {
+        let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/test");
+        let mut sb3 = Sb3::new(Cursor::new(vec![]), SimpleFileOptions::default());
+        sb3.tests = tests;
+        let mut archive = ZipArchive::new(compile_for_test(&input, sb3)).unwrap();
+        serde_json::from_reader(archive.by_name("project.json").unwrap()).unwrap()
+    }
+
+    /// Each test runs with a fresh copy of every variable and list, and stops at its
+    /// first failed assert. Tests are collected into a hidden harness sprite.
+    #[test]
+    fn test_run_tests() {
+        let project = compile_test_project(true);
+        let harness = project.targets.last().unwrap();
+        assert_eq!(harness.name, TEST_HARNESS_NAME);
+        assert_eq!(harness.visible, Some(false));
+        let (results, _) = run_tests(&project, &Config::default(), 30).unwrap();
+        let names: Vec<&str> = results.iter().map(|result| result.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "Stage::stage",
+                "main::add",
+                "main::push",
+                "main::fresh state",
+                "main::fails",
+                "main::never finishes"
+            ]
+        );
+        for result in &results[..4] {
+            assert!(!result.failed(), "{}", result.name);
+        }
+        let fails = &results[4];
+        assert_eq!(fails.output, b"before\n");
+        assert!(fails.finished);
+        assert_eq!(fails.failures, ["main.gs:29:5: 1 + 1 should be 3"]);
+        let never_finishes = &results[5];
+        assert!(never_finishes.failures.is_empty());
+        assert!(!never_finishes.finished);
+    }
+
+    /// `test` blocks, and asserts outside of them, are left out of normal builds.
+    #[test]
+    fn test_tests_not_built() {
+        let project = compile_test_project(false);
+        let (results, _) = run_tests(&project, &Config::default(), 30).unwrap();
+        assert!(results.is_empty());
+        assert!(!project
+            .targets
+            .iter()
+            .any(|target| target.name == TEST_HARNESS_NAME));
+        let blocks = project
+            .targets
+            .iter()
+            .flat_map(|target| target.blocks.values());
+        assert!(!blocks.into_iter().any(|block| {
+            matches!(block, BlockEntry::Block(block) if block.opcode == "control_if")
+        }));
+    }
+}
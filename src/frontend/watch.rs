@@ -0,0 +1,104 @@
+use std::{
+    env,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::mpsc,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+use colored::Colorize;
+use fxhash::FxHashSet;
+use notify::{
+    EventKind,
+    RecursiveMode,
+    Watcher,
+};
+
+use super::build::{
+    self,
+    output_path,
+    partial_output_path,
+};
+use crate::config::Compression;
+
+/// Changes that happen within this duration of each other trigger a single rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Builds the project, and rebuilds it whenever a file in the project directory, or a
+/// file outside it that the project includes or reads, changes. Only returns if
+/// watching fails.
+pub fn watch(
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+    reproducible: bool,
+    compression: Option<Compression>,
+    compression_level: Option<i64>,
+    sizes: bool,
+) -> anyhow::Result<()> {
+    let input = input.unwrap_or_else(|| env::current_dir().unwrap());
+    let output = output_path(&input, output)?;
+    let canonical_input = input.canonicalize()?;
+    let ignored = [
+        output.file_name().unwrap_or_default().to_owned(),
+        partial_output_path(&output)
+            .file_name()
+            .unwrap_or_default()
+            .to_owned(),
+    ];
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&canonical_input, RecursiveMode::Recursive)?;
+    let mut watched_dirs: FxHashSet<PathBuf> = Default::default();
+    loop {
+        let begin = Instant::now();
+        let mut dependencies = vec![];
+        let result = build::build(
+            Some(input.clone()),
+            Some(output.clone()),
+            reproducible,
+            compression,
+            compression_level,
+            sizes,
+            &mut dependencies,
+        );
+        super::build_exit_code(result, false);
+        eprintln!("{} in {:?}", "Finished".green().bold(), begin.elapsed());
+        let dependencies: FxHashSet<PathBuf> = dependencies
+            .iter()
+            .filter_map(|path| path.canonicalize().ok())
+            .filter(|path| !path.starts_with(&canonical_input))
+            .collect();
+        // Files outside the project directory are watched through their parent
+        // directory, because editors often save a file by replacing it.
+        for dir in dependencies.iter().filter_map(|path| path.parent()) {
+            if watched_dirs.insert(dir.to_owned()) {
+                watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            }
+        }
+        eprintln!("{} for changes...", "Watching".cyan().bold());
+        let is_relevant = |path: &Path| {
+            if path.starts_with(&canonical_input) {
+                path.file_name()
+                    .is_none_or(|file_name| !ignored.iter().any(|it| it == file_name))
+            } else {
+                dependencies.contains(path)
+            }
+        };
+        loop {
+            let event = rx.recv()??;
+            // Reading a file is reported as an access, including when the build reads it.
+            if !matches!(event.kind, EventKind::Access(_))
+                && event.paths.iter().any(|path| is_relevant(path))
+            {
+                break;
+            }
+        }
+        // Wait for the changes to settle, editors often write a file in several steps.
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+    }
+}
@@ -0,0 +1,577 @@
+mod expr;
+mod stmt;
+mod target;
+mod thread;
+mod value;
+
+use std::io::{
+    self,
+    Write,
+};
+
+use fxhash::FxHashMap;
+use serde_json::Value as Json;
+use target::{
+    List,
+    Sprite,
+    Target,
+    Variable,
+};
+use thread::{
+    Frame,
+    Loop,
+    Thread,
+};
+use value::Value;
+
+use crate::{
+    codegen::sb3::{
+        TEST_HARNESS_BROADCAST_PREFIX,
+        TEST_HARNESS_NAME,
+    },
+    config::Config,
+    decompile::project::{
+        Block,
+        Project,
+    },
+    misc::IndexSet,
+};
+
+/// How many steps a thread running without screen refresh may take in a frame before it
+/// yields anyway. Scratch yields after 500ms instead, a step count keeps runs
+/// reproducible.
+const WARP_STEPS: usize = 100_000;
+
+/// Runs a compiled project without rendering it. Motion and looks blocks only change the
+/// state of targets, and there is no input from a user, so key presses, clicks and
+/// touching are never detected.
+pub struct Runtime<'a, W> {
+    sprites: Vec<Sprite<'a>>,
+    /// Every target and clone, deleted clones are `None`.
+    targets: Vec<Option<Target>>,
+    stage: usize,
+    /// Sprites and clones, from the back layer to the front.
+    layers: Vec<usize>,
+    threads: Vec<Thread<'a>>,
+    next_thread_id: u64,
+    /// Index of the thread being stepped.
+    running: Option<usize>,
+    /// Last value of the predicate of each edge-activated hat, keyed by target and hat.
+    edges: FxHashMap<(usize, &'a str), bool>,
+    /// Seconds since the project started, time passes by one frame at a time.
+    time: f64,
+    timer_start: f64,
+    frame_rate: f64,
+    max_clones: f64,
+    stage_width: f64,
+    stage_height: f64,
+    rng: u64,
+    out: W,
+    /// Messages of `error` statements, which are collected instead of printed while a
+    /// test is running.
+    errors: Option<Vec<String>>,
+    /// Opcodes of blocks which are not supported, and did nothing.
+    pub unsupported: IndexSet<String>,
+}
+
+/// A `test` block, started by a script of the test harness sprite.
+pub struct Test<'a> {
+    /// Name of the test, qualified by the name of the target it is declared in.
+    pub name: &'a str,
+    target: usize,
+    hat: &'a str,
+}
+
+/// The target that an expression is evaluated for, and the thread evaluating it, if any.
+#[derive(Debug, Clone, Copy)]
+struct Context {
+    target: usize,
+    thread: Option<usize>,
+}
+
+enum Flow {
+    Continue,
+    Yield,
+}
+
+impl<'a, W: Write> Runtime<'a, W> {
+    /// Creates a runtime for a project, `log` statements are written to `out`.
+    pub fn new(project: &'a Project, config: &Config, out: W) -> Self {
+        let sprites = project.targets.iter().map(Sprite::new).collect();
+        let targets = project
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(index, target)| Some(Target::new(index, target)))
+            .collect();
+        let stage = project
+            .targets
+            .iter()
+            .position(|target| target.is_stage)
+            .unwrap_or_default();
+        let mut layers: Vec<usize> = (0..project.targets.len())
+            .filter(|&index| index != stage)
+            .collect();
+        layers.sort_by_key(|&index| project.targets[index].layer_order);
+        Self {
+            sprites,
+            targets,
+            stage,
+            layers,
+            threads: vec![],
+            next_thread_id: 0,
+            running: None,
+            edges: FxHashMap::default(),
+            time: 0.0,
+            timer_start: 0.0,
+            frame_rate: config.frame_rate.unwrap_or(30) as f64,
+            max_clones: config.max_clones.unwrap_or(300.0),
+            stage_width: config.stage_width.unwrap_or(480) as f64,
+            stage_height: config.stage_height.unwrap_or(360) as f64,
+            rng: 0x2545_f491_4f6c_dd1d,
+            out,
+            errors: None,
+            unsupported: IndexSet::default(),
+        }
+    }
+
+    /// Clicks the green flag, and runs the project until every script is done, or for at
+    /// most `frames` frames.
+    pub fn run(&mut self, frames: Option<u64>) -> io::Result<()> {
+        self.start_hats("event_whenflagclicked", None, None);
+        let mut frame = 0;
+        while !self.threads.is_empty() && frames.is_none_or(|frames| frame < frames) {
+            self.tick()?;
+            frame += 1;
+        }
+        self.out.flush()
+    }
+
+    /// Returns every test collected into the test harness sprite, in order of the targets
+    /// they are declared in.
+    pub fn tests(&self) -> Vec<Test<'a>> {
+        let Some((target, harness)) = self
+            .sprites
+            .iter()
+            .enumerate()
+            .find(|(_, sprite)| !sprite.is_stage && sprite.name == TEST_HARNESS_NAME)
+        else {
+            return vec![];
+        };
+        harness
+            .hats
+            .iter()
+            .filter_map(|&(hat, block)| {
+                if block.opcode != "event_whenbroadcastreceived" {
+                    return None;
+                }
+                let name = block.field()?.strip_prefix(TEST_HARNESS_BROADCAST_PREFIX)?;
+                Some(Test { name, target, hat })
+            })
+            .collect()
+    }
+
+    /// Runs a test until its script in the test harness is done, or for at most `frames`
+    /// frames. The green flag is not clicked, so only scripts started by the test run. Returns the messages
+    /// of the failed asserts, a failed assert stops the script it is in, and whether the
+    /// script was done within `frames` frames.
+    pub fn run_test(&mut self, test: &Test<'a>, frames: u64) -> io::Result<(Vec<String>, bool)> {
+        self.errors = Some(vec![]);
+        let hat = self.block(test.target, test.hat).unwrap();
+        let thread_id = self.start_thread(test.target, test.hat, hat, false);
+        let mut frame = 0;
+        let running = |runtime: &Self| {
+            runtime
+                .threads
+                .iter()
+                .any(|thread| Some(thread.id) == thread_id)
+        };
+        while running(self) && frame < frames {
+            self.tick()?;
+            frame += 1;
+        }
+        self.out.flush()?;
+        Ok((self.errors.take().unwrap_or_default(), !running(self)))
+    }
+
+    /// Runs one frame, every thread runs until it yields.
+    fn tick(&mut self) -> io::Result<()> {
+        self.start_edge_hats();
+        // Threads started during the frame run in the same frame.
+        let mut index = 0;
+        while index < self.threads.len() {
+            self.running = Some(index);
+            let result = self.step(index);
+            self.running = None;
+            result?;
+            index += 1;
+        }
+        self.threads.retain(|thread| !thread.done);
+        self.time += 1.0 / self.frame_rate;
+        Ok(())
+    }
+
+    fn step(&mut self, index: usize) -> io::Result<()> {
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            let thread = &mut self.threads[index];
+            if thread.done {
+                return Ok(());
+            }
+            if thread.restarted {
+                thread.restarted = false;
+                return Ok(());
+            }
+            let warp = thread.warp() && steps < WARP_STEPS;
+            let context = Context {
+                target: thread.target,
+                thread: Some(index),
+            };
+            // The frame is pushed back if it has not finished.
+            let Some(frame) = thread.stack.pop() else {
+                thread.done = true;
+                return Ok(());
+            };
+            match frame {
+                Frame::Stack(None) | Frame::Call { .. } => {}
+                Frame::Stack(Some(id)) => {
+                    let Some(block) = self.block(context.target, id) else {
+                        continue;
+                    };
+                    self.push(index, Frame::Stack(block.next.as_deref()));
+                    if let Flow::Yield = self.stmt(context, block)? {
+                        return Ok(());
+                    }
+                }
+                Frame::Loop { block, kind, ran } => {
+                    // Loops yield after each iteration, unless the thread is running
+                    // without screen refresh.
+                    if ran && !warp {
+                        let ran = false;
+                        self.push(index, Frame::Loop { block, kind, ran });
+                        return Ok(());
+                    }
+                    let (kind, enter) = match kind {
+                        Loop::Repeat(times) => (Loop::Repeat(times - 1.0), times >= 1.0),
+                        Loop::Forever => (kind, true),
+                        Loop::Until => (kind, !self.condition(context, block, "CONDITION")),
+                        Loop::While => (kind, self.condition(context, block, "CONDITION")),
+                    };
+                    if enter {
+                        let ran = true;
+                        self.push(index, Frame::Loop { block, kind, ran });
+                        self.push(index, Frame::Stack(substack(block, "SUBSTACK")));
+                    }
+                }
+                Frame::Wait {
+                    until,
+                    clear_bubble,
+                } => {
+                    if self.time < until {
+                        self.push(
+                            index,
+                            Frame::Wait {
+                                until,
+                                clear_bubble,
+                            },
+                        );
+                        return Ok(());
+                    }
+                    if clear_bubble {
+                        self.target_mut(context.target).bubble = None;
+                    }
+                }
+                Frame::WaitUntil(block) => {
+                    if !self.condition(context, block, "CONDITION") {
+                        self.push(index, Frame::WaitUntil(block));
+                        return Ok(());
+                    }
+                }
+                Frame::Glide {
+                    from,
+                    to,
+                    start,
+                    duration,
+                } => {
+                    let elapsed = self.time - start;
+                    let target = self.target_mut(context.target);
+                    if elapsed < duration {
+                        let t = elapsed / duration;
+                        target.x = from.0 + (to.0 - from.0) * t;
+                        target.y = from.1 + (to.1 - from.1) * t;
+                        self.push(
+                            index,
+                            Frame::Glide {
+                                from,
+                                to,
+                                start,
+                                duration,
+                            },
+                        );
+                        return Ok(());
+                    }
+                    (target.x, target.y) = to;
+                }
+                Frame::Broadcast(ids) => {
+                    let waiting = self
+                        .threads
+                        .iter()
+                        .any(|thread| !thread.done && ids.contains(&thread.id));
+                    if waiting {
+                        self.push(index, Frame::Broadcast(ids));
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, index: usize, frame: Frame<'a>) {
+        self.threads[index].stack.push(frame);
+    }
+
+    /// Targets in the order their scripts are started, from the front layer to the
+    /// back, and then the stage.
+    fn execution_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = self.layers.iter().rev().copied().collect();
+        order.push(self.stage);
+        order
+    }
+
+    /// Starts the scripts under hat blocks with `opcode`, whose field matches `field`
+    /// without case, in every target or only in `only`. Returns the ids of the started
+    /// threads.
+    fn start_hats(&mut self, opcode: &str, field: Option<&str>, only: Option<usize>) -> Vec<u64> {
+        let restart = matches!(
+            opcode,
+            "event_whenflagclicked" | "event_whenbroadcastreceived"
+        );
+        let targets = match only {
+            Some(target) => vec![target],
+            None => self.execution_order(),
+        };
+        let mut started = vec![];
+        for target in targets {
+            let hats: Vec<(&'a str, &'a Block)> = self
+                .sprite(target)
+                .hats
+                .iter()
+                .filter(|(_, hat)| {
+                    hat.opcode == opcode
+                        && field.is_none_or(|field| {
+                            hat.field()
+                                .is_some_and(|hat_field| hat_field.eq_ignore_ascii_case(field))
+                        })
+                })
+                .copied()
+                .collect();
+            for (id, hat) in hats {
+                started.extend(self.start_thread(target, id, hat, restart));
+            }
+        }
+        started
+    }
+
+    /// Starts a script, if it is already running it is restarted if `restart` is set,
+    /// otherwise it is left running and no thread is started.
+    fn start_thread(
+        &mut self,
+        target: usize,
+        id: &'a str,
+        hat: &'a Block,
+        restart: bool,
+    ) -> Option<u64> {
+        let first = hat.next.as_deref();
+        let running = self.running;
+        let existing = self
+            .threads
+            .iter_mut()
+            .enumerate()
+            .find(|(_, thread)| !thread.done && thread.target == target && thread.hat == id);
+        if let Some((index, thread)) = existing {
+            if !restart {
+                return None;
+            }
+            thread.stack = vec![Frame::Stack(first)];
+            thread.restarted = running == Some(index);
+            return Some(thread.id);
+        }
+        let thread_id = self.next_thread_id;
+        self.next_thread_id += 1;
+        self.threads.push(Thread::new(thread_id, target, id, first));
+        Some(thread_id)
+    }
+
+    /// Starts the scripts under edge-activated hats whose predicate became true since the
+    /// last frame.
+    fn start_edge_hats(&mut self) {
+        for target in self.execution_order() {
+            let hats: Vec<(&'a str, &'a Block)> = self
+                .sprite(target)
+                .hats
+                .iter()
+                .filter(|(_, hat)| hat.opcode == "event_whengreaterthan")
+                .copied()
+                .collect();
+            for (id, hat) in hats {
+                let value = match hat.field_named("WHENGREATERTHANMENU") {
+                    Some(menu) if menu.eq_ignore_ascii_case("timer") => self.timer(),
+                    _ => -1.0,
+                };
+                let context = Context {
+                    target,
+                    thread: None,
+                };
+                let triggered = value > self.input(context, hat, "VALUE").to_number();
+                let was_triggered = self.edges.insert((target, id), triggered);
+                if triggered && !was_triggered.unwrap_or_default() {
+                    self.start_thread(target, id, hat, false);
+                }
+            }
+        }
+    }
+
+    /// Clones a target, the clone is placed behind it. Returns the index of the clone,
+    /// or `None` if there are too many clones.
+    fn clone_target(&mut self, target: usize) -> Option<usize> {
+        let clones = self
+            .targets
+            .iter()
+            .flatten()
+            .filter(|target| target.is_clone);
+        if clones.count() as f64 >= self.max_clones || target == self.stage {
+            return None;
+        }
+        let layer = self.layers.iter().position(|&layer| layer == target)?;
+        let mut clone = self.target(target).clone();
+        clone.is_clone = true;
+        clone.bubble = None;
+        let index = self.targets.len();
+        self.targets.push(Some(clone));
+        self.layers.insert(layer, index);
+        self.start_hats("control_start_as_clone", None, Some(index));
+        Some(index)
+    }
+
+    fn delete_clone(&mut self, target: usize) {
+        if !self.target(target).is_clone {
+            return;
+        }
+        for thread in &mut self.threads {
+            if thread.target == target {
+                thread.done = true;
+            }
+        }
+        self.targets[target] = None;
+        self.layers.retain(|&layer| layer != target);
+    }
+
+    /// Stops every thread and deletes every clone.
+    fn stop_all(&mut self) {
+        for thread in &mut self.threads {
+            thread.done = true;
+        }
+        let clones: Vec<usize> = self
+            .targets
+            .iter()
+            .enumerate()
+            .filter(|(_, target)| target.as_ref().is_some_and(|target| target.is_clone))
+            .map(|(index, _)| index)
+            .collect();
+        for clone in clones {
+            self.delete_clone(clone);
+        }
+    }
+
+    fn target(&self, index: usize) -> &Target {
+        self.targets[index].as_ref().expect("target was deleted")
+    }
+
+    fn target_mut(&mut self, index: usize) -> &mut Target {
+        self.targets[index].as_mut().expect("target was deleted")
+    }
+
+    fn sprite(&self, target: usize) -> &Sprite<'a> {
+        &self.sprites[self.target(target).sprite]
+    }
+
+    fn block(&self, target: usize, id: &str) -> Option<&'a Block> {
+        self.sprite(target).blocks.get(id).copied()
+    }
+
+    /// Returns the sprite named `name`, not one of its clones.
+    fn find_sprite(&self, name: &str) -> Option<usize> {
+        self.targets.iter().position(|target| {
+            target.as_ref().is_some_and(|target| {
+                let sprite = &self.sprites[target.sprite];
+                !target.is_clone && !sprite.is_stage && sprite.name == name
+            })
+        })
+    }
+
+    fn timer(&self) -> f64 {
+        self.time - self.timer_start
+    }
+
+    /// Returns a random number from 0 to 1. The generator always starts with the same
+    /// seed, so that runs of a project can be compared.
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns the variable named by a field of a block. Variables which do not exist
+    /// are created in the target, as in Scratch.
+    fn var(&mut self, target: usize, id: &str, name: &str) -> &mut Variable {
+        let (owner, id) = [target, self.stage]
+            .into_iter()
+            .find_map(|owner| Some((owner, self.target(owner).find_var(id, name)?)))
+            .unwrap_or_else(|| {
+                let var = Variable {
+                    name: name.into(),
+                    value: Value::Number(0.0),
+                };
+                self.target_mut(target).vars.insert(id.into(), var);
+                (target, id.into())
+            });
+        &mut self.target_mut(owner).vars[&id]
+    }
+
+    fn list(&mut self, target: usize, id: &str, name: &str) -> &mut List {
+        let (owner, id) = [target, self.stage]
+            .into_iter()
+            .find_map(|owner| Some((owner, self.target(owner).find_list(id, name)?)))
+            .unwrap_or_else(|| {
+                let list = List {
+                    name: name.into(),
+                    items: vec![],
+                };
+                self.target_mut(target).lists.insert(id.into(), list);
+                (target, id.into())
+            });
+        &mut self.target_mut(owner).lists[&id]
+    }
+}
+
+/// Returns the id of the first block of a substack input.
+fn substack<'a>(block: &'a Block, name: &str) -> Option<&'a str> {
+    block.inputs.get(name)?.get(1)?.as_str()
+}
+
+/// Returns the name and id of a variable, list or broadcast field. If the id is missing,
+/// the name is used as the id.
+fn field_name_id<'a>(block: &'a Block, name: &str) -> (&'a str, &'a str) {
+    let field = block.fields.get(name);
+    let name = field
+        .and_then(|field| field.get(0))
+        .and_then(Json::as_str)
+        .unwrap_or_default();
+    let id = field
+        .and_then(|field| field.get(1))
+        .and_then(Json::as_str)
+        .unwrap_or(name);
+    (name, id)
+}
@@ -0,0 +1,381 @@
+use std::{
+    f64::consts::PI,
+    io::Write,
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use serde_json::Value as Json;
+
+use super::{
+    field_name_id,
+    value::Value,
+    Context,
+    Runtime,
+};
+use crate::decompile::project::Block;
+
+/// Lists can not grow beyond this many items, as in Scratch.
+pub const LIST_ITEM_LIMIT: usize = 200000;
+
+pub enum ListIndex {
+    All,
+    /// A valid index, starting from 0.
+    Item(usize),
+    Invalid,
+}
+
+impl<'a, W: Write> Runtime<'a, W> {
+    /// Returns the value given to the input `name` of a block, or an empty string if the
+    /// input is empty.
+    pub(super) fn input(&mut self, context: Context, block: &'a Block, name: &str) -> Value {
+        let Some(input) = block.inputs.get(name) else {
+            return Value::default();
+        };
+        // The value of an input is either a block, or a primitive such as a literal or a
+        // variable. If it is null, the input's shadow is used instead.
+        let value = match input.get(1) {
+            Some(Json::Null) => input.get(2),
+            value => value,
+        };
+        match value {
+            Some(Json::String(id)) => self.reporter(context, id),
+            Some(Json::Array(primitive)) => self.primitive(context, primitive),
+            _ => Value::default(),
+        }
+    }
+
+    pub(super) fn number(&mut self, context: Context, block: &'a Block, name: &str) -> f64 {
+        self.input(context, block, name).to_number()
+    }
+
+    pub(super) fn string(&mut self, context: Context, block: &'a Block, name: &str) -> String {
+        self.input(context, block, name).to_string()
+    }
+
+    /// Returns the condition given to a boolean input, an empty input is false.
+    pub(super) fn condition(&mut self, context: Context, block: &'a Block, name: &str) -> bool {
+        self.input(context, block, name).to_bool()
+    }
+
+    fn primitive(&mut self, context: Context, primitive: &[Json]) -> Value {
+        let value = primitive.get(1).unwrap_or(&Json::Null);
+        let kind = primitive.first().and_then(Json::as_u64);
+        if !matches!(kind, Some(12 | 13)) {
+            return value.into();
+        }
+        let name = value.as_str().unwrap_or_default();
+        let id = primitive.get(2).and_then(Json::as_str).unwrap_or(name);
+        if kind == Some(12) {
+            self.var(context.target, id, name).value.clone()
+        } else {
+            list_contents(&self.list(context.target, id, name).items)
+        }
+    }
+
+    fn reporter(&mut self, context: Context, id: &str) -> Value {
+        let Some(block) = self.block(context.target, id) else {
+            return Value::default();
+        };
+        // Menus are shadow blocks with a single field.
+        if block.shadow && block.inputs.is_empty() && !block.opcode.starts_with("argument_") {
+            return block.field().unwrap_or_default().into();
+        }
+        let number = |number: f64| Value::Number(number);
+        match block.opcode.as_str() {
+            "argument_reporter_string_number" | "argument_reporter_boolean" => {
+                let name = block.field_named("VALUE").unwrap_or_default();
+                let arg = context
+                    .thread
+                    .and_then(|thread| self.threads[thread].arg(name))
+                    .cloned();
+                // Arguments used outside of their custom block report a default.
+                arg.unwrap_or(if block.opcode == "argument_reporter_boolean" {
+                    Value::Bool(false)
+                } else {
+                    Value::Number(0.0)
+                })
+            }
+            "motion_xposition" => number(limit_precision(self.target(context.target).x)),
+            "motion_yposition" => number(limit_precision(self.target(context.target).y)),
+            "motion_direction" => number(self.target(context.target).direction),
+            "looks_size" => number(self.target(context.target).size.round()),
+            "looks_costumenumbername" => self.costume_number_name(context.target, block),
+            "looks_backdropnumbername" => self.costume_number_name(self.stage, block),
+            "sound_volume" => number(self.target(context.target).volume),
+            "sensing_touchingobject" | "sensing_touchingcolor" | "sensing_coloristouchingcolor" => {
+                Value::Bool(false)
+            }
+            "sensing_keypressed" | "sensing_mousedown" => Value::Bool(false),
+            "sensing_mousex" | "sensing_mousey" => number(0.0),
+            "sensing_loudness" => number(-1.0),
+            "sensing_answer" | "sensing_username" => Value::default(),
+            "sensing_timer" => number(self.timer()),
+            "sensing_distanceto" => {
+                let object = self.string(context, block, "DISTANCETOMENU");
+                number(self.distance_to(context.target, &object))
+            }
+            "sensing_current" => number(current(block.field().unwrap_or_default())),
+            "sensing_dayssince2000" => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH);
+                let days = now.unwrap_or_default().as_secs_f64() / 86400.0;
+                number(days - 10957.0)
+            }
+            "operator_add" => number(self.bin_op(context, block, |lhs, rhs| lhs + rhs)),
+            "operator_subtract" => number(self.bin_op(context, block, |lhs, rhs| lhs - rhs)),
+            "operator_multiply" => number(self.bin_op(context, block, |lhs, rhs| lhs * rhs)),
+            "operator_divide" => number(self.bin_op(context, block, |lhs, rhs| lhs / rhs)),
+            "operator_mod" => number(self.bin_op(context, block, |lhs, rhs| {
+                // The result has the sign of the divisor.
+                let result = lhs % rhs;
+                if result / rhs < 0.0 {
+                    result + rhs
+                } else {
+                    result
+                }
+            })),
+            "operator_lt" | "operator_gt" | "operator_equals" => {
+                let lhs = self.input(context, block, "OPERAND1");
+                let rhs = self.input(context, block, "OPERAND2");
+                let ordering = lhs.compare(&rhs);
+                Value::Bool(match block.opcode.as_str() {
+                    "operator_lt" => ordering.is_lt(),
+                    "operator_gt" => ordering.is_gt(),
+                    _ => ordering.is_eq(),
+                })
+            }
+            "operator_and" => Value::Bool(
+                self.condition(context, block, "OPERAND1")
+                    && self.condition(context, block, "OPERAND2"),
+            ),
+            "operator_or" => Value::Bool(
+                self.condition(context, block, "OPERAND1")
+                    || self.condition(context, block, "OPERAND2"),
+            ),
+            "operator_not" => Value::Bool(!self.condition(context, block, "OPERAND")),
+            "operator_join" => {
+                let lhs = self.string(context, block, "STRING1");
+                let rhs = self.string(context, block, "STRING2");
+                (lhs + &rhs).into()
+            }
+            "operator_letter_of" => {
+                let index = self.number(context, block, "LETTER") - 1.0;
+                let string = self.string(context, block, "STRING");
+                if index < 0.0 {
+                    return Value::default();
+                }
+                let letter = string.chars().nth(index as usize);
+                letter.map(String::from).unwrap_or_default().into()
+            }
+            "operator_length" => {
+                number(self.string(context, block, "STRING").chars().count() as f64)
+            }
+            "operator_contains" => {
+                let string = self.string(context, block, "STRING1").to_lowercase();
+                let substring = self.string(context, block, "STRING2").to_lowercase();
+                Value::Bool(string.contains(&substring))
+            }
+            "operator_random" => {
+                let from = self.input(context, block, "FROM");
+                let to = self.input(context, block, "TO");
+                let low = from.to_number().min(to.to_number());
+                let high = from.to_number().max(to.to_number());
+                if low == high {
+                    return number(low);
+                }
+                // Random integers are only picked if both bounds are written as integers.
+                if from.is_int() && to.is_int() {
+                    number(low + (self.random() * (high + 1.0 - low)).floor())
+                } else {
+                    number(low + self.random() * (high - low))
+                }
+            }
+            "operator_round" => number((self.number(context, block, "NUM") + 0.5).floor()),
+            "operator_mathop" => {
+                let operand = self.number(context, block, "NUM");
+                number(mathop(block.field().unwrap_or_default(), operand))
+            }
+            "data_variable" => {
+                let (name, id) = field_name_id(block, "VARIABLE");
+                self.var(context.target, id, name).value.clone()
+            }
+            "data_listcontents" => {
+                let (name, id) = field_name_id(block, "LIST");
+                list_contents(&self.list(context.target, id, name).items)
+            }
+            "data_itemoflist" => {
+                let index = self.input(context, block, "INDEX");
+                let (name, id) = field_name_id(block, "LIST");
+                let len = self.list(context.target, id, name).items.len();
+                match self.list_index(&index, len, false) {
+                    ListIndex::Item(index) => {
+                        self.list(context.target, id, name).items[index].clone()
+                    }
+                    ListIndex::All | ListIndex::Invalid => Value::default(),
+                }
+            }
+            "data_itemnumoflist" => {
+                let item = self.input(context, block, "ITEM");
+                let (name, id) = field_name_id(block, "LIST");
+                let items = &self.list(context.target, id, name).items;
+                let index = items.iter().position(|other| other.equals(&item));
+                number(index.map_or(0.0, |index| index as f64 + 1.0))
+            }
+            "data_lengthoflist" => {
+                let (name, id) = field_name_id(block, "LIST");
+                number(self.list(context.target, id, name).items.len() as f64)
+            }
+            "data_listcontainsitem" => {
+                let item = self.input(context, block, "ITEM");
+                let (name, id) = field_name_id(block, "LIST");
+                let items = &self.list(context.target, id, name).items;
+                Value::Bool(items.iter().any(|other| other.equals(&item)))
+            }
+            opcode => {
+                self.unsupported.insert(opcode.to_string());
+                Value::default()
+            }
+        }
+    }
+
+    fn bin_op(&mut self, context: Context, block: &'a Block, op: fn(f64, f64) -> f64) -> f64 {
+        let lhs = self.number(context, block, "NUM1");
+        let rhs = self.number(context, block, "NUM2");
+        op(lhs, rhs)
+    }
+
+    fn costume_number_name(&self, target: usize, block: &Block) -> Value {
+        let target = self.target(target);
+        let costumes = &self.sprites[target.sprite].costumes;
+        match block.field() {
+            Some("name") => costumes
+                .get(target.costume)
+                .copied()
+                .unwrap_or_default()
+                .into(),
+            _ => Value::Number(target.costume as f64 + 1.0),
+        }
+    }
+
+    /// Returns the position of a sprite or the mouse, given by the value of a menu.
+    pub(super) fn position_of(&self, object: &str) -> Option<(f64, f64)> {
+        if object == "_mouse_" {
+            return Some((0.0, 0.0));
+        }
+        let target = self.target(self.find_sprite(object)?);
+        Some((target.x, target.y))
+    }
+
+    fn distance_to(&self, target: usize, object: &str) -> f64 {
+        let target = self.target(target);
+        if self.sprites[target.sprite].is_stage {
+            return 10000.0;
+        }
+        match self.position_of(object) {
+            Some((x, y)) => (target.x - x).hypot(target.y - y),
+            None => 10000.0,
+        }
+    }
+
+    /// Converts a list index to an index into a list of `len` items, `all` is only
+    /// accepted if `accept_all` is set.
+    pub(super) fn list_index(&mut self, index: &Value, len: usize, accept_all: bool) -> ListIndex {
+        if let Value::String(string) = index {
+            match &**string {
+                "all" if accept_all => return ListIndex::All,
+                "all" => return ListIndex::Invalid,
+                "last" if len > 0 => return ListIndex::Item(len - 1),
+                "random" | "any" if len > 0 => {
+                    return ListIndex::Item((self.random() * len as f64) as usize)
+                }
+                "last" | "random" | "any" => return ListIndex::Invalid,
+                _ => {}
+            }
+        }
+        let index = index.to_number().floor();
+        if index < 1.0 || index > len as f64 {
+            return ListIndex::Invalid;
+        }
+        ListIndex::Item(index as usize - 1)
+    }
+}
+
+/// Items of a list are joined without a separator if they are all single letters,
+/// otherwise with spaces.
+pub fn list_contents(items: &[Value]) -> Value {
+    let single_letters = items
+        .iter()
+        .all(|item| matches!(item, Value::String(string) if string.chars().count() == 1));
+    let separator = if single_letters { "" } else { " " };
+    let strings: Vec<String> = items.iter().map(Value::to_string).collect();
+    strings.join(separator).into()
+}
+
+/// Rounds coordinates which are very close to an integer, to hide floating point errors.
+fn limit_precision(coordinate: f64) -> f64 {
+    let rounded = coordinate.round();
+    if (coordinate - rounded).abs() < 1e-9 {
+        rounded
+    } else {
+        coordinate
+    }
+}
+
+/// Trigonometric functions use degrees, and are rounded to 10 decimal places.
+fn mathop(operator: &str, operand: f64) -> f64 {
+    let round = |number: f64| (number * 1e10).round() / 1e10;
+    match operator.to_lowercase().as_str() {
+        "abs" => operand.abs(),
+        "floor" => operand.floor(),
+        "ceiling" => operand.ceil(),
+        "sqrt" => operand.sqrt(),
+        "sin" => round((operand * PI / 180.0).sin()),
+        "cos" => round((operand * PI / 180.0).cos()),
+        "tan" => match operand % 360.0 {
+            -270.0 | 90.0 => f64::INFINITY,
+            -90.0 | 270.0 => f64::NEG_INFINITY,
+            _ => round((operand * PI / 180.0).tan()),
+        },
+        "asin" => operand.asin() * 180.0 / PI,
+        "acos" => operand.acos() * 180.0 / PI,
+        "atan" => operand.atan() * 180.0 / PI,
+        "ln" => operand.ln(),
+        "log" => operand.log10(),
+        "e ^" => operand.exp(),
+        "10 ^" => 10f64.powf(operand),
+        _ => 0.0,
+    }
+}
+
+/// Returns a part of the current date and time, in UTC.
+fn current(menu: &str) -> f64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+    let seconds = now.unwrap_or_default().as_secs() as i64;
+    let days = seconds.div_euclid(86400);
+    let seconds = seconds.rem_euclid(86400);
+    // Converts days since 1970-01-01 to a date in the proleptic Gregorian calendar.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let date = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    let value = match menu.to_uppercase().as_str() {
+        "YEAR" => year,
+        "MONTH" => month,
+        "DATE" => date,
+        // 1970-01-01 was a Thursday, Sunday is 1.
+        "DAYOFWEEK" => (days + 4).rem_euclid(7) + 1,
+        "HOUR" => seconds / 3600,
+        "MINUTE" => seconds % 3600 / 60,
+        "SECOND" => seconds % 60,
+        _ => 0,
+    };
+    value as f64
+}
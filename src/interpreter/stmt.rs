@@ -0,0 +1,628 @@
+use std::{
+    f64::consts::PI,
+    io::{
+        self,
+        Write,
+    },
+};
+
+use colored::Colorize;
+
+use super::{
+    expr::{
+        ListIndex,
+        LIST_ITEM_LIMIT,
+    },
+    field_name_id,
+    substack,
+    thread::{
+        Frame,
+        Loop,
+    },
+    value::Value,
+    Context,
+    Flow,
+    Runtime,
+};
+use crate::decompile::project::Block;
+
+impl<'a, W: Write> Runtime<'a, W> {
+    /// Runs a statement, returns whether the thread should yield.
+    pub(super) fn stmt(&mut self, context: Context, block: &'a Block) -> io::Result<Flow> {
+        let Context { target, thread } = context;
+        let thread = thread.unwrap();
+        let is_stage = self.sprite(target).is_stage;
+        match block.opcode.as_str() {
+            "control_repeat" => {
+                let times = (self.number(context, block, "TIMES") + 0.5).floor();
+                let kind = Loop::Repeat(times);
+                self.push(
+                    thread,
+                    Frame::Loop {
+                        block,
+                        kind,
+                        ran: false,
+                    },
+                );
+            }
+            "control_forever" | "control_repeat_until" | "control_while" => {
+                let kind = match block.opcode.as_str() {
+                    "control_forever" => Loop::Forever,
+                    "control_repeat_until" => Loop::Until,
+                    _ => Loop::While,
+                };
+                self.push(
+                    thread,
+                    Frame::Loop {
+                        block,
+                        kind,
+                        ran: false,
+                    },
+                );
+            }
+            "control_if" | "control_if_else" => {
+                let branch = if self.condition(context, block, "CONDITION") {
+                    "SUBSTACK"
+                } else {
+                    "SUBSTACK2"
+                };
+                self.push(thread, Frame::Stack(substack(block, branch)));
+            }
+            "control_wait" => {
+                let until = self.time + self.number(context, block, "DURATION");
+                let clear_bubble = false;
+                self.push(
+                    thread,
+                    Frame::Wait {
+                        until,
+                        clear_bubble,
+                    },
+                );
+                return Ok(Flow::Yield);
+            }
+            "control_wait_until" => self.push(thread, Frame::WaitUntil(block)),
+            "control_stop" => match block.field().unwrap_or_default() {
+                "all" => {
+                    self.stop_all();
+                    return Ok(Flow::Yield);
+                }
+                "this script" => self.threads[thread].stop_this_script(),
+                _ => {
+                    let id = self.threads[thread].id;
+                    for other in &mut self.threads {
+                        if other.target == target && other.id != id {
+                            other.done = true;
+                        }
+                    }
+                }
+            },
+            "control_create_clone_of" => {
+                let option = self.string(context, block, "CLONE_OPTION");
+                let original = if option == "_myself_" {
+                    Some(target)
+                } else {
+                    self.find_sprite(&option)
+                };
+                if let Some(original) = original {
+                    self.clone_target(original);
+                }
+            }
+            "control_delete_this_clone" => {
+                if self.target(target).is_clone {
+                    self.delete_clone(target);
+                    return Ok(Flow::Yield);
+                }
+            }
+            "event_broadcast" => {
+                let name = self.string(context, block, "BROADCAST_INPUT");
+                self.broadcast(&name);
+            }
+            "event_broadcastandwait" => {
+                let name = self.string(context, block, "BROADCAST_INPUT");
+                let started = self.broadcast(&name);
+                if !started.is_empty() {
+                    self.push(thread, Frame::Broadcast(started));
+                    return Ok(Flow::Yield);
+                }
+            }
+            "data_setvariableto" => {
+                let value = self.input(context, block, "VALUE");
+                let (name, id) = field_name_id(block, "VARIABLE");
+                self.var(target, id, name).value = value;
+            }
+            "data_changevariableby" => {
+                let value = self.number(context, block, "VALUE");
+                let (name, id) = field_name_id(block, "VARIABLE");
+                let var = self.var(target, id, name);
+                var.value = Value::Number(var.value.to_number() + value);
+            }
+            "data_addtolist" => {
+                let item = self.input(context, block, "ITEM");
+                let (name, id) = field_name_id(block, "LIST");
+                let items = &mut self.list(target, id, name).items;
+                if items.len() < LIST_ITEM_LIMIT {
+                    items.push(item);
+                }
+            }
+            "data_deleteoflist" => {
+                let index = self.input(context, block, "INDEX");
+                let (name, id) = field_name_id(block, "LIST");
+                let len = self.list(target, id, name).items.len();
+                match self.list_index(&index, len, true) {
+                    ListIndex::All => self.list(target, id, name).items.clear(),
+                    ListIndex::Item(index) => {
+                        self.list(target, id, name).items.remove(index);
+                    }
+                    ListIndex::Invalid => {}
+                }
+            }
+            "data_deletealloflist" => {
+                let (name, id) = field_name_id(block, "LIST");
+                self.list(target, id, name).items.clear();
+            }
+            "data_insertatlist" => {
+                let item = self.input(context, block, "ITEM");
+                let index = self.input(context, block, "INDEX");
+                let (name, id) = field_name_id(block, "LIST");
+                let len = self.list(target, id, name).items.len();
+                if let ListIndex::Item(index) = self.list_index(&index, len + 1, false) {
+                    let items = &mut self.list(target, id, name).items;
+                    items.insert(index, item);
+                    items.truncate(LIST_ITEM_LIMIT);
+                }
+            }
+            "data_replaceitemoflist" => {
+                let index = self.input(context, block, "INDEX");
+                let item = self.input(context, block, "ITEM");
+                let (name, id) = field_name_id(block, "LIST");
+                let len = self.list(target, id, name).items.len();
+                if let ListIndex::Item(index) = self.list_index(&index, len, false) {
+                    self.list(target, id, name).items[index] = item;
+                }
+            }
+            "data_showvariable" | "data_hidevariable" | "data_showlist" | "data_hidelist" => {}
+            "procedures_call" => return self.proc_call(context, block),
+            // Motion blocks do nothing on the stage.
+            opcode if opcode.starts_with("motion_") && is_stage => {}
+            "motion_movesteps" => {
+                let steps = self.number(context, block, "STEPS");
+                let target = self.target_mut(target);
+                let radians = (90.0 - target.direction) * PI / 180.0;
+                target.x += steps * radians.cos();
+                target.y += steps * radians.sin();
+            }
+            "motion_turnright" | "motion_turnleft" => {
+                let mut degrees = self.number(context, block, "DEGREES");
+                if block.opcode == "motion_turnleft" {
+                    degrees = -degrees;
+                }
+                let direction = self.target(target).direction + degrees;
+                self.set_direction(target, direction);
+            }
+            "motion_goto" => {
+                let object = self.string(context, block, "TO");
+                if let Some((x, y)) = self.position_or_random(&object) {
+                    let target = self.target_mut(target);
+                    (target.x, target.y) = (x, y);
+                }
+            }
+            "motion_gotoxy" => {
+                let x = self.number(context, block, "X");
+                let y = self.number(context, block, "Y");
+                let target = self.target_mut(target);
+                (target.x, target.y) = (x, y);
+            }
+            "motion_glideto" | "motion_glidesecstoxy" => {
+                let duration = self.number(context, block, "SECS");
+                let to = if block.opcode == "motion_glideto" {
+                    let object = self.string(context, block, "TO");
+                    self.position_or_random(&object)
+                } else {
+                    let x = self.number(context, block, "X");
+                    let y = self.number(context, block, "Y");
+                    Some((x, y))
+                };
+                let Some(to) = to else {
+                    return Ok(Flow::Continue);
+                };
+                let target = self.target_mut(target);
+                if duration <= 0.0 {
+                    (target.x, target.y) = to;
+                    return Ok(Flow::Continue);
+                }
+                let from = (target.x, target.y);
+                let start = self.time;
+                self.push(
+                    thread,
+                    Frame::Glide {
+                        from,
+                        to,
+                        start,
+                        duration,
+                    },
+                );
+                return Ok(Flow::Yield);
+            }
+            "motion_pointindirection" => {
+                let direction = self.number(context, block, "DIRECTION");
+                self.set_direction(target, direction);
+            }
+            "motion_pointtowards" => {
+                let object = self.string(context, block, "TOWARDS");
+                let direction = if object == "_random_" {
+                    (self.random() * 360.0 + 0.5).floor() - 180.0
+                } else {
+                    let Some((x, y)) = self.position_of(&object) else {
+                        return Ok(Flow::Continue);
+                    };
+                    let target = self.target(target);
+                    90.0 - (y - target.y).atan2(x - target.x) * 180.0 / PI
+                };
+                self.set_direction(target, direction);
+            }
+            "motion_changexby" => self.target_mut(target).x += self.number(context, block, "DX"),
+            "motion_changeyby" => self.target_mut(target).y += self.number(context, block, "DY"),
+            "motion_setx" => self.target_mut(target).x = self.number(context, block, "X"),
+            "motion_sety" => self.target_mut(target).y = self.number(context, block, "Y"),
+            "motion_ifonedgebounce" => self.bounce(target),
+            "motion_setrotationstyle" => {
+                let style = block.field().unwrap_or_default();
+                self.target_mut(target).rotation_style = style.into();
+            }
+            "looks_say" | "looks_think" | "looks_sayforsecs" | "looks_thinkforsecs" => {
+                let message = self.string(context, block, "MESSAGE");
+                let kind = if block.opcode.starts_with("looks_say") {
+                    "say"
+                } else {
+                    "think"
+                };
+                let bubble = (!message.is_empty()).then(|| (kind.into(), message));
+                self.target_mut(target).bubble = bubble;
+                if block.opcode.ends_with("forsecs") {
+                    let until = self.time + self.number(context, block, "SECS");
+                    let clear_bubble = true;
+                    self.push(
+                        thread,
+                        Frame::Wait {
+                            until,
+                            clear_bubble,
+                        },
+                    );
+                    return Ok(Flow::Yield);
+                }
+            }
+            "looks_switchcostumeto" => {
+                let costume = self.input(context, block, "COSTUME");
+                if !is_stage {
+                    self.switch_costume(target, &costume);
+                }
+            }
+            "looks_nextcostume" => {
+                if !is_stage {
+                    let costume = self.target(target).costume;
+                    self.set_costume(target, costume as f64 + 1.0);
+                }
+            }
+            "looks_switchbackdropto" => {
+                let backdrop = self.input(context, block, "BACKDROP");
+                self.switch_costume(self.stage, &backdrop);
+            }
+            "looks_nextbackdrop" => {
+                let backdrop = self.target(self.stage).costume;
+                self.set_costume(self.stage, backdrop as f64 + 1.0);
+            }
+            "looks_changeeffectby" | "looks_seteffectto" => {
+                let effect = block.field().unwrap_or_default().to_lowercase();
+                let (value, change) = if block.opcode == "looks_changeeffectby" {
+                    (self.number(context, block, "CHANGE"), true)
+                } else {
+                    (self.number(context, block, "VALUE"), false)
+                };
+                let effects = &mut self.target_mut(target).effects;
+                let value = if change {
+                    effects.get(effect.as_str()).copied().unwrap_or_default() + value
+                } else {
+                    value
+                };
+                let value = match effect.as_str() {
+                    "ghost" => value.clamp(0.0, 100.0),
+                    "brightness" => value.clamp(-100.0, 100.0),
+                    _ => value,
+                };
+                effects.insert(effect.into(), value);
+            }
+            "looks_cleargraphiceffects" => self.target_mut(target).effects.clear(),
+            "looks_show" | "looks_hide" => {
+                self.target_mut(target).visible = block.opcode == "looks_show";
+            }
+            "looks_gotofrontback" if !is_stage => {
+                self.layers.retain(|&layer| layer != target);
+                if block.field() == Some("back") {
+                    self.layers.insert(0, target);
+                } else {
+                    self.layers.push(target);
+                }
+            }
+            "looks_goforwardbackwardlayers" if !is_stage => {
+                let mut layers = self.number(context, block, "NUM").trunc();
+                if block.field() == Some("backward") {
+                    layers = -layers;
+                }
+                let Some(layer) = self.layers.iter().position(|&layer| layer == target) else {
+                    return Ok(Flow::Continue);
+                };
+                self.layers.remove(layer);
+                let layer = (layer as f64 + layers).clamp(0.0, self.layers.len() as f64);
+                self.layers.insert(layer as usize, target);
+            }
+            "looks_gotofrontback" | "looks_goforwardbackwardlayers" => {}
+            "looks_changesizeby" if !is_stage => {
+                self.target_mut(target).size += self.number(context, block, "CHANGE");
+            }
+            "looks_setsizeto" if !is_stage => {
+                self.target_mut(target).size = self.number(context, block, "SIZE");
+            }
+            "looks_changesizeby" | "looks_setsizeto" => {}
+            "sound_play" | "sound_stopallsounds" => {}
+            "sound_playuntildone" => {
+                let sound = self.input(context, block, "SOUND_MENU");
+                if let Some(duration) = self.sound_duration(target, &sound) {
+                    let until = self.time + duration;
+                    let clear_bubble = false;
+                    self.push(
+                        thread,
+                        Frame::Wait {
+                            until,
+                            clear_bubble,
+                        },
+                    );
+                    return Ok(Flow::Yield);
+                }
+            }
+            "sound_changeeffectby" | "sound_seteffectto" => {
+                let effect = block.field().unwrap_or_default().to_lowercase();
+                let value = self.number(context, block, "VALUE");
+                let effects = &mut self.target_mut(target).sound_effects;
+                let value = if block.opcode == "sound_changeeffectby" {
+                    effects.get(effect.as_str()).copied().unwrap_or_default() + value
+                } else {
+                    value
+                };
+                let value = match effect.as_str() {
+                    "pitch" => value.clamp(-360.0, 360.0),
+                    _ => value.clamp(-100.0, 100.0),
+                };
+                effects.insert(effect.into(), value);
+            }
+            "sound_cleareffects" => self.target_mut(target).sound_effects.clear(),
+            "sound_changevolumeby" | "sound_setvolumeto" => {
+                let mut volume = self.number(context, block, "VOLUME");
+                if block.opcode == "sound_changevolumeby" {
+                    volume += self.target(target).volume;
+                }
+                self.target_mut(target).volume = volume.clamp(0.0, 100.0);
+            }
+            // Nothing is asked, so the answer stays empty.
+            "sensing_askandwait" => {}
+            "sensing_resettimer" => self.timer_start = self.time,
+            "sensing_setdragmode" => {
+                self.target_mut(target).draggable = block.field() == Some("draggable");
+            }
+            opcode if opcode.starts_with("pen_") => {}
+            "music_restForBeats" => {
+                let beats = self.number(context, block, "BEATS");
+                let until = self.time + beats * 60.0 / self.target(self.stage).tempo;
+                let clear_bubble = false;
+                self.push(
+                    thread,
+                    Frame::Wait {
+                        until,
+                        clear_bubble,
+                    },
+                );
+                return Ok(Flow::Yield);
+            }
+            "music_setTempo" | "music_changeTempo" => {
+                let mut tempo = self.number(context, block, "TEMPO");
+                if block.opcode == "music_changeTempo" {
+                    tempo += self.target(self.stage).tempo;
+                }
+                self.target_mut(self.stage).tempo = tempo.clamp(20.0, 500.0);
+            }
+            opcode => {
+                self.unsupported.insert(opcode.to_string());
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn proc_call(&mut self, context: Context, block: &'a Block) -> io::Result<Flow> {
+        let thread = context.thread.unwrap();
+        let Some(mutation) = &block.mutation else {
+            return Ok(Flow::Continue);
+        };
+        // The log, breakpoint and error statements compile to calls to hidden custom
+        // blocks, with names surrounded by zero-width spaces.
+        if mutation.proccode.starts_with('\u{200b}') {
+            let name = mutation.proccode.split(' ').next().unwrap();
+            let arg = match mutation.argument_ids().first() {
+                Some(arg_id) => self.string(context, block, arg_id),
+                None => String::new(),
+            };
+            match name.trim_matches('\u{200b}') {
+                "log" => writeln!(self.out, "{arg}")?,
+                "error" => match &mut self.errors {
+                    Some(errors) => {
+                        errors.push(arg);
+                        self.threads[thread].done = true;
+                    }
+                    None => eprintln!("{}: {arg}", "error".red().bold()),
+                },
+                _ => {}
+            }
+            return Ok(Flow::Continue);
+        }
+        let Some(proc) = self
+            .sprite(context.target)
+            .procs
+            .get(mutation.proccode.as_str())
+        else {
+            self.unsupported
+                .insert(format!("call to {}", mutation.proccode));
+            return Ok(Flow::Continue);
+        };
+        let (body, warp) = (proc.body, proc.warp);
+        let arg_ids = proc.arg_ids.clone();
+        let arg_names = proc.arg_names.clone();
+        let args = arg_ids
+            .iter()
+            .zip(arg_names)
+            .map(|(arg_id, name)| (name, self.input(context, block, arg_id)))
+            .collect();
+        let warp = warp || self.threads[thread].warp();
+        // Recursive calls yield, unless the thread is running without screen refresh.
+        let recursive = self.threads[thread].is_calling(&mutation.proccode);
+        self.push(
+            thread,
+            Frame::Call {
+                proccode: &mutation.proccode,
+                args,
+                warp,
+            },
+        );
+        self.push(thread, Frame::Stack(body));
+        if recursive && !warp {
+            return Ok(Flow::Yield);
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn broadcast(&mut self, name: &str) -> Vec<u64> {
+        self.start_hats("event_whenbroadcastreceived", Some(name), None)
+    }
+
+    /// Returns the position of a sprite, the mouse or a random position on the stage,
+    /// given by the value of a menu.
+    fn position_or_random(&mut self, object: &str) -> Option<(f64, f64)> {
+        if object == "_random_" {
+            let x = (self.stage_width * (self.random() - 0.5)).round();
+            let y = (self.stage_height * (self.random() - 0.5)).round();
+            return Some((x, y));
+        }
+        self.position_of(object)
+    }
+
+    /// Directions wrap around to be from -179 to 180.
+    fn set_direction(&mut self, target: usize, direction: f64) {
+        if direction.is_finite() {
+            let direction = direction - ((direction + 179.0) / 360.0).floor() * 360.0;
+            self.target_mut(target).direction = direction;
+        }
+    }
+
+    /// Bounces off the edge of the stage that the sprite is nearest to, if it is on or
+    /// beyond it. Sprites are points, as there are no costumes to measure.
+    fn bounce(&mut self, target: usize) {
+        let (half_width, half_height) = (self.stage_width / 2.0, self.stage_height / 2.0);
+        let (x, y, direction) = {
+            let target = self.target(target);
+            (target.x, target.y, target.direction)
+        };
+        let distances = [
+            half_width + x,
+            half_height - y,
+            half_width - x,
+            half_height + y,
+        ];
+        let (edge, distance) = distances.iter().copied().enumerate().fold(
+            (0, f64::INFINITY),
+            |nearest, (edge, distance)| {
+                if distance.max(0.0) < nearest.1 {
+                    (edge, distance.max(0.0))
+                } else {
+                    nearest
+                }
+            },
+        );
+        if distance > 0.0 {
+            return;
+        }
+        let radians = (90.0 - direction) * PI / 180.0;
+        let (mut dx, mut dy) = (radians.cos(), -radians.sin());
+        match edge {
+            0 => dx = dx.abs().max(0.2),
+            1 => dy = dy.abs().max(0.2),
+            2 => dx = -dx.abs().max(0.2),
+            _ => dy = -dy.abs().max(0.2),
+        }
+        self.set_direction(target, dy.atan2(dx) * 180.0 / PI + 90.0);
+        let target = self.target_mut(target);
+        target.x = target.x.clamp(-half_width, half_width);
+        target.y = target.y.clamp(-half_height, half_height);
+    }
+
+    /// Switches to a costume by name, by number, or to the next or previous costume. The
+    /// stage can also switch to a random backdrop.
+    fn switch_costume(&mut self, target: usize, costume: &Value) {
+        let current = self.target(target).costume as f64;
+        if let Value::Number(number) = costume {
+            self.set_costume(target, number - 1.0);
+            return;
+        }
+        let name = costume.to_string();
+        let costumes = &self.sprite(target).costumes;
+        if let Some(index) = costumes.iter().position(|costume| *costume == name) {
+            self.set_costume(target, index as f64);
+            return;
+        }
+        let len = costumes.len();
+        let is_stage = self.sprite(target).is_stage;
+        match name.as_str() {
+            "next costume" | "next backdrop" => self.set_costume(target, current + 1.0),
+            "previous costume" | "previous backdrop" => self.set_costume(target, current - 1.0),
+            "random backdrop" if is_stage && len > 1 => {
+                let offset = 1.0 + (self.random() * (len - 1) as f64).floor();
+                self.set_costume(target, current + offset);
+            }
+            _ => {
+                // Strings which are numbers switch to a costume by number.
+                let is_whitespace = name.trim().is_empty();
+                if !is_whitespace && name.trim().parse::<f64>().is_ok() {
+                    self.set_costume(target, costume.to_number() - 1.0);
+                }
+            }
+        }
+    }
+
+    /// Switches to a costume by index, wrapping around. If the stage switches to a
+    /// backdrop, scripts waiting for that backdrop are started.
+    fn set_costume(&mut self, target: usize, index: f64) {
+        let len = self.sprite(target).costumes.len();
+        if len == 0 {
+            return;
+        }
+        let index = (index + 0.5).floor();
+        let index = if index.is_finite() { index } else { 0.0 };
+        let index = index.rem_euclid(len as f64) as usize;
+        self.target_mut(target).costume = index;
+        if target == self.stage {
+            let name = self.sprite(target).costumes[index];
+            self.start_hats("event_whenbackdropswitchesto", Some(name), None);
+        }
+    }
+
+    /// Returns the duration of a sound by name or by number.
+    fn sound_duration(&self, target: usize, sound: &Value) -> Option<f64> {
+        let sounds = &self.sprite(target).sounds;
+        let name = sound.to_string();
+        if let Some(sound) = sounds.iter().find(|sound| sound.name == name) {
+            return Some(sound.duration);
+        }
+        if sounds.is_empty() || name.trim().parse::<f64>().is_err() {
+            return None;
+        }
+        let index = (sound.to_number() - 1.0).round();
+        let index = index.rem_euclid(sounds.len() as f64) as usize;
+        Some(sounds[index].duration)
+    }
+}
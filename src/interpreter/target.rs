@@ -0,0 +1,231 @@
+use fxhash::FxHashMap;
+
+use super::value::Value;
+use crate::{
+    decompile::project::{
+        Block,
+        BlockEntry,
+        Target as TargetJson,
+    },
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
+};
+
+/// The parts of a target which do not change while the project runs, shared by a sprite
+/// and its clones.
+pub struct Sprite<'a> {
+    pub name: &'a str,
+    pub is_stage: bool,
+    pub blocks: FxHashMap<&'a str, &'a Block>,
+    /// Top-level hat blocks and their ids, in the order of the project's scripts.
+    pub hats: Vec<(&'a str, &'a Block)>,
+    /// Custom blocks, keyed by proccode.
+    pub procs: FxHashMap<&'a str, Proc<'a>>,
+    pub costumes: Vec<&'a str>,
+    pub sounds: Vec<Sound<'a>>,
+}
+
+pub struct Proc<'a> {
+    pub body: Option<&'a str>,
+    pub arg_ids: Vec<String>,
+    pub arg_names: Vec<SmolStr>,
+    pub warp: bool,
+}
+
+pub struct Sound<'a> {
+    pub name: &'a str,
+    /// Duration in seconds.
+    pub duration: f64,
+}
+
+/// The state of a sprite, a clone or the stage while the project runs.
+#[derive(Clone)]
+pub struct Target {
+    /// Index of the target's sprite in the runtime.
+    pub sprite: usize,
+    pub is_clone: bool,
+    /// Variables and lists, keyed by id.
+    pub vars: IndexMap<SmolStr, Variable>,
+    pub lists: IndexMap<SmolStr, List>,
+    pub x: f64,
+    pub y: f64,
+    pub direction: f64,
+    pub size: f64,
+    pub costume: usize,
+    pub volume: f64,
+    pub tempo: f64,
+    // Only a renderer would read the rest of the state, and there is none.
+    #[allow(dead_code)]
+    pub visible: bool,
+    #[allow(dead_code)]
+    pub draggable: bool,
+    #[allow(dead_code)]
+    pub rotation_style: SmolStr,
+    #[allow(dead_code)]
+    pub effects: IndexMap<SmolStr, f64>,
+    #[allow(dead_code)]
+    pub sound_effects: IndexMap<SmolStr, f64>,
+    #[allow(dead_code)]
+    pub bubble: Option<(SmolStr, String)>,
+}
+
+#[derive(Clone)]
+pub struct Variable {
+    pub name: SmolStr,
+    pub value: Value,
+}
+
+#[derive(Clone)]
+pub struct List {
+    pub name: SmolStr,
+    pub items: Vec<Value>,
+}
+
+impl<'a> Sprite<'a> {
+    pub fn new(target: &'a TargetJson) -> Self {
+        let blocks: FxHashMap<&str, &Block> = target
+            .blocks
+            .iter()
+            .filter_map(|(id, entry)| match entry {
+                BlockEntry::Block(block) => Some((id.as_str(), &**block)),
+                BlockEntry::Primitive(_) => None,
+            })
+            .collect();
+        let mut hats = vec![];
+        let mut procs = FxHashMap::default();
+        for (id, entry) in &target.blocks {
+            let BlockEntry::Block(block) = entry else {
+                continue;
+            };
+            if !block.top_level {
+                continue;
+            }
+            if block.opcode == "procedures_definition" {
+                let prototype = block
+                    .inputs
+                    .get("custom_block")
+                    .and_then(|input| input.get(1))
+                    .and_then(|id| blocks.get(id.as_str()?));
+                let Some(mutation) = prototype.and_then(|prototype| prototype.mutation.as_ref())
+                else {
+                    continue;
+                };
+                procs.insert(
+                    mutation.proccode.as_str(),
+                    Proc {
+                        body: block.next.as_deref(),
+                        arg_ids: mutation.argument_ids(),
+                        arg_names: mutation
+                            .argument_names()
+                            .iter()
+                            .map(|name| name.as_str().into())
+                            .collect(),
+                        warp: mutation.warp(),
+                    },
+                );
+            } else if is_hat(&block.opcode) {
+                hats.push((id.as_str(), &**block));
+            }
+        }
+        Self {
+            name: &target.name,
+            is_stage: target.is_stage,
+            blocks,
+            hats,
+            procs,
+            costumes: target
+                .costumes
+                .iter()
+                .map(|costume| costume.name.as_str())
+                .collect(),
+            sounds: target
+                .sounds
+                .iter()
+                .map(|sound| Sound {
+                    name: &sound.name,
+                    duration: match (sound.sample_count, sound.rate) {
+                        (Some(sample_count), Some(rate)) if rate > 0.0 => sample_count / rate,
+                        _ => 0.0,
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Target {
+    pub fn new(sprite: usize, target: &TargetJson) -> Self {
+        Self {
+            sprite,
+            is_clone: false,
+            vars: target
+                .variables
+                .iter()
+                .map(|(id, var)| {
+                    let var = Variable {
+                        name: var.name().into(),
+                        value: var.value().into(),
+                    };
+                    (id.as_str().into(), var)
+                })
+                .collect(),
+            lists: target
+                .lists
+                .iter()
+                .map(|(id, list)| {
+                    let list = List {
+                        name: list.0.as_str().into(),
+                        items: list.1.iter().map(Value::from).collect(),
+                    };
+                    (id.as_str().into(), list)
+                })
+                .collect(),
+            x: target.x.unwrap_or_default(),
+            y: target.y.unwrap_or_default(),
+            direction: target.direction.unwrap_or(90.0),
+            size: target.size.unwrap_or(100.0),
+            costume: target.current_costume,
+            volume: target.volume.unwrap_or(100.0),
+            tempo: 60.0,
+            visible: target.visible.unwrap_or(true),
+            draggable: target.draggable.unwrap_or_default(),
+            rotation_style: target
+                .rotation_style
+                .as_deref()
+                .unwrap_or("all around")
+                .into(),
+            effects: Default::default(),
+            sound_effects: Default::default(),
+            bubble: None,
+        }
+    }
+
+    /// Returns the id of the variable with `id`, or else the first variable named `name`.
+    pub fn find_var(&self, id: &str, name: &str) -> Option<SmolStr> {
+        find(&self.vars, id, |var| &*var.name == name)
+    }
+
+    pub fn find_list(&self, id: &str, name: &str) -> Option<SmolStr> {
+        find(&self.lists, id, |list| &*list.name == name)
+    }
+}
+
+fn find<T>(
+    entries: &IndexMap<SmolStr, T>,
+    id: &str,
+    is_named: impl Fn(&T) -> bool,
+) -> Option<SmolStr> {
+    if let Some((id, _)) = entries.get_key_value(id) {
+        return Some(id.clone());
+    }
+    entries
+        .iter()
+        .find(|(_, entry)| is_named(entry))
+        .map(|(id, _)| id.clone())
+}
+
+pub fn is_hat(opcode: &str) -> bool {
+    opcode.starts_with("event_when") || opcode == "control_start_as_clone"
+}
@@ -0,0 +1,113 @@
+use super::value::Value;
+use crate::{
+    decompile::project::Block,
+    misc::SmolStr,
+};
+
+/// A running script.
+pub struct Thread<'a> {
+    pub id: u64,
+    pub target: usize,
+    /// Id of the hat block the script starts with.
+    pub hat: &'a str,
+    pub stack: Vec<Frame<'a>>,
+    pub done: bool,
+    /// Set if the thread was restarted while it was running, so that it stops running
+    /// the script it was restarted from until the next frame.
+    pub restarted: bool,
+}
+
+pub enum Frame<'a> {
+    /// Runs a stack of blocks, holds the id of the block to run next.
+    Stack(Option<&'a str>),
+    /// Runs the body of a loop block until the loop ends. `ran` is set after the body
+    /// was started, so that the thread yields when it finishes.
+    Loop {
+        block: &'a Block,
+        kind: Loop,
+        ran: bool,
+    },
+    /// Waits until the time reaches `until`, and then clears the speech bubble if the
+    /// wait was for a say or think block.
+    Wait {
+        until: f64,
+        clear_bubble: bool,
+    },
+    WaitUntil(&'a Block),
+    Glide {
+        from: (f64, f64),
+        to: (f64, f64),
+        start: f64,
+        duration: f64,
+    },
+    /// Waits until the threads started by a broadcast are done.
+    Broadcast(Vec<u64>),
+    /// A call to a custom block, the body of the custom block is the frame above it.
+    Call {
+        proccode: &'a str,
+        args: Vec<(SmolStr, Value)>,
+        warp: bool,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub enum Loop {
+    Repeat(f64),
+    Forever,
+    Until,
+    While,
+}
+
+impl<'a> Thread<'a> {
+    pub fn new(id: u64, target: usize, hat: &'a str, first: Option<&'a str>) -> Self {
+        Self {
+            id,
+            target,
+            hat,
+            stack: vec![Frame::Stack(first)],
+            done: false,
+            restarted: false,
+        }
+    }
+
+    /// Whether the thread is running in a custom block with run without screen refresh.
+    pub fn warp(&self) -> bool {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|frame| match frame {
+                Frame::Call { warp, .. } => Some(*warp),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of an argument of the innermost custom block being run.
+    pub fn arg(&self, name: &str) -> Option<&Value> {
+        self.stack.iter().rev().find_map(|frame| match frame {
+            Frame::Call { args, .. } => Some(
+                args.iter()
+                    .find(|(arg_name, _)| &**arg_name == name)
+                    .map(|(_, value)| value),
+            ),
+            _ => None,
+        })?
+    }
+
+    pub fn is_calling(&self, proccode: &str) -> bool {
+        self.stack.iter().any(
+            |frame| matches!(frame, Frame::Call { proccode: called, .. } if *called == proccode),
+        )
+    }
+
+    /// Stops the innermost custom block being run, or the whole script if it is not in
+    /// one.
+    pub fn stop_this_script(&mut self) {
+        while let Some(frame) = self.stack.pop() {
+            if matches!(frame, Frame::Call { .. }) {
+                return;
+            }
+        }
+        self.done = true;
+    }
+}
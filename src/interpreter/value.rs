@@ -0,0 +1,259 @@
+use std::{
+    cmp::Ordering,
+    fmt::{
+        self,
+        Display,
+    },
+};
+
+use crate::misc::SmolStr;
+
+/// A value at runtime, converted between types following the rules of Scratch, which
+/// are the rules of JavaScript.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    String(SmolStr),
+    Bool(bool),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Self::String(SmolStr::new())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(number: f64) -> Self {
+        Self::Number(number)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(bool: bool) -> Self {
+        Self::Bool(bool)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Self::String(string.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Self::String(string.into())
+    }
+}
+
+impl From<&serde_json::Value> for Value {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Number(number) => Self::Number(number.as_f64().unwrap_or(0.0)),
+            serde_json::Value::String(string) => Self::String(string.as_str().into()),
+            serde_json::Value::Bool(bool) => Self::Bool(*bool),
+            _ => Self::default(),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{}", number_to_string(*number)),
+            Self::String(string) => write!(f, "{}", string),
+            Self::Bool(bool) => write!(f, "{}", bool),
+        }
+    }
+}
+
+impl Value {
+    /// Converts to a number, anything which is not a number is 0.
+    pub fn to_number(&self) -> f64 {
+        let number = self.to_number_or_nan();
+        if number.is_nan() {
+            0.0
+        } else {
+            number
+        }
+    }
+
+    fn to_number_or_nan(&self) -> f64 {
+        match self {
+            Self::Number(number) => *number,
+            Self::String(string) => parse_number(string).unwrap_or(f64::NAN),
+            Self::Bool(bool) => *bool as u8 as f64,
+        }
+    }
+
+    /// The strings `""`, `"0"` and `"false"` in any case are false.
+    pub fn to_bool(&self) -> bool {
+        match self {
+            Self::Number(number) => *number != 0.0 && !number.is_nan(),
+            Self::String(string) => {
+                !(string.is_empty() || &**string == "0" || string.eq_ignore_ascii_case("false"))
+            }
+            Self::Bool(bool) => *bool,
+        }
+    }
+
+    fn is_whitespace(&self) -> bool {
+        matches!(self, Self::String(string) if string.trim().is_empty())
+    }
+
+    /// Whether a number written as this value has no fractional part, used by `random`
+    /// to decide between an integer and a decimal.
+    pub fn is_int(&self) -> bool {
+        match self {
+            Self::Number(number) => number.is_nan() || number.fract() == 0.0,
+            Self::String(string) => !string.contains('.'),
+            Self::Bool(_) => true,
+        }
+    }
+
+    /// Compares as numbers if both values are numbers, otherwise compares as strings
+    /// without case.
+    pub fn compare(&self, other: &Value) -> Ordering {
+        let mut lhs = self.to_number_or_nan();
+        let mut rhs = other.to_number_or_nan();
+        if lhs == 0.0 && self.is_whitespace() {
+            lhs = f64::NAN;
+        }
+        if rhs == 0.0 && other.is_whitespace() {
+            rhs = f64::NAN;
+        }
+        if lhs.is_nan() || rhs.is_nan() {
+            let lhs = self.to_string().to_lowercase();
+            let rhs = other.to_string().to_lowercase();
+            return lhs.cmp(&rhs);
+        }
+        lhs.partial_cmp(&rhs).unwrap()
+    }
+
+    pub fn equals(&self, other: &Value) -> bool {
+        self.compare(other).is_eq()
+    }
+}
+
+/// Parses a string the way JavaScript's `Number` does, returning `None` where it would
+/// return `NaN`.
+fn parse_number(string: &str) -> Option<f64> {
+    let string = string.trim();
+    if string.is_empty() {
+        return Some(0.0);
+    }
+    match string {
+        "Infinity" | "+Infinity" => return Some(f64::INFINITY),
+        "-Infinity" => return Some(f64::NEG_INFINITY),
+        _ => {}
+    }
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        let digits = string
+            .strip_prefix(prefix)
+            .or_else(|| string.strip_prefix(&*prefix.to_uppercase()));
+        if let Some(digits) = digits {
+            if digits.is_empty() {
+                return None;
+            }
+            return digits.chars().try_fold(0.0, |number, c| {
+                Some(number * radix as f64 + c.to_digit(radix)? as f64)
+            });
+        }
+    }
+    // Rust also parses `inf` and `nan`, which JavaScript does not.
+    if !string
+        .bytes()
+        .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'))
+    {
+        return None;
+    }
+    string.parse().ok()
+}
+
+/// Formats a number the way JavaScript's `Number.prototype.toString` does.
+pub fn number_to_string(number: f64) -> String {
+    if number.is_nan() {
+        return "NaN".to_string();
+    }
+    if number.is_infinite() {
+        return if number > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .to_string();
+    }
+    if number == 0.0 {
+        return "0".to_string();
+    }
+    let sign = if number < 0.0 { "-" } else { "" };
+    // The shortest digits which round-trip, and the position of the decimal point.
+    let exponential = format!("{:e}", number.abs());
+    let (mantissa, exponent) = exponential.split_once('e').unwrap();
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().unwrap() + 1;
+    let unsigned = if k <= n && n <= 21 {
+        digits + &"0".repeat((n - k) as usize)
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{digits}", "0".repeat(-n as usize))
+    } else {
+        let exponent = n - 1;
+        let exponent = if exponent >= 0 {
+            format!("+{exponent}")
+        } else {
+            exponent.to_string()
+        };
+        if k == 1 {
+            format!("{digits}e{exponent}")
+        } else {
+            format!("{}.{}e{exponent}", &digits[..1], &digits[1..])
+        }
+    };
+    format!("{sign}{unsigned}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_to_string() {
+        assert_eq!(number_to_string(1.0), "1");
+        assert_eq!(number_to_string(-0.5), "-0.5");
+        assert_eq!(number_to_string(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(number_to_string(123456789.125), "123456789.125");
+        assert_eq!(number_to_string(1e21), "1e+21");
+        assert_eq!(number_to_string(1.5e-7), "1.5e-7");
+        assert_eq!(number_to_string(0.000001), "0.000001");
+        assert_eq!(number_to_string(f64::NEG_INFINITY), "-Infinity");
+    }
+
+    #[test]
+    fn test_coercion() {
+        assert_eq!(Value::from(" 12 ").to_number(), 12.0);
+        assert_eq!(Value::from("0x1f").to_number(), 31.0);
+        assert_eq!(Value::from("1e3").to_number(), 1000.0);
+        assert_eq!(Value::from("inf").to_number(), 0.0);
+        assert_eq!(Value::from("abc").to_number(), 0.0);
+        assert_eq!(Value::from(true).to_number(), 1.0);
+        assert!(!Value::from("FALSE").to_bool());
+        assert!(!Value::from("0").to_bool());
+        assert!(Value::from("0.0").to_bool());
+        assert!(!Value::from(f64::NAN).to_bool());
+    }
+
+    #[test]
+    fn test_compare() {
+        assert!(Value::from("10").compare(&Value::from(9.0)).is_gt());
+        assert!(Value::from("10").compare(&Value::from("9a")).is_lt());
+        assert!(Value::from("ABC").equals(&Value::from("abc")));
+        assert!(Value::from("1.0").equals(&Value::from(1.0)));
+        assert!(!Value::from("").equals(&Value::from(0.0)));
+        assert!(Value::from(true).equals(&Value::from("true")));
+        assert!(Value::from(f64::INFINITY).equals(&Value::from("Infinity")));
+    }
+}
@@ -1,26 +1,83 @@
+use std::collections::VecDeque;
+
 use logos::{
     Logos,
     SpannedIter,
 };
 
-use super::token::Token;
-use crate::diagnostic::{
-    Diagnostic,
-    DiagnosticKind,
+use super::token::{
+    FStrPart,
+    Token,
+};
+use crate::{
+    diagnostic::{
+        Diagnostic,
+        DiagnosticKind,
+    },
+    misc::SmolStr,
 };
 
+type Spanned = Result<(usize, Token, usize), Diagnostic>;
+
 pub struct Lexer<'source> {
+    source: &'source str,
     token_stream: SpannedIter<'source, Token>,
+    pending: VecDeque<Spanned>,
 }
 
 impl<'source> Lexer<'source> {
     pub fn new(source: &'source str) -> Self {
         Self {
+            source,
             token_stream: Token::lexer(source).spanned(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Splits an f-string into text segments, with the tokens of each embedded
+    /// expression in between. An f-string without expressions is a plain string.
+    fn fstring(&mut self, parts: Vec<FStrPart>, start: usize, end: usize) {
+        let last = parts.len() - 1;
+        for (i, part) in parts.iter().enumerate() {
+            match part {
+                FStrPart::Text(text) => {
+                    let l = match i.checked_sub(1).map(|i| &parts[i]) {
+                        Some(FStrPart::Expr(expr)) => start + expr.end + 1,
+                        _ => start,
+                    };
+                    let r = match parts.get(i + 1) {
+                        Some(FStrPart::Expr(expr)) => start + expr.start - 1,
+                        _ => end,
+                    };
+                    let token = fstring_text(text.clone(), i == 0, i == last);
+                    self.pending.push_back(Ok((l, token, r)));
+                }
+                FStrPart::Expr(expr) => {
+                    let offset = start + expr.start;
+                    let source = &self.source[offset..start + expr.end];
+                    self.pending
+                        .extend(Lexer::new(source).map(|token| match token {
+                            Ok((l, token, r)) => Ok((l + offset, token, r + offset)),
+                            Err(diagnostic) => Err(Diagnostic {
+                                kind: diagnostic.kind,
+                                span: diagnostic.span.start + offset..diagnostic.span.end + offset,
+                            }),
+                        }));
+                }
+            }
         }
     }
 }
 
+fn fstring_text(text: SmolStr, first: bool, last: bool) -> Token {
+    match (first, last) {
+        (true, true) => Token::Str(text),
+        (true, false) => Token::FStrStart(text),
+        (false, true) => Token::FStrEnd(text),
+        (false, false) => Token::FStrMiddle(text),
+    }
+}
+
 impl<'source> From<&'source str> for Lexer<'source> {
     fn from(source: &'source str) -> Self {
         Lexer::new(source)
@@ -28,16 +85,23 @@ impl<'source> From<&'source str> for Lexer<'source> {
 }
 
 impl Iterator for Lexer<'_> {
-    type Item = Result<(usize, Token, usize), Diagnostic>;
+    type Item = Spanned;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.token_stream.next().map(|(token, span)| {
-            token
-                .map(|token| (span.start, token, span.end))
-                .map_err(|_| Diagnostic {
-                    kind: DiagnosticKind::InvalidToken,
-                    span: span.clone(),
-                })
-        })
+        if let Some(token) = self.pending.pop_front() {
+            return Some(token);
+        }
+        let (token, span) = self.token_stream.next()?;
+        match token {
+            Ok(Token::FStr(parts)) => {
+                self.fstring(parts, span.start, span.end);
+                self.next()
+            }
+            Ok(token) => Some(Ok((span.start, token, span.end))),
+            Err(_) => Some(Err(Diagnostic {
+                kind: DiagnosticKind::InvalidToken,
+                span,
+            })),
+        }
     }
 }
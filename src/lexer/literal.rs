@@ -1,6 +1,9 @@
 use logos::Lexer;
 
-use super::token::Token;
+use super::token::{
+    FStrPart,
+    Token,
+};
 use crate::misc::SmolStr;
 
 pub fn name(lex: &mut Lexer<Token>) -> SmolStr {
@@ -11,10 +14,89 @@ pub fn string(lex: &mut Lexer<Token>) -> SmolStr {
     SmolStr::from(serde_json::from_str::<'_, String>(lex.slice()).unwrap())
 }
 
+pub fn fstring(lex: &mut Lexer<Token>) -> Option<Vec<FStrPart>> {
+    let rest = lex.remainder();
+    let bytes = rest.as_bytes();
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut i = 0;
+    loop {
+        match *bytes.get(i)? {
+            b'"' => break,
+            b'\\' => {
+                let c = rest[i + 1..].chars().next()?;
+                text.push('\\');
+                text.push(c);
+                i += 1 + c.len_utf8();
+            }
+            b'{' if bytes.get(i + 1) == Some(&b'{') => {
+                text.push('{');
+                i += 2;
+            }
+            b'}' if bytes.get(i + 1) == Some(&b'}') => {
+                text.push('}');
+                i += 2;
+            }
+            b'{' => {
+                parts.push(FStrPart::Text(unescape(&text)?));
+                text.clear();
+                let end = closing_brace(bytes, i)?;
+                // Offsets are relative to the start of the token, which begins with `f"`.
+                parts.push(FStrPart::Expr(i + 3..end + 2));
+                i = end + 1;
+            }
+            b'}' => return None,
+            _ => {
+                let c = rest[i..].chars().next()?;
+                text.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    parts.push(FStrPart::Text(unescape(&text)?));
+    lex.bump(i + 1);
+    Some(parts)
+}
+
+fn closing_brace(bytes: &[u8], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open;
+    loop {
+        match *bytes.get(i)? {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            b'"' => {
+                i += 1;
+                while *bytes.get(i)? != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+fn unescape(text: &str) -> Option<SmolStr> {
+    serde_json::from_str::<'_, String>(&format!("\"{text}\""))
+        .ok()
+        .map(SmolStr::from)
+}
+
 pub fn cmd(lex: &mut Lexer<Token>) -> SmolStr {
     SmolStr::from(&lex.slice()[3..lex.slice().len() - 3])
 }
 
+pub fn doc_comment(lex: &mut Lexer<Token>) -> SmolStr {
+    let line = lex.slice()[2..].trim_end();
+    SmolStr::from(line.strip_prefix(' ').unwrap_or(line))
+}
+
 pub fn arg(lex: &mut Lexer<Token>) -> SmolStr {
     SmolStr::from(&lex.slice()[1..])
 }
@@ -31,12 +31,21 @@ pub enum Token {
     Float(f64),
     #[regex(r#""([^"\\]|\\["\\/bfnrt]|\\u[0-9a-zA-Z]{4})*""#, string)]
     Str(SmolStr),
+    #[token("f\"", fstring)]
+    FStr(Vec<FStrPart>),
+    FStrStart(SmolStr),
+    FStrMiddle(SmolStr),
+    FStrEnd(SmolStr),
     #[regex(r#"```([^`]|\n)*```"#, cmd)]
     Cmd(SmolStr),
+    #[regex(r"##[^\n]*\n", doc_comment)]
+    DocComment(SmolStr),
     #[token("costumes")]
     Costumes,
     #[token("sounds")]
     Sounds,
+    #[token("sprite")]
+    Sprite,
     #[token("local")]
     Local,
     #[token("proc")]
@@ -69,12 +78,28 @@ pub enum Token {
     Else,
     #[token("elif")]
     Elif,
+    #[token("then")]
+    Then,
     #[token("until")]
     Until,
     #[token("forever")]
     Forever,
     #[token("repeat")]
     Repeat,
+    #[token("for")]
+    For,
+    #[token("step")]
+    Step,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
+    #[token("match")]
+    Match,
+    #[token("test")]
+    Test,
+    #[token("assert")]
+    Assert,
     #[token(",")]
     Comma,
     #[token("(")]
@@ -89,6 +114,8 @@ pub enum Token {
     Assign,
     #[token("==")]
     Eq,
+    #[token("=>")]
+    FatArrow,
     #[token("++")]
     Increment,
     #[token("--")]
@@ -113,6 +140,8 @@ pub enum Token {
     RBracket,
     #[token(".")]
     Dot,
+    #[token("..")]
+    DotDot,
     #[token("!=")]
     Ne,
     #[token("<")]
@@ -203,14 +232,28 @@ pub enum Token {
     Enum,
     #[token("struct")]
     Struct,
+    #[token("const")]
+    Const,
     #[token("true")]
     True,
     #[token("false")]
     False,
     #[token("list")]
     List,
+    #[token("map")]
+    Map,
     #[token("cloud")]
     Cloud,
+    #[token("|")]
+    Bar,
     #[token("|>")]
     Pipe,
 }
+
+/// A segment of an f-string literal. Expression ranges are relative to the start of
+/// the literal, and are lexed again by the adaptor.
+#[derive(Debug, Clone)]
+pub enum FStrPart {
+    Text(SmolStr),
+    Expr(std::ops::Range<usize>),
+}
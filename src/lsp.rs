@@ -0,0 +1,365 @@
+mod analysis;
+mod position;
+
+use std::{
+    fs,
+    panic::{
+        self,
+        AssertUnwindSafe,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use analysis::Analysis;
+use fxhash::FxHashMap;
+use lsp_server::{
+    Connection,
+    Message,
+    Notification,
+    Request,
+    Response,
+};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument,
+        DidCloseTextDocument,
+        DidOpenTextDocument,
+        DidSaveTextDocument,
+        Notification as _,
+        PublishDiagnostics,
+    },
+    request::{
+        Completion,
+        GotoDefinition,
+        HoverRequest,
+        Request as _,
+    },
+    CompletionItem,
+    CompletionItemKind,
+    CompletionOptions,
+    CompletionParams,
+    CompletionResponse,
+    DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams,
+    DidSaveTextDocumentParams,
+    GotoDefinitionParams,
+    GotoDefinitionResponse,
+    Hover,
+    HoverContents,
+    HoverParams,
+    HoverProviderCapability,
+    MarkupContent,
+    MarkupKind,
+    OneOf,
+    Position,
+    PublishDiagnosticsParams,
+    ServerCapabilities,
+    TextDocumentSyncCapability,
+    TextDocumentSyncKind,
+    TextDocumentSyncOptions,
+    TextDocumentSyncSaveOptions,
+    Uri,
+};
+use position::{
+    path_to_uri,
+    position_to_offset,
+    uri_to_path,
+};
+use serde::de::DeserializeOwned;
+
+use crate::blocks::{
+    Block,
+    Repr,
+};
+
+/// Runs the language server over stdin and stdout until the client shuts it down.
+pub fn run() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL),
+                save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                ..Default::default()
+            },
+        )),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    let mut server = Server {
+        connection: &connection,
+        documents: Default::default(),
+        projects: Default::default(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                server.request(request)?;
+            }
+            Message::Notification(notification) => server.notification(notification)?,
+            Message::Response(_) => {}
+        }
+    }
+    drop(server);
+    // The writer thread stops once every sender of the connection is dropped.
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    /// Text of the open documents, which may not be saved yet.
+    documents: FxHashMap<PathBuf, String>,
+    /// Analysis of each project as of the last time one of its files was saved, keyed
+    /// by project directory.
+    projects: FxHashMap<PathBuf, Analysis>,
+}
+
+impl Server<'_> {
+    fn request(&mut self, request: Request) -> anyhow::Result<()> {
+        let result = match request.method.as_str() {
+            GotoDefinition::METHOD => {
+                let params: GotoDefinitionParams = params(request.params)?;
+                let position = params.text_document_position_params;
+                serde_json::to_value(
+                    self.definition(&position.text_document.uri, position.position),
+                )?
+            }
+            HoverRequest::METHOD => {
+                let params: HoverParams = params(request.params)?;
+                let position = params.text_document_position_params;
+                serde_json::to_value(self.hover(&position.text_document.uri, position.position))?
+            }
+            Completion::METHOD => {
+                let params: CompletionParams = params(request.params)?;
+                let uri = params.text_document_position.text_document.uri;
+                serde_json::to_value(self.completion(&uri))?
+            }
+            _ => serde_json::Value::Null,
+        };
+        self.connection
+            .sender
+            .send(Response::new_ok(request.id, result).into())?;
+        Ok(())
+    }
+
+    fn notification(&mut self, notification: Notification) -> anyhow::Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams = params(notification.params)?;
+                if let Some(path) = uri_to_path(&params.text_document.uri) {
+                    self.documents
+                        .insert(path.clone(), params.text_document.text);
+                    self.analyze(&path)?;
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams = params(notification.params)?;
+                if let (Some(path), Some(change)) = (
+                    uri_to_path(&params.text_document.uri),
+                    params.content_changes.into_iter().last(),
+                ) {
+                    self.documents.insert(path, change.text);
+                }
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: DidSaveTextDocumentParams = params(notification.params)?;
+                if let Some(path) = uri_to_path(&params.text_document.uri) {
+                    self.analyze(&path)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams = params(notification.params)?;
+                if let Some(path) = uri_to_path(&params.text_document.uri) {
+                    self.documents.remove(&path);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Analyzes the project that the file at `path` belongs to, and publishes its
+    /// diagnostics.
+    fn analyze(&mut self, path: &Path) -> anyhow::Result<()> {
+        let Some(input) = self.project_dir(path) else {
+            return Ok(());
+        };
+        // A bug in the compiler should not take down the language server.
+        let analysis = match panic::catch_unwind(AssertUnwindSafe(|| Analysis::new(&input))) {
+            Ok(Ok(analysis)) => analysis,
+            Ok(Err(err)) => {
+                eprintln!("failed to analyze {}: {err:?}", input.display());
+                return Ok(());
+            }
+            Err(_) => return Ok(()),
+        };
+        for (path, diagnostics) in analysis.lsp_diagnostics() {
+            let Some(uri) = path_to_uri(&path) else {
+                continue;
+            };
+            let params = PublishDiagnosticsParams {
+                uri,
+                diagnostics,
+                version: None,
+            };
+            self.connection
+                .sender
+                .send(Notification::new(PublishDiagnostics::METHOD.to_string(), params).into())?;
+        }
+        self.projects.insert(input, analysis);
+        Ok(())
+    }
+
+    /// Finds the directory of the project that the file at `path` belongs to. Files
+    /// outside of any project directory belong to the project that includes them.
+    fn project_dir(&self, path: &Path) -> Option<PathBuf> {
+        let path = path.canonicalize().ok()?;
+        if let Some(dir) = path
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("stage.gs").is_file())
+        {
+            return Some(dir.to_owned());
+        }
+        self.projects
+            .iter()
+            .find(|(_, analysis)| analysis.contains(&path))
+            .map(|(dir, _)| dir.clone())
+    }
+
+    fn analysis(&self, path: &Path) -> Option<&Analysis> {
+        self.projects.get(&self.project_dir(path)?)
+    }
+
+    /// Returns the name under the cursor, not including the `.` separated parts after it.
+    fn word_at(&self, path: &Path, position: Position) -> Option<String> {
+        let text = match self.documents.get(path) {
+            Some(text) => text.clone(),
+            None => fs::read_to_string(path).ok()?,
+        };
+        let offset = position_to_offset(&text, position);
+        let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let start = text[..offset].rfind(|c| !is_name(c)).map_or(0, |i| i + 1);
+        let end = text[offset..]
+            .find(|c| !is_name(c))
+            .map_or(text.len(), |i| offset + i);
+        // Arguments are not declarations of the project.
+        if start == end || text[..start].ends_with('$') {
+            return None;
+        }
+        Some(text[start..end].to_owned())
+    }
+
+    fn definition(&self, uri: &Uri, position: Position) -> Option<GotoDefinitionResponse> {
+        let path = uri_to_path(uri)?.canonicalize().ok()?;
+        let word = self.word_at(&path, position)?;
+        let locations: Vec<_> = self
+            .analysis(&path)?
+            .symbols(&path)
+            .into_iter()
+            .filter(|(symbol, _)| symbol.name() == word)
+            .filter_map(|(symbol, translation_unit)| symbol.location(translation_unit))
+            .collect();
+        (!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations))
+    }
+
+    fn hover(&self, uri: &Uri, position: Position) -> Option<Hover> {
+        let path = uri_to_path(uri)?.canonicalize().ok()?;
+        let word = self.word_at(&path, position)?;
+        let symbols = self
+            .analysis(&path)
+            .map(|analysis| analysis.symbols(&path))
+            .unwrap_or_default();
+        let value =
+            if let Some((symbol, _)) = symbols.iter().find(|(symbol, _)| symbol.name() == word) {
+                let mut value = format!("```goboscript\n{}\n```", symbol.signature());
+                if let Some(doc) = symbol.doc() {
+                    value.push_str("\n\n");
+                    value.push_str(doc);
+                }
+                value
+            } else {
+                let signatures = block_signatures(&word);
+                if signatures.is_empty() {
+                    return None;
+                }
+                format!("```goboscript\n{}\n```", signatures.join("\n"))
+            };
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: None,
+        })
+    }
+
+    fn completion(&self, uri: &Uri) -> Option<CompletionResponse> {
+        let path = uri_to_path(uri)?.canonicalize().ok()?;
+        let mut items = vec![];
+        if let Some(analysis) = self.analysis(&path) {
+            for (symbol, _) in analysis.symbols(&path) {
+                items.push(CompletionItem {
+                    label: symbol.name().to_owned(),
+                    kind: Some(symbol.completion_kind()),
+                    detail: Some(symbol.signature()),
+                    ..Default::default()
+                });
+            }
+        }
+        let blocks = Block::all_names().iter().chain(Repr::all_names());
+        for name in blocks {
+            items.push(CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(block_signatures(name).join("\n")),
+                ..Default::default()
+            });
+        }
+        Some(CompletionResponse::Array(items))
+    }
+}
+
+/// Returns the signature of each variant of the block or reporter with the given name.
+fn block_signatures(name: &str) -> Vec<String> {
+    let blocks = Block::variants(name).iter().map(|block| {
+        let args: Vec<String> = block.args().iter().map(|arg| arg.to_lowercase()).collect();
+        format!("{name} {};", args.join(", "))
+    });
+    let reprs = Repr::variants(name).iter().map(|repr| {
+        let args: Vec<String> = repr.args().iter().map(|arg| arg.to_lowercase()).collect();
+        format!("{name}({})", args.join(", "))
+    });
+    blocks.chain(reprs).collect()
+}
+
+fn params<T>(params: serde_json::Value) -> anyhow::Result<T>
+where T: DeserializeOwned {
+    Ok(serde_json::from_value(params)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_signatures() {
+        assert_eq!(block_signatures("move"), ["move steps;"]);
+        assert_eq!(block_signatures("goto"), ["goto to;", "goto x, y;"]);
+        assert_eq!(block_signatures("x_position"), ["x_position()"]);
+        assert!(block_signatures("nope").is_empty());
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use annotate_snippets::Level;
+use fxhash::{
+    FxHashMap,
+    FxHashSet,
+};
+use logos::Span;
+use lsp_types::{
+    CompletionItemKind,
+    DiagnosticSeverity,
+    Location,
+    Range,
+};
+
+use super::position::{
+    offset_to_position,
+    path_to_uri,
+};
+use crate::{
+    ast::*,
+    diagnostic::{
+        ProjectDiagnostics,
+        SpriteDiagnostics,
+    },
+    frontend::build::{
+        analyze,
+        load,
+        BuildError,
+    },
+    translation_unit::{
+        Owner,
+        TranslationUnit,
+    },
+};
+
+/// A project which has been parsed and run through every pass, with the diagnostics
+/// that were reported for it.
+pub struct Analysis {
+    project: ProjectDiagnostics,
+    /// The files which each sprite is compiled from, in the order of [`Analysis::sprites`].
+    files: Vec<FxHashSet<PathBuf>>,
+}
+
+/// A declaration that can be referred to by name.
+pub enum Symbol<'a> {
+    Proc(&'a Proc),
+    Func(&'a Func),
+    Var(&'a Var),
+    List(&'a List),
+    Struct(&'a Struct),
+    Enum(&'a Enum),
+    Const(&'a Const),
+}
+
+impl Analysis {
+    pub fn new(input: &Path) -> anyhow::Result<Self> {
+        let (config, stdlib) = load(input)?;
+        let project = match analyze(input, &config, &stdlib, false, &mut vec![]) {
+            Ok(project) => project,
+            Err(BuildError::ProjectDiagnostics(project)) => *project,
+            Err(BuildError::AnyhowError(err)) => return Err(err),
+        };
+        let mut analysis = Self {
+            project,
+            files: vec![],
+        };
+        analysis.files = analysis
+            .sprites()
+            .map(|(_, d)| local_files(&d.translation_unit))
+            .collect();
+        Ok(analysis)
+    }
+
+    /// Returns the stage followed by every sprite, with their diagnostics.
+    pub fn sprites(&self) -> impl Iterator<Item = (&Sprite, &SpriteDiagnostics)> {
+        let stage = (&self.project.project.stage, &self.project.stage_diagnostics);
+        let sprites = self
+            .project
+            .project
+            .sprites
+            .iter()
+            .map(|(name, sprite)| (sprite, &self.project.sprites_diagnostics[name]));
+        std::iter::once(stage).chain(sprites)
+    }
+
+    /// Returns whether any sprite is compiled from the file at `path`.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.iter().any(|files| files.contains(path))
+    }
+
+    /// Returns every file any sprite is compiled from.
+    pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter().flatten()
+    }
+
+    /// Returns the declarations which can be referred to from the file at `path`, with
+    /// the translation unit they were declared in. Declarations of the stage are visible
+    /// from every sprite.
+    pub fn symbols(&self, path: &Path) -> Vec<(Symbol<'_>, &TranslationUnit)> {
+        let mut symbols = vec![];
+        let mut sprites = self.sprites().zip(&self.files);
+        let ((stage, stage_d), stage_files) = sprites.next().unwrap();
+        let mut stage_visible = stage_files.contains(path);
+        for ((sprite, d), files) in sprites {
+            if files.contains(path) {
+                stage_visible = true;
+                symbols.extend(sprite_symbols(sprite).map(|symbol| (symbol, &d.translation_unit)));
+            }
+        }
+        if stage_visible {
+            let stage_symbols = sprite_symbols(stage);
+            symbols.extend(stage_symbols.map(|symbol| (symbol, &stage_d.translation_unit)));
+        }
+        symbols
+    }
+
+    /// Returns the diagnostics of the project for each file they are reported in. Every
+    /// file of the project is included, so that fixed diagnostics are cleared.
+    pub fn lsp_diagnostics(&self) -> FxHashMap<PathBuf, Vec<lsp_types::Diagnostic>> {
+        let mut diagnostics: FxHashMap<PathBuf, Vec<lsp_types::Diagnostic>> =
+            self.files().map(|path| (path.clone(), vec![])).collect();
+        for (sprite, d) in self.sprites() {
+            for diagnostic in &d.diagnostics {
+                let Some((path, range)) = source_range(&d.translation_unit, &diagnostic.span)
+                else {
+                    continue;
+                };
+                let severity = match Level::from(&diagnostic.kind) {
+                    Level::Error => DiagnosticSeverity::ERROR,
+                    Level::Warning => DiagnosticSeverity::WARNING,
+                    _ => DiagnosticSeverity::INFORMATION,
+                };
+                let mut message = diagnostic.kind.to_string(sprite);
+                if let Some(help) = diagnostic.kind.help() {
+                    message.push_str("\nhelp: ");
+                    message.push_str(&help);
+                }
+                diagnostics
+                    .entry(path)
+                    .or_default()
+                    .push(lsp_types::Diagnostic {
+                        range,
+                        severity: Some(severity),
+                        source: Some("goboscript".to_string()),
+                        message,
+                        ..Default::default()
+                    });
+            }
+        }
+        diagnostics
+    }
+}
+
+impl Symbol<'_> {
+    pub fn name(&self) -> &str {
+        match self {
+            Symbol::Proc(proc) => &proc.name,
+            Symbol::Func(func) => &func.name,
+            Symbol::Var(var) => &var.name,
+            Symbol::List(list) => &list.name,
+            Symbol::Struct(struct_) => &struct_.name,
+            Symbol::Enum(enum_) => &enum_.name,
+            Symbol::Const(const_) => &const_.name,
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        match self {
+            Symbol::Proc(proc) => &proc.span,
+            Symbol::Func(func) => &func.span,
+            Symbol::Var(var) => &var.span,
+            Symbol::List(list) => &list.span,
+            Symbol::Struct(struct_) => &struct_.span,
+            Symbol::Enum(enum_) => &enum_.span,
+            Symbol::Const(const_) => &const_.span,
+        }
+    }
+
+    pub fn location(&self, translation_unit: &TranslationUnit) -> Option<Location> {
+        let (path, range) = source_range(translation_unit, self.span())?;
+        Some(Location {
+            uri: path_to_uri(&path)?,
+            range,
+        })
+    }
+
+    /// Returns the declaration of the symbol, as it would be written in goboscript.
+    pub fn signature(&self) -> String {
+        match self {
+            Symbol::Proc(proc) => {
+                let nowarp = if proc.warp { "" } else { "nowarp " };
+                format!("{nowarp}proc {} {}", proc.name, args(&proc.args))
+            }
+            Symbol::Func(func) => match &func.type_ {
+                Type::Value => format!("func {}({})", func.name, args(&func.args)),
+                type_ => format!("func {}({}) {type_}", func.name, args(&func.args)),
+            },
+            Symbol::Var(var) => match &var.type_ {
+                Type::Value => format!("{} = ...;", var.name),
+                type_ => format!("{type_} {} = ...;", var.name),
+            },
+            Symbol::List(list) => match &list.type_ {
+                Type::Value => format!("list {};", list.name),
+                type_ => format!("list {type_} {};", list.name),
+            },
+            Symbol::Struct(struct_) => {
+                let fields: Vec<String> = struct_
+                    .fields
+                    .iter()
+                    .map(|field| match &field.type_ {
+                        Type::Value => field.name.to_string(),
+                        type_ => format!("{type_} {}", field.name),
+                    })
+                    .collect();
+                format!("struct {} {{ {} }}", struct_.name, fields.join(", "))
+            }
+            Symbol::Enum(enum_) => {
+                let variants: Vec<&str> = enum_
+                    .variants
+                    .iter()
+                    .map(|variant| &*variant.name)
+                    .collect();
+                format!("enum {} {{ {} }}", enum_.name, variants.join(", "))
+            }
+            Symbol::Const(const_) => match &const_.type_ {
+                Type::Value => format!("const {} = ...;", const_.name),
+                type_ => format!("const {type_} {} = ...;", const_.name),
+            },
+        }
+    }
+
+    pub fn doc(&self) -> Option<&str> {
+        match self {
+            Symbol::Proc(proc) => proc.doc.as_deref(),
+            Symbol::Func(func) => func.doc.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn completion_kind(&self) -> CompletionItemKind {
+        match self {
+            Symbol::Proc(_) | Symbol::Func(_) => CompletionItemKind::FUNCTION,
+            Symbol::Var(_) | Symbol::List(_) => CompletionItemKind::VARIABLE,
+            Symbol::Struct(_) => CompletionItemKind::STRUCT,
+            Symbol::Enum(_) => CompletionItemKind::ENUM,
+            Symbol::Const(_) => CompletionItemKind::CONSTANT,
+        }
+    }
+}
+
+fn sprite_symbols(sprite: &Sprite) -> impl Iterator<Item = Symbol<'_>> {
+    let locals = sprite
+        .procs
+        .values()
+        .flat_map(|proc| proc.locals.values())
+        .chain(sprite.funcs.values().flat_map(|func| func.locals.values()));
+    let procs = sprite.procs.values().map(Symbol::Proc);
+    let funcs = sprite.funcs.values().map(Symbol::Func);
+    let vars = locals.chain(sprite.vars.values()).map(Symbol::Var);
+    let lists = sprite.lists.values().map(Symbol::List);
+    let structs = sprite.structs.values().map(Symbol::Struct);
+    let enums = sprite.enums.values().map(Symbol::Enum);
+    let consts = sprite.consts.values().map(Symbol::Const);
+    procs
+        .chain(funcs)
+        .chain(vars)
+        .chain(lists)
+        .chain(structs)
+        .chain(enums)
+        .chain(consts)
+        // Hidden names generated by the compiler, such as `@0` and `f:return`.
+        .filter(|symbol| !symbol.name().starts_with('@') && !symbol.name().contains(':'))
+}
+
+fn args(args: &[Arg]) -> String {
+    let args: Vec<String> = args
+        .iter()
+        .map(|arg| match &arg.type_ {
+            Type::Value => arg.name.to_string(),
+            type_ => format!("{type_} {}", arg.name),
+        })
+        .collect();
+    args.join(", ")
+}
+
+fn local_files(translation_unit: &TranslationUnit) -> FxHashSet<PathBuf> {
+    translation_unit
+        .get_includes()
+        .iter()
+        .filter(|include| matches!(include.owner, Owner::Local))
+        .filter_map(|include| include.path.canonicalize().ok())
+        .collect()
+}
+
+/// Maps a span in the translation unit back to the file it was included from, and its
+/// range in that file.
+fn source_range(translation_unit: &TranslationUnit, span: &Span) -> Option<(PathBuf, Range)> {
+    let len = translation_unit.get_text().len();
+    if span.start >= len {
+        return None;
+    }
+    let (start, include) = translation_unit.translate_position(span.start);
+    if !matches!(include.owner, Owner::Local) {
+        return None;
+    }
+    let end = if span.end > span.start && span.end <= len {
+        translation_unit.translate_position(span.end - 1).0 + 1
+    } else {
+        start
+    };
+    let text = fs::read_to_string(&include.path).ok()?;
+    let range = Range {
+        start: offset_to_position(&text, start),
+        end: offset_to_position(&text, end),
+    };
+    Some((include.path.canonicalize().ok()?, range))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        process,
+    };
+
+    use lsp_types::Position;
+
+    use super::*;
+    use crate::lsp::position::uri_to_path;
+
+    /// Diagnostics and declarations in an included file are mapped back to that file.
+    #[test]
+    fn test_include_positions() {
+        let input = env::temp_dir().join(format!("goboscript-lsp-include-{}", process::id()));
+        let _ = fs::remove_dir_all(&input);
+        fs::create_dir_all(input.join("lib")).unwrap();
+        let blank = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/run/blank.svg");
+        fs::copy(blank, input.join("blank.svg")).unwrap();
+        fs::write(input.join("stage.gs"), "costumes \"blank.svg\";\n").unwrap();
+        fs::write(
+            input.join("main.gs"),
+            "costumes \"blank.svg\";\n%include lib/helpers.gs\nonflag { greet; }\n",
+        )
+        .unwrap();
+        let helpers = input.join("lib/helpers.gs");
+        fs::write(&helpers, "# helpers\nproc greet {\n    say nope;\n}\n").unwrap();
+        let helpers = helpers.canonicalize().unwrap();
+
+        let analysis = Analysis::new(&input).unwrap();
+        let diagnostics = analysis.lsp_diagnostics();
+        let [diagnostic] = &diagnostics[&helpers][..] else {
+            panic!("{diagnostics:?}");
+        };
+        assert_eq!(diagnostic.range.start, Position::new(2, 8));
+        assert_eq!(diagnostic.range.end, Position::new(2, 12));
+        assert!(diagnostics[&input.join("main.gs").canonicalize().unwrap()].is_empty());
+
+        let (symbol, translation_unit) = analysis
+            .symbols(&helpers)
+            .into_iter()
+            .find(|(symbol, _)| symbol.name() == "greet")
+            .unwrap();
+        let location = symbol.location(translation_unit).unwrap();
+        assert_eq!(uri_to_path(&location.uri).unwrap(), helpers);
+        assert_eq!(location.range.start, Position::new(1, 5));
+        assert_eq!(location.range.end, Position::new(1, 10));
+        let _ = fs::remove_dir_all(&input);
+    }
+}
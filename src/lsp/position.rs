@@ -0,0 +1,86 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use lsp_types::{
+    Position,
+    Uri,
+};
+
+/// Converts a byte offset in `text` to an LSP position, whose column is counted in
+/// UTF-16 code units.
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// Converts an LSP position to a byte offset in `text`. Positions past the end of a line
+/// are clamped to the end of that line.
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let mut character = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if c == '\n' || character >= position.character {
+            return line_start + i;
+        }
+        character += c.len_utf16() as u32;
+    }
+    text.len()
+}
+
+pub fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    let path = uri.as_str().strip_prefix("file://")?;
+    let mut bytes = vec![];
+    let mut iter = path.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+pub fn path_to_uri(path: &Path) -> Option<Uri> {
+    let path = path.canonicalize().ok()?;
+    let mut uri = "file://".to_string();
+    for b in path.to_str()?.bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{b:02X}"));
+        }
+    }
+    uri.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positions() {
+        let text = "say \"é\";\nmove 10;\n";
+        let position = offset_to_position(text, text.find("10").unwrap());
+        assert_eq!(position, Position::new(1, 5));
+        assert_eq!(position_to_offset(text, position), text.find("10").unwrap());
+        let position = offset_to_position(text, text.find(';').unwrap());
+        assert_eq!(position, Position::new(0, 7));
+        assert_eq!(position_to_offset(text, Position::new(0, 100)), 9);
+        assert_eq!(position_to_offset(text, Position::new(5, 0)), text.len());
+    }
+}
@@ -2,10 +2,13 @@ mod ast;
 mod blocks;
 mod codegen;
 mod config;
+mod decompile;
 mod diagnostic;
 mod fmt;
 mod frontend;
+mod interpreter;
 mod lexer;
+mod lsp;
 mod misc;
 mod parser;
 mod pre_processor;
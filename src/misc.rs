@@ -22,3 +22,8 @@ where T: fmt::Write {
 }
 
 pub type SmolStr = ArcStr;
+
+/// Map that iterates in insertion order, used for everything whose order ends up in the
+/// compiled project so that identical input produces identical output.
+pub type IndexMap<K, V> = indexmap::IndexMap<K, V, fxhash::FxBuildHasher>;
+pub type IndexSet<T> = indexmap::IndexSet<T, fxhash::FxBuildHasher>;
@@ -165,5 +165,43 @@ pub fn pre_processor(tokens: &mut Vec<SpannedToken>) -> Result<(), Diagnostic> {
             i += 1;
         }
     }
+    strip_doc_comments(tokens);
     Ok(())
 }
+
+/// Doc comments are only kept above procedures, functions and events. Anywhere else,
+/// they are ordinary comments.
+fn strip_doc_comments(tokens: &mut Vec<SpannedToken>) {
+    let mut i = 0;
+    while i < tokens.len() {
+        if !matches!(tokens[i].1, Token::DocComment(_)) {
+            i += 1;
+            continue;
+        }
+        let next = tokens[i..]
+            .iter()
+            .find(|token| !matches!(token.1, Token::DocComment(_)));
+        if next.is_some_and(|token| {
+            matches!(
+                token.1,
+                Token::NoWarp
+                    | Token::Proc
+                    | Token::Func
+                    | Token::On
+                    | Token::OnFlag
+                    | Token::OnKey
+                    | Token::OnClick
+                    | Token::OnBackdrop
+                    | Token::OnLoudness
+                    | Token::OnTimer
+                    | Token::OnClone
+            )
+        }) {
+            while matches!(tokens.get(i), Some((_, Token::DocComment(_), _))) {
+                i += 1;
+            }
+        } else {
+            tokens.remove(i);
+        }
+    }
+}
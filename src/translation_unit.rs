@@ -4,7 +4,10 @@ use std::{
         File,
     },
     io::Read,
-    path::PathBuf,
+    path::{
+        Path,
+        PathBuf,
+    },
     str,
 };
 
@@ -71,6 +74,10 @@ impl TranslationUnit {
         self.parse(0, stdlib)
     }
 
+    pub fn get_includes(&self) -> &[Include] {
+        &self.includes
+    }
+
     pub fn get_text(&self) -> &str {
         str::from_utf8(&self.text).unwrap()
     }
@@ -286,4 +293,16 @@ impl TranslationUnit {
         }
         panic!("invalid position {position} in {}", self.path.display());
     }
+
+    /// Returns the file that `position` was included from, with the line and column of
+    /// `position` in that file, both starting from 1.
+    pub fn source_location(&self, position: usize) -> (&Path, usize, usize) {
+        let (position, include) = self.translate_position(position);
+        let text = fs::read_to_string(&include.path).unwrap_or_default();
+        let before = text.get(..position).unwrap_or_default();
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        let line = before.matches('\n').count() + 1;
+        let column = before[line_start..].chars().count() + 1;
+        (&include.path, line, column)
+    }
 }
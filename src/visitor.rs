@@ -1,5 +1,8 @@
+pub mod loops;
 pub mod pass0;
 pub mod pass1;
 pub mod pass2;
 pub mod pass3;
+pub mod resolve;
 mod transformations;
+pub mod unused;
@@ -0,0 +1,325 @@
+use logos::Span;
+
+use crate::{
+    ast::*,
+    blocks::{
+        BinOp,
+        Block,
+        UnOp,
+    },
+    codegen::sb3::D,
+    diagnostic::{
+        DiagnosticKind,
+        SpriteDiagnostics,
+    },
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
+};
+
+/// Value of a loop's flag variable after `continue`.
+const CONTINUE: i64 = 1;
+/// Value of a loop's flag variable after `break`.
+const BREAK: i64 = 2;
+
+struct V<'a> {
+    locals: Option<&'a mut IndexMap<SmolStr, Var>>,
+    vars: &'a mut IndexMap<SmolStr, Var>,
+    loops: &'a mut usize,
+}
+
+impl V<'_> {
+    fn declare(&mut self, name: SmolStr, span: Span) -> Name {
+        let var = Var {
+            name: name.clone(),
+            span: span.clone(),
+            type_: Type::Value,
+            is_cloud: false,
+            is_used: false,
+        };
+        match &mut self.locals {
+            Some(locals) => locals.insert(name.clone(), var),
+            None => self.vars.insert(name.clone(), var),
+        };
+        Name::Name { name, span }
+    }
+}
+
+pub fn visit_project(
+    project: &mut Project,
+    stage_diagnostics: &mut SpriteDiagnostics,
+    sprites_diagnostics: &mut IndexMap<SmolStr, SpriteDiagnostics>,
+) {
+    let mut loops = 0;
+    visit_sprite(&mut project.stage, stage_diagnostics, &mut loops);
+    for (sprite_name, sprite) in &mut project.sprites {
+        visit_sprite(
+            sprite,
+            sprites_diagnostics.get_mut(sprite_name).unwrap(),
+            &mut loops,
+        );
+    }
+}
+
+fn visit_sprite(sprite: &mut Sprite, d: D, loops: &mut usize) {
+    for proc in sprite.procs.values_mut() {
+        let proc_definition = sprite.proc_definitions.get_mut(&proc.name).unwrap();
+        visit_stmts(
+            proc_definition,
+            &mut V {
+                locals: Some(&mut proc.locals),
+                vars: &mut sprite.vars,
+                loops,
+            },
+            d,
+            false,
+            true,
+        );
+    }
+    for func in sprite.funcs.values_mut() {
+        let func_definition = sprite.func_definitions.get_mut(&func.name).unwrap();
+        visit_stmts(
+            func_definition,
+            &mut V {
+                locals: Some(&mut func.locals),
+                vars: &mut sprite.vars,
+                loops,
+            },
+            d,
+            false,
+            // `stop this script` must not be used inside functions.
+            false,
+        );
+    }
+    for event in &mut sprite.events {
+        visit_stmts(
+            &mut event.body,
+            &mut V {
+                locals: None,
+                vars: &mut sprite.vars,
+                loops,
+            },
+            d,
+            false,
+            true,
+        );
+    }
+}
+
+/// `is_tail` is true if nothing in the script runs after `stmts`, so that a `break`
+/// out of a loop at the end of `stmts` can simply stop the script.
+fn visit_stmts(stmts: &mut Vec<Stmt>, v: &mut V, d: D, in_loop: bool, is_tail: bool) {
+    let mut i = 0;
+    while i < stmts.len() {
+        let is_last = i == stmts.len() - 1;
+        match &mut stmts[i] {
+            Stmt::Repeat { body, .. } | Stmt::Forever { body, .. } | Stmt::Until { body, .. } => {
+                visit_stmts(body, v, d, true, false);
+                let lowered = lower_loop(stmts.remove(i), v, d, is_tail && is_last);
+                let len = lowered.len();
+                stmts.splice(i..i, lowered);
+                i += len;
+            }
+            Stmt::Branch {
+                if_body, else_body, ..
+            } => {
+                visit_stmts(if_body, v, d, in_loop, is_tail && is_last);
+                visit_stmts(else_body, v, d, in_loop, is_tail && is_last);
+                i += 1;
+            }
+            Stmt::Break(span) if !in_loop => {
+                d.report(DiagnosticKind::OutsideLoop("break"), span);
+                stmts.remove(i);
+            }
+            Stmt::Continue(span) if !in_loop => {
+                d.report(DiagnosticKind::OutsideLoop("continue"), span);
+                stmts.remove(i);
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+/// Finds the `break` and `continue` statements that belong to the loop with body
+/// `stmts`. Nested loops have already been lowered, so only branches are searched.
+fn find_loop_control(stmts: &[Stmt]) -> (Option<Span>, Option<Span>) {
+    let mut found = (None, None);
+    for stmt in stmts {
+        let (break_span, continue_span) = match stmt {
+            Stmt::Break(span) => (Some(span.clone()), None),
+            Stmt::Continue(span) => (None, Some(span.clone())),
+            Stmt::Branch {
+                if_body, else_body, ..
+            } => {
+                let (if_break, if_continue) = find_loop_control(if_body);
+                let (else_break, else_continue) = find_loop_control(else_body);
+                (if_break.or(else_break), if_continue.or(else_continue))
+            }
+            _ => (None, None),
+        };
+        found.0 = found.0.or(break_span);
+        found.1 = found.1.or(continue_span);
+    }
+    found
+}
+
+/// Lowers `break` and `continue` using a flag variable, which is reset at the start
+/// of every iteration. Statements which follow a possible `break` or `continue` are
+/// only run while the flag is unset, and a loop which contains a `break` is turned
+/// into a `repeat until` which also checks the flag. If the loop is the last
+/// statement of its script, `break` is lowered to `stop this script` instead.
+fn lower_loop(stmt: Stmt, v: &mut V, d: D, is_tail: bool) -> Vec<Stmt> {
+    let (break_span, continue_span) = match &stmt {
+        Stmt::Repeat { body, .. } | Stmt::Forever { body, .. } | Stmt::Until { body, .. } => {
+            find_loop_control(body)
+        }
+        _ => unreachable!(),
+    };
+    let Some(span) = continue_span.clone().or(break_span.clone()) else {
+        return vec![stmt];
+    };
+    if continue_span.is_none() && is_tail {
+        return vec![map_body(stmt, |body| guard(body, None, true, d))];
+    }
+    *v.loops += 1;
+    let flag = v.declare(format!("@loop{}", *v.loops).into(), span.clone());
+    let stmt = map_body(stmt, |body| {
+        let mut new_body = vec![set(&flag, Value::Int(0).to_expr(span.clone()))];
+        new_body.extend(guard(body, Some(&flag), is_tail, d));
+        new_body
+    });
+    if break_span.is_none() || is_tail {
+        return vec![stmt];
+    }
+    let mut lowered = vec![set(&flag, Value::Int(0).to_expr(span.clone()))];
+    let is_break = flag_is(&flag, BREAK);
+    let stmt = match stmt {
+        Stmt::Repeat { times, mut body } => {
+            let counter = v.declare(format!("@times{}", *v.loops).into(), span.clone());
+            lowered.push(set(&counter, UnOp::Round.to_expr(span.clone(), *times)));
+            body.insert(0, Stmt::decrement(counter.clone()));
+            Stmt::Until {
+                cond: Box::new(BinOp::Or.to_expr(
+                    span.clone(),
+                    is_break,
+                    BinOp::Lt.to_expr(
+                        span.clone(),
+                        Expr::Name(counter),
+                        Value::Int(1).to_expr(span),
+                    ),
+                )),
+                body,
+            }
+        }
+        Stmt::Forever { body, .. } => Stmt::Until {
+            cond: Box::new(is_break),
+            body,
+        },
+        Stmt::Until { cond, body } => Stmt::Until {
+            cond: Box::new(BinOp::Or.to_expr(span, is_break, *cond)),
+            body,
+        },
+        _ => unreachable!(),
+    };
+    lowered.push(stmt);
+    lowered
+}
+
+fn map_body<F>(stmt: Stmt, f: F) -> Stmt
+where F: FnOnce(Vec<Stmt>) -> Vec<Stmt> {
+    match stmt {
+        Stmt::Repeat { times, body } => Stmt::Repeat {
+            times,
+            body: f(body),
+        },
+        Stmt::Forever { body, span } => Stmt::Forever {
+            body: f(body),
+            span,
+        },
+        Stmt::Until { cond, body } => Stmt::Until {
+            cond,
+            body: f(body),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// Replaces `break` and `continue` with assignments to `flag`, and wraps the
+/// statements that follow them in a check that the flag is still unset.
+fn guard(stmts: Vec<Stmt>, flag: Option<&Name>, is_tail: bool, d: D) -> Vec<Stmt> {
+    let mut guarded = vec![];
+    let mut stmts = stmts.into_iter();
+    while let Some(stmt) = stmts.next() {
+        match stmt {
+            Stmt::Break(ref span) | Stmt::Continue(ref span) if !stmts.as_slice().is_empty() => {
+                d.report(DiagnosticKind::FollowedByUnreachableCode, span);
+                guarded.extend(guard(vec![stmt], flag, is_tail, d));
+                break;
+            }
+            Stmt::Break(span) if is_tail => {
+                guarded.push(Stmt::Block {
+                    block: Block::StopThisScript,
+                    span,
+                    args: vec![],
+                });
+            }
+            Stmt::Break(span) => {
+                let flag = flag.unwrap();
+                guarded.push(set(flag, Value::Int(BREAK).to_expr(span)));
+            }
+            Stmt::Continue(span) => {
+                let flag = flag.unwrap();
+                guarded.push(set(flag, Value::Int(CONTINUE).to_expr(span)));
+            }
+            Stmt::Branch {
+                cond,
+                if_body,
+                else_body,
+            } => {
+                let (if_break, if_continue) = find_loop_control(&if_body);
+                let (else_break, else_continue) = find_loop_control(&else_body);
+                let sets_flag = if_continue.is_some()
+                    || else_continue.is_some()
+                    || (!is_tail && (if_break.is_some() || else_break.is_some()));
+                guarded.push(Stmt::Branch {
+                    cond,
+                    if_body: guard(if_body, flag, is_tail, d),
+                    else_body: guard(else_body, flag, is_tail, d),
+                });
+                if sets_flag {
+                    let rest = guard(stmts.collect(), flag, is_tail, d);
+                    if !rest.is_empty() {
+                        guarded.push(Stmt::Branch {
+                            cond: Box::new(flag_is(flag.unwrap(), 0)),
+                            if_body: rest,
+                            else_body: vec![],
+                        });
+                    }
+                    break;
+                }
+            }
+            stmt => guarded.push(stmt),
+        }
+    }
+    guarded
+}
+
+fn set(name: &Name, value: Expr) -> Stmt {
+    Stmt::SetVar {
+        name: name.clone(),
+        value: Box::new(value),
+        type_: Type::Value,
+        is_local: false,
+        is_cloud: false,
+    }
+}
+
+fn flag_is(flag: &Name, value: i64) -> Expr {
+    let span = flag.span();
+    BinOp::Eq.to_expr(
+        span.clone(),
+        Expr::Name(flag.clone()),
+        Value::Int(value).to_expr(span),
+    )
+}
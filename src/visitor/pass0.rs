@@ -1,60 +1,246 @@
-use fxhash::FxHashMap;
+use std::path::Path;
+
 use glob::glob;
+use logos::Span;
 
+use super::transformations::{
+    map_index,
+    map_list,
+};
 use crate::{
     ast::*,
-    misc::SmolStr,
+    blocks::{
+        BinOp,
+        UnOp,
+    },
+    codegen::sb3::D,
+    diagnostic::{
+        DiagnosticKind,
+        SpriteDiagnostics,
+    },
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
 };
 
 struct V<'a> {
-    locals: Option<&'a mut FxHashMap<SmolStr, Var>>,
-    vars: &'a mut FxHashMap<SmolStr, Var>,
-    global_vars: Option<&'a mut FxHashMap<SmolStr, Var>>,
+    locals: Option<&'a mut IndexMap<SmolStr, Var>>,
+    args: Option<&'a [Arg]>,
+    vars: &'a mut IndexMap<SmolStr, Var>,
+    global_vars: Option<&'a mut IndexMap<SmolStr, Var>>,
+    lists: &'a IndexMap<SmolStr, List>,
+    global_lists: Option<&'a IndexMap<SmolStr, List>>,
+    maps: &'a IndexMap<SmolStr, Map>,
+    global_maps: Option<&'a IndexMap<SmolStr, Map>>,
+    enums: &'a IndexMap<SmolStr, Enum>,
+    global_enums: Option<&'a IndexMap<SmolStr, Enum>>,
+    consts: &'a IndexMap<SmolStr, Const>,
+    global_consts: Option<&'a IndexMap<SmolStr, Const>>,
+    returns: &'a IndexMap<SmolStr, usize>,
+    temps: &'a mut usize,
+    /// Project directory, the location of an `assert` is relative to it.
+    input: &'a Path,
+    /// Whether `assert` is lowered, otherwise it is removed. Asserts are only kept in
+    /// `test` blocks, unless the project is compiled with its tests.
+    asserts: bool,
 }
 
-pub fn visit_project(project: &mut Project) {
-    visit_sprite(&mut project.stage, None);
-    for sprite in project.sprites.values_mut() {
-        visit_sprite(sprite, Some(&mut project.stage));
+impl V<'_> {
+    fn get_list(&self, name: &str) -> Option<&List> {
+        self.lists.get(name).or_else(|| {
+            self.global_lists
+                .and_then(|global_lists| global_lists.get(name))
+        })
+    }
+
+    fn get_map(&self, name: &str) -> Option<&Map> {
+        self.maps.get(name).or_else(|| {
+            self.global_maps
+                .and_then(|global_maps| global_maps.get(name))
+        })
+    }
+
+    fn get_enum(&self, name: &str) -> Option<&Enum> {
+        self.enums.get(name).or_else(|| {
+            self.global_enums
+                .and_then(|global_enums| global_enums.get(name))
+        })
+    }
+
+    fn get_var(&self, name: &str) -> Option<&Var> {
+        self.locals
+            .as_deref()
+            .and_then(|locals| locals.get(name))
+            .or_else(|| self.vars.get(name))
+            .or_else(|| {
+                self.global_vars
+                    .as_deref()
+                    .and_then(|global_vars| global_vars.get(name))
+            })
+    }
+
+    fn get_const(&self, name: &str) -> Option<&Const> {
+        self.consts.get(name).or_else(|| {
+            self.global_consts
+                .and_then(|global_consts| global_consts.get(name))
+        })
     }
 }
 
-fn visit_sprite(sprite: &mut Sprite, mut stage: Option<&mut Sprite>) {
-    visit_costumes(&mut sprite.costumes);
-    for enum_ in sprite.enums.values_mut() {
+pub fn visit_project(
+    project: &mut Project,
+    input: &Path,
+    tests: bool,
+    stage_diagnostics: &mut SpriteDiagnostics,
+    sprites_diagnostics: &mut IndexMap<SmolStr, SpriteDiagnostics>,
+) {
+    let mut temps = 0;
+    for enum_ in project.stage.enums.values_mut() {
         visit_enum(enum_);
     }
+    visit_types(&mut project.stage, None);
+    visit_structs(&mut project.stage.structs, None, stage_diagnostics);
+    visit_sprite(
+        &mut project.stage,
+        None,
+        None,
+        None,
+        None,
+        None,
+        input,
+        tests,
+        stage_diagnostics,
+        &mut temps,
+    );
+    visit_consts(
+        &project.stage.consts,
+        &project.stage.structs,
+        None,
+        None,
+        stage_diagnostics,
+    );
+    for (sprite_name, sprite) in &mut project.sprites {
+        let d = sprites_diagnostics.get_mut(sprite_name).unwrap();
+        for enum_ in sprite.enums.values_mut() {
+            visit_enum(enum_);
+        }
+        visit_types(sprite, Some(&project.stage.enums));
+        visit_structs(&mut sprite.structs, Some(&project.stage.structs), d);
+        visit_sprite(
+            sprite,
+            Some(&mut project.stage.vars),
+            Some(&project.stage.lists),
+            Some(&project.stage.maps),
+            Some(&project.stage.enums),
+            Some(&project.stage.consts),
+            input,
+            tests,
+            d,
+            &mut temps,
+        );
+        visit_consts(
+            &sprite.consts,
+            &sprite.structs,
+            Some(&project.stage.structs),
+            Some(&project.stage.vars),
+            d,
+        );
+    }
+}
+
+fn visit_sprite(
+    sprite: &mut Sprite,
+    mut global_vars: Option<&mut IndexMap<SmolStr, Var>>,
+    global_lists: Option<&IndexMap<SmolStr, List>>,
+    global_maps: Option<&IndexMap<SmolStr, Map>>,
+    global_enums: Option<&IndexMap<SmolStr, Enum>>,
+    global_consts: Option<&IndexMap<SmolStr, Const>>,
+    input: &Path,
+    tests: bool,
+    d: D,
+    temps: &mut usize,
+) {
+    visit_costumes(&mut sprite.costumes);
+    visit_sounds(&mut sprite.sounds);
+    for map in sprite.maps.values() {
+        for name in [map.keys(), map.values()] {
+            let mut list = List::new(name.clone(), map.span.clone(), Type::Value);
+            list.is_used = true;
+            sprite.lists.insert(name, list);
+        }
+    }
+    let returns: IndexMap<SmolStr, usize> = sprite
+        .func_definitions
+        .iter()
+        .map(|(name, definition)| (name.clone(), return_count(definition)))
+        .collect();
     for proc in sprite.procs.values_mut() {
         let proc_definition = sprite.proc_definitions.get_mut(&proc.name).unwrap();
         visit_stmts(
             proc_definition,
             &mut V {
                 locals: Some(&mut proc.locals),
+                args: Some(&proc.args),
                 vars: &mut sprite.vars,
-                global_vars: stage.as_mut().map(|stage| &mut stage.vars),
+                global_vars: global_vars.as_deref_mut(),
+                lists: &sprite.lists,
+                global_lists,
+                maps: &sprite.maps,
+                global_maps,
+                enums: &sprite.enums,
+                global_enums,
+                consts: &sprite.consts,
+                global_consts,
+                returns: &returns,
+                temps,
+                input,
+                asserts: tests,
             },
+            d,
         );
     }
     for func in sprite.funcs.values_mut() {
-        let name: SmolStr = format!("{}:return", func.name).into();
-        sprite.vars.insert(
-            name.clone(),
-            Var {
-                name,
-                span: func.span.clone(),
-                type_: func.type_.clone(),
-                is_cloud: false,
-                is_used: true,
-            },
-        );
+        for slot in 0..returns[&func.name] {
+            let name = return_var(&func.name, slot);
+            sprite.vars.insert(
+                name.clone(),
+                Var {
+                    name,
+                    span: func.span.clone(),
+                    type_: if slot == 0 {
+                        func.type_.clone()
+                    } else {
+                        Type::Value
+                    },
+                    is_cloud: false,
+                    is_used: true,
+                },
+            );
+        }
         let func_definition = sprite.func_definitions.get_mut(&func.name).unwrap();
+        check_return_counts(func_definition, &func.span, d);
         visit_stmts(
             func_definition,
             &mut V {
                 locals: Some(&mut func.locals),
+                args: Some(&func.args),
                 vars: &mut sprite.vars,
-                global_vars: stage.as_mut().map(|stage| &mut stage.vars),
+                global_vars: global_vars.as_deref_mut(),
+                lists: &sprite.lists,
+                global_lists,
+                maps: &sprite.maps,
+                global_maps,
+                enums: &sprite.enums,
+                global_enums,
+                consts: &sprite.consts,
+                global_consts,
+                returns: &returns,
+                temps,
+                input,
+                asserts: tests,
             },
+            d,
         );
     }
     for event in &mut sprite.events {
@@ -62,13 +248,101 @@ fn visit_sprite(sprite: &mut Sprite, mut stage: Option<&mut Sprite>) {
             &mut event.body,
             &mut V {
                 locals: None,
+                args: None,
                 vars: &mut sprite.vars,
-                global_vars: stage.as_mut().map(|stage| &mut stage.vars),
+                global_vars: global_vars.as_deref_mut(),
+                lists: &sprite.lists,
+                global_lists,
+                maps: &sprite.maps,
+                global_maps,
+                enums: &sprite.enums,
+                global_enums,
+                consts: &sprite.consts,
+                global_consts,
+                returns: &returns,
+                temps,
+                input,
+                asserts: tests || matches!(event.kind, EventKind::Test { .. }),
             },
+            d,
         );
     }
 }
 
+/// Reports return statements which return a different number of values than the first
+/// return statement of the function.
+fn check_return_counts(definition: &[Stmt], func_span: &Span, d: D) {
+    let mut returns = vec![];
+    collect_returns(definition, &mut returns);
+    let Some((first, rest)) = returns.split_first() else {
+        return;
+    };
+    for values in rest {
+        if values.len() != first.len() {
+            let span = values.first().map_or_else(|| func_span.clone(), Expr::span);
+            d.report(
+                DiagnosticKind::ReturnCountMismatch {
+                    returns: values.len(),
+                    given: first.len(),
+                },
+                &span,
+            );
+        }
+    }
+}
+
+/// Collects the values of every return statement, in order.
+fn collect_returns<'a>(stmts: &'a [Stmt], returns: &mut Vec<&'a [Expr]>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Return { values, .. } => returns.push(values),
+            Stmt::Repeat { body, .. }
+            | Stmt::Forever { body, .. }
+            | Stmt::Until { body, .. }
+            | Stmt::ForRange { body, .. }
+            | Stmt::ForEach { body, .. } => collect_returns(body, returns),
+            Stmt::Branch {
+                if_body, else_body, ..
+            } => {
+                collect_returns(if_body, returns);
+                collect_returns(else_body, returns);
+            }
+            Stmt::Match { arms, .. } => {
+                for arm in arms {
+                    collect_returns(&arm.body, returns);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The number of values returned by a function, which is more than one if it returns a
+/// tuple.
+fn return_count(stmts: &[Stmt]) -> usize {
+    stmts
+        .iter()
+        .map(|stmt| match stmt {
+            Stmt::Return { values, .. } => values.len(),
+            Stmt::Repeat { body, .. }
+            | Stmt::Forever { body, .. }
+            | Stmt::Until { body, .. }
+            | Stmt::ForRange { body, .. }
+            | Stmt::ForEach { body, .. } => return_count(body),
+            Stmt::Branch {
+                if_body, else_body, ..
+            } => return_count(if_body).max(return_count(else_body)),
+            Stmt::Match { arms, .. } => arms
+                .iter()
+                .map(|arm| return_count(&arm.body))
+                .max()
+                .unwrap_or(1),
+            _ => 1,
+        })
+        .max()
+        .unwrap_or(1)
+}
+
 fn visit_enum(enum_: &mut Enum) {
     let mut index = 0;
     for variant in &mut enum_.variants {
@@ -83,6 +357,151 @@ fn visit_enum(enum_: &mut Enum) {
     }
 }
 
+/// Reports fields which make a struct contain itself, and fields with unrecognized
+/// struct types. These fields are turned into value fields, so that later passes can
+/// flatten nested structs without checking for cycles.
+/// Resolves the declared types which name an enum instead of a struct to
+/// [`Type::Enum`].
+fn visit_types(sprite: &mut Sprite, global_enums: Option<&IndexMap<SmolStr, Enum>>) {
+    let enums = &sprite.enums;
+    let is_enum = |name: &SmolStr| {
+        enums.contains_key(name)
+            || global_enums.is_some_and(|global_enums| global_enums.contains_key(name))
+    };
+    for list in sprite.lists.values_mut() {
+        resolve_enum_type(&mut list.type_, is_enum);
+    }
+    for proc in sprite.procs.values_mut() {
+        for arg in &mut proc.args {
+            resolve_enum_type(&mut arg.type_, is_enum);
+        }
+    }
+    for func in sprite.funcs.values_mut() {
+        resolve_enum_type(&mut func.type_, is_enum);
+        for arg in &mut func.args {
+            resolve_enum_type(&mut arg.type_, is_enum);
+        }
+    }
+    for struct_ in sprite.structs.values_mut() {
+        for field in &mut struct_.fields {
+            resolve_enum_type(&mut field.type_, is_enum);
+        }
+    }
+    for const_ in sprite.consts.values_mut() {
+        resolve_enum_type(&mut const_.type_, is_enum);
+    }
+}
+
+/// Reports constants with a struct type, as a constant holds a single value, and
+/// constants of a sprite which shadow a variable of the stage.
+fn visit_consts(
+    consts: &IndexMap<SmolStr, Const>,
+    structs: &IndexMap<SmolStr, Struct>,
+    global_structs: Option<&IndexMap<SmolStr, Struct>>,
+    global_vars: Option<&IndexMap<SmolStr, Var>>,
+    d: D,
+) {
+    for const_ in consts.values() {
+        if let Some((type_name, type_span)) = const_.type_.struct_() {
+            if structs.contains_key(type_name)
+                || global_structs
+                    .is_some_and(|global_structs| global_structs.contains_key(type_name))
+            {
+                d.report(
+                    DiagnosticKind::TypeMismatch {
+                        expected: const_.type_.clone(),
+                        given: Type::Value,
+                    },
+                    &const_.span,
+                );
+            } else {
+                d.report(
+                    DiagnosticKind::UnrecognizedStruct(type_name.clone()),
+                    type_span,
+                );
+            }
+        }
+        if global_vars.is_some_and(|global_vars| global_vars.contains_key(&const_.name)) {
+            d.report(
+                DiagnosticKind::ConstShadowsVariable(const_.name.clone()),
+                &const_.span,
+            );
+        }
+    }
+}
+
+fn resolve_enum_type(type_: &mut Type, is_enum: impl Fn(&SmolStr) -> bool) {
+    if let Type::Struct { name, span } = type_ {
+        if is_enum(name) {
+            *type_ = Type::Enum {
+                name: name.clone(),
+                span: span.clone(),
+            };
+        }
+    }
+}
+
+fn visit_structs(
+    structs: &mut IndexMap<SmolStr, Struct>,
+    global_structs: Option<&IndexMap<SmolStr, Struct>>,
+    d: D,
+) {
+    let names: Vec<SmolStr> = structs.keys().cloned().collect();
+    for name in names {
+        let get_struct = |name: &str| {
+            structs
+                .get(name)
+                .or_else(|| global_structs.and_then(|global_structs| global_structs.get(name)))
+        };
+        let mut invalid_fields = vec![];
+        for (i, field) in structs[&name].fields.iter().enumerate() {
+            let Some((type_name, type_span)) = field.type_.struct_() else {
+                continue;
+            };
+            if get_struct(type_name).is_none() {
+                d.report(
+                    DiagnosticKind::UnrecognizedStruct(type_name.clone()),
+                    type_span,
+                );
+                invalid_fields.push(i);
+            } else if contains_struct(type_name, &name, &get_struct, &mut vec![]) {
+                d.report(DiagnosticKind::RecursiveStruct(name.clone()), &field.span);
+                invalid_fields.push(i);
+            }
+        }
+        let struct_ = structs.get_mut(&name).unwrap();
+        for i in invalid_fields {
+            struct_.fields[i].type_ = Type::Value;
+        }
+    }
+}
+
+fn contains_struct<'a, F>(
+    type_name: &'a str,
+    target: &str,
+    get_struct: &F,
+    visited: &mut Vec<&'a str>,
+) -> bool
+where
+    F: Fn(&str) -> Option<&'a Struct>,
+{
+    if type_name == target {
+        return true;
+    }
+    if visited.contains(&type_name) {
+        return false;
+    }
+    visited.push(type_name);
+    let Some(struct_) = get_struct(type_name) else {
+        return false;
+    };
+    struct_.fields.iter().any(|field| {
+        field.type_.struct_().is_some_and(|(field_type_name, _)| {
+            contains_struct(field_type_name, target, get_struct, visited)
+        })
+    })
+}
+
 fn visit_costumes(new: &mut Vec<Costume>) {
     let old: Vec<Costume> = std::mem::take(new);
     for costume in old {
@@ -110,23 +529,470 @@ fn visit_costumes(new: &mut Vec<Costume>) {
     }
 }
 
-fn visit_stmts(stmts: &mut Vec<Stmt>, v: &mut V) {
-    for stmt in stmts {
-        visit_stmt(stmt, v);
+fn visit_sounds(new: &mut Vec<Sound>) {
+    let old: Vec<Sound> = std::mem::take(new);
+    for sound in old {
+        if sound.path.contains('*') {
+            let mut sounds: Vec<Sound> = glob(&sound.path)
+                .unwrap()
+                .map(Result::unwrap)
+                .map(|path| Sound {
+                    name: path.file_stem().unwrap().to_string_lossy().into(),
+                    path: path.to_string_lossy().into(),
+                    span: sound.span.clone(),
+                })
+                .collect();
+            sounds.sort_by(|a, b| a.name.cmp(&b.name));
+            new.extend(sounds);
+        } else {
+            new.push(sound);
+        }
     }
 }
 
-fn visit_stmt(stmt: &mut Stmt, v: &mut V) {
+fn visit_stmts(stmts: &mut Vec<Stmt>, v: &mut V, d: D) {
+    let mut i = 0;
+    while i < stmts.len() {
+        if matches!(stmts[i], Stmt::ForRange { .. } | Stmt::ForEach { .. }) {
+            let lowered = lower_for(stmts.remove(i), v);
+            stmts.splice(i..i, lowered);
+            continue;
+        }
+        if matches!(stmts[i], Stmt::Match { .. }) {
+            let lowered = lower_match(stmts.remove(i), v, d);
+            stmts.splice(i..i, lowered);
+            continue;
+        }
+        if matches!(&stmts[i], Stmt::SetListIndex { name, .. } | Stmt::DeleteListIndex { name, .. } if v.get_map(name.basename()).is_some())
+        {
+            let lowered = lower_map_index(stmts.remove(i), v);
+            stmts.splice(i..i, lowered);
+            continue;
+        }
+        if matches!(&stmts[i], Stmt::ProcCall { name, .. } if name == "assert") {
+            let lowered = lower_assert(stmts.remove(i), v, d);
+            stmts.splice(i..i, lowered);
+            continue;
+        }
+        if let Stmt::SetVar { name, .. } | Stmt::ChangeVar { name, .. } = &stmts[i] {
+            if v.get_const(name.basename()).is_some() {
+                d.report(
+                    DiagnosticKind::ConstReassignment(name.basename().clone()),
+                    &name.span(),
+                );
+                stmts.remove(i);
+                continue;
+            }
+        }
+        if let Stmt::Destructure { names, value, .. } = &stmts[i] {
+            if let Some(name) = names
+                .iter()
+                .find(|name| v.get_const(name.basename()).is_some())
+            {
+                d.report(
+                    DiagnosticKind::ConstReassignment(name.basename().clone()),
+                    &name.span(),
+                );
+                stmts.remove(i);
+                continue;
+            }
+            // Only function calls can return more than one value.
+            let returns = match value.as_ref() {
+                Expr::FuncCall { name, .. } => v.returns.get(name).copied(),
+                _ => Some(1),
+            };
+            if let Some(returns) = returns.filter(|&returns| returns != names.len()) {
+                d.report(
+                    DiagnosticKind::ReturnCountMismatch {
+                        returns,
+                        given: names.len(),
+                    },
+                    &value.span(),
+                );
+                stmts.remove(i);
+                continue;
+            }
+        }
+        visit_stmt(&mut stmts[i], v, d);
+        i += 1;
+    }
+}
+
+/// Lowers `assert` into an `if` statement, which calls `error` with the location of the
+/// `assert` and its message if the condition is false. `goboscript test` reports these
+/// errors as failed asserts. Asserts which are not compiled are removed.
+fn lower_assert(stmt: Stmt, v: &V, d: D) -> Option<Stmt> {
+    let Stmt::ProcCall { span, args, .. } = stmt else {
+        unreachable!()
+    };
+    if args.is_empty() || args.len() > 2 {
+        d.report(
+            DiagnosticKind::AssertArgsCountMismatch { given: args.len() },
+            &span,
+        );
+        return None;
+    }
+    if !v.asserts {
+        return None;
+    }
+    let mut args = args.into_iter().map(|(_, arg)| arg);
+    let cond = args.next().unwrap();
+    let (path, line, column) = d.translation_unit.source_location(span.start);
+    let path = path.strip_prefix(v.input).unwrap_or(path);
+    let location = format!("{}:{line}:{column}", path.display());
+    let message = match args.next() {
+        Some(message) => BinOp::Join.to_expr(
+            span.clone(),
+            Value::from(format!("{location}: ")).to_expr(span.clone()),
+            message,
+        ),
+        None => Value::from(format!("{location}: assertion failed")).to_expr(span.clone()),
+    };
+    Some(Stmt::Branch {
+        cond: Box::new(UnOp::Not.to_expr(span.clone(), cond)),
+        if_body: vec![Stmt::ProcCall {
+            name: "error".into(),
+            span,
+            args: vec![(None, message)],
+        }],
+        else_body: vec![],
+    })
+}
+
+/// Lowers `for` loops into a `repeat` loop. The loop variable is incremented at the
+/// start of each iteration, so that its value is the same for the entire body.
+fn lower_for(stmt: Stmt, v: &mut V) -> Vec<Stmt> {
+    match stmt {
+        Stmt::ForRange {
+            name,
+            is_local,
+            start,
+            end,
+            step,
+            body,
+        } => {
+            let span = name.span();
+            let is_local = is_local && v.locals.is_some();
+            // The bounds are evaluated once, in order, before the loop starts.
+            let mut lowered = vec![];
+            let start = evaluate_once(*start, "for", &span, v, &mut lowered);
+            let end = evaluate_once(*end, "for", &span, v, &mut lowered);
+            let step = step.map(|step| evaluate_once(*step, "for", &span, v, &mut lowered));
+            let mut count = BinOp::Sub.to_expr(span.clone(), end, start.clone());
+            if let Some(step) = &step {
+                count = BinOp::Div.to_expr(span.clone(), count, step.clone());
+            }
+            let count = BinOp::Add.to_expr(
+                span.clone(),
+                UnOp::Floor.to_expr(span.clone(), count),
+                Value::Int(1).to_expr(span.clone()),
+            );
+            let step = step.unwrap_or_else(|| Value::Int(1).to_expr(span.clone()));
+            let mut repeat_body = vec![Stmt::ChangeVar {
+                name: name.clone(),
+                value: Box::new(step.clone()),
+            }];
+            repeat_body.extend(body);
+            lowered.extend([
+                Stmt::SetVar {
+                    name,
+                    value: Box::new(BinOp::Sub.to_expr(span, start, step)),
+                    type_: Type::Value,
+                    is_local,
+                    is_cloud: false,
+                },
+                Stmt::Repeat {
+                    times: Box::new(count),
+                    body: repeat_body,
+                },
+            ]);
+            lowered
+        }
+        Stmt::ForEach {
+            name,
+            is_local,
+            list,
+            body,
+        } => {
+            let span = list.span();
+            let is_local = is_local && v.locals.is_some();
+            *v.temps += 1;
+            let counter = Name::Name {
+                name: format!("@for{}", *v.temps).into(),
+                span: span.clone(),
+            };
+            let type_ = v
+                .get_list(list.basename())
+                .map(|list| list.type_.clone())
+                .unwrap_or(Type::Value);
+            let mut repeat_body = vec![
+                Stmt::increment(counter.clone()),
+                Stmt::SetVar {
+                    name,
+                    value: Box::new(BinOp::Of.to_expr(
+                        span.clone(),
+                        Expr::Name(list.clone()),
+                        Expr::Name(counter.clone()),
+                    )),
+                    type_,
+                    is_local,
+                    is_cloud: false,
+                },
+            ];
+            repeat_body.extend(body);
+            vec![
+                Stmt::SetVar {
+                    name: counter,
+                    value: Box::new(Value::Int(0).to_expr(span.clone())),
+                    type_: Type::Value,
+                    is_local: v.locals.is_some(),
+                    is_cloud: false,
+                },
+                Stmt::Repeat {
+                    times: Box::new(UnOp::Length.to_expr(span, Expr::Name(list))),
+                    body: repeat_body,
+                },
+            ]
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Whether an expression can be evaluated again without side effects or extra work,
+/// so that it does not need to be stored in a temporary variable.
+/// Lowers setting or deleting the value for a key of a map to statements on the map's
+/// keys and values lists. The key and its index in the keys list are stored in
+/// temporary variables, so that the key is evaluated and looked up once.
+fn lower_map_index(stmt: Stmt, v: &mut V) -> Vec<Stmt> {
+    let (Stmt::SetListIndex { name, index, .. } | Stmt::DeleteListIndex { name, index }) = &stmt
+    else {
+        unreachable!()
+    };
+    let map = v.get_map(name.basename()).unwrap();
+    let keys = map_list(name, map.keys());
+    let values = map_list(name, map.values());
+    let basename = name.basename().clone();
+    let span = name.span();
+    let key_span = index.span();
+    let mut lowered = vec![];
     match stmt {
-        Stmt::Repeat { body, .. } => visit_stmts(body, v),
-        Stmt::Forever { body, .. } => visit_stmts(body, v),
+        Stmt::SetListIndex { index, value, .. } => {
+            let key = evaluate_once(*index, "map", &span, v, &mut lowered);
+            let index = temp_index(&key, &keys, &span, v, &mut lowered);
+            // The value of an augmented assignment such as `map[key] += 1` reads the
+            // entry which is assigned, which is at the same index.
+            let mut value = *value;
+            if let Expr::BinOp { lhs, .. } = &mut value {
+                if let Expr::BinOp {
+                    op: BinOp::Of,
+                    span,
+                    lhs: map,
+                    rhs: key,
+                } = lhs.as_ref()
+                {
+                    if matches!(map.as_ref(), Expr::Name(map) if map.basename() == &basename)
+                        && key.span() == key_span
+                    {
+                        **lhs = BinOp::Of.to_expr(
+                            span.clone(),
+                            Expr::Name(values.clone()),
+                            index.clone(),
+                        );
+                    }
+                }
+            }
+            lowered.push(Stmt::Branch {
+                cond: Box::new(index.clone()),
+                if_body: vec![Stmt::SetListIndex {
+                    name: values.clone(),
+                    index: Box::new(index),
+                    value: Box::new(value.clone()),
+                }],
+                else_body: vec![
+                    Stmt::AddToList {
+                        name: keys,
+                        value: Box::new(key),
+                    },
+                    Stmt::AddToList {
+                        name: values,
+                        value: Box::new(value),
+                    },
+                ],
+            });
+        }
+        // The value is deleted first, as the index of `key` is looked up in the keys
+        // list.
+        Stmt::DeleteListIndex { index, .. } => {
+            let index = temp_index(&index, &keys, &span, v, &mut lowered);
+            lowered.push(Stmt::DeleteListIndex {
+                name: values,
+                index: Box::new(index.clone()),
+            });
+            lowered.push(Stmt::DeleteListIndex {
+                name: keys,
+                index: Box::new(index),
+            });
+        }
+        _ => unreachable!(),
+    }
+    lowered
+}
+
+/// Stores the index of `key` in a map's keys list in a temporary variable.
+fn temp_index(key: &Expr, keys: &Name, span: &Span, v: &mut V, lowered: &mut Vec<Stmt>) -> Expr {
+    evaluate_once(map_index(key, keys), "map", span, v, lowered)
+}
+
+fn is_cheap(expr: &Expr) -> bool {
+    match expr {
+        Expr::Value { .. } | Expr::Name(_) | Expr::Arg(_) => true,
+        Expr::Dot { lhs, .. } => matches!(lhs.as_ref(), Expr::Name(_) | Expr::Arg(_)),
+        _ => false,
+    }
+}
+
+/// Returns `expr` if it is cheap, otherwise stores it in a temporary variable named
+/// after `kind` and returns the temporary variable.
+fn evaluate_once(expr: Expr, kind: &str, span: &Span, v: &mut V, lowered: &mut Vec<Stmt>) -> Expr {
+    if is_cheap(&expr) {
+        return expr;
+    }
+    *v.temps += 1;
+    let temp = Name::Name {
+        name: format!("@{kind}{}", *v.temps).into(),
+        span: span.clone(),
+    };
+    lowered.push(Stmt::SetVar {
+        name: temp.clone(),
+        value: Box::new(expr),
+        type_: Type::Value,
+        is_local: v.locals.is_some(),
+        is_cloud: false,
+    });
+    Expr::Name(temp)
+}
+
+/// Lowers a `match` statement into a chain of `if`/`elif`/`else` branches, which
+/// compare the scrutinee against each pattern in order. Unless the scrutinee is a
+/// variable or a constant, it is first stored in a temporary variable so that it is
+/// only evaluated once.
+fn lower_match(stmt: Stmt, v: &mut V, d: D) -> Vec<Stmt> {
+    let Stmt::Match {
+        scrutinee,
+        arms,
+        span,
+    } = stmt
+    else {
+        unreachable!()
+    };
+    if let Some(wildcard) = arms.iter().position(MatchArm::is_wildcard) {
+        if wildcard != arms.len() - 1 {
+            d.report(
+                DiagnosticKind::FollowedByUnreachableCode,
+                &arms[wildcard].span,
+            );
+        }
+    } else {
+        check_match_exhaustive(&scrutinee, &arms, &span, v, d);
+    }
+    let mut lowered = vec![];
+    let scrutinee = evaluate_once(*scrutinee, "match", &span, v, &mut lowered);
+    let mut else_body = vec![];
+    for arm in arms.into_iter().rev() {
+        if arm.is_wildcard() {
+            else_body = arm.body;
+            continue;
+        }
+        let cond = arm
+            .patterns
+            .into_iter()
+            .map(|pattern| BinOp::Eq.to_expr(arm.span.clone(), scrutinee.clone(), pattern))
+            .reduce(|lhs, rhs| BinOp::Or.to_expr(arm.span.clone(), lhs, rhs))
+            .unwrap();
+        else_body = vec![Stmt::Branch {
+            cond: Box::new(cond),
+            if_body: arm.body,
+            else_body,
+        }];
+    }
+    lowered.extend(else_body);
+    lowered
+}
+
+/// Warns about the variants of an enum which are not matched by a `match` statement
+/// without a wildcard arm. The enum is the type of the scrutinee, or if the scrutinee
+/// is not typed, the enum of the patterns if they are all variants of the same enum.
+fn check_match_exhaustive(scrutinee: &Expr, arms: &[MatchArm], span: &Span, v: &V, d: D) {
+    let patterns = || {
+        arms.iter()
+            .flat_map(|arm| &arm.patterns)
+            .filter_map(|pattern| match pattern {
+                Expr::Dot { lhs, rhs, .. } => match lhs.as_ref() {
+                    Expr::Name(Name::Name { name, .. }) => Some((name, rhs)),
+                    _ => None,
+                },
+                _ => None,
+            })
+    };
+    let enum_ = scrutinee_enum(scrutinee, v).or_else(|| {
+        let mut names = patterns().map(|(name, _)| name);
+        let first = names.next()?;
+        if names.any(|name| name != first) {
+            return None;
+        }
+        v.get_enum(first)
+    });
+    let Some(enum_) = enum_ else {
+        return;
+    };
+    let covered: Vec<&SmolStr> = patterns()
+        .filter(|(name, _)| **name == enum_.name)
+        .map(|(_, variant)| variant)
+        .collect();
+    let missing: Vec<SmolStr> = enum_
+        .variants
+        .iter()
+        .filter(|variant| !covered.contains(&&variant.name))
+        .map(|variant| format!("{}.{}", enum_.name, variant.name).into())
+        .collect();
+    if !missing.is_empty() {
+        d.report(DiagnosticKind::NonExhaustiveMatch(missing), span);
+    }
+}
+
+/// The enum of a variable or argument declared with an enum type, or of an enum
+/// variant.
+fn scrutinee_enum<'a>(scrutinee: &Expr, v: &'a V) -> Option<&'a Enum> {
+    let type_ = match scrutinee {
+        Expr::Name(Name::Name { name, .. }) => &v.get_var(name)?.type_,
+        Expr::Arg(Name::Name { name, .. }) => &v.args?.iter().find(|arg| &arg.name == name)?.type_,
+        Expr::Dot { lhs, .. } => {
+            let Expr::Name(Name::Name { name, .. }) = lhs.as_ref() else {
+                return None;
+            };
+            return v.get_enum(name);
+        }
+        _ => return None,
+    };
+    let Type::Enum { name, .. } = type_ else {
+        return None;
+    };
+    v.get_enum(name)
+}
+
+fn visit_stmt(stmt: &mut Stmt, v: &mut V, d: D) {
+    check_single_returns(stmt, v, d);
+    match stmt {
+        Stmt::Repeat { body, .. } => visit_stmts(body, v, d),
+        Stmt::Forever { body, .. } => visit_stmts(body, v, d),
         Stmt::Branch {
             if_body, else_body, ..
         } => {
-            visit_stmts(if_body, v);
-            visit_stmts(else_body, v)
+            visit_stmts(if_body, v, d);
+            visit_stmts(else_body, v, d)
         }
-        Stmt::Until { body, .. } => visit_stmts(body, v),
+        Stmt::Until { body, .. } => visit_stmts(body, v, d),
+        Stmt::ForRange { .. } | Stmt::ForEach { .. } | Stmt::Match { .. } => unreachable!(),
         Stmt::SetVar {
             name,
             type_,
@@ -134,59 +1000,140 @@ fn visit_stmt(stmt: &mut Stmt, v: &mut V) {
             is_cloud,
             ..
         } => {
-            let basename = name.basename();
-            let var = Var {
-                name: basename.clone(),
-                span: name.span(),
-                type_: type_.clone(),
-                is_cloud: *is_cloud,
-                is_used: false,
-            };
-            if *is_local {
-                if let Some(locals) = &mut v.locals {
-                    if let Some(existing_declaration) = locals.get(basename) {
-                        if existing_declaration.type_.is_value() {
-                            locals.insert(basename.clone(), var);
-                        }
-                    } else {
-                        locals.insert(basename.clone(), var);
-                    }
-                }
-                return;
+            resolve_enum_type(type_, |name| v.get_enum(name).is_some());
+            declare_var(name, type_, *is_local, *is_cloud, v)
+        }
+        Stmt::Destructure {
+            names, is_local, ..
+        } => {
+            for name in names {
+                declare_var(name, &Type::Value, *is_local, false, v);
             }
-            if v.locals
-                .as_ref()
-                .is_some_and(|locals| locals.contains_key(basename))
-            {
-                return;
+        }
+        _ => (),
+    }
+}
+
+/// Reports calls of functions which return more than one value in the expressions of
+/// `stmt`, as an expression only has a single value. The values of a function call are
+/// only used together when they are destructured.
+fn check_single_returns(stmt: &Stmt, v: &V, d: D) {
+    let exprs: Vec<&Expr> = match stmt {
+        Stmt::Repeat { times, .. } => vec![times],
+        Stmt::Branch { cond, .. } | Stmt::Until { cond, .. } => vec![cond],
+        Stmt::SetVar { value, .. }
+        | Stmt::ChangeVar { value, .. }
+        | Stmt::AddToList { value, .. } => vec![value],
+        Stmt::DeleteListIndex { index, .. } => vec![index],
+        Stmt::InsertAtList { index, value, .. } | Stmt::SetListIndex { index, value, .. } => {
+            vec![index, value]
+        }
+        Stmt::Block { args, .. } | Stmt::ProcCall { args, .. } | Stmt::FuncCall { args, .. } => {
+            args.iter().map(|(_, arg)| arg).collect()
+        }
+        Stmt::Destructure { value, .. } => match value.as_ref() {
+            Expr::FuncCall { args, .. } => args.iter().map(|(_, arg)| arg).collect(),
+            value => vec![value],
+        },
+        Stmt::Return { values, .. } => values.iter().collect(),
+        _ => vec![],
+    };
+    for expr in exprs {
+        check_single_return(expr, v, d);
+    }
+}
+
+fn check_single_return(expr: &Expr, v: &V, d: D) {
+    match expr {
+        Expr::Value { .. } | Expr::Name(_) | Expr::Arg(_) => {}
+        Expr::Dot { lhs, .. } => check_single_return(lhs, v, d),
+        Expr::Repr { args, .. } => {
+            for (_, arg) in args {
+                check_single_return(arg, v, d);
             }
-            if v.global_vars
-                .as_ref()
-                .is_some_and(|global_vars| global_vars.contains_key(basename))
-            {
-                return;
-            }
-            if let Some(existing_declaration) = v.vars.get(basename) {
-                // This condition ensures that variables with a specific type (e.g., a struct type) are not overwritten
-                // by a previous statement that didn't specify a type (which defaults to type `Value`).
-                // In this context, variables don't need to be explicitly declared if the type is `Value`.
-                // The syntax for setting variables is as follows:
-                // - For `Value` type: `variable_name = value;`
-                // - For a specific struct type: `typeName variable_name = value;`
-                //
-                // Since the visitor processes every variable assignment statement, this check ensures that if an
-                // existing variable has a specific type (not `Value`), it is preserved when a new statement tries to
-                // reassign it without a type (defaulting to `Value`). Only variables that are of type `Value` can be
-                // overwritten by the new assignment.
-
-                // TODO: Make redeclaration of variables with different struct types an error.
+        }
+        Expr::FuncCall { name, span, args } => {
+            if let Some(&returns) = v.returns.get(name).filter(|&&returns| returns > 1) {
+                d.report(
+                    DiagnosticKind::ReturnCountMismatch { returns, given: 1 },
+                    span,
+                );
+            }
+            for (_, arg) in args {
+                check_single_return(arg, v, d);
+            }
+        }
+        Expr::UnOp { opr, .. } => check_single_return(opr, v, d),
+        Expr::BinOp { lhs, rhs, .. } => {
+            check_single_return(lhs, v, d);
+            check_single_return(rhs, v, d);
+        }
+        Expr::StructLiteral { fields, .. } => {
+            for field in fields {
+                check_single_return(&field.value, v, d);
+            }
+        }
+        Expr::Ternary {
+            cond, then, else_, ..
+        } => {
+            check_single_return(cond, v, d);
+            check_single_return(then, v, d);
+            check_single_return(else_, v, d);
+        }
+    }
+}
+
+fn declare_var(name: &Name, type_: &Type, is_local: bool, is_cloud: bool, v: &mut V) {
+    let basename = name.basename();
+    let var = Var {
+        name: basename.clone(),
+        span: name.span(),
+        type_: type_.clone(),
+        is_cloud,
+        is_used: false,
+    };
+    if is_local {
+        if let Some(locals) = &mut v.locals {
+            if let Some(existing_declaration) = locals.get(basename) {
                 if existing_declaration.type_.is_value() {
-                    v.vars.insert(basename.clone(), var);
+                    locals.insert(basename.clone(), var);
                 }
             } else {
-                v.vars.insert(basename.clone(), var);
+                locals.insert(basename.clone(), var);
             }
         }
-        _ => (),
+        return;
+    }
+    if v.locals
+        .as_ref()
+        .is_some_and(|locals| locals.contains_key(basename))
+    {
+        return;
+    }
+    if v.global_vars
+        .as_ref()
+        .is_some_and(|global_vars| global_vars.contains_key(basename))
+    {
+        return;
+    }
+    if let Some(existing_declaration) = v.vars.get(basename) {
+        // This condition ensures that variables with a specific type (e.g., a struct type) are not overwritten
+        // by a previous statement that didn't specify a type (which defaults to type `Value`).
+        // In this context, variables don't need to be explicitly declared if the type is `Value`.
+        // The syntax for setting variables is as follows:
+        // - For `Value` type: `variable_name = value;`
+        // - For a specific struct type: `typeName variable_name = value;`
+        //
+        // Since the visitor processes every variable assignment statement, this check ensures that if an
+        // existing variable has a specific type (not `Value`), it is preserved when a new statement tries to
+        // reassign it without a type (defaulting to `Value`). Only variables that are of type `Value` can be
+        // overwritten by the new assignment.
+
+        // TODO: Make redeclaration of variables with different struct types an error.
+        if existing_declaration.type_.is_value() {
+            v.vars.insert(basename.clone(), var);
+        }
+    } else {
+        v.vars.insert(basename.clone(), var);
     }
 }
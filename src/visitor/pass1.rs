@@ -1,20 +1,27 @@
-use fxhash::{
-    FxHashMap,
-    FxHashSet,
-};
+use fxhash::FxHashSet;
 
+use super::transformations::coerce_condition;
 use crate::{
     ast::*,
-    misc::SmolStr,
+    blocks::{
+        BinOp,
+        UnOp,
+    },
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
 };
 
 struct S<'a> {
     references: &'a mut References,
-    vars: &'a mut FxHashMap<SmolStr, Var>,
+    vars: &'a mut IndexMap<SmolStr, Var>,
     callsites: &'a mut usize,
-    funcs: &'a FxHashMap<SmolStr, Func>,
+    funcs: &'a IndexMap<SmolStr, Func>,
     func: Option<&'a Func>,
-    proc: Option<&'a Proc>,
+    /// The local variables of the procedure or function being visited, temporary
+    /// variables are declared here instead of in `vars` when it is present.
+    locals: Option<&'a mut IndexMap<SmolStr, Var>>,
     used_args: Option<&'a mut FxHashSet<SmolStr>>,
 }
 
@@ -40,15 +47,17 @@ fn visit_sprite(sprite: &mut Sprite, callsites: &mut usize) {
                 callsites,
                 funcs: &sprite.funcs,
                 func: None,
-                proc: Some(proc),
+                locals: Some(&mut proc.locals),
                 used_args: Some(used_args),
             },
         );
     }
+    let mut func_locals = vec![];
     for func in sprite.funcs.values() {
         let func_definition = sprite.func_definitions.get_mut(&func.name).unwrap();
         let func_references = sprite.func_references.get_mut(&func.name).unwrap();
         let used_args = sprite.func_used_args.get_mut(&func.name).unwrap();
+        let mut locals = IndexMap::default();
         visit_stmts(
             func_definition,
             &mut S {
@@ -57,10 +66,14 @@ fn visit_sprite(sprite: &mut Sprite, callsites: &mut usize) {
                 callsites,
                 funcs: &sprite.funcs,
                 func: Some(func),
-                proc: None,
+                locals: Some(&mut locals),
                 used_args: Some(used_args),
             },
         );
+        func_locals.push((func.name.clone(), locals));
+    }
+    for (name, locals) in func_locals {
+        sprite.funcs.get_mut(&name).unwrap().locals.extend(locals);
     }
     for event in &mut sprite.events {
         visit_stmts(
@@ -71,7 +84,7 @@ fn visit_sprite(sprite: &mut Sprite, callsites: &mut usize) {
                 callsites,
                 funcs: &sprite.funcs,
                 func: None,
-                proc: None,
+                locals: None,
                 used_args: None,
             },
         );
@@ -114,6 +127,10 @@ fn visit_stmt(stmt: &mut Stmt, s: &mut S) -> Vec<Stmt> {
             visit_expr(cond, &mut before, s);
             visit_stmts(body, s);
         }
+        Stmt::ForRange { .. } | Stmt::ForEach { .. } | Stmt::Match { .. } => {
+            unreachable!("lowered in pass0")
+        }
+        Stmt::Break(_) | Stmt::Continue(_) => unreachable!("lowered in loops"),
         Stmt::SetVar {
             name: _,
             value,
@@ -180,23 +197,68 @@ fn visit_stmt(stmt: &mut Stmt, s: &mut S) -> Vec<Stmt> {
                 visit_expr(arg, &mut before, s);
             }
         }
-        Stmt::Return { value, visited } => {
-            if !*visited {
-                *visited = true;
+        Stmt::Destructure {
+            names,
+            value,
+            is_local,
+        } => {
+            let Expr::FuncCall {
+                name: func,
+                span,
+                args,
+            } = value.as_mut()
+            else {
+                unreachable!("checked in pass0")
+            };
+            s.references.funcs.insert(func.clone());
+            for (_, arg) in &mut *args {
+                visit_expr(arg, &mut before, s);
+            }
+            before.push(Stmt::FuncCall {
+                name: func.clone(),
+                span: span.clone(),
+                args: args.clone(),
+            });
+            let mut set_vars: Vec<Stmt> = names
+                .iter()
+                .enumerate()
+                .map(|(slot, name)| Stmt::SetVar {
+                    name: name.clone(),
+                    value: Box::new(Expr::Name(Name::Name {
+                        name: return_var(func, slot),
+                        span: span.clone(),
+                    })),
+                    type_: Type::Value,
+                    is_local: *is_local,
+                    is_cloud: false,
+                })
+                .collect();
+            *stmt = set_vars.pop().unwrap();
+            before.extend(set_vars);
+        }
+        Stmt::Return { values, visited } => {
+            for (slot, value) in values.iter_mut().enumerate() {
+                // The span of the return value is kept before it is lowered, so that type
+                // mismatches with the function's return type are reported at the return.
+                let span = value.span();
+                visit_expr(value, &mut before, s);
+                if *visited {
+                    continue;
+                }
                 if let Some(func) = s.func {
                     before.push(Stmt::SetVar {
                         name: Name::Name {
-                            name: format!("{}:return", func.name).into(),
-                            span: 0..0,
+                            name: return_var(&func.name, slot),
+                            span,
                         },
-                        value: value.clone(),
+                        value: Box::new(value.clone()),
                         type_: Type::Value,
                         is_local: false,
                         is_cloud: false,
                     })
                 }
             }
-            visit_expr(value, &mut before, s);
+            *visited = true;
         }
     }
     before
@@ -211,9 +273,15 @@ fn visit_expr(expr: &mut Expr, before: &mut Vec<Stmt>, s: &mut S) {
         }
         Expr::Dot {
             lhs,
-            rhs: _,
+            rhs,
             rhs_span: _,
         } => {
+            // Recorded for any name, pass3 only marks it if the name is an enum.
+            if let Expr::Name(name) = &**lhs {
+                s.references
+                    .enum_variants
+                    .insert((name.basename().clone(), rhs.clone()));
+            }
             visit_expr(lhs, before, s);
             None
         }
@@ -258,7 +326,7 @@ fn visit_expr(expr: &mut Expr, before: &mut Vec<Stmt>, s: &mut S) {
                 before.push(Stmt::SetVar {
                     name: callsite.clone(),
                     value: Box::new(Expr::Name(Name::Name {
-                        name: format!("{}:return", name).into(),
+                        name: return_var(name, 0),
                         span: span.clone(),
                     })),
                     type_: Type::Value,
@@ -302,8 +370,86 @@ fn visit_expr(expr: &mut Expr, before: &mut Vec<Stmt>, s: &mut S) {
             }
             None
         }
+        Expr::Ternary {
+            span,
+            cond,
+            then,
+            else_,
+        } => {
+            visit_expr(cond, before, s);
+            if is_number(then) && is_number(else_) {
+                let cond = coerce_condition(cond).unwrap_or_else(|| cond.as_ref().clone());
+                Some(BinOp::Add.to_expr(
+                    span.clone(),
+                    else_.as_ref().clone(),
+                    BinOp::Mul.to_expr(
+                        span.clone(),
+                        cond,
+                        BinOp::Sub.to_expr(
+                            span.clone(),
+                            then.as_ref().clone(),
+                            else_.as_ref().clone(),
+                        ),
+                    ),
+                ))
+            } else {
+                let mut if_body = vec![];
+                let mut else_body = vec![];
+                visit_expr(then, &mut if_body, s);
+                visit_expr(else_, &mut else_body, s);
+                *s.callsites += 1;
+                let temp = Name::Name {
+                    name: format!("@{}", *s.callsites).into(),
+                    span: span.clone(),
+                };
+                let var = Var {
+                    name: temp.basename().clone(),
+                    span: temp.basespan().clone(),
+                    type_: Type::Value,
+                    is_cloud: false,
+                    is_used: true,
+                };
+                // Inside a procedure or function the temporary variable is one of its
+                // local variables.
+                let is_local = s.locals.is_some();
+                match &mut s.locals {
+                    Some(locals) => locals.insert(var.name.clone(), var),
+                    None => s.vars.insert(var.name.clone(), var),
+                };
+                for (body, value) in [(&mut if_body, then), (&mut else_body, else_)] {
+                    body.push(Stmt::SetVar {
+                        name: temp.clone(),
+                        value: value.clone(),
+                        type_: Type::Value,
+                        is_local,
+                        is_cloud: false,
+                    });
+                }
+                before.push(Stmt::Branch {
+                    cond: cond.clone(),
+                    if_body,
+                    else_body,
+                });
+                Some(Expr::Name(temp))
+            }
+        }
     };
     if let Some(replace) = replace {
         *expr = replace;
     }
 }
+
+fn is_number(expr: &Expr) -> bool {
+    match expr {
+        Expr::Value {
+            value: Value::Int(_) | Value::Float(_),
+            ..
+        } => true,
+        Expr::UnOp {
+            op: UnOp::Minus,
+            opr,
+            ..
+        } => is_number(opr),
+        _ => false,
+    }
+}
@@ -1,5 +1,3 @@
-use fxhash::FxHashMap;
-
 use super::transformations::{
     self,
     keyword_arguments,
@@ -18,23 +16,30 @@ use crate::{
         DiagnosticKind,
         SpriteDiagnostics,
     },
-    misc::SmolStr,
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
 };
 
 #[derive(Copy, Clone)]
 pub struct S<'a> {
     pub args: Option<&'a Vec<Arg>>,
-    pub local_vars: Option<&'a FxHashMap<SmolStr, Var>>,
-    pub vars: &'a FxHashMap<SmolStr, Var>,
-    pub lists: &'a FxHashMap<SmolStr, List>,
-    pub enums: &'a FxHashMap<SmolStr, Enum>,
-    pub structs: &'a FxHashMap<SmolStr, Struct>,
-    pub procs: &'a FxHashMap<SmolStr, Proc>,
-    pub funcs: &'a FxHashMap<SmolStr, Func>,
-    pub global_vars: Option<&'a FxHashMap<SmolStr, Var>>,
-    pub global_lists: Option<&'a FxHashMap<SmolStr, List>>,
-    pub global_enums: Option<&'a FxHashMap<SmolStr, Enum>>,
-    pub global_structs: Option<&'a FxHashMap<SmolStr, Struct>>,
+    pub local_vars: Option<&'a IndexMap<SmolStr, Var>>,
+    pub vars: &'a IndexMap<SmolStr, Var>,
+    pub lists: &'a IndexMap<SmolStr, List>,
+    pub maps: &'a IndexMap<SmolStr, Map>,
+    pub enums: &'a IndexMap<SmolStr, Enum>,
+    pub structs: &'a IndexMap<SmolStr, Struct>,
+    pub consts: &'a IndexMap<SmolStr, Const>,
+    pub procs: &'a IndexMap<SmolStr, Proc>,
+    pub funcs: &'a IndexMap<SmolStr, Func>,
+    pub global_vars: Option<&'a IndexMap<SmolStr, Var>>,
+    pub global_lists: Option<&'a IndexMap<SmolStr, List>>,
+    pub global_maps: Option<&'a IndexMap<SmolStr, Map>>,
+    pub global_enums: Option<&'a IndexMap<SmolStr, Enum>>,
+    pub global_structs: Option<&'a IndexMap<SmolStr, Struct>>,
+    pub global_consts: Option<&'a IndexMap<SmolStr, Const>>,
 }
 
 impl S<'_> {
@@ -55,18 +60,32 @@ impl S<'_> {
         })
     }
 
+    pub fn get_map(&self, name: &str) -> Option<&Map> {
+        self.maps.get(name).or_else(|| {
+            self.global_maps
+                .and_then(|global_maps| global_maps.get(name))
+        })
+    }
+
     pub fn get_struct(&self, name: &str) -> Option<&Struct> {
         self.structs.get(name).or_else(|| {
             self.global_structs
                 .and_then(|global_structs| global_structs.get(name))
         })
     }
+
+    pub fn get_const(&self, name: &str) -> Option<&Const> {
+        self.consts.get(name).or_else(|| {
+            self.global_consts
+                .and_then(|global_consts| global_consts.get(name))
+        })
+    }
 }
 
 pub fn visit_project(
     project: &mut Project,
     stage_diagnostics: &mut SpriteDiagnostics,
-    sprites_diagnostics: &mut FxHashMap<SmolStr, SpriteDiagnostics>,
+    sprites_diagnostics: &mut IndexMap<SmolStr, SpriteDiagnostics>,
 ) {
     visit_sprite(&mut project.stage, None, stage_diagnostics);
     for (sprite_name, sprite) in &mut project.sprites {
@@ -88,14 +107,18 @@ fn visit_sprite(sprite: &mut Sprite, stage: Option<&Sprite>, d: D) {
                 local_vars: Some(&proc.locals),
                 vars: &sprite.vars,
                 lists: &sprite.lists,
+                maps: &sprite.maps,
                 enums: &sprite.enums,
                 structs: &sprite.structs,
+                consts: &sprite.consts,
                 procs: &sprite.procs,
                 funcs: &sprite.funcs,
                 global_vars: stage.map(|stage| &stage.vars),
                 global_lists: stage.map(|stage| &stage.lists),
+                global_maps: stage.map(|stage| &stage.maps),
                 global_enums: stage.map(|stage| &stage.enums),
                 global_structs: stage.map(|stage| &stage.structs),
+                global_consts: stage.map(|stage| &stage.consts),
             },
             d,
             true,
@@ -110,14 +133,18 @@ fn visit_sprite(sprite: &mut Sprite, stage: Option<&Sprite>, d: D) {
                 local_vars: Some(&func.locals),
                 vars: &sprite.vars,
                 lists: &sprite.lists,
+                maps: &sprite.maps,
                 enums: &sprite.enums,
                 structs: &sprite.structs,
+                consts: &sprite.consts,
                 procs: &sprite.procs,
                 funcs: &sprite.funcs,
                 global_vars: stage.map(|stage| &stage.vars),
                 global_lists: stage.map(|stage| &stage.lists),
+                global_maps: stage.map(|stage| &stage.maps),
                 global_enums: stage.map(|stage| &stage.enums),
                 global_structs: stage.map(|stage| &stage.structs),
+                global_consts: stage.map(|stage| &stage.consts),
             },
             d,
             true,
@@ -131,14 +158,18 @@ fn visit_sprite(sprite: &mut Sprite, stage: Option<&Sprite>, d: D) {
                 local_vars: None,
                 vars: &sprite.vars,
                 lists: &sprite.lists,
+                maps: &sprite.maps,
                 enums: &sprite.enums,
                 structs: &sprite.structs,
+                consts: &sprite.consts,
                 procs: &sprite.procs,
                 funcs: &sprite.funcs,
                 global_vars: stage.map(|stage| &stage.vars),
                 global_lists: stage.map(|stage| &stage.lists),
+                global_maps: stage.map(|stage| &stage.maps),
                 global_enums: stage.map(|stage| &stage.enums),
                 global_structs: stage.map(|stage| &stage.structs),
+                global_consts: stage.map(|stage| &stage.consts),
             },
             d,
             true,
@@ -164,32 +195,32 @@ fn visit_stmts(stmts: &mut Vec<Stmt>, s: S, d: D, top_level: bool) {
                 visit_stmt_list_set(s, d, name, index, value)
             }
             Stmt::AddToList { name, value } => visit_stmt_list_add(s, d, name, value),
-            Stmt::DeleteList(name) => visit_stmt_delete_list(s, d, name),
+            Stmt::DeleteList(name) => {
+                visit_stmt_delete_map(s, name).or_else(|| visit_stmt_delete_list(s, d, name))
+            }
             Stmt::DeleteListIndex { name, index } => {
                 visit_stmt_delete_list_index(s, d, name, index)
             }
             Stmt::InsertAtList { name, index, value } => {
                 visit_stmt_insert_at_list(s, d, name, index, value)
             }
-            Stmt::Return { value, .. } => {
+            Stmt::Return { .. } => {
                 // Don't add stop_this_script after return stmt if it's the last stmt.
                 if top_level && i == stmts.len() - 1 {
                     Some(vec![])
                 } else {
-                    visit_stmt_return(value)
+                    visit_stmt_return()
                 }
             }
             _ => None,
         };
         if let Some(replace) = replace {
             let len = replace.len();
-            stmts.remove(i);
-            for replace in replace.into_iter().rev() {
-                stmts.insert(i, replace);
-            }
-            i += len.saturating_sub(1);
+            stmts.splice(i..=i, replace);
+            i += len;
+        } else {
+            i += 1;
         }
-        i += 1;
     }
 }
 
@@ -215,6 +246,10 @@ fn visit_stmt(stmt: &mut Stmt, s: S, d: D) {
             visit_expr(cond, s, d, true);
             visit_stmts(body, s, d, false);
         }
+        Stmt::ForRange { .. } | Stmt::ForEach { .. } | Stmt::Match { .. } => {
+            unreachable!("lowered in pass0")
+        }
+        Stmt::Break(_) | Stmt::Continue(_) => unreachable!("lowered in loops"),
         Stmt::SetVar {
             name: _,
             value,
@@ -282,7 +317,12 @@ fn visit_stmt(stmt: &mut Stmt, s: S, d: D) {
                 visit_expr(arg, s, d, false);
             }
         }
-        Stmt::Return { value, .. } => visit_expr(value, s, d, false),
+        Stmt::Destructure { .. } => unreachable!("lowered in pass1"),
+        Stmt::Return { values, .. } => {
+            for value in values {
+                visit_expr(value, s, d, false);
+            }
+        }
     }
 }
 
@@ -354,7 +394,9 @@ fn visit_expr(expr: &mut Expr, s: S, d: D, coerce_condition: bool) {
                 visit_expr(&mut field.value, s, d, false);
             }
         }
+        Expr::Ternary { .. } => unreachable!("lowered in pass1"),
     }
+    transformations::apply(expr, |expr| transformations::const_access(expr, s));
     transformations::apply(expr, transformations::minus);
     transformations::apply(expr, transformations::less_than_equal);
     transformations::apply(expr, transformations::greater_than_equal);
@@ -362,6 +404,9 @@ fn visit_expr(expr: &mut Expr, s: S, d: D, coerce_condition: bool) {
     transformations::apply(expr, transformations::floor_div);
     transformations::apply(expr, transformations::bin_op);
     transformations::apply(expr, transformations::un_op);
+    transformations::apply(expr, |expr| transformations::map_access(expr, s));
+    transformations::apply(expr, |expr| transformations::map_contains(expr, s));
+    transformations::apply(expr, |expr| transformations::map_length(expr, s));
     transformations::apply(expr, |expr| transformations::variable_field_access(expr, s));
     transformations::apply(expr, |expr| transformations::arg_field_access(expr, s));
     transformations::apply(expr, |expr| transformations::list_field_access(expr, s));
@@ -383,6 +428,29 @@ fn visit_stmt_set_var(
     is_cloud: &bool,
 ) -> Option<Vec<Stmt>> {
     let expr = value;
+    if let Expr::StructLiteral {
+        name: struct_literal_name,
+        span: struct_literal_span,
+        ..
+    } = expr
+    {
+        if name.fieldname().is_none()
+            && s.get_var(name.basename())
+                .is_some_and(|var| var.type_.is_value())
+        {
+            d.report(
+                DiagnosticKind::TypeMismatch {
+                    expected: Type::Value,
+                    given: Type::Struct {
+                        name: struct_literal_name.clone(),
+                        span: struct_literal_span.clone(),
+                    },
+                },
+                &name.basespan(),
+            );
+            return Some(vec![]);
+        }
+    }
     let struct_literal_fields = get_struct_literal_for_type(s, d, name, expr, |basename| {
         s.get_var(basename).map(|var| &var.type_)
     })?;
@@ -405,6 +473,14 @@ fn visit_stmt_set_var(
     )
 }
 
+fn visit_stmt_delete_map(s: S, name: &Name) -> Option<Vec<Stmt>> {
+    let map = s.get_map(name.basename())?;
+    Some(vec![
+        Stmt::DeleteList(transformations::map_list(name, map.keys())),
+        Stmt::DeleteList(transformations::map_list(name, map.values())),
+    ])
+}
+
 fn visit_stmt_list_set(s: S, d: D, name: &Name, index: &Expr, value: &Expr) -> Option<Vec<Stmt>> {
     let expr = value;
     let struct_literal_fields = get_struct_literal_for_type(s, d, name, expr, |basename| {
@@ -458,14 +534,14 @@ fn visit_stmt_delete_list(s: S, d: D, name: &Name) -> Option<Vec<Stmt>> {
     let struct_ = s.get_struct(type_name)?;
     Some(
         struct_
-            .fields
-            .iter()
-            .map(|struct_field| {
+            .leaf_fields(&|name| s.get_struct(name))
+            .into_iter()
+            .map(|(path, span)| {
                 Stmt::DeleteList(Name::DotName {
                     lhs: name.basename().clone(),
                     lhs_span: name.basespan().clone(),
-                    rhs: struct_field.name.clone(),
-                    rhs_span: struct_field.span.clone(),
+                    rhs: path,
+                    rhs_span: span,
                 })
             })
             .collect(),
@@ -510,14 +586,14 @@ fn visit_stmt_delete_list_index(s: S, _d: D, name: &Name, index: &Expr) -> Optio
     let struct_ = s.get_struct(type_name)?;
     Some(
         struct_
-            .fields
-            .iter()
-            .map(|struct_field| Stmt::DeleteListIndex {
+            .leaf_fields(&|name| s.get_struct(name))
+            .into_iter()
+            .map(|(path, span)| Stmt::DeleteListIndex {
                 name: Name::DotName {
                     lhs: name.basename().clone(),
                     lhs_span: name.basespan().clone(),
-                    rhs: struct_field.name.clone(),
-                    rhs_span: struct_field.span.clone(),
+                    rhs: path,
+                    rhs_span: span,
                 },
                 index: Box::new(index.clone()),
             })
@@ -525,22 +601,28 @@ fn visit_stmt_delete_list_index(s: S, _d: D, name: &Name, index: &Expr) -> Optio
     )
 }
 
+/// If `name` has a struct type, returns the fields of the struct literal `expr`,
+/// flattened into the paths of the value fields of `name`. `name` can be a field of a
+/// struct variable or list, if that field has a struct type itself. If `expr` does not
+/// match the type, no fields are returned so that the statement is removed.
 fn get_struct_literal_for_type<'a, T>(
-    s: S,
+    s: S<'a>,
     d: D,
     name: &Name,
-    expr: &'a Expr,
+    expr: &Expr,
     get_type: T,
-) -> Option<&'a [StructLiteralField]>
+) -> Option<Vec<StructLiteralField>>
 where
     T: FnOnce(&str) -> Option<&'a Type>,
 {
-    if name.fieldname().is_some() {
-        return None;
-    }
     let basename = name.basename();
     let basespan = name.basespan();
-    let type_ = get_type(basename)?;
+    let mut type_ = get_type(basename)?;
+    if let Some(fieldname) = name.fieldname() {
+        let (type_name, _) = type_.struct_()?;
+        let struct_ = s.get_struct(type_name)?;
+        type_ = &struct_.field(fieldname, &|name| s.get_struct(name))?.type_;
+    }
     let (type_name, type_span) = type_.struct_()?;
     let struct_ = s.get_struct(type_name)?;
     let Expr::StructLiteral {
@@ -556,14 +638,14 @@ where
             },
             &basespan,
         );
-        return None;
+        return Some(vec![]);
     };
     let Some(value_struct) = s.get_struct(struct_literal_name) else {
         d.report(
             DiagnosticKind::UnrecognizedStruct(struct_literal_name.clone()),
             struct_literal_span,
         );
-        return None;
+        return Some(vec![]);
     };
     if struct_.name != value_struct.name {
         d.report(
@@ -579,12 +661,15 @@ where
             },
             &basespan,
         );
-        return None;
+        return Some(vec![]);
     }
-    Some(struct_literal_fields)
+    Some(flatten_struct_literal_fields(
+        struct_literal_fields,
+        name.fieldname().map(|fieldname| fieldname.as_str()),
+    ))
 }
 
-fn visit_stmt_return(_value: &Expr) -> Option<Vec<Stmt>> {
+fn visit_stmt_return() -> Option<Vec<Stmt>> {
     Some(vec![Stmt::Block {
         block: Block::StopThisScript,
         span: 0..0,
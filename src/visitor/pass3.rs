@@ -1,22 +1,26 @@
-use fxhash::{
-    FxHashMap,
-    FxHashSet,
-};
+use fxhash::FxHashSet;
 
 use crate::{
     ast::*,
-    misc::SmolStr,
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
 };
 
 struct Scope<'a> {
     used_procs: &'a mut FxHashSet<SmolStr>,
     used_funcs: &'a mut FxHashSet<SmolStr>,
-    vars: &'a mut FxHashMap<SmolStr, Var>,
-    lists: &'a mut FxHashMap<SmolStr, List>,
-    structs: &'a mut FxHashMap<SmolStr, Struct>,
-    enums: &'a mut FxHashMap<SmolStr, Enum>,
-    global_vars: Option<&'a mut FxHashMap<SmolStr, Var>>,
-    global_lists: Option<&'a mut FxHashMap<SmolStr, List>>,
+    vars: &'a mut IndexMap<SmolStr, Var>,
+    lists: &'a mut IndexMap<SmolStr, List>,
+    maps: &'a mut IndexMap<SmolStr, Map>,
+    structs: &'a mut IndexMap<SmolStr, Struct>,
+    enums: &'a mut IndexMap<SmolStr, Enum>,
+    consts: &'a mut IndexMap<SmolStr, Const>,
+    global_vars: Option<&'a mut IndexMap<SmolStr, Var>>,
+    global_lists: Option<&'a mut IndexMap<SmolStr, List>>,
+    global_maps: Option<&'a mut IndexMap<SmolStr, Map>>,
+    global_consts: Option<&'a mut IndexMap<SmolStr, Const>>,
 }
 
 pub fn visit_project(project: &mut Project) {
@@ -28,10 +32,14 @@ pub fn visit_project(project: &mut Project) {
                 used_funcs: &mut project.stage.used_funcs,
                 vars: &mut project.stage.vars,
                 lists: &mut project.stage.lists,
+                maps: &mut project.stage.maps,
                 structs: &mut project.stage.structs,
                 enums: &mut project.stage.enums,
+                consts: &mut project.stage.consts,
                 global_vars: None,
                 global_lists: None,
+                global_maps: None,
+                global_consts: None,
             },
             &project.stage.procs,
             &project.stage.proc_references,
@@ -50,10 +58,14 @@ pub fn visit_project(project: &mut Project) {
                     used_funcs: &mut sprite.used_funcs,
                     vars: &mut sprite.vars,
                     lists: &mut sprite.lists,
+                    maps: &mut sprite.maps,
                     structs: &mut sprite.structs,
                     enums: &mut sprite.enums,
+                    consts: &mut sprite.consts,
                     global_vars: Some(&mut project.stage.vars),
                     global_lists: Some(&mut project.stage.lists),
+                    global_maps: Some(&mut project.stage.maps),
+                    global_consts: Some(&mut project.stage.consts),
                 },
                 &sprite.procs,
                 &sprite.proc_references,
@@ -63,17 +75,61 @@ pub fn visit_project(project: &mut Project) {
             );
         }
     }
+
+    visit_nested_structs(&mut project.stage.structs);
+    for sprite in project.sprites.values_mut() {
+        visit_nested_structs(&mut sprite.structs);
+    }
+}
+
+/// Marks the types of the struct-typed fields of used structs as used.
+fn visit_nested_structs(structs: &mut IndexMap<SmolStr, Struct>) {
+    let mut used: Vec<SmolStr> = structs
+        .values()
+        .filter(|struct_| struct_.is_used)
+        .map(|struct_| struct_.name.clone())
+        .collect();
+    while let Some(name) = used.pop() {
+        let field_type_names: Vec<SmolStr> = structs[&name]
+            .fields
+            .iter()
+            .filter_map(|field| {
+                field
+                    .type_
+                    .struct_()
+                    .map(|(type_name, _)| type_name.clone())
+            })
+            .collect();
+        for type_name in field_type_names {
+            if let Some(struct_) = structs.get_mut(&type_name) {
+                if !struct_.is_used {
+                    struct_.is_used = true;
+                    used.push(type_name);
+                }
+            }
+        }
+    }
 }
 
 fn resolve_references(
     scope: &mut Scope,
-    procs: &FxHashMap<SmolStr, Proc>,
-    proc_references: &FxHashMap<SmolStr, References>,
-    funcs: &FxHashMap<SmolStr, Func>,
-    func_references: &FxHashMap<SmolStr, References>,
+    procs: &IndexMap<SmolStr, Proc>,
+    proc_references: &IndexMap<SmolStr, References>,
+    funcs: &IndexMap<SmolStr, Func>,
+    func_references: &IndexMap<SmolStr, References>,
     references: &References,
 ) {
     for name in &references.names {
+        if let Some(const_) = scope.consts.get_mut(name) {
+            const_.is_used = true;
+            continue;
+        }
+        if let Some(global_consts) = &mut scope.global_consts {
+            if let Some(const_) = global_consts.get_mut(name) {
+                const_.is_used = true;
+                continue;
+            }
+        }
         if let Some(global_vars) = &mut scope.global_vars {
             if let Some(var) = global_vars.get_mut(name) {
                 var.is_used = true;
@@ -92,6 +148,15 @@ fn resolve_references(
         if let Some(list) = scope.lists.get_mut(name) {
             list.is_used = true;
         }
+        if let Some(global_maps) = &mut scope.global_maps {
+            if let Some(map) = global_maps.get_mut(name) {
+                map.is_used = true;
+                continue;
+            }
+        }
+        if let Some(map) = scope.maps.get_mut(name) {
+            map.is_used = true;
+        }
     }
     for struct_name in &references.structs {
         if let Some(struct_) = scope.structs.get_mut(struct_name) {
@@ -109,13 +174,15 @@ fn resolve_references(
         }
     }
     for (enum_name, variant_name) in &references.enum_variants {
-        if let Some(enum_) = &mut scope.enums.get_mut(enum_name) {
-            let enum_variant = &mut enum_
+        if let Some(enum_) = scope.enums.get_mut(enum_name) {
+            enum_.is_used = true;
+            if let Some(enum_variant) = enum_
                 .variants
                 .iter_mut()
                 .find(|variant| &variant.name == variant_name)
-                .unwrap();
-            enum_variant.is_used = true;
+            {
+                enum_variant.is_used = true;
+            }
         }
     }
     for proc in &references.procs {
@@ -0,0 +1,498 @@
+use logos::Span;
+
+use crate::{
+    ast::*,
+    blocks::{
+        BinOp,
+        UnOp,
+    },
+    diagnostic::{
+        DiagnosticKind,
+        SpriteDiagnostics,
+    },
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
+};
+
+/// Arguments which TurboWarp and forkphorus recognize without them being declared.
+const BUILTIN_ARGS: [&str; 3] = ["tw_is_compiled", "tw_is_turbowarp", "tw_is_forkphorus"];
+
+#[derive(Copy, Clone)]
+struct V<'a> {
+    stage: Option<&'a Sprite>,
+    sprite: &'a Sprite,
+    args: Option<&'a [Arg]>,
+    locals: Option<&'a IndexMap<SmolStr, Var>>,
+}
+
+/// What a name refers to.
+enum Resolved {
+    Var,
+    List,
+}
+
+impl<'a> V<'a> {
+    fn get_var(&self, name: &str) -> Option<&'a Var> {
+        self.locals
+            .and_then(|locals| locals.get(name))
+            .or_else(|| self.sprite.vars.get(name))
+            .or_else(|| self.stage.and_then(|stage| stage.vars.get(name)))
+    }
+
+    fn get_list(&self, name: &str) -> Option<&'a List> {
+        self.sprite
+            .lists
+            .get(name)
+            .or_else(|| self.stage.and_then(|stage| stage.lists.get(name)))
+    }
+
+    fn get_struct(&self, name: &str) -> Option<&'a Struct> {
+        self.sprite
+            .structs
+            .get(name)
+            .or_else(|| self.stage.and_then(|stage| stage.structs.get(name)))
+    }
+
+    fn get_enum(&self, name: &str) -> Option<&'a Enum> {
+        self.sprite
+            .enums
+            .get(name)
+            .or_else(|| self.stage.and_then(|stage| stage.enums.get(name)))
+    }
+}
+
+/// Reports the names which do not refer to anything, the calls with the wrong number of
+/// arguments and the statements which can never run. Must run after every pass which
+/// lowers the project, as it checks what codegen will see.
+pub fn visit_project(
+    project: &Project,
+    stage_diagnostics: &mut SpriteDiagnostics,
+    sprites_diagnostics: &mut IndexMap<SmolStr, SpriteDiagnostics>,
+) {
+    visit_sprite(&project.stage, None, stage_diagnostics);
+    for (sprite_name, sprite) in &project.sprites {
+        visit_sprite(
+            sprite,
+            Some(&project.stage),
+            sprites_diagnostics.get_mut(sprite_name).unwrap(),
+        );
+    }
+}
+
+fn visit_sprite(sprite: &Sprite, stage: Option<&Sprite>, d: &mut SpriteDiagnostics) {
+    if sprite.costumes.is_empty() {
+        d.report(DiagnosticKind::NoCostumes, &(0..0));
+    }
+    for var in sprite.vars.values().filter(|var| var.is_used) {
+        visit_declaration_type(sprite, &var.type_, d);
+    }
+    for list in sprite.lists.values().filter(|list| list.is_used) {
+        visit_declaration_type(sprite, &list.type_, d);
+    }
+    let v = V {
+        stage,
+        sprite,
+        args: None,
+        locals: None,
+    };
+    for proc in sprite.procs.values() {
+        visit_declarations(sprite, &proc.args, &proc.locals, d);
+        let v = V {
+            args: Some(&proc.args),
+            locals: Some(&proc.locals),
+            ..v
+        };
+        visit_stmts(&sprite.proc_definitions[&proc.name], v, d);
+    }
+    for func in sprite.funcs.values() {
+        visit_declarations(sprite, &func.args, &func.locals, d);
+        let v = V {
+            args: Some(&func.args),
+            locals: Some(&func.locals),
+            ..v
+        };
+        visit_stmts(&sprite.func_definitions[&func.name], v, d);
+    }
+    for event in &sprite.events {
+        visit_stmts(&event.body, v, d);
+    }
+}
+
+fn visit_declarations(
+    sprite: &Sprite,
+    args: &[Arg],
+    locals: &IndexMap<SmolStr, Var>,
+    d: &mut SpriteDiagnostics,
+) {
+    for arg in args {
+        visit_declaration_type(sprite, &arg.type_, d);
+    }
+    for var in locals.values() {
+        visit_declaration_type(sprite, &var.type_, d);
+    }
+}
+
+/// Struct types of declarations are only looked up in the sprite itself.
+fn visit_declaration_type(sprite: &Sprite, type_: &Type, d: &mut SpriteDiagnostics) {
+    if let Some((type_name, type_span)) = type_.struct_() {
+        if !sprite.structs.contains_key(type_name) {
+            d.report(
+                DiagnosticKind::UnrecognizedStruct(type_name.clone()),
+                type_span,
+            );
+        }
+    }
+}
+
+fn visit_stmts(stmts: &[Stmt], v: V, d: &mut SpriteDiagnostics) {
+    for (i, stmt) in stmts.iter().enumerate() {
+        visit_stmt(stmt, v, d);
+        if let Some(span) = stmt.terminator_span() {
+            if i != stmts.len() - 1 {
+                d.report(DiagnosticKind::FollowedByUnreachableCode, span);
+            }
+            break;
+        }
+    }
+}
+
+fn visit_stmt(stmt: &Stmt, v: V, d: &mut SpriteDiagnostics) {
+    match stmt {
+        Stmt::Repeat { times, body } => {
+            visit_expr(times, v, d);
+            visit_stmts(body, v, d);
+        }
+        Stmt::Forever { body, span: _ } => visit_stmts(body, v, d),
+        Stmt::Branch {
+            cond,
+            if_body,
+            else_body,
+        } => {
+            visit_expr(cond, v, d);
+            visit_stmts(if_body, v, d);
+            visit_stmts(else_body, v, d);
+        }
+        Stmt::Until { cond, body } => {
+            visit_expr(cond, v, d);
+            visit_stmts(body, v, d);
+        }
+        Stmt::ForRange { .. } | Stmt::ForEach { .. } | Stmt::Match { .. } => {
+            unreachable!("lowered in pass0")
+        }
+        Stmt::Break(_) | Stmt::Continue(_) => unreachable!("lowered in loops"),
+        Stmt::Destructure { .. } => unreachable!("lowered in pass1"),
+        Stmt::SetVar { name, value, .. } | Stmt::ChangeVar { name, value } => {
+            if let Some(Resolved::List) = resolve_name(name, v, d) {
+                d.report(
+                    DiagnosticKind::UnrecognizedVariable(name.basename().clone()),
+                    &name.span(),
+                );
+            }
+            visit_expr(value, v, d);
+        }
+        Stmt::Show(name) | Stmt::Hide(name) => {
+            resolve_name(name, v, d);
+        }
+        Stmt::AddToList { name, value } => {
+            visit_list_name(name, v, d);
+            visit_expr(value, v, d);
+        }
+        Stmt::DeleteList(name) => visit_list_name(name, v, d),
+        Stmt::DeleteListIndex { name, index } => {
+            visit_list_name(name, v, d);
+            visit_expr(index, v, d);
+        }
+        Stmt::InsertAtList { name, index, value } | Stmt::SetListIndex { name, index, value } => {
+            visit_list_name(name, v, d);
+            visit_expr(index, v, d);
+            visit_expr(value, v, d);
+        }
+        Stmt::Block { block, span, args } => {
+            if block.args().len() != args.len() {
+                d.report(
+                    DiagnosticKind::BlockArgsCountMismatch {
+                        block: *block,
+                        given: args.len(),
+                    },
+                    span,
+                );
+            }
+            for (_, arg) in args {
+                visit_expr(arg, v, d);
+            }
+        }
+        Stmt::ProcCall { name, span, args } => visit_proc_call(name, span, args, v, d),
+        Stmt::FuncCall { name, span, args } => visit_func_call(name, span, args, v, d),
+        Stmt::Return { values, .. } => {
+            for value in values {
+                visit_expr(value, v, d);
+            }
+        }
+    }
+}
+
+fn visit_list_name(name: &Name, v: V, d: &mut SpriteDiagnostics) {
+    if let Some(Resolved::Var) = resolve_name(name, v, d) {
+        d.report(
+            DiagnosticKind::UnrecognizedList(name.basename().clone()),
+            &name.span(),
+        );
+    }
+}
+
+/// The debugger blocks are called like procedures, `breakpoint` and `error` can be
+/// shadowed by a procedure of the same name.
+fn builtin_proc_args(name: &str) -> Option<usize> {
+    match name {
+        "breakpoint" => Some(0),
+        "log" | "error" => Some(1),
+        _ => None,
+    }
+}
+
+fn visit_proc_call(
+    name: &SmolStr,
+    span: &Span,
+    args: &[(Option<(SmolStr, Span)>, Expr)],
+    v: V,
+    d: &mut SpriteDiagnostics,
+) {
+    let proc = (name != "log").then(|| v.sprite.procs.get(name)).flatten();
+    let expected = match proc {
+        Some(proc) => proc.args.len(),
+        None => {
+            let Some(expected) = builtin_proc_args(name) else {
+                d.report(DiagnosticKind::UnrecognizedProcedure(name.clone()), span);
+                return;
+            };
+            expected
+        }
+    };
+    if expected != args.len() {
+        d.report(
+            DiagnosticKind::ProcArgsCountMismatch {
+                proc: name.clone(),
+                given: args.len(),
+            },
+            span,
+        );
+    }
+    match proc {
+        Some(proc) => visit_call_args(&proc.args, args, v, d),
+        None => {
+            for (_, arg) in args {
+                visit_expr(arg, v, d);
+            }
+        }
+    }
+}
+
+fn visit_func_call(
+    name: &SmolStr,
+    span: &Span,
+    args: &[(Option<(SmolStr, Span)>, Expr)],
+    v: V,
+    d: &mut SpriteDiagnostics,
+) {
+    let Some(func) = v.sprite.funcs.get(name) else {
+        d.report(DiagnosticKind::UnrecognizedFunction(name.clone()), span);
+        return;
+    };
+    if func.args.len() != args.len() {
+        d.report(
+            DiagnosticKind::FuncArgsCountMismatch {
+                func: name.clone(),
+                given: args.len(),
+            },
+            span,
+        );
+    }
+    visit_call_args(&func.args, args, v, d);
+}
+
+/// Visits the values given to the arguments of a procedure or function. A struct
+/// argument is given a struct literal of the same struct, whose fields are visited.
+fn visit_call_args(
+    params: &[Arg],
+    args: &[(Option<(SmolStr, Span)>, Expr)],
+    v: V,
+    d: &mut SpriteDiagnostics,
+) {
+    for (param, (_, arg)) in params.iter().zip(args) {
+        let Some((type_name, type_span)) = param.type_.struct_() else {
+            visit_expr(arg, v, d);
+            continue;
+        };
+        if !v.sprite.structs.contains_key(type_name) {
+            continue;
+        }
+        let Expr::StructLiteral {
+            name: struct_literal_name,
+            span: struct_literal_span,
+            fields,
+        } = arg
+        else {
+            continue;
+        };
+        if struct_literal_name != type_name {
+            d.report(
+                DiagnosticKind::TypeMismatch {
+                    expected: param.type_.clone(),
+                    given: Type::Struct {
+                        name: struct_literal_name.clone(),
+                        span: struct_literal_span.clone(),
+                    },
+                },
+                type_span,
+            );
+            continue;
+        }
+        for field in flatten_struct_literal_fields(fields, None) {
+            visit_expr(&field.value, v, d);
+        }
+    }
+}
+
+fn visit_expr(expr: &Expr, v: V, d: &mut SpriteDiagnostics) {
+    match expr {
+        Expr::Value { .. } => {}
+        Expr::Name(name) => {
+            resolve_name(name, v, d);
+        }
+        Expr::Arg(name) => {
+            let basename = name.basename();
+            if !v
+                .args
+                .is_some_and(|args| args.iter().any(|arg| &arg.name == basename))
+                && !BUILTIN_ARGS.contains(&basename.as_str())
+            {
+                d.report(
+                    DiagnosticKind::UnrecognizedArgument(basename.clone()),
+                    &name.span(),
+                );
+            }
+        }
+        Expr::Repr { repr, span, args } => {
+            if args.len() != repr.args().len() {
+                d.report(
+                    DiagnosticKind::ReprArgsCountMismatch {
+                        repr: *repr,
+                        given: args.len(),
+                    },
+                    span,
+                );
+                return;
+            }
+            for (_, arg) in args {
+                visit_expr(arg, v, d);
+            }
+        }
+        Expr::FuncCall { name, span, .. } => {
+            d.report(DiagnosticKind::UnrecognizedFunction(name.clone()), span);
+        }
+        Expr::UnOp { op, span: _, opr } => {
+            if let (UnOp::Length, Expr::Name(Name::Name { name, .. })) = (op, &**opr) {
+                if v.get_list(name).is_some() {
+                    return;
+                }
+            }
+            visit_expr(opr, v, d);
+        }
+        Expr::BinOp {
+            op,
+            span: _,
+            lhs,
+            rhs,
+        } => match (op, &**lhs, &**rhs) {
+            (BinOp::Of, Expr::Name(name), index) => {
+                resolve_name(name, v, d);
+                visit_expr(index, v, d);
+            }
+            (BinOp::In, item, Expr::Name(name)) => {
+                visit_expr(item, v, d);
+                resolve_name(name, v, d);
+            }
+            _ => {
+                visit_expr(lhs, v, d);
+                visit_expr(rhs, v, d);
+            }
+        },
+        Expr::StructLiteral { name, span, .. } => {
+            d.report(
+                DiagnosticKind::TypeMismatch {
+                    expected: Type::Value,
+                    given: Type::Struct {
+                        name: name.clone(),
+                        span: span.clone(),
+                    },
+                },
+                &expr.span(),
+            );
+        }
+        Expr::Dot { lhs, rhs, rhs_span } => {
+            if let Expr::Name(lhs_name) = &**lhs {
+                if let Some(enum_) = v.get_enum(lhs_name.basename()) {
+                    if !enum_.variants.iter().any(|variant| &variant.name == rhs) {
+                        d.report(
+                            DiagnosticKind::UnrecognizedEnumVariant(lhs_name.basename().clone()),
+                            rhs_span,
+                        );
+                    }
+                    return;
+                }
+            }
+            visit_expr(lhs, v, d);
+        }
+        Expr::Ternary { .. } => unreachable!("lowered in pass1"),
+    }
+}
+
+/// Resolves a name the way codegen qualifies it, reporting it if it does not refer to a
+/// variable or list, or if its field does not match the type of the variable or list.
+fn resolve_name(name: &Name, v: V, d: &mut SpriteDiagnostics) -> Option<Resolved> {
+    let basename = name.basename();
+    let (type_, resolved) = if let Some(list) = v.get_list(basename) {
+        (&list.type_, Resolved::List)
+    } else if let Some(var) = v.get_var(basename) {
+        (&var.type_, Resolved::Var)
+    } else {
+        d.report(
+            DiagnosticKind::UnrecognizedVariable(basename.clone()),
+            &name.span(),
+        );
+        return None;
+    };
+    match (type_, name.fieldname()) {
+        (Type::Value | Type::Enum { .. }, None) => Some(resolved),
+        (Type::Value | Type::Enum { .. }, Some(_)) => {
+            d.report(DiagnosticKind::NotStruct, &name.span());
+            None
+        }
+        (Type::Struct { .. }, None) => None,
+        (
+            Type::Struct {
+                name: type_name,
+                span: type_span,
+            },
+            Some(field_name),
+        ) => {
+            let struct_ = v.get_struct(type_name)?;
+            if !struct_
+                .field(field_name, &|name| v.get_struct(name))
+                .is_some_and(|field| field.type_.is_value())
+            {
+                d.report(
+                    DiagnosticKind::StructDoesNotHaveField {
+                        type_name: type_name.clone(),
+                        field_name: field_name.clone(),
+                    },
+                    type_span,
+                );
+                return None;
+            }
+            Some(resolved)
+        }
+    }
+}
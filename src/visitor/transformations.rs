@@ -6,6 +6,7 @@ use crate::{
         Arg,
         Expr,
         Name,
+        Struct,
         StructLiteralField,
         Value,
     },
@@ -52,6 +53,88 @@ pub fn struct_literal_field_access(expr: &Expr, d: D) -> Option<Expr> {
     Some(field.value.as_ref().clone())
 }
 
+pub fn const_access(expr: &Expr, s: S) -> Option<Expr> {
+    let Expr::Name(Name::Name { name, span }) = expr else {
+        return None;
+    };
+    let const_ = s.get_const(name)?;
+    Some(const_.value.evaluate().to_expr(span.clone()))
+}
+
+/// Returns the name of one of the hidden lists of the map `name`.
+pub fn map_list(name: &Name, list_name: SmolStr) -> Name {
+    Name::Name {
+        name: list_name,
+        span: name.span(),
+    }
+}
+
+/// Returns the index of `key` in a map's keys list, which is 0 if the map does not
+/// contain `key`.
+pub fn map_index(key: &Expr, keys: &Name) -> Expr {
+    BinOp::In.to_expr(key.span(), key.clone(), Expr::Name(keys.clone()))
+}
+
+pub fn map_access(expr: &Expr, s: S) -> Option<Expr> {
+    let Expr::BinOp {
+        op: BinOp::Of,
+        span,
+        lhs,
+        rhs,
+    } = expr
+    else {
+        return None;
+    };
+    let Expr::Name(name @ Name::Name { .. }) = lhs.as_ref() else {
+        return None;
+    };
+    let map = s.get_map(name.basename())?;
+    let keys = map_list(name, map.keys());
+    let values = map_list(name, map.values());
+    Some(BinOp::Of.to_expr(span.clone(), Expr::Name(values), map_index(rhs, &keys)))
+}
+
+pub fn map_contains(expr: &Expr, s: S) -> Option<Expr> {
+    let Expr::BinOp {
+        op: BinOp::In,
+        span,
+        lhs,
+        rhs,
+    } = expr
+    else {
+        return None;
+    };
+    let Expr::Name(name @ Name::Name { .. }) = rhs.as_ref() else {
+        return None;
+    };
+    let map = s.get_map(name.basename())?;
+    let keys = map_list(name, map.keys());
+    // `item # of` returns an index, which is compared with 0 so that the result is a
+    // boolean.
+    Some(BinOp::Gt.to_expr(
+        span.clone(),
+        map_index(lhs, &keys),
+        Value::from(0).to_expr(span.clone()),
+    ))
+}
+
+pub fn map_length(expr: &Expr, s: S) -> Option<Expr> {
+    let Expr::UnOp {
+        op: UnOp::Length,
+        span,
+        opr,
+    } = expr
+    else {
+        return None;
+    };
+    let Expr::Name(name @ Name::Name { .. }) = opr.as_ref() else {
+        return None;
+    };
+    let map = s.get_map(name.basename())?;
+    let keys = map_list(name, map.keys());
+    Some(UnOp::Length.to_expr(span.clone(), Expr::Name(keys)))
+}
+
 pub fn list_field_access(expr: &Expr, s: S) -> Option<Expr> {
     let Expr::BinOp {
         op: BinOp::Of,
@@ -68,30 +151,24 @@ pub fn list_field_access(expr: &Expr, s: S) -> Option<Expr> {
     let list = s.get_list(name)?;
     let (type_name, type_span) = list.type_.struct_()?;
     let struct_ = s.get_struct(type_name)?;
-    Some(Expr::StructLiteral {
-        name: type_name.clone(),
-        span: type_span.clone(),
-        fields: struct_
-            .fields
-            .iter()
-            .map(|field| StructLiteralField {
-                name: field.name.clone(),
-                span: field.span.clone(),
-                value: BinOp::Of
-                    .to_expr(
-                        span.clone(),
-                        Expr::Name(Name::DotName {
-                            lhs: name.clone(),
-                            lhs_span: span.clone(),
-                            rhs: field.name.clone(),
-                            rhs_span: field.span.clone(),
-                        }),
-                        rhs.as_ref().clone(),
-                    )
-                    .into(),
-            })
-            .collect(),
-    })
+    Some(struct_literal(
+        s,
+        struct_,
+        type_span,
+        None,
+        &|path, field_span| {
+            BinOp::Of.to_expr(
+                span.clone(),
+                Expr::Name(Name::DotName {
+                    lhs: name.clone(),
+                    lhs_span: span.clone(),
+                    rhs: path,
+                    rhs_span: field_span.clone(),
+                }),
+                rhs.as_ref().clone(),
+            )
+        },
+    ))
 }
 
 pub fn variable_field_access(expr: &Expr, s: S) -> Option<Expr> {
@@ -106,25 +183,20 @@ pub fn variable_field_access(expr: &Expr, s: S) -> Option<Expr> {
     let var = &s.get_var(basename)?;
     let (type_name, type_span) = var.type_.struct_()?;
     let struct_ = s.get_struct(type_name)?;
-    Some(Expr::StructLiteral {
-        name: type_name.clone(),
-        span: type_span.clone(),
-        fields: struct_
-            .fields
-            .iter()
-            .map(|field| StructLiteralField {
-                name: field.name.clone(),
-                span: field.span.clone(),
-                value: Expr::Name(Name::DotName {
-                    lhs: var.name.clone(),
-                    lhs_span: span.clone(),
-                    rhs: field.name.clone(),
-                    rhs_span: field.span.clone(),
-                })
-                .into(),
+    Some(struct_literal(
+        s,
+        struct_,
+        type_span,
+        None,
+        &|path, field_span| {
+            Expr::Name(Name::DotName {
+                lhs: var.name.clone(),
+                lhs_span: span.clone(),
+                rhs: path,
+                rhs_span: field_span.clone(),
             })
-            .collect(),
-    })
+        },
+    ))
 }
 
 pub fn arg_field_access(expr: &Expr, s: S) -> Option<Expr> {
@@ -139,25 +211,64 @@ pub fn arg_field_access(expr: &Expr, s: S) -> Option<Expr> {
     let arg = s.args?.iter().find(|arg| &arg.name == basename)?;
     let (type_name, type_span) = arg.type_.struct_()?;
     let struct_ = s.get_struct(type_name)?;
-    Some(Expr::StructLiteral {
-        name: type_name.clone(),
-        span: type_span.clone(),
+    Some(struct_literal(
+        s,
+        struct_,
+        type_span,
+        None,
+        &|path, field_span| {
+            Expr::Arg(Name::DotName {
+                lhs: arg.name.clone(),
+                lhs_span: span.clone(),
+                rhs: path,
+                rhs_span: field_span.clone(),
+            })
+        },
+    ))
+}
+
+/// Builds a struct literal of type `struct_`, whose value fields are given by
+/// `field_value` from their path. Fields with a struct type become nested struct
+/// literals, so that they can be accessed with further dots.
+fn struct_literal<F>(
+    s: S,
+    struct_: &Struct,
+    span: &Span,
+    prefix: Option<&str>,
+    field_value: &F,
+) -> Expr
+where
+    F: Fn(SmolStr, &Span) -> Expr,
+{
+    Expr::StructLiteral {
+        name: struct_.name.clone(),
+        span: span.clone(),
         fields: struct_
             .fields
             .iter()
-            .map(|field| StructLiteralField {
-                name: field.name.clone(),
-                span: field.span.clone(),
-                value: Expr::Arg(Name::DotName {
-                    lhs: arg.name.clone(),
-                    lhs_span: span.clone(),
-                    rhs: field.name.clone(),
-                    rhs_span: field.span.clone(),
-                })
-                .into(),
+            .map(|field| {
+                let path: SmolStr = match prefix {
+                    Some(prefix) => format!("{}.{}", prefix, field.name).into(),
+                    None => field.name.clone(),
+                };
+                let value = match field
+                    .type_
+                    .struct_()
+                    .and_then(|(type_name, _)| s.get_struct(type_name))
+                {
+                    Some(field_struct) => {
+                        struct_literal(s, field_struct, &field.span, Some(&path), field_value)
+                    }
+                    None => field_value(path, &field.span),
+                };
+                StructLiteralField {
+                    name: field.name.clone(),
+                    span: field.span.clone(),
+                    value: value.into(),
+                }
             })
             .collect(),
-    })
+    }
 }
 
 pub fn bin_op(expr: &Expr) -> Option<Expr> {
@@ -0,0 +1,80 @@
+use crate::{
+    ast::*,
+    diagnostic::{
+        DiagnosticKind,
+        SpriteDiagnostics,
+    },
+    misc::{
+        IndexMap,
+        SmolStr,
+    },
+};
+
+/// Reports the procedures, functions, arguments, structs, constants and enums that are
+/// never used. Must run after [`super::pass3`], which marks what is used.
+pub fn visit_project(
+    project: &Project,
+    stage_diagnostics: &mut SpriteDiagnostics,
+    sprites_diagnostics: &mut IndexMap<SmolStr, SpriteDiagnostics>,
+) {
+    visit_sprite(&project.stage, stage_diagnostics);
+    for (sprite_name, sprite) in &project.sprites {
+        visit_sprite(sprite, sprites_diagnostics.get_mut(sprite_name).unwrap());
+    }
+}
+
+fn visit_sprite(sprite: &Sprite, d: &mut SpriteDiagnostics) {
+    for proc in sprite.procs.values() {
+        if !sprite.used_procs.contains(&proc.name) {
+            d.report(DiagnosticKind::UnusedProc(proc.name.clone()), &proc.span);
+        } else {
+            for arg in &proc.args {
+                if !sprite
+                    .proc_used_args
+                    .get(&proc.name)
+                    .unwrap()
+                    .contains(&arg.name)
+                {
+                    d.report(DiagnosticKind::UnusedArg(arg.name.clone()), &arg.span);
+                }
+            }
+        }
+    }
+    for func in sprite.funcs.values() {
+        if !sprite.used_funcs.contains(&func.name) {
+            d.report(DiagnosticKind::UnusedFunc(func.name.clone()), &func.span);
+        } else {
+            for arg in &func.args {
+                if !sprite
+                    .func_used_args
+                    .get(&func.name)
+                    .unwrap()
+                    .contains(&arg.name)
+                {
+                    d.report(DiagnosticKind::UnusedArg(arg.name.clone()), &arg.span);
+                }
+            }
+        }
+    }
+    for struct_ in sprite.structs.values() {
+        if !struct_.is_used {
+            d.report(
+                DiagnosticKind::UnusedStruct(struct_.name.clone()),
+                &struct_.span,
+            );
+        }
+    }
+    for const_ in sprite.consts.values() {
+        if !const_.is_used {
+            d.report(
+                DiagnosticKind::UnusedConst(const_.name.clone()),
+                &const_.span,
+            );
+        }
+    }
+    for enum_ in sprite.enums.values() {
+        if !enum_.is_used {
+            d.report(DiagnosticKind::UnusedEnum(enum_.name.clone()), &enum_.span);
+        }
+    }
+}
@@ -0,0 +1,13 @@
+costumes "blank.svg";
+
+const SPEED = 2 * 5;
+
+onflag {
+    say TITLE;
+    y_velocity = 0;
+    repeat 10 {
+        y_velocity += GRAVITY / SPEED;
+        change_y y_velocity;
+        move SPEED;
+    }
+}
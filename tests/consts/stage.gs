@@ -0,0 +1,4 @@
+costumes "blank.svg";
+
+const GRAVITY = -9.8;
+const TITLE = "consts";
@@ -0,0 +1,14 @@
+costumes "blank.svg";
+
+onflag {
+    pen_down;
+    set_pen_color "#ff0000";
+    set_pen_size 4;
+    change_pen_hue 10;
+    pen_up;
+    set_tempo 120;
+    rest 0.5;
+    change_tempo 10;
+    log "done";
+    breakpoint;
+}
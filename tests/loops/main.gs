@@ -0,0 +1,71 @@
+costumes "blank.svg";
+
+struct Point {
+    x,
+    y
+}
+
+list Point points;
+list names = ["a", "b", "c"];
+
+onflag {
+    n = 10;
+    for i in 1..n {
+        say i;
+    }
+    for j in 10..0 step -2 {
+        say j;
+    }
+    for name in names {
+        say name;
+    }
+    add Point { x: 1, y: 2 } to points;
+    sum_points;
+    find_name "b";
+}
+
+proc sum_points {
+    local total = 0;
+    for p in points {
+        total += p.x + p.y;
+    }
+    for local k in 0..length names - 1 {
+        for local name in names {
+            say name & k;
+        }
+    }
+    say total;
+}
+
+onclick {
+    for i in 1..10 {
+        if i % 2 == 0 {
+            continue;
+        }
+        if i > 7 {
+            break;
+        }
+        say i;
+    }
+    until timer() > 5 {
+        if mouse_down() {
+            break;
+        }
+        say "waiting";
+    }
+    forever {
+        if key_pressed("space") {
+            break;
+        }
+    }
+    say "done";
+}
+
+proc find_name needle {
+    repeat length names {
+        if names[1] == $needle {
+            say "found";
+            break;
+        }
+    }
+}
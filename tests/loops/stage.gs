@@ -0,0 +1 @@
+costumes "blank.svg";
@@ -0,0 +1,15 @@
+costumes "blank.svg";
+
+map scores;
+
+onflag {
+    scores["alice"] = 10;
+    scores["bob"] = 5;
+    scores["alice"] += 1;
+    if "bob" in scores {
+        say scores["bob"];
+    }
+    delete scores["bob"];
+    say length scores;
+    delete scores;
+}
@@ -0,0 +1,37 @@
+costumes "blank.svg";
+
+enum Direction {
+    North,
+    East,
+    South,
+    West
+}
+
+onflag {
+    dir = Direction.East;
+    match dir {
+        Direction.North => {
+            say "up";
+        }
+        Direction.East | Direction.West => {
+            say "sideways";
+        }
+        _ => {
+            say "down";
+        }
+    }
+    match dir + 1 {
+        1 => { say "one"; },
+        2 => { say "two"; },
+    }
+    turn_in dir;
+}
+
+proc turn_in d {
+    match $d {
+        Direction.North => { point_in_direction 0; }
+        Direction.East => { point_in_direction 90; }
+        Direction.South => { point_in_direction 180; }
+        Direction.West => { point_in_direction -90; }
+    }
+}
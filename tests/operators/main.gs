@@ -38,4 +38,26 @@ onflag {
     say log lhs;
     say antiln lhs;
     say antilog lhs;
+    say lhs |> random(rhs);
+    say "hello" |> contains("ell");
+    say lhs & rhs |> plus(b: 10);
+    say lhs |> plus(b: rhs) |> plus(10);
+    say lhs |> plus(rhs) in "123";
+    say lhs |> plus(rhs) == 3;
+    say if lhs > rhs then 10 else -5;
+    say if key_pressed("space") then 1.5 else 0;
+    say if lhs then "yes" else "no";
+    say if lhs == rhs then plus(lhs, 1) else plus(rhs, 2);
+    size = 1 + (if lhs < rhs then lhs else rhs);
+    say f"lhs: {lhs}, rhs: {rhs}";
+    quotient, remainder = divmod(lhs, rhs);
+    say f"{lhs + rhs} is {if lhs > rhs then "more" else "less"} than {{3}}";
+}
+
+func plus(a, b) {
+    return $a + $b;
+}
+
+func divmod(a, b) {
+    return ($a // $b, $a % $b);
 }
@@ -1,5 +1,14 @@
 costumes "blank.svg";
 
+## Says hello.
 onflag {
     say "Hello, World!";
+    greet "World";
+}
+
+## Greets someone.
+## Used by the flag script.
+proc greet name {
+    ## Not a doc comment.
+    say "Hello, " & $name;
 }
@@ -0,0 +1,5 @@
+costumes "blank.svg";
+
+sprite {
+    layer: 3,
+}
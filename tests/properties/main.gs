@@ -0,0 +1,13 @@
+costumes "blank.svg", "blank.svg" as "other";
+
+sprite {
+    x: -100,
+    y: 50.5,
+    size: 25,
+    direction: -90,
+    visible: false,
+    draggable: true,
+    rotation_style: "left-right",
+    costume: "other",
+    layer: 2,
+}
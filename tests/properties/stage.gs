@@ -0,0 +1,5 @@
+costumes "blank.svg";
+
+sprite {
+    volume: 50,
+}
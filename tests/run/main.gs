@@ -0,0 +1,176 @@
+costumes "blank.svg";
+
+list items;
+
+map scores;
+
+struct point {
+    x,
+    y,
+}
+
+struct segment {
+    point a,
+    point b,
+}
+
+list segment segments;
+
+enum dir {
+    up,
+    down,
+    left,
+}
+
+const STEPS = 2 * 3;
+const dir HOME = 1;
+
+onflag {
+    repeat 2 {
+        log "a";
+    }
+    broadcast_and_wait "coerce";
+    broadcast_and_wait "loops";
+    broadcast_and_wait "enums";
+    broadcast_and_wait "maps";
+    broadcast_and_wait "structs";
+    goto 10, 20;
+    turn_right 200;
+    clone;
+    wait 0.1;
+    calls = 0;
+    match get().x {
+        1 => {
+            log "one";
+        },
+        2 => {
+            log "two";
+        },
+        _ => {}
+    }
+    log "calls " & calls;
+    log "fact " & fact(5);
+    log sign(-3) & " " & sign(2);
+    stop_all;
+}
+
+onflag {
+    burst;
+    repeat 2 {
+        log "b";
+    }
+}
+
+on "coerce" {
+    a = "10";
+    b = "9a";
+    log a < b;
+    log a + 0.5;
+    log a / 0;
+    c = 0.1;
+    log c + 0.2;
+    add "x" to items;
+    add "y" to items;
+    log items;
+    add 3 to items;
+    log items;
+    log "y" in items;
+    log items["last"];
+    scores["bob"] = 1;
+    log "Bob" in scores;
+    log "eve" in scores;
+}
+
+on "loops" {
+    bumps = 0;
+    for i in bump()..4 step bump() {
+        log "for " & i;
+    }
+    log "bumps " & bumps;
+}
+
+on "enums" {
+    dir d = dir.left;
+    match d {
+        dir.up => {
+            log "up";
+        },
+        dir.down | dir.left => {
+            log "down or left";
+        },
+    }
+    walk dir.down;
+    walk HOME;
+    log GREETING & " " & STEPS;
+}
+
+on "maps" {
+    keys_made = 0;
+    scores[next_key()] = 1;
+    scores[next_key()] += 10;
+    delete scores["k1"];
+    log scores["k2"] & " " & keys_made & " " & ("k1" in scores);
+}
+
+on "structs" {
+    segment s = segment { a: point { x: 1, y: 2 }, b: point { x: 4, y: 6 } };
+    s.b.x += s.a.x;
+    s.a = padd(s.a, point { x: 10, y: 10 });
+    add s to segments;
+    segments[1].b.y = 9;
+    log s.a.x & " " & s.a.y & " " & segments[1].b.x & " " & segments[1].b.y;
+}
+
+onclone {
+    log "clone " & x_position() & " " & direction();
+    forever {
+        wait 1;
+    }
+}
+
+func fact(n) {
+    if $n < 2 {
+        return 1;
+    }
+    return $n * fact($n - 1);
+}
+
+func sign(n) {
+    return if $n > 0 then "positive" else "negative";
+}
+
+func next_key() {
+    keys_made += 1;
+    return "k" & keys_made;
+}
+
+func bump() {
+    bumps += 1;
+    return bumps;
+}
+
+func padd(point p, point q) point {
+    return point { x: $p.x + $q.x, y: $p.y + $q.y };
+}
+
+func get() point {
+    calls += 1;
+    return point { x: 2, y: 0 };
+}
+
+proc walk dir way {
+    match $way {
+        dir.up => {
+            log "walk up";
+        },
+        dir.left | _ => {
+            log "walk " & $way;
+        },
+    }
+}
+
+proc burst {
+    repeat 2 {
+        log "w";
+    }
+}
@@ -0,0 +1,7 @@
+costumes "blank.svg";
+
+const GREETING = "hi";
+
+onflag {
+    log "stage";
+}
@@ -1,4 +1,5 @@
 costumes "blank.svg";
+sounds "sound.wav";
 
 onflag {
     play_sound_until_done "sound";
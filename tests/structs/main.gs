@@ -0,0 +1,43 @@
+costumes "blank.svg";
+
+struct Vec2 {
+    x,
+    y
+}
+
+struct Entity {
+    Vec2 pos,
+    Vec2 vel,
+    hp
+}
+
+list Entity entities;
+
+onflag {
+    Entity player = Entity {
+        pos: Vec2 { x: 0, y: 0 },
+        vel: Vec2 { x: 1, y: 2 },
+        hp: 10
+    };
+    player.pos.x += player.vel.x;
+    player.pos = Vec2 { x: 5, y: player.pos.y };
+    player.hp -= 1;
+    add player to entities;
+    entities[1].pos.y = 3;
+    say entities[1].pos.x;
+    advance player;
+    Vec2 sum = vadd(player.pos, vadd(player.vel, Vec2 { x: 1, y: 1 }));
+    say vlen(sum);
+}
+
+func vadd(Vec2 a, Vec2 b) Vec2 {
+    return Vec2 { x: $a.x + $b.x, y: $a.y + $b.y };
+}
+
+func vlen(Vec2 v) {
+    return sqrt($v.x * $v.x + $v.y * $v.y);
+}
+
+proc advance Entity e {
+    goto $e.pos.x + $e.vel.x, $e.pos.y + $e.vel.y;
+}
@@ -0,0 +1,47 @@
+costumes "blank.svg";
+
+list numbers;
+
+onflag {
+    count = 0;
+    push 10;
+}
+
+test "add" {
+    assert(sum(1, 2) == 3, "1 + 2 should be 3");
+    assert sum(0.5, 0.25) == 0.75;
+}
+
+test "push" {
+    push 1;
+    push 2;
+    assert(length(numbers) == 2, "numbers should have 2 items");
+}
+
+test "fresh state" {
+    push 3;
+    assert(length(numbers) == 1, "numbers should start empty");
+    assert(count == 1, "count should start at 0");
+}
+
+test "fails" {
+    log "before";
+    assert(sum(1, 1) == 3, "1 + 1 should be 3");
+    log "after";
+}
+
+test "never finishes" {
+    forever {
+        count += 1;
+    }
+}
+
+func sum(a, b) {
+    return $a + $b;
+}
+
+proc push n {
+    assert $n > 0, "n should be positive";
+    add $n to numbers;
+    count += 1;
+}
@@ -0,0 +1,5 @@
+costumes "blank.svg";
+
+test "stage" {
+    assert(timer() == 0, "timer should start at 0");
+}